@@ -6,6 +6,9 @@ use super::event::sd_event;
 pub mod vtable;
 pub use self::vtable::sd_bus_vtable;
 
+pub const SD_BUS_MESSAGE_DUMP_WITH_HEADER: u64 = 1 << 0;
+pub const SD_BUS_MESSAGE_DUMP_SUBTREE_ONLY: u64 = 1 << 1;
+
 #[allow(non_camel_case_types)]
 pub enum sd_bus {}
 #[allow(non_camel_case_types)]
@@ -87,8 +90,8 @@ extern "C" {
 
     pub fn sd_bus_new(ret: *mut *mut sd_bus) -> c_int;
 
-    pub fn sd_bus_set_address(bus: *mut sd_bus) -> c_int;
-    pub fn sd_bus_set_fd(bus: *mut sd_bus) -> c_int;
+    pub fn sd_bus_set_address(bus: *mut sd_bus, address: *const c_char) -> c_int;
+    pub fn sd_bus_set_fd(bus: *mut sd_bus, input_fd: c_int, output_fd: c_int) -> c_int;
     pub fn sd_bus_set_exec(bus: *mut sd_bus,
                            path: *const c_char,
                            argv: *const *mut c_char)
@@ -410,6 +413,7 @@ extern "C" {
                                       -> c_int;
     pub fn sd_bus_message_at_end(m: *mut sd_bus_message, complete: c_int) -> c_int;
     pub fn sd_bus_message_rewind(m: *mut sd_bus_message, complete: c_int) -> c_int;
+    pub fn sd_bus_message_dump(m: *mut sd_bus_message, f: *mut ::libc::FILE, flags: u64) -> c_int;
 
     // Bus management
 
@@ -58,6 +58,73 @@ impl sd_bus_vtable {
         unsafe { transmute(val) }
     }
 
+    fn with_data(typ: SdBusVtableType, flags: u64, union_data: [usize; 5]) -> Self {
+        sd_bus_vtable {
+            type_and_flags: Self::type_and_flags(typ as u32, flags),
+            union_data: union_data,
+        }
+    }
+
+    /// Equivalent to `SD_BUS_VTABLE_START(flags)`. `element_size` should be
+    /// `size_of::<sd_bus_vtable>()`.
+    pub fn start(element_size: size_t, flags: u64) -> Self {
+        Self::with_data(SdBusVtableType::Start, flags, [element_size as usize, 0, 0, 0, 0])
+    }
+
+    /// Equivalent to `SD_BUS_VTABLE_END`
+    pub fn end() -> Self {
+        Self::with_data(SdBusVtableType::End, 0, [0; 5])
+    }
+
+    /// Equivalent to `SD_BUS_METHOD_WITH_OFFSET(member, signature, result, handler, offset,
+    /// flags)`
+    pub fn method(member: *const c_char,
+                  signature: *const c_char,
+                  result: *const c_char,
+                  handler: sd_bus_message_handler_t,
+                  offset: size_t,
+                  flags: u64)
+                  -> Self {
+        Self::with_data(SdBusVtableType::Method,
+                        flags,
+                        [member as usize,
+                         signature as usize,
+                         result as usize,
+                         handler.map_or(0, |f| f as usize),
+                         offset as usize])
+    }
+
+    /// Equivalent to `SD_BUS_SIGNAL(member, signature, flags)`
+    pub fn signal(member: *const c_char, signature: *const c_char, flags: u64) -> Self {
+        Self::with_data(SdBusVtableType::Signal,
+                        flags,
+                        [member as usize, signature as usize, 0, 0, 0])
+    }
+
+    /// Equivalent to `SD_BUS_PROPERTY(member, signature, get, offset, flags)` when `set` is
+    /// `None`, and `SD_BUS_WRITABLE_PROPERTY(member, signature, get, set, offset, flags)`
+    /// otherwise.
+    pub fn property(member: *const c_char,
+                    signature: *const c_char,
+                    get: sd_bus_property_get_t,
+                    set: sd_bus_property_set_t,
+                    offset: size_t,
+                    flags: u64)
+                    -> Self {
+        let typ = if set.is_some() {
+            SdBusVtableType::WritableProperty
+        } else {
+            SdBusVtableType::Property
+        };
+        Self::with_data(typ,
+                        flags,
+                        [member as usize,
+                         signature as usize,
+                         get.map_or(0, |f| f as usize),
+                         set.map_or(0, |f| f as usize),
+                         offset as usize])
+    }
+
     // type & flags are stored in a bit field, the ordering of which might change depending on the
     // platform.
     //
@@ -91,6 +158,18 @@ fn vtable_bitfield() {
     assert_eq!(b.flags(), 0xBBCCBB);
 }
 
+#[test]
+fn vtable_entries() {
+    let m = b"Member\0";
+    let v = sd_bus_vtable::method(m.as_ptr() as *const _, 0 as *const _, 0 as *const _, None, 8,
+                                  SdBusVtableFlag::Deprecated as u64);
+    assert_eq!(v.typ(), 'M' as u32);
+    assert_eq!(v.flags(), SdBusVtableFlag::Deprecated as u64);
+    assert_eq!(v.union_data, [m.as_ptr() as usize, 0, 0, 0, 8]);
+
+    assert_eq!(sd_bus_vtable::end().typ(), '>' as u32);
+}
+
 #[test]
 fn size_eq() {
     use std::mem::size_of;
@@ -1,7 +1,7 @@
 // automatically generated by rust-bindgen with manual adjustments
 
-use super::{c_void, c_int, c_char, uint32_t, uint64_t, signalfd_siginfo, siginfo_t, clockid_t,
-            pid_t, int64_t};
+use super::{c_void, c_int, c_uint, c_char, uint32_t, uint64_t, signalfd_siginfo, siginfo_t,
+            clockid_t, pid_t, int64_t, inotify_event};
 
 #[allow(non_camel_case_types)]
 pub enum sd_event {}
@@ -48,6 +48,11 @@ pub type sd_event_child_handler_t = Option<unsafe extern "C" fn(s: *mut sd_event
                                                                 si: *const siginfo_t,
                                                                 userdata: *mut c_void)
                                                                 -> c_int>;
+#[allow(non_camel_case_types)]
+pub type sd_event_inotify_handler_t = Option<unsafe extern "C" fn(s: *mut sd_event_source,
+                                                                  event: *const inotify_event,
+                                                                  userdata: *mut c_void)
+                                                                  -> c_int>;
 extern "C" {
     pub fn sd_event_default(e: *mut *mut sd_event) -> c_int;
     pub fn sd_event_new(e: *mut *mut sd_event) -> c_int;
@@ -81,6 +86,20 @@ extern "C" {
                               callback: sd_event_child_handler_t,
                               userdata: *mut c_void)
                               -> c_int;
+    pub fn sd_event_add_child_pidfd(e: *mut sd_event,
+                                    s: *mut *mut sd_event_source,
+                                    pidfd: c_int,
+                                    options: c_int,
+                                    callback: sd_event_child_handler_t,
+                                    userdata: *mut c_void)
+                                    -> c_int;
+    pub fn sd_event_add_inotify(e: *mut sd_event,
+                                s: *mut *mut sd_event_source,
+                                path: *const c_char,
+                                mask: uint32_t,
+                                callback: sd_event_inotify_handler_t,
+                                userdata: *mut c_void)
+                                -> c_int;
     pub fn sd_event_add_defer(e: *mut sd_event,
                               s: *mut *mut sd_event_source,
                               callback: sd_event_handler_t,
@@ -146,4 +165,10 @@ extern "C" {
     pub fn sd_event_source_get_time_clock(s: *mut sd_event_source, clock: *mut clockid_t) -> c_int;
     pub fn sd_event_source_get_signal(s: *mut sd_event_source) -> c_int;
     pub fn sd_event_source_get_child_pid(s: *mut sd_event_source, pid: *mut pid_t) -> c_int;
+    pub fn sd_event_source_get_child_pidfd(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_send_child_signal(s: *mut sd_event_source,
+                                             sig: c_int,
+                                             si: *const siginfo_t,
+                                             flags: c_uint)
+                                             -> c_int;
 }
@@ -1,6 +1,7 @@
 use super::{c_char, c_int};
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct sd_id128_t {
     pub bytes: [u8; 16],
 }
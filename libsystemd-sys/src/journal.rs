@@ -9,6 +9,10 @@ pub const SD_JOURNAL_RUNTIME_ONLY: c_int = 2;
 pub const SD_JOURNAL_SYSTEM: c_int = 4;
 pub const SD_JOURNAL_CURRENT_USER: c_int = 8;
 
+pub const SD_JOURNAL_NOP: c_int = 0;
+pub const SD_JOURNAL_APPEND: c_int = 1;
+pub const SD_JOURNAL_INVALIDATE: c_int = 2;
+
 use id128::sd_id128_t;
 pub enum sd_journal {}
 
@@ -38,11 +42,11 @@ extern "C" {
 
     pub fn sd_journal_get_data(j: *mut sd_journal,
                                field: *const c_char,
-                               data: *const *mut u8,
+                               data: *mut *const c_void,
                                l: *mut size_t)
                                -> c_int;
     pub fn sd_journal_enumerate_data(j: *mut sd_journal,
-                                     data: *const *mut u8,
+                                     data: *mut *const c_void,
                                      l: *mut size_t)
                                      -> c_int;
     pub fn sd_journal_restart_data(j: *mut sd_journal) -> ();
@@ -0,0 +1,157 @@
+/*!
+ * Generate the files needed for D-Bus activation of a systemd service
+ *
+ * Bus activation needs two files that must agree with each other: a D-Bus service file (installed
+ * in `/usr/share/dbus-1/system-services/` or `/usr/share/dbus-1/services/`), which tells the bus
+ * which systemd unit to start for a name, and the systemd unit itself, which tells systemd that
+ * the service is ready once it has acquired that name.
+ *
+ * Following the convention used by systemd's own services, the D-Bus service file refers to the
+ * unit by the alias `dbus-<name>.service`, so disabling the unit also disables its activation.
+ */
+
+use std::path::{Path, PathBuf};
+use std::result;
+use super::{BusName, BusNameBuf};
+
+/// Describes how a service owning a well-known bus name is started.
+#[derive(Debug, Clone)]
+pub struct Activation {
+    name: BusNameBuf,
+    exec: PathBuf,
+    unit: String,
+    description: Option<String>,
+    user: Option<String>,
+}
+
+/// Quote `s` for use in an `Exec=` line. Both D-Bus and systemd split these on whitespace and
+/// understand double quotes with backslash escapes.
+fn quote(s: &str) -> String {
+    if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return s.to_owned();
+    }
+
+    let mut q = String::with_capacity(s.len() + 2);
+    q.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            q.push('\\');
+        }
+        q.push(c);
+    }
+    q.push('"');
+    q
+}
+
+impl Activation {
+    /// Activate `name` by running the executable at `exec`.
+    ///
+    /// The unit is named after the bus name alias (`dbus-<name>.service`) unless changed with
+    /// `unit()`. Fails if `name` is a unique name, which can not be activated.
+    pub fn new<P: AsRef<Path>>(name: &BusName, exec: P) -> result::Result<Self, &'static str> {
+        if name.to_bytes().starts_with(b":") {
+            return Err("Unique names can not be activated");
+        }
+
+        let name = name.to_owned();
+        Ok(Activation {
+            unit: Self::alias_for(&name),
+            name: name,
+            exec: exec.as_ref().to_owned(),
+            description: None,
+            user: None,
+        })
+    }
+
+    fn alias_for(name: &BusName) -> String {
+        format!("dbus-{}.service", name.to_string_lossy())
+    }
+
+    /// Use `unit` as the name of the systemd unit. An `Alias=` for `dbus-<name>.service` is added
+    /// to its `[Install]` section.
+    pub fn unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.unit = unit.into();
+        self
+    }
+
+    /// Set the `Description=` of the unit.
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Run the service as `user`. Required by the system bus in the D-Bus service file, and
+    /// mirrored as `User=` in the unit.
+    pub fn user<S: Into<String>>(mut self, user: S) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// The name of the systemd unit, as passed to `unit()`.
+    #[inline]
+    pub fn unit_name(&self) -> &str {
+        &self.unit
+    }
+
+    /// The file name the D-Bus service file must be installed under (`<name>.service`).
+    pub fn service_file_name(&self) -> String {
+        format!("{}.service", self.name.to_string_lossy())
+    }
+
+    /// Contents of the D-Bus service file.
+    pub fn service_file(&self) -> String {
+        let mut s = String::from("[D-BUS Service]\n");
+        s += &format!("Name={}\n", self.name.to_string_lossy());
+        s += &format!("Exec={}\n", quote(&self.exec.to_string_lossy()));
+        if let Some(ref user) = self.user {
+            s += &format!("User={}\n", user);
+        }
+        s += &format!("SystemdService={}\n", Self::alias_for(&self.name));
+        s
+    }
+
+    /// Contents of the systemd unit file.
+    pub fn unit_file(&self) -> String {
+        let mut s = String::from("[Unit]\n");
+        if let Some(ref description) = self.description {
+            s += &format!("Description={}\n", description);
+        }
+        s += "\n[Service]\nType=dbus\n";
+        s += &format!("BusName={}\n", self.name.to_string_lossy());
+        s += &format!("ExecStart={}\n", quote(&self.exec.to_string_lossy()));
+        if let Some(ref user) = self.user {
+            s += &format!("User={}\n", user);
+        }
+
+        let alias = Self::alias_for(&self.name);
+        if self.unit != alias {
+            s += &format!("\n[Install]\nAlias={}\n", alias);
+        }
+        s
+    }
+}
+
+#[test]
+fn t_activation() {
+    let name = BusName::from_bytes(b"org.example.Foo\0").unwrap();
+    let a = Activation::new(name, "/usr/lib/foo/foo daemon").unwrap();
+    assert_eq!(a.unit_name(), "dbus-org.example.Foo.service");
+    assert_eq!(a.service_file_name(), "org.example.Foo.service");
+    assert_eq!(a.service_file(),
+               "[D-BUS Service]\nName=org.example.Foo\nExec=\"/usr/lib/foo/foo daemon\"\n\
+                SystemdService=dbus-org.example.Foo.service\n");
+    assert_eq!(a.unit_file(),
+               "[Unit]\n\n[Service]\nType=dbus\nBusName=org.example.Foo\n\
+                ExecStart=\"/usr/lib/foo/foo daemon\"\n");
+
+    let a = a.unit("foo.service").description("Foo").user("foo");
+    assert_eq!(a.service_file(),
+               "[D-BUS Service]\nName=org.example.Foo\nExec=\"/usr/lib/foo/foo daemon\"\n\
+                User=foo\nSystemdService=dbus-org.example.Foo.service\n");
+    assert_eq!(a.unit_file(),
+               "[Unit]\nDescription=Foo\n\n[Service]\nType=dbus\nBusName=org.example.Foo\n\
+                ExecStart=\"/usr/lib/foo/foo daemon\"\nUser=foo\n\n\
+                [Install]\nAlias=dbus-org.example.Foo.service\n");
+
+    Activation::new(BusName::from_bytes(b":1.2\0").unwrap(), "/bin/true").err().unwrap();
+}
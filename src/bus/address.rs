@@ -0,0 +1,211 @@
+/*!
+ * D-Bus server addresses (`unix:path=/run/dbus/system_bus_socket,guid=...;tcp:host=...`)
+ *
+ * An address names a transport and its parameters. Several addresses can be combined into a
+ * list separated by `;`, in which case they are tried in turn. Values are escaped by writing
+ * bytes other than `[-0-9A-Za-z_/.\*]` as `%XX`.
+ *
+ * Only values which are valid UTF-8 (once unescaped) are supported.
+ */
+
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+
+/// A single D-Bus server address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Address {
+    transport: String,
+    params: Vec<(String, String)>,
+}
+
+/// Whether `b` may appear unescaped in a value
+fn is_optionally_escaped(b: u8) -> bool {
+    match b {
+        b'-' | b'0'...b'9' | b'A'...b'Z' | b'a'...b'z' | b'_' | b'/' | b'.' | b'\\' | b'*' => true,
+        _ => false,
+    }
+}
+
+/// Whether `s` can be used as a transport name or key
+fn is_valid_name(s: &str) -> bool {
+    !s.is_empty() && !s.bytes().any(|b| b == b':' || b == b';' || b == b',' || b == b'=')
+}
+
+fn escape(s: &str, out: &mut String) {
+    for b in s.bytes() {
+        if is_optionally_escaped(b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02x}", b));
+        }
+    }
+}
+
+fn unescape(s: &str) -> result::Result<String, &'static str> {
+    let s = s.as_bytes();
+    let mut v = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        let b = s[i];
+        if b == b'%' {
+            let hex = match s.get(i + 1..i + 3) {
+                Some(hex) => hex,
+                None => return Err("truncated escape sequence"),
+            };
+            let hex = try!(::std::str::from_utf8(hex).map_err(|_| "invalid escape sequence"));
+            v.push(try!(u8::from_str_radix(hex, 16).map_err(|_| "invalid escape sequence")));
+            i += 3;
+        } else if is_optionally_escaped(b) {
+            v.push(b);
+            i += 1;
+        } else {
+            return Err("value contains a character which must be escaped");
+        }
+    }
+    String::from_utf8(v).map_err(|_| "value is not UTF-8")
+}
+
+impl Address {
+    /// An address using `transport`, without any parameters yet.
+    pub fn new<T: Into<String>>(transport: T) -> result::Result<Address, &'static str> {
+        let transport = transport.into();
+        if !is_valid_name(&transport) {
+            return Err("invalid transport name");
+        }
+        Ok(Address { transport: transport, params: Vec::new() })
+    }
+
+    /// The unix socket at `path`
+    pub fn unix(path: &str) -> Address {
+        Address::new("unix").unwrap().param("path", path)
+    }
+
+    /// The unix socket named `name` in the abstract namespace
+    pub fn unix_abstract(name: &str) -> Address {
+        Address::new("unix").unwrap().param("abstract", name)
+    }
+
+    /// A TCP socket at `host` and `port`
+    pub fn tcp(host: &str, port: u16) -> Address {
+        Address::new("tcp").unwrap().param("host", host).param("port", port.to_string())
+    }
+
+    /// Add the parameter `key` set to `value`. `value` is escaped when the address is formatted.
+    ///
+    /// Panics if `key` is empty or contains any of `:;,=`.
+    pub fn param<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Address {
+        let key = key.into();
+        assert!(is_valid_name(&key), "invalid address key: {:?}", key);
+        self.params.push((key, value.into()));
+        self
+    }
+
+    /// The transport, ie: `unix` or `tcp`
+    pub fn transport(&self) -> &str {
+        &self.transport
+    }
+
+    /// The (unescaped) value of the parameter `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|p| p.0 == key).map(|p| &p.1[..])
+    }
+
+    /// All parameters as (key, unescaped value) pairs, in order
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// The GUID of the server, if the address includes it
+    pub fn guid(&self) -> Option<&str> {
+        self.get("guid")
+    }
+
+    /// Parse a list of addresses separated by `;`. Empty entries are skipped.
+    pub fn parse_list(s: &str) -> result::Result<Vec<Address>, &'static str> {
+        s.split(';').filter(|a| !a.is_empty()).map(str::parse).collect()
+    }
+
+    /// Format `addresses` as a list, as accepted by `parse_list()`
+    pub fn join_list(addresses: &[Address]) -> String {
+        addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(";")
+    }
+}
+
+impl FromStr for Address {
+    type Err = &'static str;
+
+    /// Parse a single address
+    fn from_str(s: &str) -> result::Result<Address, &'static str> {
+        let (transport, params) = match s.find(':') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => return Err("address lacks a transport"),
+        };
+        if s.contains(';') {
+            return Err("expected a single address");
+        }
+        let mut a = try!(Address::new(transport));
+        for p in params.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = match p.find('=') {
+                Some(i) => (&p[..i], &p[i + 1..]),
+                None => return Err("parameter lacks a value"),
+            };
+            if !is_valid_name(key) {
+                return Err("invalid address key");
+            }
+            a.params.push((key.to_owned(), try!(unescape(value))));
+        }
+        Ok(a)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = self.transport.clone();
+        s.push(':');
+        for (i, &(ref key, ref value)) in self.params.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+            s.push_str(key);
+            s.push('=');
+            escape(value, &mut s);
+        }
+        fmt.write_str(&s)
+    }
+}
+
+#[test]
+fn t_address_parse() {
+    let a: Address = "unix:path=/run/user/1000/bus,guid=4be02b89".parse().unwrap();
+    assert_eq!(a.transport(), "unix");
+    assert_eq!(a.get("path"), Some("/run/user/1000/bus"));
+    assert_eq!(a.guid(), Some("4be02b89"));
+    assert_eq!(a.get("abstract"), None);
+
+    let l = Address::parse_list("unix:abstract=%2ftmp%2Fx%20y;tcp:host=%3a%3a1,port=1234;")
+        .unwrap();
+    assert_eq!(l.len(), 2);
+    assert_eq!(l[0].get("abstract"), Some("/tmp/x y"));
+    assert_eq!(l[1], Address::tcp("::1", 1234));
+
+    "unix".parse::<Address>().err().unwrap();
+    ":path=/a".parse::<Address>().err().unwrap();
+    "unix:path".parse::<Address>().err().unwrap();
+    "unix:path=a b".parse::<Address>().err().unwrap();
+    "unix:path=%2".parse::<Address>().err().unwrap();
+    "unix:path=%zz".parse::<Address>().err().unwrap();
+    "unix:path=%ff".parse::<Address>().err().unwrap();
+    "unix:path=/a;tcp:".parse::<Address>().err().unwrap();
+}
+
+#[test]
+fn t_address_format() {
+    let a = Address::unix("/run/my bus").param("guid", "abc");
+    assert_eq!(a.to_string(), "unix:path=/run/my%20bus,guid=abc");
+    assert_eq!(a.to_string().parse::<Address>().unwrap(), a);
+    assert_eq!(Address::tcp("::1", 80).to_string(), "tcp:host=%3a%3a1,port=80");
+    assert_eq!(Address::join_list(&[Address::unix("/a"), Address::unix_abstract("b")]),
+               "unix:path=/a;unix:abstract=b");
+    Address::new("a:b").err().unwrap();
+}
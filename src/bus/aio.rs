@@ -0,0 +1,671 @@
+/*!
+ * Driving a `Bus` from an async runtime
+ *
+ * `AsyncBus` watches the fd of a bus using a `Reactor` of the runtime. The futures it hands out
+ * run `process()` themselves whenever they are polled, and have the reactor wake them once the fd
+ * is ready for whatever sd-bus is waiting for (`events()`) or its `timeout()` elapsed. There is
+ * no separate task to spawn.
+ *
+ * Reactors are provided for tokio (`TokioReactor`, with the `tokio` feature) and for async-io, as
+ * used by smol (`AsyncIoReactor`, with the `async-io` feature). Other runtimes can be supported by
+ * implementing `Reactor`.
+ *
+ * With the `futures-core` feature, `AsyncBus::subscribe_stream()` provides matching messages as a
+ * `Stream`.
+ *
+ * Like `Bus`, none of this is `Send`: use it on a single thread, ie: through tokio's `LocalSet` or
+ * `Runtime::block_on()`, or smol's `LocalExecutor` or `block_on()`.
+ */
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::mem;
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+use ffi::c_int;
+#[cfg(feature = "futures-core")]
+use futures_core::Stream;
+use super::{Bus, Message, MessageRef, Slot};
+
+/// What `AsyncBus` needs from a runtime: readiness of the fd of the bus, and a timer.
+///
+/// The `poll_*` methods arrange for the task of `cx` to be woken when they would return `Ready`,
+/// like `Future::poll()`.
+pub trait Reactor {
+    /// Whether the fd became readable since this last returned `Ready`
+    fn poll_readable(&mut self, cx: &mut Context) -> Poll<io::Result<()>>;
+
+    /// Whether the fd became writable since this last returned `Ready`
+    fn poll_writable(&mut self, cx: &mut Context) -> Poll<io::Result<()>>;
+
+    /// Whether `deadline` has passed. `None` means there is no deadline (any earlier one is
+    /// cancelled), which never is `Ready`.
+    fn poll_timeout(&mut self, deadline: Option<Instant>, cx: &mut Context) -> Poll<()>;
+}
+
+/// The fd of the bus, which stays owned by sd-bus
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+struct BusFd(RawFd);
+
+#[cfg(any(feature = "tokio", feature = "async-io"))]
+impl AsRawFd for BusFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl ::std::os::unix::io::AsFd for BusFd {
+    fn as_fd<'a>(&'a self) -> ::std::os::unix::io::BorrowedFd<'a> {
+        unsafe { ::std::os::unix::io::BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+/// Watches a bus using tokio (requires the `tokio` feature)
+#[cfg(feature = "tokio")]
+pub struct TokioReactor {
+    fd: ::tokio::io::unix::AsyncFd<BusFd>,
+    timer: Option<Pin<Box<::tokio::time::Sleep>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioReactor {
+    /// Register the fd of `bus` with the current tokio runtime (which must have IO and time
+    /// enabled).
+    pub fn new(bus: &super::BusRef) -> ::Result<TokioReactor> {
+        let fd = try!(bus.fd());
+        Ok(TokioReactor { fd: try!(::tokio::io::unix::AsyncFd::new(BusFd(fd))), timer: None })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Reactor for TokioReactor {
+    fn poll_readable(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.fd.poll_read_ready(cx) {
+            Poll::Ready(Ok(mut guard)) => {
+                guard.clear_ready();
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_writable(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.fd.poll_write_ready(cx) {
+            Poll::Ready(Ok(mut guard)) => {
+                guard.clear_ready();
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_timeout(&mut self, deadline: Option<Instant>, cx: &mut Context) -> Poll<()> {
+        let deadline = match deadline {
+            Some(d) => ::tokio::time::Instant::from_std(d),
+            None => {
+                self.timer = None;
+                return Poll::Pending;
+            }
+        };
+        match self.timer {
+            Some(ref mut t) => t.as_mut().reset(deadline),
+            None => self.timer = Some(Box::pin(::tokio::time::sleep_until(deadline))),
+        }
+        self.timer.as_mut().unwrap().as_mut().poll(cx)
+    }
+}
+
+/// Watches a bus using async-io, the reactor of smol (requires the `async-io` feature)
+#[cfg(feature = "async-io")]
+pub struct AsyncIoReactor {
+    fd: ::async_io::Async<BusFd>,
+    timer: Option<::async_io::Timer>,
+}
+
+#[cfg(feature = "async-io")]
+impl AsyncIoReactor {
+    /// Register the fd of `bus` with async-io
+    pub fn new(bus: &super::BusRef) -> ::Result<AsyncIoReactor> {
+        let fd = try!(bus.fd());
+        Ok(AsyncIoReactor { fd: try!(::async_io::Async::new(BusFd(fd))), timer: None })
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl Reactor for AsyncIoReactor {
+    fn poll_readable(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.fd.poll_readable(cx)
+    }
+
+    fn poll_writable(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.fd.poll_writable(cx)
+    }
+
+    fn poll_timeout(&mut self, deadline: Option<Instant>, cx: &mut Context) -> Poll<()> {
+        let deadline = match deadline {
+            Some(d) => d,
+            None => {
+                self.timer = None;
+                return Poll::Pending;
+            }
+        };
+        match self.timer {
+            Some(ref mut t) => t.set_at(deadline),
+            None => self.timer = Some(::async_io::Timer::at(deadline)),
+        }
+        Pin::new(self.timer.as_mut().unwrap()).poll(cx).map(|_| ())
+    }
+}
+
+struct Inner {
+    bus: Bus,
+    reactor: RefCell<Box<Reactor>>,
+    // the reactor only wakes the task that last polled the fd or the timer, so the others are
+    // woken (to register themselves again) whenever one of the futures goes away
+    waiters: RefCell<Vec<Waker>>,
+}
+
+/// Now, on the clock sd-bus uses for its timeouts
+fn monotonic_usec() -> u64 {
+    let mut ts: ::libc::timespec = unsafe { mem::zeroed() };
+    unsafe { ::libc::clock_gettime(::libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+impl Inner {
+    /// Process everything that is pending, then arrange for `cx` to be woken when there is more
+    /// to do.
+    fn drive(&self, cx: &mut Context) -> ::Result<()> {
+        loop {
+            /* handlers may poll futures of this bus, the reactor must not be borrowed meanwhile */
+            while try!(self.bus.process()) {}
+
+            let mut reactor = self.reactor.borrow_mut();
+            let events = try!(self.bus.events());
+            let mut ready = false;
+            if events & ::libc::POLLIN as c_int != 0 {
+                if let Poll::Ready(r) = reactor.poll_readable(cx) {
+                    try!(r);
+                    ready = true;
+                }
+            }
+            if events & ::libc::POLLOUT as c_int != 0 {
+                if let Poll::Ready(r) = reactor.poll_writable(cx) {
+                    try!(r);
+                    ready = true;
+                }
+            }
+
+            let timeout = try!(self.bus.timeout());
+            let deadline = if timeout == !0 {
+                None
+            } else {
+                let now = monotonic_usec();
+                if timeout <= now {
+                    continue;
+                }
+                Some(Instant::now() + Duration::from_micros(timeout - now))
+            };
+            if reactor.poll_timeout(deadline, cx).is_ready() {
+                ready = true;
+            }
+
+            if !ready {
+                break;
+            }
+        }
+
+        let mut waiters = self.waiters.borrow_mut();
+        if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+            waiters.push(cx.waker().clone());
+        }
+        Ok(())
+    }
+
+    fn wake_waiters(&self) {
+        let waiters = mem::replace(&mut *self.waiters.borrow_mut(), Vec::new());
+        for w in waiters {
+            w.wake();
+        }
+    }
+}
+
+/// A `Bus` driven by an async runtime, see the module documentation.
+///
+/// Clones refer to the same bus.
+#[derive(Clone)]
+pub struct AsyncBus {
+    inner: Rc<Inner>,
+}
+
+impl AsyncBus {
+    /// Drive `bus` using `reactor`, which watches the fd of `bus`.
+    pub fn new<R: Reactor + 'static>(bus: Bus, reactor: R) -> AsyncBus {
+        AsyncBus {
+            inner: Rc::new(Inner {
+                bus: bus,
+                reactor: RefCell::new(Box::new(reactor)),
+                waiters: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Drive `bus` from the current tokio runtime (requires the `tokio` feature)
+    #[cfg(feature = "tokio")]
+    pub fn tokio(bus: Bus) -> ::Result<AsyncBus> {
+        let reactor = try!(TokioReactor::new(&bus));
+        Ok(AsyncBus::new(bus, reactor))
+    }
+
+    /// Drive `bus` using async-io, ie: from smol (requires the `async-io` feature)
+    #[cfg(feature = "async-io")]
+    pub fn async_io(bus: Bus) -> ::Result<AsyncBus> {
+        let reactor = try!(AsyncIoReactor::new(&bus));
+        Ok(AsyncBus::new(bus, reactor))
+    }
+
+    /// The underlying bus, ie: to create messages or register objects. Handlers registered on it
+    /// are called while any of the futures of this `AsyncBus` are polled.
+    #[inline]
+    pub fn bus(&self) -> &Bus {
+        &self.inner.bus
+    }
+
+    /// Send `m`, completing with its cookie once it has been written out.
+    pub fn send(&self, mut m: Message) -> SendMessage {
+        let r = m.send();
+        SendMessage { inner: self.inner.clone(), cookie: Some(r) }
+    }
+
+    /// Call the method `m`, completing with the reply (or the error it carries). `usec` is the
+    /// timeout, 0 uses the default of sd-bus.
+    ///
+    /// Dropping the future before it completed cancels the call.
+    pub fn call(&self, mut m: Message, usec: u64) -> Call {
+        let state = Rc::new(RefCell::new(Pending::new()));
+        let s = state.clone();
+        let slot = m.call_async(move |reply: &mut MessageRef| {
+            let r = match reply.error() {
+                Some(e) => Err(e),
+                None => Ok(reply.to_owned()),
+            };
+            s.borrow_mut().complete(r);
+            Ok(())
+        }, usec);
+        let slot = match slot {
+            Ok(slot) => Some(slot),
+            Err(e) => {
+                state.borrow_mut().complete(Err(From::from(e)));
+                None
+            }
+        };
+        Call { inner: self.inner.clone(), state: state, _slot: slot }
+    }
+
+    /// Receive the messages matching `rule` (see `BusRef::add_match()`), ie: signals.
+    ///
+    /// Matching messages are queued until they are read using `Subscription::next()`. The match
+    /// is removed when the `Subscription` is dropped.
+    pub fn subscribe(&self, rule: &str) -> ::Result<Subscription> {
+        let queue = Rc::new(RefCell::new(Queue { messages: VecDeque::new(), waker: None }));
+        let q = queue.clone();
+        let slot = try!(self.inner.bus.add_match(rule, move |m| {
+            let mut q = q.borrow_mut();
+            q.messages.push_back(m.to_owned());
+            if let Some(w) = q.waker.take() {
+                w.wake();
+            }
+            Ok(())
+        }));
+        Ok(Subscription { inner: self.inner.clone(), queue: queue, _slot: slot })
+    }
+
+    /// Like `subscribe()`, but as a `Stream` of the matching messages (requires the
+    /// `futures-core` feature). The stream ends if the bus fails, ie: once it is disconnected.
+    #[cfg(feature = "futures-core")]
+    pub fn subscribe_stream(&self, rule: &str) -> ::Result<impl Stream<Item = Message>> {
+        let sub = try!(self.subscribe(rule));
+        Ok(MessageStream { sub: Some(sub) })
+    }
+}
+
+/// The result of a call, once it arrived
+struct Pending {
+    result: Option<super::Result<Message>>,
+    waker: Option<Waker>,
+}
+
+impl Pending {
+    fn new() -> Pending {
+        Pending { result: None, waker: None }
+    }
+
+    fn complete(&mut self, r: super::Result<Message>) {
+        self.result = Some(r);
+        if let Some(w) = self.waker.take() {
+            w.wake();
+        }
+    }
+}
+
+/// The future returned by `AsyncBus::call()`
+pub struct Call {
+    inner: Rc<Inner>,
+    state: Rc<RefCell<Pending>>,
+    _slot: Option<Slot>,
+}
+
+impl Future for Call {
+    type Output = super::Result<Message>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Some(r) = self.state.borrow_mut().result.take() {
+            return Poll::Ready(r);
+        }
+        if let Err(e) = self.inner.drive(cx) {
+            return Poll::Ready(Err(From::from(e)));
+        }
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(r) => Poll::Ready(r),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        self.inner.wake_waiters();
+    }
+}
+
+/// The future returned by `AsyncBus::send()`
+pub struct SendMessage {
+    inner: Rc<Inner>,
+    cookie: Option<::Result<u64>>,
+}
+
+impl Future for SendMessage {
+    type Output = ::Result<u64>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.cookie {
+            Some(Ok(_)) => {}
+            _ => return Poll::Ready(self.cookie.take().unwrap()),
+        }
+        let flushed = self.inner.drive(cx)
+            .and_then(|_| self.inner.bus.events())
+            .map(|events| events & ::libc::POLLOUT as c_int == 0);
+        match flushed {
+            Ok(false) => Poll::Pending,
+            Ok(true) => Poll::Ready(self.cookie.take().unwrap()),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl Drop for SendMessage {
+    fn drop(&mut self) {
+        self.inner.wake_waiters();
+    }
+}
+
+struct Queue {
+    messages: VecDeque<Message>,
+    waker: Option<Waker>,
+}
+
+/// Messages matching a rule, returned by `AsyncBus::subscribe()`
+pub struct Subscription {
+    inner: Rc<Inner>,
+    queue: Rc<RefCell<Queue>>,
+    _slot: Slot,
+}
+
+impl Subscription {
+    /// Wait for the next matching message
+    pub fn next<'a>(&'a mut self) -> Next<'a> {
+        Next { sub: self }
+    }
+
+    fn poll_message(&self, cx: &mut Context) -> Poll<super::Result<Message>> {
+        if let Some(m) = self.queue.borrow_mut().messages.pop_front() {
+            return Poll::Ready(Ok(m));
+        }
+        if let Err(e) = self.inner.drive(cx) {
+            return Poll::Ready(Err(From::from(e)));
+        }
+        let mut queue = self.queue.borrow_mut();
+        match queue.messages.pop_front() {
+            Some(m) => Poll::Ready(Ok(m)),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.inner.wake_waiters();
+    }
+}
+
+/// The future returned by `Subscription::next()`
+pub struct Next<'a> {
+    sub: &'a mut Subscription,
+}
+
+impl<'a> Future for Next<'a> {
+    type Output = super::Result<Message>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.sub.poll_message(cx)
+    }
+}
+
+impl<'a> Drop for Next<'a> {
+    fn drop(&mut self) {
+        self.sub.inner.wake_waiters();
+    }
+}
+
+/// The stream returned by `AsyncBus::subscribe_stream()`
+#[cfg(feature = "futures-core")]
+struct MessageStream {
+    // dropped once the bus failed, which ends the stream
+    sub: Option<Subscription>,
+}
+
+#[cfg(feature = "futures-core")]
+impl Stream for MessageStream {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Message>> {
+        let r = match self.sub {
+            Some(ref sub) => sub.poll_message(cx),
+            None => return Poll::Ready(None),
+        };
+        match r {
+            Poll::Ready(Ok(m)) => Poll::Ready(Some(m)),
+            Poll::Ready(Err(_)) => {
+                self.sub = None;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::thread;
+    use super::*;
+    use super::super::testing;
+
+    /// Runs futures to completion, in whichever way the reactor under test needs
+    trait BlockOn {
+        fn block_on<F: Future>(&self, f: F) -> F::Output;
+    }
+
+    /// Polls the bus fd without blocking, for `Spin`
+    struct PollReactor(c_int);
+
+    impl PollReactor {
+        fn poll(&self, events: i16) -> Poll<io::Result<()>> {
+            let mut pfd = ::libc::pollfd { fd: self.0, events: events, revents: 0 };
+            match unsafe { ::libc::poll(&mut pfd, 1, 0) } {
+                r if r < 0 => Poll::Ready(Err(io::Error::last_os_error())),
+                0 => Poll::Pending,
+                _ => Poll::Ready(Ok(())),
+            }
+        }
+    }
+
+    impl Reactor for PollReactor {
+        fn poll_readable(&mut self, _: &mut Context) -> Poll<io::Result<()>> {
+            self.poll(::libc::POLLIN)
+        }
+
+        fn poll_writable(&mut self, _: &mut Context) -> Poll<io::Result<()>> {
+            self.poll(::libc::POLLOUT)
+        }
+
+        fn poll_timeout(&mut self, deadline: Option<Instant>, _: &mut Context) -> Poll<()> {
+            match deadline {
+                Some(d) if d <= Instant::now() => Poll::Ready(()),
+                _ => Poll::Pending,
+            }
+        }
+    }
+
+    /// Polls futures in a loop, as nothing wakes them with `PollReactor`
+    struct Spin;
+
+    impl BlockOn for Spin {
+        fn block_on<F: Future>(&self, f: F) -> F::Output {
+            let mut f = Box::pin(f);
+            let mut cx = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(r) = f.as_mut().poll(&mut cx) {
+                    return r;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    fn client() -> Bus {
+        testing::serve_echo().unwrap()
+    }
+
+    fn echo(bus: &AsyncBus, s: &str) -> Message {
+        testing::new_call(&mut bus.bus().clone(), "Echo", s).unwrap()
+    }
+
+    /// Send, call and subscribe through `bus`
+    fn round_trip<E: BlockOn>(e: &E, bus: AsyncBus) {
+        let mut sub = bus.subscribe("type='signal',member='Echoed'").unwrap();
+
+        let mut reply = e.block_on(bus.call(echo(&bus, "a"), 0)).unwrap();
+        assert_eq!(reply.read_body::<String>().unwrap(), "a");
+        let mut signal = e.block_on(sub.next()).unwrap();
+        assert_eq!(signal.read_body::<String>().unwrap(), "a");
+
+        /* no reply is expected, but the service still emits the signal */
+        let mut m = echo(&bus, "b");
+        m.set_expect_reply(false).unwrap();
+        e.block_on(bus.send(m)).unwrap();
+        let mut signal = e.block_on(sub.next()).unwrap();
+        assert_eq!(signal.read_body::<String>().unwrap(), "b");
+    }
+
+    #[test]
+    fn t_async_bus() {
+        let client = client();
+        let fd = client.fd().unwrap();
+        round_trip(&Spin, AsyncBus::new(client, PollReactor(fd)));
+    }
+
+    #[test]
+    fn t_async_bus_reentrant() {
+        let client = client();
+        let fd = client.fd().unwrap();
+        let bus = AsyncBus::new(client, PollReactor(fd));
+        /* a handler polling a future of the bus it is called from */
+        let polled = Rc::new(Cell::new(false));
+        let _slot = {
+            let bus = bus.clone();
+            let polled = polled.clone();
+            bus.bus().clone().add_match("type='signal',member='Echoed'", move |_| {
+                let mut send = Box::pin(bus.send(echo(&bus, "c")));
+                let _ = send.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+                polled.set(true);
+                Ok(())
+            }).unwrap()
+        };
+        Spin.block_on(bus.call(echo(&bus, "a"), 0)).unwrap();
+        assert!(polled.get());
+    }
+
+    #[cfg(feature = "tokio")]
+    impl BlockOn for ::tokio::runtime::Runtime {
+        fn block_on<F: Future>(&self, f: F) -> F::Output {
+            ::tokio::runtime::Runtime::block_on(self, f)
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn t_tokio() {
+        let rt = ::tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let bus = {
+            let _guard = rt.enter();
+            AsyncBus::tokio(client()).unwrap()
+        };
+        round_trip(&rt, bus);
+    }
+
+    #[cfg(feature = "async-io")]
+    struct AsyncIo;
+
+    #[cfg(feature = "async-io")]
+    impl BlockOn for AsyncIo {
+        fn block_on<F: Future>(&self, f: F) -> F::Output {
+            ::async_io::block_on(f)
+        }
+    }
+
+    #[cfg(feature = "async-io")]
+    #[test]
+    fn t_async_io() {
+        round_trip(&AsyncIo, AsyncBus::async_io(client()).unwrap());
+    }
+
+    #[cfg(feature = "futures-core")]
+    #[test]
+    fn t_subscribe_stream() {
+        let client = client();
+        let fd = client.fd().unwrap();
+        let bus = AsyncBus::new(client, PollReactor(fd));
+        let mut stream = Box::pin(bus.subscribe_stream("type='signal',member='Echoed'").unwrap());
+        Spin.block_on(bus.call(echo(&bus, "a"), 0)).unwrap();
+        let next = ::std::future::poll_fn(|cx| stream.as_mut().poll_next(cx));
+        let mut signal = Spin.block_on(next).unwrap();
+        assert_eq!(signal.read_body::<String>().unwrap(), "a");
+    }
+}
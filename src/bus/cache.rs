@@ -0,0 +1,119 @@
+/*!
+ * Client side caching of property values
+ *
+ * `PropertyCache` keeps the replies to `org.freedesktop.DBus.Properties.Get` for the properties of
+ * one interface of a remote object, and drops them when the object announces changes with
+ * `PropertiesChanged` or its owner changes. Clients which read the same properties repeatedly
+ * (such as UIs polling for state) then only hit the bus after something changed.
+ *
+ * Properties which change without being announced (`EmitsChangedSignal` set to `false` in the
+ * introspection data) must not be read through the cache.
+ */
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use super::{Bus, BusName, BusNameBuf, BusRef, InterfaceName, InterfaceNameBuf, MemberName,
+            Message, MessageRef, ObjectPath, ObjectPathBuf, Result, Slot, names,
+            read_property_reply};
+use super::types::FromSdBusMessage;
+
+type Replies = Rc<RefCell<HashMap<String, Message>>>;
+
+/// A cache of the properties of `interface` on the object at `path` owned by `dest`.
+///
+/// Invalidation relies on signals, which are only seen while the bus is being processed. Values
+/// may be stale if the bus is not processed regularly.
+pub struct PropertyCache {
+    bus: Bus,
+    dest: BusNameBuf,
+    path: ObjectPathBuf,
+    interface: InterfaceNameBuf,
+    replies: Replies,
+    _changed: Slot,
+    _owner: Slot,
+}
+
+impl PropertyCache {
+    /// Start caching the properties of `interface` on the object at `path` owned by `dest`.
+    ///
+    /// Nothing is fetched until a property is first read.
+    pub fn new(bus: &BusRef, dest: &BusName, path: &ObjectPath, interface: &InterfaceName)
+        -> super::super::Result<PropertyCache>
+    {
+        let replies: Replies = Rc::new(RefCell::new(HashMap::new()));
+
+        let r = replies.clone();
+        let changed = try!(bus.on_properties_changed(dest, path, interface, move |c| {
+            let mut r = r.borrow_mut();
+            for name in c.changed.keys().chain(c.invalidated.iter()) {
+                r.remove(name);
+            }
+            Ok(())
+        }));
+
+        /* a restarted service does not announce that its properties changed */
+        let rule = format!("type='signal',sender='org.freedesktop.DBus',\
+                            interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='{}'",
+                           dest.to_string_lossy());
+        let r = replies.clone();
+        let owner = try!(bus.add_match(&rule, move |_: &mut MessageRef| {
+            r.borrow_mut().clear();
+            Ok(())
+        }));
+
+        Ok(PropertyCache {
+            bus: bus.to_owned(),
+            dest: dest.to_owned(),
+            path: path.to_owned(),
+            interface: interface.to_owned(),
+            replies: replies,
+            _changed: changed,
+            _owner: owner,
+        })
+    }
+
+    /// The value of the property `name`, fetched from the object if it is not cached.
+    pub fn get<T>(&mut self, name: &MemberName) -> Result<T>
+        where T: for<'b> FromSdBusMessage<'b>
+    {
+        let key = name.to_string_lossy().into_owned();
+        if let Some(reply) = self.replies.borrow_mut().get_mut(&key) {
+            sd_try!(::ffi::bus::sd_bus_message_rewind(reply.as_mut_ptr(), 1));
+            return read_property_reply(reply);
+        }
+
+        let get = MemberName::from_bytes(b"Get\0").unwrap();
+        let mut m = try!(self.bus.new_method_call(&self.dest,
+                                                  &self.path,
+                                                  names::interface::PROPERTIES,
+                                                  get));
+        try!(m.append((&*self.interface, name)));
+        let mut reply = try!(m.call(0));
+        let v = try!(read_property_reply(&mut reply));
+        self.replies.borrow_mut().insert(key, reply);
+        Ok(v)
+    }
+
+    /// Drop the cached value of the property `name`, if any
+    pub fn invalidate(&self, name: &MemberName) {
+        self.replies.borrow_mut().remove(&*name.to_string_lossy());
+    }
+
+    /// Drop all cached values
+    pub fn clear(&self) {
+        self.replies.borrow_mut().clear();
+    }
+}
+
+impl fmt::Debug for PropertyCache {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PropertyCache")
+            .field("dest", &&*self.dest)
+            .field("path", &&*self.path)
+            .field("interface", &&*self.interface)
+            .field("cached", &self.replies.borrow().keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
@@ -0,0 +1,633 @@
+/*!
+ * Generate typed client proxies from D-Bus introspection data
+ *
+ * `generate()` takes introspection XML (as returned by `org.freedesktop.DBus.Introspectable` or
+ * shipped in `/usr/share/dbus-1/interfaces/`) and returns Rust source defining a `<Name>Proxy`
+ * struct for each interface, with a method per D-Bus method, getters (and setters) for
+ * properties and `on_<signal>()` functions to subscribe to signals.
+ *
+ * It is meant to be called from a build script, writing the result to `$OUT_DIR` and pulling it
+ * in with `include!()`. The generated code refers to this crate as `::systemd`, and uses `?`
+ * rather than `try!()` so it can be included by crates of any edition.
+ *
+ * Arguments of basic types are passed as the matching Rust types. Methods with arguments of
+ * container types are skipped (noted by a comment in the output). Return values, property values
+ * and signal arguments of container types are returned as `Variant`.
+ */
+
+use std::fmt::Write;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use super::{InterfaceName, MemberName, Result};
+
+fn invalid<S: Into<String>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Used by generated code when a reply lacks an expected value
+#[doc(hidden)]
+pub fn expect<T>(v: Option<T>) -> Result<T> {
+    v.ok_or_else(|| From::from(invalid("reply is missing a value")))
+}
+
+/// Used by generated code to turn the future returned by an asynchronous method into the
+/// `Result` expected by `VtableBuilder::method_async()`
+#[doc(hidden)]
+pub fn map_reply<F, M, R>(future: F, map: M) -> MapReply<F, M>
+    where F: Future,
+          M: FnOnce(F::Output) -> Result<R>
+{
+    MapReply {
+        future: Box::pin(future),
+        map: Some(map),
+    }
+}
+
+#[doc(hidden)]
+pub struct MapReply<F, M> {
+    future: Pin<Box<F>>,
+    map: Option<M>,
+}
+
+impl<F, M> Unpin for MapReply<F, M> {}
+
+impl<F, M, R> Future for MapReply<F, M>
+    where F: Future,
+          M: FnOnce(F::Output) -> Result<R>
+{
+    type Output = Result<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<R>> {
+        match self.future.as_mut().poll(cx) {
+            Poll::Ready(v) => {
+                let map = self.map.take().expect("MapReply polled after completion");
+                Poll::Ready(map(v))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// An XML element. Text content is not kept, introspection data doesn't have any.
+#[derive(Debug)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|a| a.0 == name).map(|a| &a.1[..])
+    }
+
+    fn children_named<'a>(&'a self, name: &str) -> Vec<&'a Element> {
+        self.children.iter().filter(|c| c.name == name).collect()
+    }
+}
+
+/// Just enough of an XML parser for introspection data: elements, attributes, comments,
+/// processing instructions and a `DOCTYPE` without an internal subset.
+struct Parser<'a> {
+    s: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_until(&mut self, end: &str) -> ::Result<()> {
+        match self.s.find(end) {
+            Some(i) => {
+                self.s = &self.s[i + end.len()..];
+                Ok(())
+            }
+            None => Err(invalid(format!("unterminated markup, expected '{}'", end))),
+        }
+    }
+
+    /// Skip text, comments, processing instructions and declarations, stopping at the next tag
+    fn skip_misc(&mut self) -> ::Result<()> {
+        loop {
+            let i = self.s.find('<').unwrap_or(self.s.len());
+            self.s = &self.s[i..];
+            if self.s.starts_with("<!--") {
+                try!(self.skip_until("-->"));
+            } else if self.s.starts_with("<?") {
+                try!(self.skip_until("?>"));
+            } else if self.s.starts_with("<!") {
+                try!(self.skip_until(">"));
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.s = self.s.trim_start();
+    }
+
+    fn name(&mut self) -> ::Result<String> {
+        let end = self.s
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+            .unwrap_or(self.s.len());
+        if end == 0 {
+            return Err(invalid("expected a name"));
+        }
+        let n = self.s[..end].to_owned();
+        self.s = &self.s[end..];
+        Ok(n)
+    }
+
+    fn expect(&mut self, s: &str) -> ::Result<()> {
+        if !self.s.starts_with(s) {
+            return Err(invalid(format!("expected '{}'", s)));
+        }
+        self.s = &self.s[s.len()..];
+        Ok(())
+    }
+
+    fn attr_value(&mut self) -> ::Result<String> {
+        let quote = match self.s.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => return Err(invalid("expected a quoted attribute value")),
+        };
+        self.s = &self.s[1..];
+        let end = try!(self.s.find(quote).ok_or_else(|| invalid("unterminated attribute value")));
+        let v = self.s[..end]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&");
+        self.s = &self.s[end + 1..];
+        Ok(v)
+    }
+
+    fn element(&mut self) -> ::Result<Element> {
+        try!(self.expect("<"));
+        let mut e = Element { name: try!(self.name()), attrs: Vec::new(), children: Vec::new() };
+        loop {
+            self.skip_whitespace();
+            if self.s.starts_with("/>") {
+                self.s = &self.s[2..];
+                return Ok(e);
+            }
+            if self.s.starts_with('>') {
+                self.s = &self.s[1..];
+                break;
+            }
+            let n = try!(self.name());
+            self.skip_whitespace();
+            try!(self.expect("="));
+            self.skip_whitespace();
+            let v = try!(self.attr_value());
+            e.attrs.push((n, v));
+        }
+
+        loop {
+            try!(self.skip_misc());
+            if self.s.starts_with("</") {
+                self.s = &self.s[2..];
+                if try!(self.name()) != e.name {
+                    return Err(invalid(format!("mismatched closing tag for '{}'", e.name)));
+                }
+                self.skip_whitespace();
+                try!(self.expect(">"));
+                return Ok(e);
+            }
+            if self.s.is_empty() {
+                return Err(invalid(format!("unterminated element '{}'", e.name)));
+            }
+            e.children.push(try!(self.element()));
+        }
+    }
+}
+
+fn parse(xml: &str) -> ::Result<Element> {
+    let mut p = Parser { s: xml };
+    try!(p.skip_misc());
+    p.element()
+}
+
+const KEYWORDS: &'static [&'static str] =
+    &["as", "box", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+      "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+      "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+      "where", "while", "abstract", "become", "do", "final", "macro", "override", "priv",
+      "typeof", "unsized", "virtual", "yield", "async", "await", "dyn", "try"];
+
+/// `GetUnitByPID` -> `get_unit_by_pid`
+fn snake_case(s: &str) -> String {
+    let c: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    for i in 0..c.len() {
+        if c[i].is_uppercase() && i > 0 &&
+           (!c[i - 1].is_uppercase() || c.get(i + 1).map_or(false, |n| n.is_lowercase())) &&
+           c[i - 1] != '_' {
+            out.push('_');
+        }
+        out.extend(c[i].to_lowercase());
+    }
+    if KEYWORDS.contains(&&out[..]) {
+        out.push('_');
+    }
+    out
+}
+
+/// Rust type for passing a value of dbus type `sig`, if it is supported
+fn in_type(sig: &str) -> Option<&'static str> {
+    Some(match sig {
+        "s" => "&str",
+        "o" => "&::systemd::bus::ObjectPath",
+        "h" => "::systemd::bus::types::UnixFd",
+        _ => return basic_type(sig),
+    })
+}
+
+/// Rust type for receiving a value of dbus type `sig`
+fn out_type(sig: &str) -> &'static str {
+    match sig {
+        "s" => "String",
+        "o" => "::systemd::bus::ObjectPathBuf",
+        "h" => "::systemd::bus::types::UnixFd",
+        _ => basic_type(sig).unwrap_or("::systemd::bus::Variant"),
+    }
+}
+
+fn basic_type(sig: &str) -> Option<&'static str> {
+    Some(match sig {
+        "y" => "u8",
+        "b" => "bool",
+        "n" => "i16",
+        "q" => "u16",
+        "i" => "i32",
+        "u" => "u32",
+        "x" => "i64",
+        "t" => "u64",
+        "d" => "f64",
+        _ => return None,
+    })
+}
+
+struct Arg {
+    name: String,
+    sig: String,
+}
+
+/// The arguments of `e` in direction `dir`. Method arguments default to `in`, signal arguments
+/// are always `out`.
+fn args(e: &Element, dir: &str) -> Vec<Arg> {
+    e.children_named("arg")
+        .iter()
+        .filter(|a| {
+            let default = if e.name == "signal" { "out" } else { "in" };
+            a.attr("direction").unwrap_or(default) == dir
+        })
+        .enumerate()
+        .map(|(i, a)| {
+            Arg {
+                name: a.attr("name").map_or_else(|| format!("arg{}", i), |n| snake_case(n)),
+                sig: a.attr("type").unwrap_or("").to_owned(),
+            }
+        })
+        .collect()
+}
+
+fn tuple(types: &[&str]) -> String {
+    if types.len() == 1 {
+        types[0].to_owned()
+    } else {
+        format!("({})", types.join(", "))
+    }
+}
+
+/// Validate `name` as a member name, returning it
+fn member_name(e: &Element) -> ::Result<&str> {
+    let name = try!(e.attr("name").ok_or_else(|| invalid(format!("<{}> without a name", e.name))));
+    try!(MemberName::from_bytes(format!("{}\0", name).as_bytes())
+        .map_err(|err| invalid(format!("invalid member name '{}': {}", name, err))));
+    Ok(name)
+}
+
+/// Code reading `outs` from the message iterator `i`, binding them to `r0`, `r1`, ...
+fn read_args(out: &mut String, indent: &str, outs: &[Arg]) {
+    for (n, a) in outs.iter().enumerate() {
+        let _ = writeln!(out,
+                         "{}let r{} = ::systemd::bus::codegen::expect(i.read_next::<{}>()?)?;",
+                         indent,
+                         n,
+                         out_type(&a.sig));
+    }
+}
+
+fn method(out: &mut String, m: &Element) -> ::Result<()> {
+    let name = try!(member_name(m));
+    let ins = args(m, "in");
+    let outs = args(m, "out");
+
+    let mut params = String::new();
+    for a in &ins {
+        match in_type(&a.sig) {
+            Some(t) => {
+                let _ = write!(params, ", {}: {}", a.name, t);
+            }
+            None => {
+                let _ = writeln!(out,
+                                 "    // skipped {}: argument type \"{}\" is not supported\n",
+                                 name,
+                                 a.sig);
+                return Ok(());
+            }
+        }
+    }
+
+    let ret: Vec<&str> = outs.iter().map(|a| out_type(&a.sig)).collect();
+    let _ = writeln!(out, "    /// Call `{}`", name);
+    let _ = writeln!(out,
+                     "    pub fn {}(&mut self{}) -> ::systemd::bus::Result<{}> {{",
+                     snake_case(name),
+                     params,
+                     tuple(&ret));
+    let _ = writeln!(out,
+                     "        let mut m = self.bus.new_method_call(&self.dest, &self.path, \
+                      Self::interface(), Self::member(b\"{}\\0\"))?;",
+                     name);
+    for a in &ins {
+        let _ = writeln!(out, "        m.append({})?;", a.name);
+    }
+    if outs.is_empty() {
+        let _ = writeln!(out, "        m.call(0)?;");
+        let _ = writeln!(out, "        Ok(())");
+    } else {
+        let _ = writeln!(out, "        let mut reply = m.call(0)?;");
+        let _ = writeln!(out, "        let mut i = reply.iter()?;");
+        read_args(out, "        ", &outs);
+        let r: Vec<String> = (0..outs.len()).map(|n| format!("r{}", n)).collect();
+        let r: Vec<&str> = r.iter().map(|s| &s[..]).collect();
+        let _ = writeln!(out, "        Ok({})", tuple(&r));
+    }
+    let _ = writeln!(out, "    }}\n");
+    Ok(())
+}
+
+/// The value of the `EmitsChangedSignal` annotation of `e`, if any
+fn emits_changed_signal(e: &Element) -> Option<&str> {
+    e.children_named("annotation")
+        .into_iter()
+        .find(|a| a.attr("name") == Some("org.freedesktop.DBus.Property.EmitsChangedSignal"))
+        .and_then(|a| a.attr("value"))
+}
+
+/// `emits_default` is the `EmitsChangedSignal` annotation of the interface
+fn property(out: &mut String, p: &Element, emits_default: &str) -> ::Result<()> {
+    let name = try!(member_name(p));
+    let sig = p.attr("type").unwrap_or("");
+    let access = p.attr("access").unwrap_or("read");
+    /* properties which change silently can't be cached */
+    let cacheable = emits_changed_signal(p).unwrap_or(emits_default) != "false";
+
+    if access.contains("read") {
+        let _ = writeln!(out, "    /// Get the `{}` property", name);
+        let _ = writeln!(out,
+                         "    pub fn {}(&mut self) -> ::systemd::bus::Result<{}> {{",
+                         snake_case(name),
+                         out_type(sig));
+        if cacheable {
+            let _ = writeln!(out,
+                             "        if let Some(ref mut c) = self.cache {{\n            \
+                              return c.get(Self::member(b\"{}\\0\"));\n        }}",
+                             name);
+        }
+        let _ = writeln!(out,
+                         "        self.bus.get_property(&self.dest, &self.path, Self::interface(), \
+                          Self::member(b\"{}\\0\"))",
+                         name);
+        let _ = writeln!(out, "    }}\n");
+    }
+
+    if access.contains("write") {
+        match in_type(sig) {
+            Some(t) => {
+                let _ = writeln!(out, "    /// Set the `{}` property", name);
+                let _ = writeln!(out,
+                                 "    pub fn set_{}(&mut self, value: {}) \
+                                  -> ::systemd::bus::Result<()> {{",
+                                 snake_case(name).trim_end_matches('_'),
+                                 t);
+                let _ = writeln!(out,
+                                 "        self.bus.set_property(&self.dest, &self.path, \
+                                  Self::interface(), Self::member(b\"{}\\0\"), value)?;",
+                                 name);
+                /* don't rely on the service announcing the change */
+                let _ = writeln!(out,
+                                 "        if let Some(ref c) = self.cache {{\n            \
+                                  c.invalidate(Self::member(b\"{}\\0\"));\n        }}\n        \
+                                  Ok(())",
+                                 name);
+                let _ = writeln!(out, "    }}\n");
+            }
+            None => {
+                let _ = writeln!(out,
+                                 "    // skipped setter for {}: type \"{}\" is not supported\n",
+                                 name,
+                                 sig);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn signal(out: &mut String, interface: &str, s: &Element) -> ::Result<()> {
+    let name = try!(member_name(s));
+    let outs = args(s, "out");
+    let types: Vec<&str> = outs.iter().map(|a| out_type(&a.sig)).collect();
+    let names: Vec<&str> = outs.iter().map(|a| &a.name[..]).collect();
+
+    let _ = writeln!(out,
+                     "    /// Subscribe to the `{}` signal, calling `f` with ({}) for each \
+                      emission. Unsubscribes when the returned `Slot` is dropped.",
+                     name,
+                     names.join(", "));
+    let _ = writeln!(out,
+                     "    pub fn on_{}<F>(&self, mut f: F) \
+                      -> ::systemd::Result<::systemd::bus::Slot>",
+                     snake_case(name).trim_end_matches('_'));
+    let _ = writeln!(out,
+                     "        where F: FnMut({}) -> ::systemd::bus::Result<()> + 'static",
+                     types.join(", "));
+    let _ = writeln!(out, "    {{");
+    let _ = writeln!(out,
+                     "        let rule = format!(\"type='signal',sender='{{}}',path='{{}}',\
+                      interface='{}',member='{}'\", self.dest.to_string_lossy(), \
+                      self.path.to_string_lossy());",
+                     interface,
+                     name);
+    let _ = writeln!(out,
+                     "        self.bus.add_match(&rule, \
+                      move |m: &mut ::systemd::bus::MessageRef| {{");
+    let _ = writeln!(out, "            let mut i = m.iter()?;");
+    read_args(out, "            ", &outs);
+    let r: Vec<String> = (0..outs.len()).map(|n| format!("r{}", n)).collect();
+    let _ = writeln!(out, "            f({})", r.join(", "));
+    let _ = writeln!(out, "        }})");
+    let _ = writeln!(out, "    }}\n");
+    Ok(())
+}
+
+fn interface(out: &mut String, i: &Element) -> ::Result<()> {
+    let name = try!(i.attr("name").ok_or_else(|| invalid("<interface> without a name")));
+    try!(InterfaceName::from_bytes(format!("{}\0", name).as_bytes())
+        .map_err(|err| invalid(format!("invalid interface name '{}': {}", name, err))));
+    let proxy = format!("{}Proxy", name.rsplit('.').next().unwrap());
+
+    let _ = write!(out,
+                   "/// Proxy for the `{name}` interface
+pub struct {proxy} {{
+    bus: ::systemd::bus::Bus,
+    dest: ::systemd::bus::BusNameBuf,
+    path: ::systemd::bus::ObjectPathBuf,
+    cache: Option<::systemd::bus::PropertyCache>,
+}}
+
+impl {proxy} {{
+    /// Use the object at `path` owned by `dest` on `bus`
+    pub fn new(bus: &::systemd::bus::BusRef,
+               dest: &::systemd::bus::BusName,
+               path: &::systemd::bus::ObjectPath)
+               -> Self {{
+        {proxy} {{
+            bus: bus.to_owned(),
+            dest: dest.to_owned(),
+            path: path.to_owned(),
+            cache: None,
+        }}
+    }}
+
+    /// Serve property reads from a `PropertyCache` (except for properties which change without
+    /// being announced)
+    pub fn cache_properties(&mut self) -> ::systemd::Result<()> {{
+        self.cache = Some(::systemd::bus::PropertyCache::new(&self.bus,
+                                                             &self.dest,
+                                                             &self.path,
+                                                             Self::interface())?);
+        Ok(())
+    }}
+
+    fn interface() -> &'static ::systemd::bus::InterfaceName {{
+        unsafe {{ ::systemd::bus::InterfaceName::from_bytes_unchecked(b\"{name}\\0\") }}
+    }}
+
+    #[allow(dead_code)]
+    fn member(name: &'static [u8]) -> &'static ::systemd::bus::MemberName {{
+        unsafe {{ ::systemd::bus::MemberName::from_bytes_unchecked(name) }}
+    }}
+
+",
+                   name = name,
+                   proxy = proxy);
+
+    for m in i.children_named("method") {
+        try!(method(out, m));
+    }
+    let emits_default = emits_changed_signal(i).unwrap_or("true");
+    for p in i.children_named("property") {
+        try!(property(out, p, emits_default));
+    }
+    for s in i.children_named("signal") {
+        try!(signal(out, name, s));
+    }
+    let _ = writeln!(out, "}}\n");
+    Ok(())
+}
+
+/// Generate proxies for all interfaces described by the introspection data `xml`.
+pub fn generate(xml: &str) -> ::Result<String> {
+    let root = try!(parse(xml));
+    if root.name != "node" {
+        return Err(invalid("introspection data must have a <node> root element"));
+    }
+
+    let mut out = String::from("// Generated by systemd::bus::codegen, do not edit\n\n");
+    for i in root.children_named("interface") {
+        try!(interface(&mut out, i));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+const T_XML: &'static str = r#"<!DOCTYPE node PUBLIC
+ "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+ <!-- a comment -->
+ <interface name="org.example.Thing">
+  <method name="GetUnitByPID">
+   <arg type="u" name="pid" direction="in"/>
+   <arg type="o" name="unit" direction="out"/>
+  </method>
+  <method name="Split">
+   <arg type="s" name="type"/>
+   <arg type="s" direction="out"/>
+   <arg type="as" direction="out"/>
+  </method>
+  <method name="TakeDict">
+   <arg type="a{sv}" name="d" direction="in"/>
+  </method>
+  <property name="Version" type="s" access="read">
+   <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>
+  </property>
+  <property name="Level" type="u" access="readwrite"/>
+  <property name="Load" type="d" access="read">
+   <annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="false"/>
+  </property>
+  <signal name="UnitNew">
+   <arg type="s" name="id"/>
+   <arg type="o" name="unit"/>
+  </signal>
+ </interface>
+</node>
+"#;
+
+#[test]
+fn t_parse() {
+    let n = parse(T_XML).unwrap();
+    assert_eq!(n.name, "node");
+    let i = n.children_named("interface");
+    assert_eq!(i.len(), 1);
+    assert_eq!(i[0].attr("name"), Some("org.example.Thing"));
+    assert_eq!(i[0].children_named("method").len(), 3);
+
+    assert_eq!(parse("<a x='&lt;&amp;'/>").unwrap().attr("x"), Some("<&"));
+    parse("<a><b></a>").err().unwrap();
+    parse("<a>").err().unwrap();
+}
+
+#[test]
+fn t_snake_case() {
+    assert_eq!(snake_case("GetUnitByPID"), "get_unit_by_pid");
+    assert_eq!(snake_case("ActiveState"), "active_state");
+    assert_eq!(snake_case("PIDFile"), "pid_file");
+    assert_eq!(snake_case("Type"), "type_");
+}
+
+#[test]
+fn t_generate() {
+    let code = generate(T_XML).unwrap();
+    assert!(code.contains("pub struct ThingProxy {"));
+    assert!(code.contains("pub fn get_unit_by_pid(&mut self, pid: u32) -> \
+                           ::systemd::bus::Result<::systemd::bus::ObjectPathBuf> {"));
+    assert!(code.contains("pub fn split(&mut self, type_: &str) -> \
+                           ::systemd::bus::Result<(String, ::systemd::bus::Variant)> {"));
+    assert!(code.contains("// skipped TakeDict"));
+    assert!(code.contains("pub fn version(&mut self) -> ::systemd::bus::Result<String> {"));
+    assert!(!code.contains("pub fn set_version("));
+    assert!(code.contains("pub fn set_level(&mut self, value: u32)"));
+    assert!(code.contains("return c.get(Self::member(b\"Level\\0\"));"));
+    assert!(code.contains("c.invalidate(Self::member(b\"Level\\0\"));"));
+    assert!(!code.contains("return c.get(Self::member(b\"Load\\0\"));"));
+    assert!(code.contains("pub fn on_unit_new<F>("));
+    assert!(code.contains("where F: FnMut(String, ::systemd::bus::ObjectPathBuf)"));
+
+    generate("<node><interface name=\"nodots\"/></node>").err().unwrap();
+    generate("<interface name=\"a.b\"/>").err().unwrap();
+}
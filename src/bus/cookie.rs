@@ -0,0 +1,83 @@
+/*!
+ * Correlate replies with the method calls that caused them
+ *
+ * When sending many method calls with `MessageRef::send()` and handling the replies from a single
+ * filter (see `BusRef::add_filter()`), each reply needs to be matched up with whatever state the
+ * caller had when sending. Every reply carries the cookie of the call it answers, so a map keyed
+ * by cookie is all that is needed.
+ */
+
+use std::collections::HashMap;
+use super::MessageRef;
+
+/// Holds a context `C` for each method call sent and not yet replied to.
+#[derive(Debug)]
+pub struct CookieMap<C> {
+    pending: HashMap<u64, C>,
+}
+
+impl<C> Default for CookieMap<C> {
+    fn default() -> Self {
+        CookieMap { pending: HashMap::new() }
+    }
+}
+
+impl<C> CookieMap<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the method call `m` and keep `context` until its reply is passed to `take_reply()`.
+    ///
+    /// Returns the cookie of the sent message. Seals `m`.
+    pub fn send(&mut self, m: &mut MessageRef, context: C) -> ::Result<u64> {
+        let cookie = try!(m.send());
+        self.insert(cookie, context);
+        Ok(cookie)
+    }
+
+    /// Keep `context` for the message with `cookie`, which has already been sent. Returns the
+    /// context previously stored for `cookie`, if any.
+    #[inline]
+    pub fn insert(&mut self, cookie: u64, context: C) -> Option<C> {
+        self.pending.insert(cookie, context)
+    }
+
+    /// Remove and return the context stored for `cookie`, for example when giving up on a reply.
+    #[inline]
+    pub fn remove(&mut self, cookie: u64) -> Option<C> {
+        self.pending.remove(&cookie)
+    }
+
+    /// If `reply` is a method reply or error for a message stored in this map, remove and return
+    /// its context.
+    ///
+    /// Returns `None` for other messages (signals, calls, replies to calls sent elsewhere), so
+    /// this can be called for every message passing through a filter.
+    pub fn take_reply(&mut self, reply: &MessageRef) -> Option<C> {
+        reply.reply_cookie().ok().and_then(|c| self.remove(c))
+    }
+
+    /// Number of calls still waiting for a reply
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[test]
+fn t_cookie_map() {
+    let mut m = CookieMap::new();
+    assert!(m.is_empty());
+    assert_eq!(m.insert(1, "a"), None);
+    assert_eq!(m.insert(2, "b"), None);
+    assert_eq!(m.len(), 2);
+    assert_eq!(m.remove(1), Some("a"));
+    assert_eq!(m.remove(1), None);
+    assert_eq!(m.len(), 1);
+}
@@ -0,0 +1,224 @@
+/*!
+ * Credentials of bus peers
+ *
+ * sd-bus takes credentials from the bus, which got them from the kernel when the peer connected.
+ * With `CredsMask::AUGMENT`, fields the bus does not provide are read from `/proc` instead. Those
+ * are subject to races (the peer may have exec()ed in the meantime), so security decisions should
+ * only be based on fields which are not part of `Creds::augmented_mask()`.
+ *
+ * Accessors fail with `ENODATA` if the field was not requested, and with `ENXIO` if it is not
+ * available for the peer.
+ */
+
+use ffi;
+use ffi::{c_char, c_int, gid_t, pid_t, uid_t};
+use std::ffi::CStr;
+use std::fmt;
+use std::io;
+use std::ops::{BitOr, BitOrAssign};
+use super::{BusName, MessageRef};
+use super::super::Result;
+
+/// Selects which fields to collect in `Creds`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CredsMask(u64);
+
+impl CredsMask {
+    pub const PID: CredsMask = CredsMask(1 << 0);
+    pub const UID: CredsMask = CredsMask(1 << 3);
+    pub const EUID: CredsMask = CredsMask(1 << 4);
+    pub const GID: CredsMask = CredsMask(1 << 7);
+    pub const EGID: CredsMask = CredsMask(1 << 8);
+    pub const COMM: CredsMask = CredsMask(1 << 12);
+    pub const EXE: CredsMask = CredsMask(1 << 14);
+    pub const UNIT: CredsMask = CredsMask(1 << 17);
+    pub const USER_UNIT: CredsMask = CredsMask(1 << 19);
+    pub const SESSION: CredsMask = CredsMask(1 << 21);
+    pub const OWNER_UID: CredsMask = CredsMask(1 << 22);
+    pub const EFFECTIVE_CAPS: CredsMask = CredsMask(1 << 23);
+    pub const UNIQUE_NAME: CredsMask = CredsMask(1 << 31);
+    /// Fill in fields the bus does not provide from `/proc`
+    pub const AUGMENT: CredsMask = CredsMask(1 << 63);
+
+    /// The raw `SD_BUS_CREDS_*` bits
+    #[inline]
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Whether all fields set in `other` are also set in `self`
+    #[inline]
+    pub fn contains(self, other: CredsMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for CredsMask {
+    type Output = CredsMask;
+    #[inline]
+    fn bitor(self, other: CredsMask) -> CredsMask {
+        CredsMask(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for CredsMask {
+    #[inline]
+    fn bitor_assign(&mut self, other: CredsMask) {
+        self.0 |= other.0
+    }
+}
+
+/// The credentials of a bus peer.
+pub struct Creds {
+    raw: *mut ffi::bus::sd_bus_creds,
+}
+
+impl Creds {
+    /// Take over the reference to `raw`
+    #[inline]
+    unsafe fn take_ptr(raw: *mut ffi::bus::sd_bus_creds) -> Creds {
+        Creds { raw: raw }
+    }
+
+    /// The fields that are available
+    #[inline]
+    pub fn mask(&self) -> CredsMask {
+        CredsMask(unsafe { ffi::bus::sd_bus_creds_get_mask(self.raw) })
+    }
+
+    /// The fields that were read from `/proc` rather than provided by the bus
+    #[inline]
+    pub fn augmented_mask(&self) -> CredsMask {
+        CredsMask(unsafe { ffi::bus::sd_bus_creds_get_augmented_mask(self.raw) })
+    }
+
+    pub fn pid(&self) -> Result<pid_t> {
+        let mut v = 0;
+        sd_try!(ffi::bus::sd_bus_creds_get_pid(self.raw, &mut v));
+        Ok(v)
+    }
+
+    pub fn uid(&self) -> Result<uid_t> {
+        let mut v = 0;
+        sd_try!(ffi::bus::sd_bus_creds_get_uid(self.raw, &mut v));
+        Ok(v)
+    }
+
+    pub fn euid(&self) -> Result<uid_t> {
+        let mut v = 0;
+        sd_try!(ffi::bus::sd_bus_creds_get_euid(self.raw, &mut v));
+        Ok(v)
+    }
+
+    pub fn gid(&self) -> Result<gid_t> {
+        let mut v = 0;
+        sd_try!(ffi::bus::sd_bus_creds_get_gid(self.raw, &mut v));
+        Ok(v)
+    }
+
+    pub fn egid(&self) -> Result<gid_t> {
+        let mut v = 0;
+        sd_try!(ffi::bus::sd_bus_creds_get_egid(self.raw, &mut v));
+        Ok(v)
+    }
+
+    /// The uid of the user owning the peer's login session
+    pub fn owner_uid(&self) -> Result<uid_t> {
+        let mut v = 0;
+        sd_try!(ffi::bus::sd_bus_creds_get_owner_uid(self.raw, &mut v));
+        Ok(v)
+    }
+
+    fn string(&self, get: unsafe extern "C" fn(*mut ffi::bus::sd_bus_creds,
+                                               *mut *const c_char)
+                                               -> c_int)
+              -> Result<&str> {
+        let mut p = ::std::ptr::null();
+        sd_try!(get(self.raw, &mut p));
+        unsafe { CStr::from_ptr(p) }
+            .to_str()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "credential is not UTF-8"))
+    }
+
+    /// The process name
+    pub fn comm(&self) -> Result<&str> {
+        self.string(ffi::bus::sd_bus_creds_get_comm)
+    }
+
+    /// The path of the executable
+    pub fn exe(&self) -> Result<&str> {
+        self.string(ffi::bus::sd_bus_creds_get_exe)
+    }
+
+    /// The system unit the peer runs in
+    pub fn unit(&self) -> Result<&str> {
+        self.string(ffi::bus::sd_bus_creds_get_unit)
+    }
+
+    /// The user unit the peer runs in
+    pub fn user_unit(&self) -> Result<&str> {
+        self.string(ffi::bus::sd_bus_creds_get_user_unit)
+    }
+
+    /// The login session the peer belongs to
+    pub fn session(&self) -> Result<&str> {
+        self.string(ffi::bus::sd_bus_creds_get_session)
+    }
+
+    /// The unique bus name of the peer
+    pub fn unique_name(&self) -> Result<&BusName> {
+        let mut p = ::std::ptr::null();
+        sd_try!(ffi::bus::sd_bus_creds_get_unique_name(self.raw, &mut p));
+        Ok(unsafe { BusName::from_ptr_unchecked(p) })
+    }
+
+    /// Whether the peer has the capability `cap` (one of the `CAP_*` values) in its effective set
+    pub fn has_effective_cap(&self, cap: c_int) -> Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_creds_has_effective_cap(self.raw, cap)) > 0)
+    }
+}
+
+impl Drop for Creds {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { ffi::bus::sd_bus_creds_unref(self.raw) };
+    }
+}
+
+impl Clone for Creds {
+    #[inline]
+    fn clone(&self) -> Creds {
+        Creds { raw: unsafe { ffi::bus::sd_bus_creds_ref(self.raw) } }
+    }
+}
+
+impl fmt::Debug for Creds {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Creds")
+            .field("mask", &self.mask())
+            .field("pid", &self.pid().ok())
+            .field("uid", &self.uid().ok())
+            .field("unique_name", &self.unique_name().ok())
+            .finish()
+    }
+}
+
+impl MessageRef {
+    /// Collect the fields in `mask` of the credentials of the peer which sent this message.
+    ///
+    /// Fields the message itself carries are used when possible, others are requested from the
+    /// bus.
+    pub fn query_sender_creds(&self, mask: CredsMask) -> Result<Creds> {
+        let mut c = ::std::ptr::null_mut();
+        sd_try!(ffi::bus::sd_bus_query_sender_creds(self.as_ptr() as *mut _, mask.bits(), &mut c));
+        Ok(unsafe { Creds::take_ptr(c) })
+    }
+}
+
+#[test]
+fn t_creds_mask() {
+    let m = CredsMask::PID | CredsMask::UNIQUE_NAME;
+    assert_eq!(m.bits(), (1 << 0) | (1 << 31));
+    assert!(m.contains(CredsMask::PID));
+    assert!(!m.contains(CredsMask::UID));
+}
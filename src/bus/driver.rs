@@ -0,0 +1,111 @@
+/*!
+ * Client for the message bus itself (`org.freedesktop.DBus`)
+ *
+ * sd-bus uses the bus driver on its own where it needs to (ie: `Hello()` when connecting, and
+ * `AddMatch()` in `BusRef::add_match()`). `DriverProxy` is for clients which need to talk to it
+ * directly, such as monitoring tools or tests of a broker.
+ */
+
+use std::io;
+use super::{Bus, BusName, BusNameBuf, BusRef, MemberName, Message, Result};
+use super::names::{bus, interface, path};
+use super::types::{FromSdBusMessage, ToSdBusMessage};
+
+fn invalid(what: &'static str) -> super::Error {
+    From::from(io::Error::new(io::ErrorKind::InvalidData, what))
+}
+
+/// A proxy for the bus driver.
+pub struct DriverProxy {
+    bus: Bus,
+}
+
+impl DriverProxy {
+    /// Talk to the driver of the bus `bus` is connected to.
+    pub fn new(bus: &BusRef) -> DriverProxy {
+        DriverProxy { bus: bus.to_owned() }
+    }
+
+    fn call<A: ToSdBusMessage>(&mut self, method: &[u8], args: A) -> Result<Message> {
+        let mut m = try!(self.bus.new_method_call(bus::DBUS,
+                                                  path::DBUS,
+                                                  interface::DBUS,
+                                                  MemberName::from_bytes(method).unwrap()));
+        try!(m.append(args));
+        m.call(0)
+    }
+
+    /// Call `method`, returning the single value in the reply
+    fn call_for<A, T>(&mut self, method: &[u8], args: A) -> Result<T>
+        where A: ToSdBusMessage,
+              T: for<'b> FromSdBusMessage<'b>
+    {
+        let mut reply = try!(self.call(method, args));
+        let mut i = try!(reply.iter());
+        try!(T::from_message(&mut i)).ok_or_else(|| invalid("reply is empty"))
+    }
+
+    fn property<T: for<'b> FromSdBusMessage<'b>>(&mut self, name: &[u8]) -> Result<T> {
+        self.bus.get_property(bus::DBUS,
+                              path::DBUS,
+                              interface::DBUS,
+                              MemberName::from_bytes(name).unwrap())
+    }
+
+    /// Register with the bus, returning the unique name assigned to the connection (`Hello`).
+    ///
+    /// Only needed on connections started with `BusBuilder::bus_client(false)`, for bus clients
+    /// sd-bus has already done so (and calling it again fails).
+    pub fn hello(&mut self) -> Result<BusNameBuf> {
+        self.call_for(b"Hello\0", ())
+    }
+
+    /// The unique name of the owner of `name` (`GetNameOwner`). Fails with
+    /// `org.freedesktop.DBus.Error.NameHasNoOwner` if nobody owns it.
+    pub fn get_name_owner(&mut self, name: &BusName) -> Result<BusNameBuf> {
+        self.call_for(b"GetNameOwner\0", &*name.to_string_lossy())
+    }
+
+    /// Whether anybody owns `name` (`NameHasOwner`)
+    pub fn name_has_owner(&mut self, name: &BusName) -> Result<bool> {
+        self.call_for(b"NameHasOwner\0", &*name.to_string_lossy())
+    }
+
+    /// The unique names of the connections queued for `name`, starting with its current owner
+    /// (`ListQueuedOwners`)
+    pub fn list_queued_owners(&mut self, name: &BusName) -> Result<Vec<BusNameBuf>> {
+        self.call_for(b"ListQueuedOwners\0", &*name.to_string_lossy())
+    }
+
+    /// Ask the bus to send messages matching `rule` to this connection (`AddMatch`).
+    ///
+    /// Unlike `BusRef::add_match()`, this does not install a handler for them: they are
+    /// dispatched to filters and object handlers like any other message.
+    pub fn add_match(&mut self, rule: &str) -> Result<()> {
+        try!(self.call(b"AddMatch\0", rule));
+        Ok(())
+    }
+
+    /// Remove a rule added with `add_match()` (`RemoveMatch`)
+    pub fn remove_match(&mut self, rule: &str) -> Result<()> {
+        try!(self.call(b"RemoveMatch\0", rule));
+        Ok(())
+    }
+
+    /// The unique id of the bus (`GetId`), as 32 hex digits
+    pub fn get_id(&mut self) -> Result<String> {
+        self.call_for(b"GetId\0", ())
+    }
+
+    /// Optional features the bus supports (the `Features` property), ie: `SystemdActivation`
+    pub fn features(&mut self) -> Result<Vec<String>> {
+        self.property(b"Features\0")
+    }
+
+    /// Interfaces the bus driver implements beyond `org.freedesktop.DBus` itself (the
+    /// `Interfaces` property), ie: `org.freedesktop.DBus.Monitoring`
+    pub fn interfaces(&mut self) -> Result<Vec<String>> {
+        self.property(b"Interfaces\0")
+    }
+}
+
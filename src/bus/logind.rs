@@ -0,0 +1,414 @@
+/*!
+ * Client for the login manager (`org.freedesktop.login1`)
+ *
+ * Covers scheduling shutdowns and reboots the way `shutdown(8)` does, including the wall message
+ * logind broadcasts to logged in users beforehand, putting the system to sleep and killing the
+ * processes of sessions and users. These require privileges (as decided by polkit), the `can_*()`
+ * methods tell whether the caller has them.
+ *
+ * `Seat` and `Session` cover switching between sessions, and the device access display servers
+ * (such as Wayland compositors) get by taking control of their session.
+ */
+
+use ffi::{c_int, uid_t};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::{Bus, BusRef, InterfaceName, MemberName, Message, ObjectPath, ObjectPathBuf, Result};
+use super::names::{bus, interface, path};
+use super::types::{FromSdBusMessage, OwnedFd, ToSdBusMessage, Variant};
+
+string_enum! {
+    /// What to do once a scheduled shutdown is due
+    pub enum ShutdownKind {
+        PowerOff = "poweroff",
+        Reboot = "reboot",
+        Halt = "halt",
+        /// Only announce a power off, without actually doing it
+        DryPowerOff = "dry-poweroff",
+        /// Only announce a reboot, without actually doing it
+        DryReboot = "dry-reboot",
+        /// Only announce a halt, without actually doing it
+        DryHalt = "dry-halt",
+    }
+}
+
+string_enum! {
+    /// Whether an operation is available to the caller, as answered by `Logind::can_suspend()` and
+    /// friends
+    pub enum Availability {
+        Yes = "yes",
+        No = "no",
+        /// Allowed after authenticating, ie: if the operation is requested with `interactive` set
+        Challenge = "challenge",
+        /// Not supported by the hardware or configuration
+        NotApplicable = "na",
+    }
+}
+
+string_enum! {
+    /// Which processes of a session `Logind::kill_session()` signals
+    pub enum KillWho {
+        /// Only the session leader (usually the login shell)
+        Leader = "leader",
+        All = "all",
+    }
+}
+
+/// A shutdown scheduled with `Logind::schedule_shutdown()` (or `shutdown(8)`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledShutdown {
+    pub kind: ShutdownKind,
+    pub when: SystemTime,
+}
+
+/// Decode the `ScheduledShutdown` property (`(st)`), which is `("", 0)` if none is scheduled
+fn scheduled_shutdown(v: Variant) -> Result<Option<ScheduledShutdown>> {
+    match v {
+        Variant::Struct(ref v) if v.len() == 2 => {
+            match (&v[0], &v[1]) {
+                (&Variant::String(ref kind), &Variant::UInt64(usec)) => {
+                    if kind.is_empty() {
+                        return Ok(None);
+                    }
+                    return Ok(Some(ScheduledShutdown {
+                        kind: ShutdownKind::from(&kind[..]),
+                        when: UNIX_EPOCH + Duration::from_micros(usec),
+                    }));
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    Err(From::from(io::Error::new(io::ErrorKind::InvalidData, "malformed ScheduledShutdown")))
+}
+
+/// Microseconds since the epoch, as logind expects them
+fn usec(t: SystemTime) -> Result<u64> {
+    let d = try!(t.duration_since(UNIX_EPOCH).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "time is before the epoch")
+    }));
+    Ok(::duration_usec(d))
+}
+
+/// Call `method` on the logind object at `path`
+fn call<A: ToSdBusMessage>(bus: &mut BusRef,
+                           path: &ObjectPath,
+                           interface: &InterfaceName,
+                           method: &[u8],
+                           args: A)
+                           -> Result<Message> {
+    let mut m = try!(bus.new_method_call(bus::LOGIN1,
+                                         path,
+                                         interface,
+                                         MemberName::from_bytes(method).unwrap()));
+    try!(m.append(args));
+    m.call(0)
+}
+
+/// A connection to logind.
+pub struct Logind {
+    bus: Bus,
+}
+
+impl Logind {
+    /// Talk to logind via `bus` (which should be a system bus connection).
+    pub fn new(bus: &BusRef) -> Logind {
+        Logind { bus: bus.to_owned() }
+    }
+
+    /// Talk to logind using the default system bus connection of this thread.
+    pub fn system() -> Result<Logind> {
+        Ok(Logind { bus: try!(Bus::default_system()) })
+    }
+
+    fn property<T: for<'b> FromSdBusMessage<'b>>(&mut self, name: &[u8]) -> Result<T> {
+        self.bus.get_property(bus::LOGIN1,
+                              path::LOGIN1,
+                              interface::LOGIN1_MANAGER,
+                              MemberName::from_bytes(name).unwrap())
+    }
+
+    fn call<A: ToSdBusMessage>(&mut self, method: &[u8], args: A) -> Result<Message> {
+        call(&mut self.bus, path::LOGIN1, interface::LOGIN1_MANAGER, method, args)
+    }
+
+    /// Call one of the `Get*` methods, returning the object path in the reply
+    fn object<A: ToSdBusMessage>(&mut self, method: &[u8], args: A) -> Result<ObjectPathBuf> {
+        let mut reply = try!(self.call(method, args));
+        let mut i = try!(reply.iter());
+        try!(ObjectPathBuf::from_message(&mut i)).ok_or_else(|| {
+            From::from(io::Error::new(io::ErrorKind::InvalidData, "reply is empty"))
+        })
+    }
+
+    /// Look up the seat `id` (ie: `seat0`) (`GetSeat`)
+    pub fn seat(&mut self, id: &str) -> Result<Seat> {
+        let path = try!(self.object(b"GetSeat\0", id));
+        Ok(Seat { bus: self.bus.clone(), path: path })
+    }
+
+    /// Look up the session `id` (`GetSession`). `auto` is the session of the caller (or the display
+    /// session of the calling user if the caller is not part of a session).
+    pub fn session(&mut self, id: &str) -> Result<Session> {
+        let path = try!(self.object(b"GetSession\0", id));
+        Ok(Session { bus: self.bus.clone(), path: path })
+    }
+
+    /// Make the session `session` the one in the foreground on the seat `seat`
+    /// (`ActivateSessionOnSeat`)
+    pub fn activate_session_on_seat(&mut self, session: &str, seat: &str) -> Result<()> {
+        try!(self.call(b"ActivateSessionOnSeat\0", (session, seat)));
+        Ok(())
+    }
+
+    /// Call one of the sleep methods (`Suspend`, `Hibernate`, ...). With `interactive`, polkit may
+    /// ask the user to authenticate.
+    fn sleep(&mut self, method: &[u8], interactive: bool) -> Result<()> {
+        try!(self.call(method, interactive));
+        Ok(())
+    }
+
+    /// Call one of the `Can*` methods
+    fn can(&mut self, method: &[u8]) -> Result<Availability> {
+        let mut reply = try!(self.call(method, ()));
+        let mut i = try!(reply.iter());
+        try!(Availability::from_message(&mut i)).ok_or_else(|| {
+            From::from(io::Error::new(io::ErrorKind::InvalidData, "reply is empty"))
+        })
+    }
+
+    /// Suspend the system to RAM.
+    pub fn suspend(&mut self, interactive: bool) -> Result<()> {
+        self.sleep(b"Suspend\0", interactive)
+    }
+
+    /// Hibernate the system to disk.
+    pub fn hibernate(&mut self, interactive: bool) -> Result<()> {
+        self.sleep(b"Hibernate\0", interactive)
+    }
+
+    /// Suspend the system to RAM and disk at once, resuming from disk if power is lost.
+    pub fn hybrid_sleep(&mut self, interactive: bool) -> Result<()> {
+        self.sleep(b"HybridSleep\0", interactive)
+    }
+
+    /// Suspend the system, hibernating after a delay (`HibernateDelaySec=` in `sleep.conf`).
+    pub fn suspend_then_hibernate(&mut self, interactive: bool) -> Result<()> {
+        self.sleep(b"SuspendThenHibernate\0", interactive)
+    }
+
+    /// Whether the caller may use `suspend()`
+    pub fn can_suspend(&mut self) -> Result<Availability> {
+        self.can(b"CanSuspend\0")
+    }
+
+    /// Whether the caller may use `hibernate()`
+    pub fn can_hibernate(&mut self) -> Result<Availability> {
+        self.can(b"CanHibernate\0")
+    }
+
+    /// Whether the caller may use `hybrid_sleep()`
+    pub fn can_hybrid_sleep(&mut self) -> Result<Availability> {
+        self.can(b"CanHybridSleep\0")
+    }
+
+    /// Whether the caller may use `suspend_then_hibernate()`
+    pub fn can_suspend_then_hibernate(&mut self) -> Result<Availability> {
+        self.can(b"CanSuspendThenHibernate\0")
+    }
+
+    /// Schedule `kind` for `when`, replacing any shutdown scheduled before (`ScheduleShutdown`).
+    ///
+    /// Users are warned with the wall message (if enabled) in increasing frequency as `when`
+    /// approaches, and new logins are refused during the last five minutes.
+    pub fn schedule_shutdown(&mut self, kind: ShutdownKind, when: SystemTime) -> Result<()> {
+        let when = try!(usec(when));
+        try!(self.call(b"ScheduleShutdown\0", (kind, when)));
+        Ok(())
+    }
+
+    /// Cancel the scheduled shutdown (`CancelScheduledShutdown`). Returns whether there was one.
+    pub fn cancel_scheduled_shutdown(&mut self) -> Result<bool> {
+        let mut reply = try!(self.call(b"CancelScheduledShutdown\0", ()));
+        let cancelled: Option<bool> = try!(try!(reply.iter()).read_next());
+        Ok(cancelled.unwrap_or(false))
+    }
+
+    /// The currently scheduled shutdown, if any
+    pub fn scheduled_shutdown(&mut self) -> Result<Option<ScheduledShutdown>> {
+        scheduled_shutdown(try!(self.property(b"ScheduledShutdown\0")))
+    }
+
+    /// Set the message broadcast to users ahead of a scheduled shutdown, and whether to broadcast
+    /// at all (`SetWallMessage`). An empty message makes logind use a default one.
+    pub fn set_wall_message(&mut self, message: &str, enable: bool) -> Result<()> {
+        try!(self.call(b"SetWallMessage\0", (message, enable)));
+        Ok(())
+    }
+
+    /// The message broadcast ahead of a scheduled shutdown
+    pub fn wall_message(&mut self) -> Result<String> {
+        self.property(b"WallMessage\0")
+    }
+
+    /// Whether users are warned ahead of a scheduled shutdown
+    pub fn wall_messages_enabled(&mut self) -> Result<bool> {
+        self.property(b"EnableWallMessages\0")
+    }
+
+    /// Send `signal` to the processes of the session `id` selected by `who` (`KillSession`)
+    pub fn kill_session(&mut self, id: &str, who: KillWho, signal: c_int) -> Result<()> {
+        try!(self.call(b"KillSession\0", (id, who, signal)));
+        Ok(())
+    }
+
+    /// Send `signal` to all processes of the user `uid`, in all sessions (`KillUser`)
+    pub fn kill_user(&mut self, uid: uid_t, signal: c_int) -> Result<()> {
+        try!(self.call(b"KillUser\0", (uid, signal)));
+        Ok(())
+    }
+
+    /// Log out the user `uid`, terminating all sessions and killing their processes
+    /// (`TerminateUser`)
+    pub fn terminate_user(&mut self, uid: uid_t) -> Result<()> {
+        try!(self.call(b"TerminateUser\0", uid));
+        Ok(())
+    }
+
+    /// Set whether the user manager of `uid` keeps running (and is started at boot) while the user
+    /// is not logged in (`SetUserLinger`). With `interactive`, polkit may ask the user to
+    /// authenticate.
+    pub fn set_user_linger(&mut self, uid: uid_t, enable: bool, interactive: bool) -> Result<()> {
+        try!(self.call(b"SetUserLinger\0", (uid, enable, interactive)));
+        Ok(())
+    }
+}
+
+/// A proxy for a seat (`org.freedesktop.login1.Seat`).
+pub struct Seat {
+    bus: Bus,
+    path: ObjectPathBuf,
+}
+
+impl Seat {
+    /// The object path of the seat
+    #[inline]
+    pub fn path(&self) -> &ObjectPath {
+        &self.path
+    }
+
+    fn call<A: ToSdBusMessage>(&mut self, method: &[u8], args: A) -> Result<Message> {
+        call(&mut self.bus, &self.path, interface::LOGIN1_SEAT, method, args)
+    }
+
+    /// Switch to the virtual terminal `vtnr`, activating the session running on it (`SwitchTo`)
+    pub fn switch_to(&mut self, vtnr: u32) -> Result<()> {
+        try!(self.call(b"SwitchTo\0", vtnr));
+        Ok(())
+    }
+}
+
+/// A device opened by `Session::take_device()`
+#[derive(Debug)]
+pub struct Device {
+    pub file: File,
+    /// Whether the session is in the background, in which case the device is paused until logind
+    /// sends `ResumeDevice`
+    pub inactive: bool,
+}
+
+/// A proxy for a session (`org.freedesktop.login1.Session`).
+///
+/// While a session is controlled, logind pauses and resumes its devices as the session is switched
+/// to the background and foreground, announcing that with the `PauseDevice` and `ResumeDevice`
+/// signals on the session object. `PauseDevice` with the type `pause` must be acknowledged with
+/// `pause_device_complete()`.
+pub struct Session {
+    bus: Bus,
+    path: ObjectPathBuf,
+}
+
+impl Session {
+    /// The object path of the session
+    #[inline]
+    pub fn path(&self) -> &ObjectPath {
+        &self.path
+    }
+
+    fn call<A: ToSdBusMessage>(&mut self, method: &[u8], args: A) -> Result<Message> {
+        call(&mut self.bus, &self.path, interface::LOGIN1_SESSION, method, args)
+    }
+
+    /// Become the controller of the session, which is required to take devices (`TakeControl`).
+    /// With `force`, control is taken away from any other controller (requires root).
+    ///
+    /// Control is released when the bus connection is closed.
+    pub fn take_control(&mut self, force: bool) -> Result<()> {
+        try!(self.call(b"TakeControl\0", force));
+        Ok(())
+    }
+
+    /// Give up control of the session, releasing all devices (`ReleaseControl`)
+    pub fn release_control(&mut self) -> Result<()> {
+        try!(self.call(b"ReleaseControl\0", ()));
+        Ok(())
+    }
+
+    /// Open the device node `major`:`minor` (`TakeDevice`), which the controller can't usually
+    /// open itself. Requires being the controller of the session.
+    pub fn take_device(&mut self, major: u32, minor: u32) -> Result<Device> {
+        let mut reply = try!(self.call(b"TakeDevice\0", (major, minor)));
+        let mut i = try!(reply.iter());
+        let fd = try!(OwnedFd::from_message(&mut i));
+        let inactive = try!(bool::from_message(&mut i));
+        match (fd, inactive) {
+            (Some(fd), Some(inactive)) => {
+                let file = unsafe { File::from_raw_fd(fd.into_raw_fd()) };
+                Ok(Device { file: file, inactive: inactive })
+            }
+            _ => {
+                Err(From::from(io::Error::new(io::ErrorKind::InvalidData,
+                                              "malformed TakeDevice reply")))
+            }
+        }
+    }
+
+    /// Close the device `major`:`minor` taken before (`ReleaseDevice`)
+    pub fn release_device(&mut self, major: u32, minor: u32) -> Result<()> {
+        try!(self.call(b"ReleaseDevice\0", (major, minor)));
+        Ok(())
+    }
+
+    /// Acknowledge that the device `major`:`minor` is no longer used after logind sent
+    /// `PauseDevice` (`PauseDeviceComplete`)
+    pub fn pause_device_complete(&mut self, major: u32, minor: u32) -> Result<()> {
+        try!(self.call(b"PauseDeviceComplete\0", (major, minor)));
+        Ok(())
+    }
+}
+
+#[test]
+fn t_scheduled_shutdown() {
+    let v = |kind: &str, usec| {
+        Variant::Struct(vec![Variant::String(kind.to_owned()), Variant::UInt64(usec)])
+    };
+    assert_eq!(scheduled_shutdown(v("", 0)).unwrap(), None);
+    assert_eq!(scheduled_shutdown(v("reboot", 1_500_000)).unwrap(),
+               Some(ScheduledShutdown {
+                   kind: ShutdownKind::Reboot,
+                   when: UNIX_EPOCH + Duration::from_millis(1_500),
+               }));
+    assert_eq!(scheduled_shutdown(v("soft-reboot", 0)).unwrap().unwrap().kind,
+               ShutdownKind::Other("soft-reboot".to_owned()));
+    scheduled_shutdown(Variant::UInt64(0)).err().unwrap();
+    assert_eq!(usec(UNIX_EPOCH + Duration::from_micros(42)).unwrap(), 42);
+}
+
+#[test]
+fn t_availability() {
+    assert_eq!(Availability::from("challenge"), Availability::Challenge);
+    assert_eq!(Availability::NotApplicable.as_str(), "na");
+}
@@ -0,0 +1,67 @@
+/*!
+ * Macros shared by the clients in `bus`
+ */
+
+/// Define an enum for the string values systemd uses for `$name`, passed over the bus as strings
+macro_rules! string_enum {
+    ($(#[$attr:meta])*
+     pub enum $name:ident { $($(#[$vattr:meta])* $variant:ident = $value:expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vattr])* $variant,)*
+            /// A value not known to this crate
+            Other(String),
+        }
+
+        impl $name {
+            /// The string systemd uses for this value
+            pub fn as_str(&self) -> &str {
+                match *self {
+                    $($name::$variant => $value,)*
+                    $name::Other(ref s) => s,
+                }
+            }
+        }
+
+        impl<'a> From<&'a str> for $name {
+            fn from(s: &'a str) -> $name {
+                match s {
+                    $($value => $name::$variant,)*
+                    s => $name::Other(s.to_owned()),
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                fmt.write_str(self.as_str())
+            }
+        }
+
+        impl $crate::bus::types::ToSdBusMessage for $name {
+            fn to_message(&self, m: &mut $crate::bus::MessageRef) -> $crate::Result<()> {
+                $crate::bus::types::ToSdBusMessage::to_message(&self.as_str(), m)
+            }
+
+            fn signature(&self) -> String {
+                "s".to_owned()
+            }
+        }
+
+        impl $crate::bus::types::SdBusType for $name {
+            fn type_signature() -> String {
+                "s".to_owned()
+            }
+        }
+
+        impl<'a> $crate::bus::types::FromSdBusMessage<'a> for $name {
+            fn from_message(m: &mut $crate::bus::MessageIter<'a>)
+                -> $crate::Result<Option<Self>>
+            {
+                let s: Option<String> = try!(m.read_next());
+                Ok(s.map(|s| $name::from(&s[..])))
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+/*!
+ * Client for the systemd service manager (`org.freedesktop.systemd1`)
+ *
+ * Only a small part of the manager's interface is covered so far. Anything missing can be called
+ * directly using the names in `bus::names` and `BusRef::new_method_call()`.
+ */
+
+use cgroup::{CGROUP_ROOT, Cgroup};
+use ffi::pid_t;
+use super::{Bus, BusRef, InterfaceName, MemberName, ObjectPath, ObjectPathBuf, Result};
+use super::names::{bus, interface, path};
+use super::types::FromSdBusMessage;
+use std::io;
+use std::path::Path;
+
+string_enum! {
+    /// How a new job interacts with jobs already queued, see `systemctl(1)`'s `--job-mode`
+    pub enum JobMode {
+        /// Replace conflicting queued jobs
+        Replace = "replace",
+        /// Fail if the job conflicts with queued jobs
+        Fail = "fail",
+        /// Start the unit and stop all others (only valid for start jobs)
+        Isolate = "isolate",
+        /// Like `Replace`, but the new jobs can't be replaced by later ones
+        ReplaceIrreversibly = "replace-irreversibly",
+        /// Ignore all unit dependencies
+        IgnoreDependencies = "ignore-dependencies",
+        /// Ignore requirement dependencies, but honour ordering
+        IgnoreRequirements = "ignore-requirements",
+        /// Cancel all queued jobs
+        Flush = "flush",
+        /// Also stop units triggering this one (only valid for stop jobs)
+        Triggering = "triggering",
+        /// Propagate restarts to dependent units
+        RestartDependencies = "restart-dependencies",
+    }
+}
+
+string_enum! {
+    /// Whether a unit's configuration was loaded (`LoadState`)
+    pub enum LoadState {
+        Stub = "stub",
+        Loaded = "loaded",
+        NotFound = "not-found",
+        BadSetting = "bad-setting",
+        Error = "error",
+        Merged = "merged",
+        Masked = "masked",
+    }
+}
+
+string_enum! {
+    /// The high-level state of a unit (`ActiveState`)
+    pub enum ActiveState {
+        Active = "active",
+        Reloading = "reloading",
+        Inactive = "inactive",
+        Failed = "failed",
+        Activating = "activating",
+        Deactivating = "deactivating",
+        Maintenance = "maintenance",
+        Refreshing = "refreshing",
+    }
+}
+
+string_enum! {
+    /// The unit type specific state of a unit (`SubState`)
+    ///
+    /// Only the most common states are covered, all others are represented by `Other`.
+    pub enum SubState {
+        Dead = "dead",
+        Running = "running",
+        Exited = "exited",
+        Failed = "failed",
+        Start = "start",
+        Stop = "stop",
+        Reload = "reload",
+        AutoRestart = "auto-restart",
+        Listening = "listening",
+        Mounted = "mounted",
+        Plugged = "plugged",
+        Waiting = "waiting",
+        Elapsed = "elapsed",
+        Active = "active",
+        Abandoned = "abandoned",
+    }
+}
+
+/// Turn a missing reply value into an error
+fn expect_value<T>(v: Option<T>) -> Result<T> {
+    v.ok_or_else(|| From::from(io::Error::new(io::ErrorKind::InvalidData, "reply is empty")))
+}
+
+/// The interface holding the cgroup properties (`ControlGroup`, ...) of units named `id`, for the
+/// unit types which have a cgroup.
+fn cgroup_interface(id: &str) -> Option<&'static InterfaceName> {
+    Some(match id.rsplit('.').next() {
+        Some("service") => interface::SYSTEMD1_SERVICE,
+        Some("socket") => interface::SYSTEMD1_SOCKET,
+        Some("scope") => interface::SYSTEMD1_SCOPE,
+        Some("slice") => interface::SYSTEMD1_SLICE,
+        Some("mount") => interface::SYSTEMD1_MOUNT,
+        Some("swap") => interface::SYSTEMD1_SWAP,
+        _ => return None,
+    })
+}
+
+/// Call `method` on the manager object, passing `arg` and returning the object path in the reply.
+fn call_for_path<A: super::types::ToSdBusMessage>(bus: &mut BusRef, method: &[u8], arg: A)
+    -> Result<ObjectPathBuf>
+{
+    let member = MemberName::from_bytes(method).unwrap();
+    let mut m = try!(bus.new_method_call(bus::SYSTEMD1,
+                                         path::SYSTEMD1,
+                                         interface::SYSTEMD1_MANAGER,
+                                         member));
+    try!(m.append(arg));
+    let mut reply = try!(m.call(0));
+    let mut i = try!(reply.iter());
+    expect_value(try!(ObjectPathBuf::from_message(&mut i)))
+}
+
+/// A connection to the service manager.
+pub struct Manager {
+    bus: Bus,
+}
+
+impl Manager {
+    /// Talk to the service manager available on `bus` (usually the system bus, or the user bus
+    /// for the user's service manager).
+    pub fn new(bus: &BusRef) -> Manager {
+        Manager { bus: bus.to_owned() }
+    }
+
+    /// Talk to the system service manager, using the default system bus connection of this
+    /// thread.
+    pub fn system() -> Result<Manager> {
+        Ok(Manager { bus: try!(Bus::default_system()) })
+    }
+
+    /// Look up a loaded unit by name (`GetUnit`). Fails if the unit is not loaded.
+    pub fn get_unit(&mut self, name: &str) -> Result<Unit> {
+        let path = try!(call_for_path(&mut self.bus, b"GetUnit\0", name));
+        Ok(Unit { bus: self.bus.clone(), path: path })
+    }
+
+    /// Look up a unit by name, loading it if necessary (`LoadUnit`).
+    pub fn load_unit(&mut self, name: &str) -> Result<Unit> {
+        let path = try!(call_for_path(&mut self.bus, b"LoadUnit\0", name));
+        Ok(Unit { bus: self.bus.clone(), path: path })
+    }
+
+    /// Find the unit the process `pid` belongs to (`GetUnitByPID`).
+    pub fn unit_by_pid(&mut self, pid: pid_t) -> Result<Unit> {
+        let path = try!(call_for_path(&mut self.bus, b"GetUnitByPID\0", pid as u32));
+        Ok(Unit { bus: self.bus.clone(), path: path })
+    }
+}
+
+/// Find the system unit the process `pid` belongs to.
+///
+/// Shorthand for `Manager::system()?.unit_by_pid(pid)`.
+pub fn unit_by_pid(pid: pid_t) -> Result<Unit> {
+    try!(Manager::system()).unit_by_pid(pid)
+}
+
+/// A proxy for a single unit (`org.freedesktop.systemd1.Unit`).
+pub struct Unit {
+    bus: Bus,
+    path: ObjectPathBuf,
+}
+
+impl Unit {
+    /// The object path of the unit
+    #[inline]
+    pub fn path(&self) -> &ObjectPath {
+        &self.path
+    }
+
+    fn property<T: for<'b> FromSdBusMessage<'b>>(&mut self, name: &[u8]) -> Result<T> {
+        self.bus.get_property(bus::SYSTEMD1,
+                              &self.path,
+                              interface::SYSTEMD1_UNIT,
+                              MemberName::from_bytes(name).unwrap())
+    }
+
+    /// The primary name of the unit, ie: `foo.service`
+    pub fn id(&mut self) -> Result<String> {
+        self.property(b"Id\0")
+    }
+
+    pub fn description(&mut self) -> Result<String> {
+        self.property(b"Description\0")
+    }
+
+    pub fn load_state(&mut self) -> Result<LoadState> {
+        self.property(b"LoadState\0")
+    }
+
+    pub fn active_state(&mut self) -> Result<ActiveState> {
+        self.property(b"ActiveState\0")
+    }
+
+    /// Unit type specific state, ie: `Running` or `Exited` for services
+    pub fn sub_state(&mut self) -> Result<SubState> {
+        self.property(b"SubState\0")
+    }
+
+    /// The path of the unit's cgroup below the root of the hierarchy, ie:
+    /// `/system.slice/foo.service` (the `ControlGroup` property). Empty if the unit is not active.
+    pub fn control_group(&mut self) -> Result<String> {
+        let id = try!(self.id());
+        let interface = try!(cgroup_interface(&id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "unit type has no cgroup")
+        }));
+        self.bus.get_property(bus::SYSTEMD1,
+                              &self.path,
+                              interface,
+                              MemberName::from_bytes(b"ControlGroup\0").unwrap())
+    }
+
+    /// The unit's cgroup in the mounted hierarchy.
+    ///
+    /// To start processes in it (ie: workers of a service with `Delegate=yes`) use
+    /// `Cgroup::spawn()`. With controllers enabled for its children, processes have to be started
+    /// in a child cgroup instead (see `Cgroup::create_child()`).
+    pub fn cgroup(&mut self) -> Result<Cgroup> {
+        let cg = try!(self.control_group());
+        if cg.is_empty() {
+            return Err(From::from(io::Error::new(io::ErrorKind::NotFound,
+                                                 "unit has no cgroup (not active)")));
+        }
+        Ok(Cgroup::from_path(Path::new(CGROUP_ROOT).join(cg.trim_start_matches('/'))))
+    }
+
+    /// Call one of the job creating methods (`Start`, `Stop`, ...) with the job `mode` (usually
+    /// `JobMode::Replace`), returning the path of the job.
+    fn job(&mut self, method: &[u8], mode: JobMode) -> Result<ObjectPathBuf> {
+        let mut m = try!(self.bus.new_method_call(bus::SYSTEMD1,
+                                                  &self.path,
+                                                  interface::SYSTEMD1_UNIT,
+                                                  MemberName::from_bytes(method).unwrap()));
+        try!(m.append(mode));
+        let mut reply = try!(m.call(0));
+        let mut i = try!(reply.iter());
+        expect_value(try!(ObjectPathBuf::from_message(&mut i)))
+    }
+
+    /// Enqueue a start job, returning the path of the job.
+    pub fn start(&mut self, mode: JobMode) -> Result<ObjectPathBuf> {
+        self.job(b"Start\0", mode)
+    }
+
+    /// Enqueue a stop job, returning the path of the job.
+    pub fn stop(&mut self, mode: JobMode) -> Result<ObjectPathBuf> {
+        self.job(b"Stop\0", mode)
+    }
+
+    /// Enqueue a restart job, returning the path of the job.
+    pub fn restart(&mut self, mode: JobMode) -> Result<ObjectPathBuf> {
+        self.job(b"Restart\0", mode)
+    }
+
+    /// Enqueue a reload job, returning the path of the job.
+    pub fn reload(&mut self, mode: JobMode) -> Result<ObjectPathBuf> {
+        self.job(b"Reload\0", mode)
+    }
+}
+
+#[test]
+fn t_cgroup_interface() {
+    let name = |id| cgroup_interface(id).map(|i| i.to_string_lossy().into_owned());
+    assert_eq!(name("foo.service").unwrap(), "org.freedesktop.systemd1.Service");
+    assert_eq!(name("user-1000.slice").unwrap(), "org.freedesktop.systemd1.Slice");
+    assert_eq!(name("foo.timer"), None);
+}
+
+#[test]
+fn t_string_enums() {
+    use super::types::ToSdBusMessage;
+    assert_eq!(JobMode::from("replace"), JobMode::Replace);
+    assert_eq!(JobMode::IgnoreDependencies.as_str(), "ignore-dependencies");
+    assert_eq!(LoadState::from("not-found"), LoadState::NotFound);
+    assert_eq!(ActiveState::from("sleeping"), ActiveState::Other("sleeping".to_owned()));
+    assert_eq!(ActiveState::Other("sleeping".to_owned()).to_string(), "sleeping");
+    assert_eq!(SubState::from("auto-restart"), SubState::AutoRestart);
+    assert_eq!(JobMode::Isolate.signature(), "s");
+}
@@ -2,18 +2,62 @@ extern crate utf8_cstr;
 
 use ffi;
 use ffi::{c_int, c_char, c_void};
-use std::{fmt,str};
-use std::ffi::CStr;
-use std::os::unix::io::AsRawFd;
-use std::mem::{uninitialized, transmute, forget};
+use std::{fmt,io,str};
+use std::ffi::{CStr, CString};
+use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::mem::{uninitialized, transmute, forget, size_of, align_of};
+use std::slice;
 use std::ptr;
 use std::ops::{Deref,DerefMut};
 use std::marker::PhantomData;
-use std::borrow::{Borrow,BorrowMut};
+use std::borrow::{Borrow,BorrowMut,Cow};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use std::result;
+use std::any::Any;
+use std::collections::HashMap;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use self::utf8_cstr::Utf8CStr;
-
+use event::{Event, EventRef};
+use id128::Id128;
+
+#[macro_use]
+mod macros;
+
+pub mod activation;
+pub mod aio;
+pub mod address;
+pub mod cache;
+pub mod codegen;
+pub mod cookie;
+pub mod creds;
+pub mod driver;
+pub mod logind;
+pub mod manager;
+pub mod names;
+pub mod polkit;
+pub mod resolve;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod stats;
+pub mod sysconfig;
+pub mod testing;
 pub mod types;
+pub mod vtable;
+
+pub use self::address::Address;
+pub use self::cache::PropertyCache;
+pub use self::cookie::CookieMap;
+pub use self::creds::{Creds, CredsMask};
+pub use self::stats::{MessageHook, Stats};
+pub use self::types::Variant;
+pub use self::vtable::{Authorizer, Interface, Spawn, Vtable, VtableBuilder, VtableFlags};
+#[cfg(feature = "derive")]
+pub use systemd_derive::dbus_interface;
 
 /**
  * Result type for dbus calls that contains errors returned by remote services (and local errors as
@@ -28,6 +72,192 @@ pub mod types;
  */
 pub type Result<T> = result::Result<T, Error>;
 
+/// The objects (and their interfaces and properties) returned by
+/// `org.freedesktop.DBus.ObjectManager.GetManagedObjects`
+pub type ManagedObjects =
+    HashMap<ObjectPathBuf, HashMap<InterfaceNameBuf, HashMap<String, Variant>>>;
+
+/// The contents of an `org.freedesktop.DBus.Properties.PropertiesChanged` signal
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertiesChanged {
+    /// The interface the properties belong to
+    pub interface: InterfaceNameBuf,
+    /// Properties that changed, along with their new values
+    pub changed: HashMap<String, Variant>,
+    /// Properties that changed, but whose new values were not included
+    pub invalidated: Vec<String>,
+}
+
+/// Define `$owned`, an owned version of the name type `$borrowed`, in the same way `CString` is an
+/// owned `CStr`.
+///
+/// Both types compare, hash and display as the text of the name (without the trailing nul), so
+/// owned names used as map keys can be looked up with borrowed ones.
+macro_rules! owned_name {
+    ($(#[$attr:meta])* $owned:ident, $borrowed:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone)]
+        pub struct $owned {
+            // always includes the trailing nul
+            inner: Vec<u8>,
+        }
+
+        impl $owned {
+            /// Validate `name` and take ownership of it.
+            ///
+            /// Unlike the `from_bytes()` of the borrowed type, `name` must not include a trailing
+            /// nul.
+            pub fn new<T: Into<Vec<u8>>>(name: T) -> result::Result<Self, &'static str> {
+                let mut inner = name.into();
+                if inner.contains(&b'\0') {
+                    return Err("Name must not contain nul bytes");
+                }
+                inner.push(b'\0');
+                try!($borrowed::from_bytes(&inner));
+                Ok($owned { inner: inner })
+            }
+        }
+
+        impl Deref for $owned {
+            type Target = $borrowed;
+            #[inline]
+            fn deref(&self) -> &Self::Target {
+                unsafe { $borrowed::from_bytes_unchecked(&self.inner) }
+            }
+        }
+
+        impl Borrow<$borrowed> for $owned {
+            #[inline]
+            fn borrow(&self) -> &$borrowed {
+                self
+            }
+        }
+
+        impl AsRef<$borrowed> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &$borrowed {
+                self
+            }
+        }
+
+        impl ToOwned for $borrowed {
+            type Owned = $owned;
+            #[inline]
+            fn to_owned(&self) -> Self::Owned {
+                $owned { inner: self.to_bytes_with_nul().to_owned() }
+            }
+        }
+
+        impl TryFrom<String> for $owned {
+            type Error = &'static str;
+            #[inline]
+            fn try_from(name: String) -> result::Result<Self, &'static str> {
+                $owned::new(name)
+            }
+        }
+
+        impl<'a> TryFrom<&'a str> for $owned {
+            type Error = &'static str;
+            #[inline]
+            fn try_from(name: &'a str) -> result::Result<Self, &'static str> {
+                $owned::new(name)
+            }
+        }
+
+        impl FromStr for $owned {
+            type Err = &'static str;
+            #[inline]
+            fn from_str(name: &str) -> result::Result<Self, &'static str> {
+                $owned::new(name)
+            }
+        }
+
+        impl PartialEq for $borrowed {
+            #[inline]
+            fn eq(&self, other: &$borrowed) -> bool {
+                self.to_bytes() == other.to_bytes()
+            }
+        }
+
+        impl Eq for $borrowed {}
+
+        impl PartialOrd for $borrowed {
+            #[inline]
+            fn partial_cmp(&self, other: &$borrowed) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $borrowed {
+            #[inline]
+            fn cmp(&self, other: &$borrowed) -> Ordering {
+                self.to_bytes().cmp(other.to_bytes())
+            }
+        }
+
+        impl Hash for $borrowed {
+            #[inline]
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.to_bytes().hash(state)
+            }
+        }
+
+        impl fmt::Display for $borrowed {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str(&self.to_string_lossy())
+            }
+        }
+
+        impl PartialEq for $owned {
+            #[inline]
+            fn eq(&self, other: &$owned) -> bool {
+                **self == **other
+            }
+        }
+
+        impl Eq for $owned {}
+
+        impl PartialOrd for $owned {
+            #[inline]
+            fn partial_cmp(&self, other: &$owned) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $owned {
+            #[inline]
+            fn cmp(&self, other: &$owned) -> Ordering {
+                (**self).cmp(&**other)
+            }
+        }
+
+        impl Hash for $owned {
+            #[inline]
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                (**self).hash(state)
+            }
+        }
+
+        impl fmt::Display for $owned {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                (**self).fmt(fmt)
+            }
+        }
+
+        impl $borrowed {
+            /// Validate `name`, borrowing it if it already ends in a nul and copying it (to add
+            /// one) otherwise.
+            pub fn new(name: &str) -> result::Result<Cow<$borrowed>, &'static str> {
+                if name.ends_with('\0') {
+                    $borrowed::from_bytes(name.as_bytes()).map(Cow::Borrowed)
+                } else {
+                    $owned::new(name).map(Cow::Owned)
+                }
+            }
+        }
+    }
+}
+
 /**
  * A wrapper which promises it always holds a valid dbus object path
  *
@@ -89,16 +319,145 @@ impl ObjectPath {
     }
 
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &ObjectPath {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &ObjectPath {
         transmute(b)
     }
 
     #[inline]
     pub unsafe fn from_ptr_unchecked<'b>(b: *const c_char) -> &'b ObjectPath {
-       Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes())
+       Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
+    }
+
+    /// Append `external_id` (such as a unit name or device id) to this path as a single element,
+    /// escaping any characters not allowed in object paths (`sd_bus_path_encode()`).
+    pub fn encode(&self, external_id: &str) -> super::Result<ObjectPathBuf> {
+        let id = try!(CString::new(external_id));
+        let mut r = ptr::null_mut();
+        sd_try!(ffi::bus::sd_bus_path_encode(self.as_ptr(), id.as_ptr(), &mut r));
+        Ok(ObjectPathBuf { inner: unsafe { take_malloced(r) } })
+    }
+
+    /// The reverse of `encode()`: if this path is `prefix` followed by a single element, return
+    /// the unescaped element (`sd_bus_path_decode()`).
+    pub fn decode(&self, prefix: &ObjectPath) -> super::Result<Option<String>> {
+        let mut r = ptr::null_mut();
+        if sd_try!(ffi::bus::sd_bus_path_decode(self.as_ptr(), prefix.as_ptr(), &mut r)) == 0 {
+            return Ok(None);
+        }
+        let mut id = unsafe { take_malloced(r) };
+        id.pop();
+        String::from_utf8(id)
+            .map(Some)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "external id is not UTF-8"))
+    }
+
+    /// Like `decode()`, but extracts several ids according to `template`, a path in which each
+    /// `%` stands for an escaped id (as created by `ObjectPathBuf::encode_many()`).
+    ///
+    /// Returns `None` if this path does not match `template`.
+    pub fn decode_many(&self, template: &str) -> super::Result<Option<Vec<String>>> {
+        /* mirrors sd_bus_path_decode_many(), which is variadic */
+        let mut path = self.to_bytes();
+        let mut template = template.as_bytes();
+        let mut ids = Vec::new();
+        loop {
+            /* everything up to the next '%' must match verbatim */
+            let n = template.iter().position(|&c| c == b'%').unwrap_or(template.len());
+            if !path.starts_with(&template[..n]) {
+                return Ok(None);
+            }
+            path = &path[n..];
+            template = &template[n..];
+            if template.is_empty() {
+                break;
+            }
+
+            /* the label ends at the next '/', and must end with the rest of the template label */
+            template = &template[1..];
+            let suffix = &template[..template.iter().position(|&c| c == b'/')
+                .unwrap_or(template.len())];
+            let label = &path[..path.iter().position(|&c| c == b'/').unwrap_or(path.len())];
+            if !label.ends_with(suffix) {
+                return Ok(None);
+            }
+            let escaped = &label[..label.len() - suffix.len()];
+            let p = ObjectPathBuf {
+                inner: b"/".iter().chain(escaped).chain(b"\0").cloned().collect(),
+            };
+            match try!(p.decode(ObjectPath::from_bytes(b"/\0").unwrap())) {
+                Some(id) => ids.push(id),
+                None => return Ok(None),
+            }
+            path = &path[escaped.len()..];
+        }
+        Ok(if path.is_empty() { Some(ids) } else { None })
+    }
+
+    /// The path as a `str` (paths are always ASCII)
+    fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(self.to_bytes()) }
+    }
+
+    /// The path without its last element, `None` for the root path
+    pub fn parent(&self) -> Option<ObjectPathBuf> {
+        let s = self.as_str();
+        if s == "/" {
+            return None;
+        }
+        /* paths start with '/', and keep it if that is the only one */
+        let i = s.rfind('/').unwrap();
+        Some(ObjectPathBuf::new(&s[..if i == 0 { 1 } else { i }]).unwrap())
+    }
+
+    /// Append `element` to this path. Fails if `element` is not a valid path element (use
+    /// `encode()` for arbitrary strings).
+    pub fn join(&self, element: &str) -> result::Result<ObjectPathBuf, &'static str> {
+        if element.is_empty() || element.contains('/') {
+            return Err("Element must be a single, non-empty path element");
+        }
+        let s = self.as_str();
+        let sep = if s == "/" { "" } else { "/" };
+        ObjectPathBuf::new(format!("{}{}{}", s, sep, element))
+    }
+
+    /// Iterate over the elements of the path (none for the root path)
+    pub fn components(&self) -> Components {
+        let mut inner = self.as_str().split('/');
+        /* skip the empty string before the leading '/' */
+        inner.next();
+        Components { inner: inner }
+    }
+
+    /// Whether `prefix` consists of the first elements of this path. The root path is a prefix
+    /// of every path, and `/a` is a prefix of `/a/b` but not of `/ab`.
+    pub fn starts_with(&self, prefix: &ObjectPath) -> bool {
+        let p = prefix.to_bytes();
+        let s = self.to_bytes();
+        p == b"/" || (s.starts_with(p) && (s.len() == p.len() || s[p.len()] == b'/'))
+    }
+}
+
+/// The elements of an `ObjectPath`, as returned by `ObjectPath::components()`
+#[derive(Clone, Debug)]
+pub struct Components<'a> {
+    inner: str::Split<'a, char>,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.inner.next().filter(|e| !e.is_empty())
     }
 }
 
+/// Copy a nul terminated string allocated by sd-bus (including the nul) and free it.
+unsafe fn take_malloced(p: *mut c_char) -> Vec<u8> {
+    let v = CStr::from_ptr(p).to_bytes_with_nul().to_owned();
+    ::libc::free(p as *mut c_void);
+    v
+}
+
 impl Deref for ObjectPath {
     type Target = CStr;
     #[inline]
@@ -107,6 +466,38 @@ impl Deref for ObjectPath {
     }
 }
 
+owned_name! {
+    /// An owned `ObjectPath`
+    ObjectPathBuf, ObjectPath
+}
+
+impl ObjectPathBuf {
+    /// Build a path from `template` by replacing each `%` with the next of `external_ids`,
+    /// escaped as by `ObjectPath::encode()` (like `sd_bus_path_encode_many()`).
+    pub fn encode_many(template: &str, external_ids: &[&str]) -> super::Result<ObjectPathBuf> {
+        let root = ObjectPath::from_bytes(b"/\0").unwrap();
+        let mut ids = external_ids.iter();
+        let mut path = Vec::new();
+        for c in template.bytes() {
+            if c != b'%' {
+                path.push(c);
+                continue;
+            }
+            let id = try!(ids.next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "too few ids for path template")
+            }));
+            /* skip the leading '/' and trailing nul */
+            let p = try!(root.encode(id));
+            path.extend(&p.to_bytes()[1..]);
+        }
+        if ids.next().is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "too many ids for path template"));
+        }
+        ObjectPathBuf::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+}
+
 #[test]
 fn t_path() {
     ObjectPath::from_bytes(b"/\0").unwrap();
@@ -117,6 +508,38 @@ fn t_path() {
     ObjectPath::from_bytes(b"/hello/\0").err().unwrap();
     ObjectPath::from_bytes(b"/hello/goodbye/013/4/HA\0").unwrap();
     ObjectPath::from_bytes(b"/hello/goodbye/013/4?/HA\0").err().unwrap();
+
+    let prefix = ObjectPath::from_bytes(b"/org/freedesktop/systemd1/unit\0").unwrap();
+    let p = prefix.encode("dbus.service").unwrap();
+    assert_eq!(p.to_bytes(), b"/org/freedesktop/systemd1/unit/dbus_2eservice");
+    assert_eq!(p.decode(prefix).unwrap().unwrap(), "dbus.service");
+    assert_eq!(p.decode(ObjectPath::from_bytes(b"/org/other\0").unwrap()).unwrap(), None);
+
+    let root = ObjectPath::new("/").unwrap();
+    let p = root.join("a").unwrap().join("b").unwrap();
+    assert_eq!(p.to_bytes(), b"/a/b");
+    assert_eq!(p.components().collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(root.components().count(), 0);
+    assert_eq!(p.parent().unwrap().to_bytes(), b"/a");
+    assert_eq!(p.parent().unwrap().parent().unwrap().to_bytes(), b"/");
+    assert_eq!(root.parent(), None);
+    p.join("c/d").err().unwrap();
+    p.join("").err().unwrap();
+    assert!(p.starts_with(&p.parent().unwrap()));
+    assert!(p.starts_with(&root));
+    assert!(!p.starts_with(&ObjectPath::new("/a/bc").unwrap()));
+    assert!(!ObjectPath::new("/ab").unwrap().starts_with(&ObjectPath::new("/a").unwrap()));
+
+    let p = ObjectPathBuf::encode_many("/a/%/b/x%y", &["1.2", "-"]).unwrap();
+    assert_eq!(p.to_bytes(), b"/a/_31_2e2/b/x_2dy");
+    assert_eq!(p.decode_many("/a/%/b/x%y").unwrap().unwrap(), vec!["1.2", "-"]);
+    assert_eq!(p.decode_many("/a/%/c/x%y").unwrap(), None);
+    ObjectPathBuf::encode_many("/a/%", &[]).err().unwrap();
+
+    let p = ObjectPathBuf::new("/a/b").unwrap();
+    assert_eq!(p.to_bytes(), b"/a/b");
+    assert_eq!(unsafe { ObjectPath::from_ptr_unchecked(p.as_ptr()) }.to_owned(), p);
+    ObjectPathBuf::new("/a/").err().unwrap();
 }
 
 /**
@@ -204,7 +627,7 @@ impl InterfaceName {
     ///  - `b` must be a nul terminated string
     ///  - `b` must contain a valid interface
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &InterfaceName {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &InterfaceName {
         transmute(b)
     }
 
@@ -227,6 +650,11 @@ impl Deref for InterfaceName {
     }
 }
 
+owned_name! {
+    /// An owned `InterfaceName`
+    InterfaceNameBuf, InterfaceName
+}
+
 
 #[test]
 fn t_interface() {
@@ -237,6 +665,9 @@ fn t_interface() {
     InterfaceName::from_bytes(b"A.Z.xar.yfds.d3490\0").unwrap();
     InterfaceName::from_bytes(b"a.b.c\0").unwrap();
     InterfaceName::from_bytes(b"a.b.c?\0").err().unwrap();
+
+    assert_eq!(InterfaceNameBuf::new("a.b").unwrap().to_bytes(), b"a.b");
+    InterfaceNameBuf::new("a").err().unwrap();
 }
 
 #[derive(Debug)]
@@ -326,14 +757,27 @@ impl BusName {
         return Err("Name must be terminated in a '\\0' byte (for use by sd-bus)");
     }
 
+    /// Whether this is a unique name (`:1.42`), assigned by the bus to a single connection
+    #[inline]
+    pub fn is_unique(&self) -> bool {
+        self.inner.to_bytes().first() == Some(&b':')
+    }
+
+    /// Whether this is a well-known name (`org.freedesktop.systemd1`), which connections request
+    /// and which may change owners
+    #[inline]
+    pub fn is_well_known(&self) -> bool {
+        !self.is_unique()
+    }
+
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
         transmute(b)
     }
 
     #[inline]
     pub unsafe fn from_ptr_unchecked<'a>(b: *const c_char) -> &'a Self {
-        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes())
+        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
     }
 }
 
@@ -345,6 +789,11 @@ impl Deref for BusName {
     }
 }
 
+owned_name! {
+    /// An owned `BusName`, in the same way `CString` is an owned `CStr`.
+    BusNameBuf, BusName
+}
+
 #[test]
 fn t_busname() {
     BusName::from_bytes(b"a.b\0").unwrap();
@@ -355,6 +804,22 @@ fn t_busname() {
     BusName::from_bytes(b"a.b-c.0a\0").err().unwrap();
     BusName::from_bytes(b":a.b-c\0").unwrap();
     BusName::from_bytes(b":a.b-c.1\0").unwrap();
+
+    let n = BusNameBuf::new("a.b").unwrap();
+    assert_eq!(n.to_bytes(), b"a.b");
+    assert_eq!(BusName::from_bytes(b"a.b\0").unwrap().to_owned(), n);
+    BusNameBuf::new("a.b\0").err().unwrap();
+    BusNameBuf::new("a").err().unwrap();
+
+    assert!(BusName::from_bytes(b":1.42\0").unwrap().is_unique());
+    assert!(n.is_well_known());
+    assert!(*n == *BusName::from_bytes(b"a.b\0").unwrap());
+
+    let mut names = HashMap::new();
+    names.insert(n.clone(), 1);
+    assert_eq!(names.get(BusName::from_bytes(b"a.b\0").unwrap()), Some(&1));
+    assert!(BusName::new("a.a").unwrap() < BusName::new("a.b").unwrap());
+    assert_eq!(n.to_string(), "a.b");
 }
 
 #[derive(Debug)]
@@ -410,13 +875,13 @@ impl MemberName {
     }
 
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
         transmute(b)
     }
 
     #[inline]
     pub unsafe fn from_ptr_unchecked<'a>(b: *const c_char) -> &'a Self {
-        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes())
+        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
     }
 }
 
@@ -428,6 +893,11 @@ impl Deref for MemberName {
     }
 }
 
+owned_name! {
+    /// An owned `MemberName`
+    MemberNameBuf, MemberName
+}
+
 #[test]
 fn t_member_name() {
     MemberName::from_bytes(b"abc13\0").unwrap();
@@ -436,26 +906,54 @@ fn t_member_name() {
     MemberName::from_bytes(b"abc").err().unwrap();
     MemberName::from_bytes(b"\0").err().unwrap();
     MemberName::from_bytes(b"a\0").unwrap();
+
+    assert!(match MemberName::new("Get\0").unwrap() { Cow::Borrowed(_) => true, _ => false });
+    assert_eq!(MemberName::new("Get").unwrap().to_bytes(), b"Get");
+    MemberName::new("a.b").err().unwrap();
+    assert_eq!("/a/b".parse::<ObjectPathBuf>().unwrap().to_bytes(), b"/a/b");
+    InterfaceNameBuf::try_from("a").err().unwrap();
+
+    let n = MemberNameBuf::try_from("Get".to_owned()).unwrap();
+    assert_eq!(n.to_bytes(), b"Get");
+    assert_eq!(MemberName::from_bytes(b"Get\0").unwrap().to_owned(), n);
+    MemberNameBuf::try_from("a.b".to_owned()).err().unwrap();
 }
 
-// TODO: consider providing a duplicate of this that promises it contains an error
+/// An owned `sd_bus_error`, which may or may not be set.
+///
+/// Most code only deals with `Error`, which is always set and is what sd-bus reports. This is for
+/// code calling into sd-bus directly (ie: C callbacks of its own vtables), which needs to fill in
+/// or inspect an `sd_bus_error`. The name and message are freed on drop if sd-bus allocated them.
 // We need this more general one for writing more direct interfaces into sd-bus, but most user code
 // will only encounter an error that is correctly populated by sd-bus itself.
-struct RawError {
+pub struct RawError {
     inner: ffi::bus::sd_bus_error,
 }
 
+// The name and message are either static strings or (when `need_free` is set) allocations owned by
+// this error alone. Neither is ever modified in place, only freed on drop.
+unsafe impl Send for RawError {}
+unsafe impl Sync for RawError {}
+
 pub struct Error {
     raw: RawError,
     name_len: usize,
     message_len: usize,
+    // the errno sd-bus returned along with this error (if any), exposed as our `source()`
+    os_error: Option<io::Error>,
+    local: bool,
 }
 
 impl Error {
+    /// An unset `raw` is described as `EIO`.
+    ///
     /// Unsafety:
     ///
-    /// - `raw` must be set.
-    unsafe fn from_raw(raw: RawError) -> Error {
+    /// - `raw` must satisfy the requirements of `RawError::from_raw()`.
+    unsafe fn from_raw(mut raw: RawError, errno: Option<c_int>, local: bool) -> Error {
+        if !raw.is_set() {
+            raw.set_errno(libc::EIO);
+        }
         let n = CStr::from_ptr(raw.inner.name).to_bytes_with_nul().len();
         let m = if raw.inner.message.is_null() {
             0
@@ -466,7 +964,9 @@ impl Error {
         Error {
             raw: raw,
             name_len: n,
-            message_len: m
+            message_len: m,
+            os_error: errno.map(io::Error::from_raw_os_error),
+            local: local,
         }
     }
 
@@ -476,10 +976,23 @@ impl Error {
         Error {
             raw: v,
             name_len: name.len() + 1,
-            message_len: message.map_or(0, |x| x.len() + 1)
+            message_len: message.map_or(0, |x| x.len() + 1),
+            os_error: None,
+            local: true,
         }
     }
 
+    /// A local error describing the errno `errno` (a positive value, ie: `libc::ENOENT`), named
+    /// the way sd-bus names it. Handlers can return this for errno-style failures.
+    ///
+    /// Values which aren't an errno (`0` or negative) are turned into `EIO`.
+    pub fn from_errno(errno: c_int) -> Error {
+        let errno = if errno > 0 { errno } else { libc::EIO };
+        let mut raw = RawError::new();
+        raw.set_errno(errno);
+        unsafe { Error::from_raw(raw, Some(errno), true) }
+    }
+
     pub fn name(&self) -> &Utf8CStr {
         unsafe { Utf8CStr::from_raw_parts(self.raw.inner.name, self.name_len) }
     }
@@ -493,23 +1006,93 @@ impl Error {
         }
     }
 
+    /// The errno for this error: the one sd-bus reported along with it if any, otherwise the one
+    /// sd-bus maps its name to (`EIO` for names it has no mapping for).
+    pub fn errno(&self) -> c_int {
+        match self.os_error.as_ref().and_then(|e| e.raw_os_error()) {
+            Some(errno) if errno > 0 => errno,
+            _ => self.raw.errno().unwrap_or(libc::EIO),
+        }
+    }
+
+    /// Whether the error has the name `name` (ie: one of those in `names::error`)
+    pub fn has_name(&self, name: &InterfaceName) -> bool {
+        self.name().as_bytes() == name.to_bytes()
+    }
+
+    /// Whether this is a timeout: either no reply arrived in time, or the peer reported one
+    pub fn is_timeout(&self) -> bool {
+        self.has_name(names::error::TIMEOUT) || self.has_name(names::error::TIMED_OUT) ||
+        self.has_name(names::error::NO_REPLY)
+    }
+
+    /// Whether the caller was not allowed to perform the operation
+    pub fn is_access_denied(&self) -> bool {
+        self.has_name(names::error::ACCESS_DENIED)
+    }
+
+    /// Whether the error originated in this process (a local failure, or an error created with
+    /// `new()`) rather than being received from a peer in an error reply.
+    #[inline]
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
     fn as_ptr(&self) -> *const ffi::bus::sd_bus_error {
         self.raw.as_ptr()
     }
 
     unsafe fn move_into(self, dest: *mut ffi::bus::sd_bus_error) {
-        let x = ::std::ptr::read(&self.raw.inner);
-        forget(self);
+        // only `raw` is handed over, `os_error` is still ours to drop
+        let Error { raw, .. } = self;
+        let x = ::std::ptr::read(&raw.inner);
+        forget(raw);
         *dest = x;
     }
 }
 
 impl ::std::error::Error for Error {
-    fn description(&self) -> &str {
-        match self.message() {
-            Some(m) => m.as_ref(),
-            None => self.name().as_ref(),
+    /// For errors that sd-bus reported along with an errno (local failures, and remote errors
+    /// which sd-bus maps to one), the `io::Error` for that errno.
+    fn source(&self) -> Option<&(::std::error::Error + 'static)> {
+        self.os_error.as_ref().map(|e| e as &(::std::error::Error + 'static))
+    }
+}
+
+/// Local failures (such as a malformed reply) are reported using the errno of the `io::Error`,
+/// or `EIO` if it has none (or it isn't a valid errno). The `io::Error` itself is kept as the
+/// `source()`.
+///
+/// An `io::Error` that wraps an `Error` (as produced by `From<Error> for io::Error`) gives back
+/// that `Error`.
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        if e.get_ref().map_or(false, |inner| inner.is::<Error>()) {
+            return *e.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+
+        let mut raw = RawError::new();
+        raw.set_errno(match e.raw_os_error() {
+            Some(errno) if errno > 0 => errno,
+            _ => libc::EIO,
+        });
+        let mut err = unsafe { Error::from_raw(raw, None, true) };
+        err.os_error = Some(e);
+        err
+    }
+}
+
+/// Local errors which carry an `io::Error` (those converted from one, or reported by sd-bus with
+/// an errno) give back that `io::Error`. Others are wrapped in an `io::Error` of the kind matching
+/// `errno()`.
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        if e.local {
+            if let Some(os_error) = e.os_error {
+                return os_error;
+            }
         }
+        io::Error::new(io::Error::from_raw_os_error(e.errno()).kind(), e)
     }
 }
 
@@ -519,20 +1102,38 @@ impl fmt::Debug for Error {
             .field("name", &self.name())
             .field("message", &self.message())
             .field("need_free", &self.raw.inner.need_free)
+            .field("os_error", &self.os_error)
+            .field("local", &self.local)
             .finish()
     }
 }
 
-// TODO: make this display nicer
+/// Formats as `name: message`, ie: `org.freedesktop.DBus.Error.AccessDenied: Access denied`
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self.message() {
-            Some(m) => write!(fmt, "Dbus Error: {}: {}", self.name(), m),
-            None => write!(fmt, "Dbus Error: {}", self.name())
+            Some(m) => write!(fmt, "{}: {}", self.name(), m),
+            None => write!(fmt, "{}", self.name()),
         }
     }
 }
 
+/// Compares the error name, so tests can use
+/// `assert_eq!(e, "org.freedesktop.DBus.Error.AccessDenied")`
+impl PartialEq<str> for Error {
+    fn eq(&self, name: &str) -> bool {
+        let n: &str = self.name().as_ref();
+        n == name
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Error {
+    #[inline]
+    fn eq(&self, name: &&'a str) -> bool {
+        *self == **name
+    }
+}
+
 impl Default for RawError {
     #[inline]
     fn default() -> Self {
@@ -547,28 +1148,66 @@ impl Default for RawError {
 }
 
 impl RawError {
+    /// An unset error, to be filled in by sd-bus
     #[inline]
-    fn new() -> Self {
+    pub fn new() -> Self {
         Default::default()
     }
 
-    fn into_result(self) -> Result<()> {
-        if self.is_set() {
-            Err(unsafe { Error::from_raw(self) })
-        } else {
-            Ok(())
+    /// Take ownership of `inner`, which will be freed on drop.
+    ///
+    /// Unsafety:
+    ///
+    /// - `inner` must be unset, or set by sd-bus (ie: by `sd_bus_error_set()`) or to static
+    ///   strings with `need_free` unset.
+    #[inline]
+    pub unsafe fn from_raw(inner: ffi::bus::sd_bus_error) -> RawError {
+        RawError { inner: inner }
+    }
+
+    /// Give up ownership of the underlying `sd_bus_error`, ie: to hand it to sd-bus through the
+    /// `ret_error` of a callback. It must be freed with `sd_bus_error_free()`.
+    #[inline]
+    pub fn into_raw(self) -> ffi::bus::sd_bus_error {
+        let inner = unsafe { ptr::read(&self.inner) };
+        forget(self);
+        inner
+    }
+
+    /// Combine this error with the return value `ret` of the sd-bus call that filled it in.
+    ///
+    /// If `ret` indicates failure but sd-bus didn't describe the error, it is described using the
+    /// errno. The error is considered local unless sd-bus described it differently than it would
+    /// have for the errno alone (as it does for error replies).
+    pub fn into_result(mut self, ret: c_int) -> Result<c_int> {
+        if ret >= 0 {
+            return Ok(ret);
+        }
+
+        let local = !self.is_set() || self.describes_errno(-ret);
+        if !self.is_set() {
+            self.set_errno(-ret);
         }
+        Err(unsafe { Error::from_raw(self, Some(-ret), local) })
     }
 
-    fn with(name: &Utf8CStr, message: Option<&Utf8CStr>) -> Self {
+    /// Whether this is the error `set_errno(errno)` produces
+    fn describes_errno(&self, errno: c_int) -> bool {
+        let mut e = RawError::new();
+        e.set_errno(errno);
+        self.name().map(|n| &**n) == e.name().map(|n| &**n) && self.message() == e.message()
+    }
+
+    /// An error set to `name` and `message`
+    pub fn with(name: &Utf8CStr, message: Option<&Utf8CStr>) -> Self {
         let mut v : Self = Default::default();
         v.set(name, message);
         v
     }
 
-    // XXX: if error is already set, this will not update the error
+    /// Set the error to `name` and `message`. Does nothing if the error is already set.
     // WARNING: using error_set causes strlen() usage even though we already have the lengths
-    fn set(&mut self, name: &Utf8CStr, message: Option<&Utf8CStr>) {
+    pub fn set(&mut self, name: &Utf8CStr, message: Option<&Utf8CStr>) {
         /* return value of sd_bus_error_set is calculated based on name, which we don't care about
          * */
         unsafe {
@@ -578,18 +1217,26 @@ impl RawError {
         }
     }
 
+    /// Set the error to the one describing `errno`, as named by `Error::from_errno()`. Does
+    /// nothing if the error is already set.
+    pub fn set_errno(&mut self, errno: c_int) {
+        /* returns -errno, which we already know */
+        unsafe { ffi::bus::sd_bus_error_set_errno(&mut self.inner, errno) };
+    }
+
+    /// Whether an error has been set
     #[inline]
-    fn is_set(&self) -> bool {
+    pub fn is_set(&self) -> bool {
         !self.inner.name.is_null()
     }
 
     #[inline]
-    fn as_mut_ptr(&mut self) -> *mut ffi::bus::sd_bus_error {
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::bus::sd_bus_error {
         &mut self.inner
     }
 
     #[inline]
-    fn as_ptr(&self) -> *const ffi::bus::sd_bus_error {
+    pub fn as_ptr(&self) -> *const ffi::bus::sd_bus_error {
         &self.inner
     }
 
@@ -608,15 +1255,16 @@ impl RawError {
     // reference. Consider caching length info somewhere.
     #[inline]
     pub fn message(&self) -> Option<&Utf8CStr> {
-        if self.is_set() {
-            Some(unsafe{Utf8CStr::from_ptr_unchecked(self.inner.name)})
-        } else {
+        if self.inner.message.is_null() {
             None
+        } else {
+            Some(unsafe{Utf8CStr::from_ptr_unchecked(self.inner.message)})
         }
     }
 
+    /// The errno sd-bus maps the error's name to (`EIO` for names it has no mapping for), or
+    /// `None` if the error is unset
     // TODO: check if the ffi function can fail, and if so in what way
-    #[allow(dead_code)]
     #[inline]
     pub fn errno(&self) -> Option<c_int> {
         if self.is_set() {
@@ -653,14 +1301,14 @@ impl fmt::Debug for RawError {
     }
 }
 
-// TODO: make this display nicer
+/// Formats like `Error`, or as `(no error)` if unset
 impl fmt::Display for RawError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_struct("RawError")
-            .field("name", &self.name())
-            .field("message", &self.message())
-            .field("need_free", &self.inner.need_free)
-            .finish()
+        match (self.name(), self.message()) {
+            (Some(n), Some(m)) => write!(fmt, "{}: {}", n.to_string_lossy(), m),
+            (Some(n), None) => write!(fmt, "{}", n.to_string_lossy()),
+            (None, _) => fmt.write_str("(no error)"),
+        }
     }
 }
 
@@ -668,22 +1316,103 @@ impl fmt::Display for RawError {
 fn t_raw_error() {
     let name = Utf8CStr::from_bytes(b"name\0").unwrap();
     let message = Utf8CStr::from_bytes(b"error\0").unwrap();
-    let _raw = RawError::new().set(name, Some(message));
+    let mut raw = RawError::new();
+    raw.set(name, Some(message));
+    assert_eq!(raw.message(), Some(message));
+    assert!(raw.name().is_some() && raw.errno() == Some(5));
+    let raw = unsafe { RawError::from_raw(raw.into_raw()) };
+    assert_eq!(raw.to_string(), "name: error");
+    assert!(!RawError::new().is_set());
 }
 
-/* XXX: fixme: return code does have meaning! */
-extern "C" fn raw_message_handler<F: FnMut(&mut MessageRef) -> Result<()>>(
-    msg: *mut ffi::bus::sd_bus_message,
-    userdata: *mut c_void,
-    ret_error: *mut ffi::bus::sd_bus_error) -> c_int
+#[test]
+fn t_error_errno() {
+    use std::error::Error as StdError;
+    let e = RawError::new().into_result(-2).err().unwrap();
+    let n: &str = e.name().as_ref();
+    assert_eq!(n, "org.freedesktop.DBus.Error.FileNotFound");
+    let source = e.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(source.raw_os_error(), Some(2));
+    assert!(e.is_local());
+    assert_eq!(e, "org.freedesktop.DBus.Error.FileNotFound");
+    assert!(e.has_name(names::error::FILE_NOT_FOUND));
+    assert!(RawError::new().into_result(-110).err().unwrap().is_timeout());
+    assert!(RawError::new().into_result(-13).err().unwrap().is_access_denied());
+    assert!(!e.is_timeout());
+
+    let name = Utf8CStr::from_bytes(b"org.example.Error.Failed\0").unwrap();
+    let message = Utf8CStr::from_bytes(b"it broke\0").unwrap();
+    let e = RawError::with(name, Some(message)).into_result(-5).err().unwrap();
+    assert!(!e.is_local());
+    assert_eq!(e.to_string(), "org.example.Error.Failed: it broke");
+    assert_eq!(RawError::with(name, None).to_string(), "org.example.Error.Failed");
+    assert_eq!(RawError::new().to_string(), "(no error)");
+
+    assert_eq!(RawError::new().into_result(1).ok(), Some(1));
+
+    let e = Error::from_errno(13);
+    assert!(e.is_access_denied() && e.is_local());
+    assert_eq!(e.errno(), 13);
+    assert_eq!(io::Error::from(e).raw_os_error(), Some(13));
+    let e = Error::new(name, Some(message));
+    assert_eq!(e.errno(), 5);
+    let e = io::Error::from(e);
+    assert_eq!(e.to_string(), "org.example.Error.Failed: it broke");
+    assert_eq!(Error::from(e), "org.example.Error.Failed");
+
+    fn send_sync<T: Send + Sync + 'static>(_: T) {}
+    send_sync(Error::from_errno(2));
+
+    for &errno in &[0, -2] {
+        let e = Error::from_errno(errno);
+        assert_eq!(e, "org.freedesktop.DBus.Error.IOError");
+        assert_eq!(e.errno(), libc::EIO);
+    }
+    let e = Error::from(io::Error::from_raw_os_error(0));
+    assert_eq!(e, "org.freedesktop.DBus.Error.IOError");
+    assert_eq!(e.errno(), libc::EIO);
+}
+
+#[test]
+fn t_error_from_io() {
+    use std::error::Error as StdError;
+    let e = Error::from(io::Error::new(io::ErrorKind::InvalidData, "bad"));
+    let n: &str = e.name().as_ref();
+    assert_eq!(n, "org.freedesktop.DBus.Error.IOError");
+    assert_eq!(e.source().unwrap().to_string(), "bad");
+    assert_eq!(io::Error::from(e).kind(), io::ErrorKind::InvalidData);
+}
+
+/// Read the value from the reply to `org.freedesktop.DBus.Properties.Get`
+fn read_property_reply<T>(reply: &mut MessageRef) -> Result<T>
+    where T: for<'b> types::FromSdBusMessage<'b>
 {
-    let m: &mut F = unsafe { transmute(userdata) };
-    let e = m(unsafe { MessageRef::from_mut_ptr(msg)});
+    let mut i = try!(reply.iter());
+    if !try!(i.enter_container_raw(b'v', ptr::null())) {
+        return Err(From::from(io::Error::new(io::ErrorKind::InvalidData,
+                                             "reply does not contain a value")));
+    }
+    match try!(T::from_message(&mut i)) {
+        Some(v) => Ok(v),
+        None => {
+            Err(From::from(io::Error::new(io::ErrorKind::InvalidData,
+                                          "property has an unexpected type")))
+        }
+    }
+}
 
-    match e {
+/// Convert the result of a rust handler into the return value sd-bus expects from a callback,
+/// moving any error into `ret_error`.
+///
+/// Unsafety:
+///
+///  - `ret_error` must be a valid, unset `sd_bus_error`
+/* XXX: fixme: return code does have meaning! */
+unsafe fn handler_return(r: Result<()>, ret_error: *mut ffi::bus::sd_bus_error) -> c_int {
+    match r {
         Err(e) => {
             /* XXX: this relies on ret_error not being allocated data, otherwise we'll leak. */
-            unsafe { e.move_into(ret_error) }
+            e.move_into(ret_error);
             /* If negative, sd_bus_reply_method_errno() is used, which should also work, but this
              * is more direct */
             0
@@ -696,6 +1425,31 @@ extern "C" fn raw_message_handler<F: FnMut(&mut MessageRef) -> Result<()>>(
     }
 }
 
+extern "C" fn raw_message_handler<F: FnMut(&mut MessageRef) -> Result<()>>(
+    msg: *mut ffi::bus::sd_bus_message,
+    userdata: *mut c_void,
+    ret_error: *mut ffi::bus::sd_bus_error) -> c_int
+{
+    let m: &mut F = unsafe { transmute(userdata) };
+    let e = m(unsafe { MessageRef::from_mut_ptr(msg)});
+    unsafe { handler_return(e, ret_error) }
+}
+
+/// Like `raw_message_handler()`, for callbacks which handle the message completely: returning 0
+/// would make sd-bus pass it on to the next handler, and for method calls reply with
+/// `UnknownMethod` when there is none.
+extern "C" fn raw_handled_message_handler<F: FnMut(&mut MessageRef) -> Result<()>>(
+    msg: *mut ffi::bus::sd_bus_message,
+    userdata: *mut c_void,
+    ret_error: *mut ffi::bus::sd_bus_error) -> c_int
+{
+    let m: &mut F = unsafe { transmute(userdata) };
+    match m(unsafe { MessageRef::from_mut_ptr(msg) }) {
+        Ok(()) => 1,
+        e => unsafe { handler_return(e, ret_error) },
+    }
+}
+
 pub struct Bus {
     raw: *mut ffi::bus::sd_bus,
 }
@@ -786,28 +1540,348 @@ impl Clone for Bus {
     }
 }
 
-pub struct BusRef {
-    _inner: ffi::bus::sd_bus,
+enum Transport {
+    Address(CString),
+    Fds(RawFd, RawFd),
 }
 
-impl fmt::Debug for BusRef {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_struct("BusRef").finish()
-    }
+/// Configures and starts a new, unshared bus connection.
+///
+/// This is mainly useful for talking to private sockets (peer-to-peer or a private broker), where
+/// `Bus::default()` and friends don't apply. sd-bus always puts the connection's fds into
+/// non-blocking mode.
+pub struct BusBuilder {
+    transport: Option<Transport>,
+    bus_client: bool,
+    server: Option<Id128>,
+    anonymous: bool,
+    trusted: bool,
+    timestamps: bool,
+    accept_fds: bool,
+    send_buffer: Option<usize>,
+    receive_buffer: Option<usize>,
+    cloexec: bool,
 }
 
-impl ToOwned for BusRef {
-    type Owned = Bus;
-    #[inline]
-    fn to_owned(&self) -> Self::Owned {
-        unsafe { Bus::from_ptr(self.as_ptr()) }
+impl BusBuilder {
+    fn with_transport(transport: Transport) -> BusBuilder {
+        BusBuilder {
+            transport: Some(transport),
+            bus_client: false,
+            server: None,
+            anonymous: false,
+            trusted: false,
+            timestamps: false,
+            accept_fds: true,
+            send_buffer: None,
+            receive_buffer: None,
+            cloexec: true,
+        }
     }
-}
 
-impl BusRef {
-    #[inline]
-    unsafe fn from_ptr<'a>(r: *const ffi::bus::sd_bus) -> &'a BusRef {
-        transmute(r)
+    /// Connect to `address`, given in D-Bus address syntax (`unix:path=/run/foo`).
+    ///
+    /// Use `addresses()` to avoid formatting addresses by hand.
+    pub fn address(address: &str) -> super::Result<BusBuilder> {
+        let address = try!(CString::new(address)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "address contains nul")));
+        Ok(BusBuilder::with_transport(Transport::Address(address)))
+    }
+
+    /// Connect to the first of `addresses` that accepts the connection.
+    pub fn addresses(addresses: &[Address]) -> super::Result<BusBuilder> {
+        if addresses.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no addresses given"));
+        }
+        BusBuilder::address(&Address::join_list(addresses))
+    }
+
+    /// Use an already connected socket. The connection takes ownership of `fd`.
+    pub fn fd<F: IntoRawFd>(fd: F) -> BusBuilder {
+        let fd = fd.into_raw_fd();
+        BusBuilder::with_transport(Transport::Fds(fd, fd))
+    }
+
+    /// Use separate fds for reading and writing. The connection takes ownership of both.
+    pub fn fd_pair<I: IntoRawFd, O: IntoRawFd>(input: I, output: O) -> BusBuilder {
+        BusBuilder::with_transport(Transport::Fds(input.into_raw_fd(), output.into_raw_fd()))
+    }
+
+    /// Whether the peer is a bus broker which needs to be sent `Hello()`. Defaults to `false`,
+    /// which is what direct peer-to-peer connections need.
+    #[inline]
+    pub fn bus_client(mut self, bus_client: bool) -> Self {
+        self.bus_client = bus_client;
+        self
+    }
+
+    /// Act as the server end of a peer-to-peer connection, identifying as `id` (which clients see
+    /// as the server's GUID in the address). The server waits for the client to authenticate.
+    #[inline]
+    pub fn server(mut self, id: Id128) -> Self {
+        self.server = Some(id);
+        self
+    }
+
+    /// Servers: whether to accept clients using `ANONYMOUS` authentication, which don't reveal
+    /// who they are. Clients: whether to authenticate that way. Defaults to `false`.
+    #[inline]
+    pub fn anonymous(mut self, anonymous: bool) -> Self {
+        self.anonymous = anonymous;
+        self
+    }
+
+    /// Whether the peer is trusted, skipping the access checks for methods and properties not
+    /// marked `VtableFlags::UNPRIVILEGED` (which otherwise require the peer to have the same uid,
+    /// or the capability named by the vtable). Defaults to `false`.
+    #[inline]
+    pub fn trusted(mut self, trusted: bool) -> Self {
+        self.trusted = trusted;
+        self
+    }
+
+    /// Whether to ask for timestamps and sequence numbers to be attached to received messages,
+    /// see `MessageRef::monotonic_usec()`. Defaults to `false`.
+    #[inline]
+    pub fn timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Whether to offer passing fds (`h` values) over the connection (the default). It is only
+    /// used if the peer agrees and the transport supports it, see `BusRef::can_send_fds()`.
+    #[inline]
+    pub fn accept_fds(mut self, accept_fds: bool) -> Self {
+        self.accept_fds = accept_fds;
+        self
+    }
+
+    /// Set the socket send buffer size (`SO_SNDBUF`) once the connection is started.
+    ///
+    /// sd-bus raises both buffers to 8MiB where it can, this allows picking a different size.
+    /// Note that the kernel doubles the value and caps it at `net.core.wmem_max`.
+    #[inline]
+    pub fn send_buffer(mut self, bytes: usize) -> Self {
+        self.send_buffer = Some(bytes);
+        self
+    }
+
+    /// Set the socket receive buffer size (`SO_RCVBUF`) once the connection is started.
+    ///
+    /// As with `send_buffer()`, the kernel doubles the value and caps it at
+    /// `net.core.rmem_max`.
+    #[inline]
+    pub fn receive_buffer(mut self, bytes: usize) -> Self {
+        self.receive_buffer = Some(bytes);
+        self
+    }
+
+    /// Whether fds passed to `fd()` or `fd_pair()` have close-on-exec set (the default) or
+    /// cleared. Sockets sd-bus opens for an address always have it set.
+    #[inline]
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Start the connection.
+    ///
+    /// Authentication (and `Hello()`, for bus clients) completes asynchronously, while processing
+    /// the bus.
+    pub fn build(mut self) -> super::Result<Bus> {
+        let transport = self.transport.take().unwrap();
+        let mut raw = ptr::null_mut();
+        if let Err(e) = ::ffi_result(unsafe { ffi::bus::sd_bus_new(&mut raw) }) {
+            close_transport(&transport);
+            return Err(e);
+        }
+        let bus = Bus { raw: raw };
+
+        let fds = match transport {
+            Transport::Address(ref address) => {
+                sd_try!(ffi::bus::sd_bus_set_address(raw, address.as_ptr()));
+                None
+            }
+            Transport::Fds(input, output) => {
+                let r = unsafe { ffi::bus::sd_bus_set_fd(raw, input, output) };
+                if let Err(e) = ::ffi_result(r) {
+                    close_transport(&transport);
+                    return Err(e);
+                }
+                Some((input, output))
+            }
+        };
+
+        sd_try!(ffi::bus::sd_bus_set_bus_client(raw, self.bus_client as c_int));
+        if let Some(ref id) = self.server {
+            let id = ffi::id128::sd_id128_t { bytes: *id.as_bytes() };
+            sd_try!(ffi::bus::sd_bus_set_server(raw, 1, id));
+        }
+        sd_try!(ffi::bus::sd_bus_set_anonymous(raw, self.anonymous as c_int));
+        sd_try!(ffi::bus::sd_bus_set_trusted(raw, self.trusted as c_int));
+        sd_try!(ffi::bus::sd_bus_negotiate_timestamp(raw, self.timestamps as c_int));
+        sd_try!(ffi::bus::sd_bus_negotiate_fds(raw, self.accept_fds as c_int));
+        sd_try!(ffi::bus::sd_bus_start(raw));
+
+        let (input, output) = match fds {
+            Some((input, output)) => {
+                /* sd-bus sets close-on-exec on all fds when starting */
+                if !self.cloexec {
+                    try!(clear_cloexec(input));
+                    try!(clear_cloexec(output));
+                }
+                (input, output)
+            }
+            None => {
+                let fd = try!(bus.fd());
+                (fd, fd)
+            }
+        };
+        try!(check_nonblocking(input));
+        try!(check_nonblocking(output));
+        if let Some(bytes) = self.receive_buffer {
+            try!(set_buffer_size(input, libc::SO_RCVBUF, bytes));
+        }
+        if let Some(bytes) = self.send_buffer {
+            try!(set_buffer_size(output, libc::SO_SNDBUF, bytes));
+        }
+
+        Ok(bus)
+    }
+}
+
+impl Drop for BusBuilder {
+    fn drop(&mut self) {
+        if let Some(ref transport) = self.transport {
+            close_transport(transport);
+        }
+    }
+}
+
+impl fmt::Debug for BusBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BusBuilder")
+            .field("bus_client", &self.bus_client)
+            .field("server", &self.server)
+            .field("anonymous", &self.anonymous)
+            .field("trusted", &self.trusted)
+            .field("timestamps", &self.timestamps)
+            .field("accept_fds", &self.accept_fds)
+            .field("send_buffer", &self.send_buffer)
+            .field("receive_buffer", &self.receive_buffer)
+            .field("cloexec", &self.cloexec)
+            .finish()
+    }
+}
+
+fn close_transport(transport: &Transport) {
+    if let Transport::Fds(input, output) = *transport {
+        unsafe { libc::close(input) };
+        if output != input {
+            unsafe { libc::close(output) };
+        }
+    }
+}
+
+fn clear_cloexec(fd: RawFd) -> super::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Fail unless `fd` is in non-blocking mode, which sd-bus relies on to never block in `process()`
+fn check_nonblocking(fd: RawFd) -> super::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if flags & libc::O_NONBLOCK == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "bus fd is in blocking mode"));
+    }
+    Ok(())
+}
+
+fn set_buffer_size(fd: RawFd, option: c_int, bytes: usize) -> super::Result<()> {
+    let v = if bytes > c_int::max_value() as usize { c_int::max_value() } else { bytes as c_int };
+    let r = unsafe {
+        libc::setsockopt(fd,
+                         libc::SOL_SOCKET,
+                         option,
+                         &v as *const c_int as *const c_void,
+                         size_of::<c_int>() as libc::socklen_t)
+    };
+    if r < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[test]
+fn t_bus_builder_fd() {
+    use std::os::unix::net::UnixStream;
+    let (a, _b) = UnixStream::pair().unwrap();
+    let bus = BusBuilder::fd(a).receive_buffer(64 * 1024).send_buffer(32 * 1024).build().unwrap();
+    let fd = bus.fd().unwrap();
+
+    let get = |option| {
+        let mut v: c_int = 0;
+        let mut len = size_of::<c_int>() as libc::socklen_t;
+        assert_eq!(unsafe {
+            libc::getsockopt(fd, libc::SOL_SOCKET, option, &mut v as *mut c_int as *mut c_void,
+                             &mut len)
+        }, 0);
+        v
+    };
+    /* the kernel doubles the requested size, how far depends on rmem_max/wmem_max */
+    assert!(get(libc::SO_RCVBUF) >= 64 * 1024);
+    assert!(get(libc::SO_SNDBUF) >= 32 * 1024);
+    assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } & libc::FD_CLOEXEC != 0);
+    check_nonblocking(fd).unwrap();
+
+    let (a, _b) = UnixStream::pair().unwrap();
+    let bus = BusBuilder::fd(a).cloexec(false).build().unwrap();
+    assert_eq!(unsafe { libc::fcntl(bus.fd().unwrap(), libc::F_GETFD) } & libc::FD_CLOEXEC, 0);
+}
+
+#[test]
+fn t_attach_event() {
+    use std::os::unix::net::UnixStream;
+    let (a, _b) = UnixStream::pair().unwrap();
+    let bus = BusBuilder::fd(a).build().unwrap();
+    let e = Event::new().unwrap();
+    assert!(bus.event().is_none());
+    bus.attach_event(&e, ::event::PRIORITY_NORMAL).unwrap();
+    assert_eq!(bus.event().unwrap().as_ptr(), e.as_ptr());
+    bus.attach_event(&e, ::event::PRIORITY_NORMAL).err().unwrap();
+    bus.detach_event().unwrap();
+    assert!(bus.event().is_none());
+    bus.attach_event(&e, 1 << 40).err().unwrap();
+}
+
+pub struct BusRef {
+    _inner: ffi::bus::sd_bus,
+}
+
+impl fmt::Debug for BusRef {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BusRef").finish()
+    }
+}
+
+impl ToOwned for BusRef {
+    type Owned = Bus;
+    #[inline]
+    fn to_owned(&self) -> Self::Owned {
+        unsafe { Bus::from_ptr(self.as_ptr()) }
+    }
+}
+
+impl BusRef {
+    #[inline]
+    unsafe fn from_ptr<'a>(r: *const ffi::bus::sd_bus) -> &'a BusRef {
+        transmute(r)
     }
 
     #[inline]
@@ -842,6 +1916,79 @@ impl BusRef {
         Ok(sd_try!(ffi::bus::sd_bus_get_fd(self.as_ptr())))
     }
 
+    /// Whether fds can be passed over this connection, which depends on the transport and what
+    /// was negotiated with the peer. Blocks until the connection is authenticated.
+    #[inline]
+    pub fn can_send_fds(&self) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_can_send(self.as_ptr(), b'h' as c_char)) > 0)
+    }
+
+    /// Dispatch one pending incoming message (or timeout) to its handlers.
+    ///
+    /// Returns `true` if anything was processed, in which case `process()` should be called again
+    /// before waiting with `wait()`.
+    #[inline]
+    pub fn process(&self) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_process(self.as_ptr(), ptr::null_mut())) > 0)
+    }
+
+    /// Block until there is something for `process()` to do, or `timeout` elapsed (`None` waits
+    /// indefinitely). Returns `false` on timeout.
+    #[inline]
+    pub fn wait(&self, timeout: Option<Duration>) -> super::Result<bool> {
+        let usec = timeout.map_or(!0, ::duration_usec);
+        Ok(sd_try!(ffi::bus::sd_bus_wait(self.as_ptr(), usec)) > 0)
+    }
+
+    /// Block until all queued outgoing messages have been written to the connection.
+    #[inline]
+    pub fn flush(&self) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_flush(self.as_ptr()));
+        Ok(())
+    }
+
+    /// Have `event` process this connection, dispatching its messages (and handling its timeouts)
+    /// whenever there is something to do, at `priority` (ie: `event::PRIORITY_NORMAL`). There is
+    /// no need to call `process()` or `wait()` then.
+    ///
+    /// When `event` exits, the connection is flushed and closed.
+    pub fn attach_event(&self, event: &EventRef, priority: i64) -> super::Result<()> {
+        if priority < c_int::min_value() as i64 || priority > c_int::max_value() as i64 {
+            return Err(io::Error::from_raw_os_error(libc::ERANGE));
+        }
+        sd_try!(ffi::bus::sd_bus_attach_event(self.as_ptr(), event.as_ptr(), priority as c_int));
+        Ok(())
+    }
+
+    /// Stop processing this connection from the event loop it was attached to
+    #[inline]
+    pub fn detach_event(&self) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_detach_event(self.as_ptr()));
+        Ok(())
+    }
+
+    /// The event loop this connection is attached to, if any
+    #[inline]
+    pub fn event(&self) -> Option<Event> {
+        let e = unsafe { ffi::bus::sd_bus_get_event(self.as_ptr()) };
+        if e.is_null() {
+            None
+        } else {
+            Some(unsafe { Event::from_ptr(e) })
+        }
+    }
+
+    /// Close the connection. Queued outgoing messages that have not been written yet are dropped,
+    /// so use `flush()` first where that matters.
+    #[inline]
+    pub fn close(&self) {
+        unsafe { ffi::bus::sd_bus_close(self.as_ptr()) }
+    }
+
+    /// The unique name the bus assigned to this connection.
+    ///
+    /// The first call blocks until the connection is registered with the bus. sd-bus keeps the
+    /// name after that, so later calls are cheap.
     #[inline]
     pub fn unique_name(&self) -> super::Result<&BusName> {
         let mut e = unsafe { uninitialized() };
@@ -881,8 +2028,6 @@ impl BusRef {
         Ok(unsafe { Message::take_ptr(m) })
     }
 
-    // new_method_errno
-
     // TODO: consider using a guard object for name handling
     /// This blocks. To get async behavior, use 'call_async' directly.
     #[inline]
@@ -900,31 +2045,297 @@ impl BusRef {
         Ok(())
     }
 
-    // XXX: alternates for (userdata: T):
-    //  - userdata: T, and automatically box as needed. Allows a useful external control.
-    //  - userdata: Box<T>, allows users to supply a box directly if they already have one
-    //  - userdata: &mut T, allows users to manage lifetime of passed in values direcly
-    //  - userdata: SizeMatches<*const _>, allows users to use things without a pointer indirection
-    //    (such as integer types). Not clear this is possible in rust today (1.9).
-    //  - cb: &FnMut
-    //  - cb: &CustomTrait
+    /// Register `cb` to handle all method calls to objects at `path`.
+    ///
+    /// `cb` is responsible for replying to the calls. If it returns an `Error`, that error is sent
+    /// as the reply instead.
+    ///
+    /// The callback is owned by the returned `Slot`: the object is unregistered (and `cb`
+    /// dropped) when the `Slot` is dropped.
     #[inline]
-    pub fn add_object<F: FnMut(&mut MessageRef) -> Result<()>>(&self,
-                                                                      path: &ObjectPath,
-                                                                      cb: &mut F)
-                                                                      -> super::Result<()> {
+    pub fn add_object<F: FnMut(&mut MessageRef) -> Result<()> + 'static>(&self,
+                                                                        path: &ObjectPath,
+                                                                        cb: F)
+                                                                        -> super::Result<Slot> {
         let f: extern "C" fn(*mut ffi::bus::sd_bus_message,
                              *mut c_void,
                              *mut ffi::bus::sd_bus_error)
-                             -> c_int = raw_message_handler::<F>;
+                             -> c_int = raw_handled_message_handler::<F>;
+        let mut b = Box::new(cb);
+        let u: *mut F = &mut *b;
+        let mut slot = ptr::null_mut();
         sd_try!(ffi::bus::sd_bus_add_object(self.as_ptr(),
-                                            ptr::null_mut(),
+                                            &mut slot,
                                             &*path as *const _ as *const _,
                                             Some(f),
-                                            cb as *mut _ as *mut _));
+                                            u as *mut _));
+        Ok(unsafe { Slot::take_ptr(slot, b) })
+    }
+
+    /// Register `cb` to be called for every message recieved on this bus, before it is
+    /// dispatched to any other handler.
+    ///
+    /// The callback is owned by the returned `Slot`: the filter is removed when the `Slot` is
+    /// dropped.
+    #[inline]
+    pub fn add_filter<F: FnMut(&mut MessageRef) -> Result<()> + 'static>(&self,
+                                                                        cb: F)
+                                                                        -> super::Result<Slot> {
+        let f: extern "C" fn(*mut ffi::bus::sd_bus_message,
+                             *mut c_void,
+                             *mut ffi::bus::sd_bus_error)
+                             -> c_int = raw_message_handler::<F>;
+        let mut b = Box::new(cb);
+        let u: *mut F = &mut *b;
+        let mut slot = ptr::null_mut();
+        sd_try!(ffi::bus::sd_bus_add_filter(self.as_ptr(), &mut slot, Some(f), u as *mut _));
+        Ok(unsafe { Slot::take_ptr(slot, b) })
+    }
+
+    /// Call `cb` for every incoming message matching `rule`, a D-Bus match rule such as
+    /// `"type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged'"`.
+    ///
+    /// When connected to a message bus, the rule is also registered with the bus so that the
+    /// matching messages are delivered to us. The match is removed when the `Slot` is dropped.
+    pub fn add_match<F: FnMut(&mut MessageRef) -> Result<()> + 'static>(&self,
+                                                                       rule: &str,
+                                                                       cb: F)
+                                                                       -> super::Result<Slot> {
+        let rule = try!(CString::new(rule)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul in match rule")));
+        let f: extern "C" fn(*mut ffi::bus::sd_bus_message,
+                             *mut c_void,
+                             *mut ffi::bus::sd_bus_error)
+                             -> c_int = raw_message_handler::<F>;
+        let mut b = Box::new(cb);
+        let u: *mut F = &mut *b;
+        let mut slot = ptr::null_mut();
+        sd_try!(ffi::bus::sd_bus_add_match(self.as_ptr(),
+                                           &mut slot,
+                                           rule.as_ptr(),
+                                           Some(f),
+                                           u as *mut _));
+        Ok(unsafe { Slot::take_ptr(slot, b) })
+    }
+
+    /// Ping `dest` and wait for its reply.
+    ///
+    /// Messages between two peers are delivered in order, so once this returns `dest` has received
+    /// every message we sent it before. This makes it usable as a barrier, for example in tests or
+    /// when handing over between processes.
+    pub fn barrier(&mut self, dest: &BusName) -> Result<()> {
+        let mut m = try!(self.new_method_call(dest,
+                                              ObjectPath::from_bytes(b"/\0").unwrap(),
+                                              names::interface::PEER,
+                                              MemberName::from_bytes(b"Ping\0").unwrap()));
+        try!(m.call(0));
+        Ok(())
+    }
+
+    /// Like `barrier()`, but returns immediately. `cb` is called with the outcome once the reply
+    /// arrives (from `process()`).
+    ///
+    /// Dropping the returned `Slot` before then cancels the barrier.
+    pub fn barrier_async<F: FnMut(Result<()>) + 'static>(&mut self, dest: &BusName, mut cb: F)
+        -> Result<Slot>
+    {
+        let mut m = try!(self.new_method_call(dest,
+                                              ObjectPath::from_bytes(b"/\0").unwrap(),
+                                              names::interface::PEER,
+                                              MemberName::from_bytes(b"Ping\0").unwrap()));
+        let slot = try!(m.call_async(move |reply: &mut MessageRef| {
+            cb(reply.error().map_or(Ok(()), Err));
+            Ok(())
+        }, 0));
+        Ok(slot)
+    }
+
+    /// Wait until `name` has an owner on the bus, processing messages on this connection in the
+    /// meantime. Returns `false` if `timeout` elapsed first.
+    ///
+    /// Useful for services that need a peer (such as `org.freedesktop.login1`) to be available
+    /// before they can continue.
+    pub fn wait_for_name(&mut self, name: &BusName, timeout: Option<Duration>) -> Result<bool> {
+        self.wait_for_name_owner(name, true, timeout)
+    }
+
+    /// Like `wait_for_name()`, but waits until `name` has no owner.
+    pub fn wait_for_name_lost(&mut self, name: &BusName, timeout: Option<Duration>)
+        -> Result<bool>
+    {
+        self.wait_for_name_owner(name, false, timeout)
+    }
+
+    fn wait_for_name_owner(&mut self, name: &BusName, owned: bool, timeout: Option<Duration>)
+        -> Result<bool>
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let done = Rc::new(Cell::new(false));
+
+        /* watch for changes before checking the current state, so none can be missed */
+        let rule = format!("type='signal',sender='org.freedesktop.DBus',\
+                            interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='{}'",
+                           name.to_string_lossy());
+        let d = done.clone();
+        let _slot = try!(self.add_match(&rule, move |m: &mut MessageRef| {
+            let mut i = try!(m.iter());
+            let _name: Option<String> = try!(i.read_next());
+            let _old: Option<String> = try!(i.read_next());
+            let new: Option<String> = try!(i.read_next());
+            if new.map_or(false, |n| !n.is_empty()) == owned {
+                d.set(true);
+            }
+            Ok(())
+        }));
+
+        let mut m = try!(self.new_method_call(names::bus::DBUS,
+                                              names::path::DBUS,
+                                              names::interface::DBUS,
+                                              MemberName::from_bytes(b"NameHasOwner\0").unwrap()));
+        try!(m.append(&*name.to_string_lossy()));
+        let mut reply = try!(m.call(0));
+        let has_owner: Option<bool> = try!(try!(reply.iter()).read_next());
+        if has_owner == Some(owned) {
+            return Ok(true);
+        }
+
+        while !done.get() {
+            if try!(self.process()) {
+                continue;
+            }
+            let left = match deadline {
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        return Ok(false);
+                    }
+                    Some(d - now)
+                }
+                None => None,
+            };
+            try!(self.wait(left));
+        }
+        Ok(true)
+    }
+
+    /// Call `org.freedesktop.DBus.ObjectManager.GetManagedObjects` on the object at `path` owned
+    /// by `dest`, returning every object below it along with all their interfaces and properties.
+    pub fn get_managed_objects(&mut self, dest: &BusName, path: &ObjectPath)
+        -> Result<ManagedObjects>
+    {
+        let member = MemberName::from_bytes(b"GetManagedObjects\0").unwrap();
+        let mut m = try!(self.new_method_call(dest,
+                                              path,
+                                              names::interface::OBJECT_MANAGER,
+                                              member));
+        let mut reply = try!(m.call(0));
+        let mut i = try!(reply.iter());
+        let objects = try!(i.read_container(b'a', b"{oa{sa{sv}}}\0", |i| {
+            i.read_entry(b"oa{sa{sv}}\0",
+                         |i| types::FromSdBusMessage::from_message(i),
+                         |i| {
+                             let v = try!(i.read_container(b'a', b"{sa{sv}}\0", |i| {
+                                 i.read_entry(b"sa{sv}\0",
+                                              |i| types::FromSdBusMessage::from_message(i),
+                                              |i| i.read_properties())
+                             }));
+                             Ok(v.map(|v| v.into_iter().collect()))
+                         })
+        }));
+        Ok(objects.map_or_else(HashMap::new, |v| v.into_iter().collect()))
+    }
+
+    /// Call `f` whenever the properties of `interface` on the object at `path` owned by `dest`
+    /// change. Unsubscribes when the returned `Slot` is dropped.
+    ///
+    /// This only sees changes the service announces, that is of properties flagged with
+    /// `EmitsChangedSignal` (the default) in its introspection data.
+    pub fn on_properties_changed<F>(&self,
+                                    dest: &BusName,
+                                    path: &ObjectPath,
+                                    interface: &InterfaceName,
+                                    mut f: F)
+                                    -> super::Result<Slot>
+        where F: FnMut(PropertiesChanged) -> Result<()> + 'static
+    {
+        let rule = format!("type='signal',sender='{}',path='{}',\
+                            interface='org.freedesktop.DBus.Properties',\
+                            member='PropertiesChanged',arg0='{}'",
+                           dest.to_string_lossy(),
+                           path.to_string_lossy(),
+                           interface.to_string_lossy());
+        self.add_match(&rule, move |m| {
+            let mut i = try!(m.iter());
+            let interface = try!(types::FromSdBusMessage::from_message(&mut i));
+            let changed = try!(i.read_properties());
+            let invalidated = try!(i.read_container(b'a', b"s\0", |i| unsafe {
+                i.read_string(b's')
+            }));
+            match (interface, changed, invalidated) {
+                (Some(interface), Some(changed), Some(invalidated)) => {
+                    f(PropertiesChanged {
+                        interface: interface,
+                        changed: changed,
+                        invalidated: invalidated,
+                    })
+                }
+                _ => {
+                    Err(From::from(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "malformed PropertiesChanged signal")))
+                }
+            }
+        })
+    }
+
+    /// Call `org.freedesktop.DBus.Properties.Get` for the property `name` of `interface` on the
+    /// object at `path` owned by `dest`, returning its value.
+    pub fn get_property<T>(&mut self,
+                           dest: &BusName,
+                           path: &ObjectPath,
+                           interface: &InterfaceName,
+                           name: &MemberName)
+                           -> Result<T>
+        where T: for<'b> types::FromSdBusMessage<'b>
+    {
+        let member = MemberName::from_bytes(b"Get\0").unwrap();
+        let mut m = try!(self.new_method_call(dest, path, names::interface::PROPERTIES, member));
+        try!(m.append((interface, name)));
+        let mut reply = try!(m.call(0));
+        read_property_reply(&mut reply)
+    }
+
+    /// Call `org.freedesktop.DBus.Properties.Set` to change the property `name` of `interface` on
+    /// the object at `path` owned by `dest` to `value`.
+    pub fn set_property<T: types::ToSdBusMessage>(&mut self,
+                                                  dest: &BusName,
+                                                  path: &ObjectPath,
+                                                  interface: &InterfaceName,
+                                                  name: &MemberName,
+                                                  value: T)
+                                                  -> Result<()> {
+        let member = MemberName::from_bytes(b"Set\0").unwrap();
+        let mut m = try!(self.new_method_call(dest, path, names::interface::PROPERTIES, member));
+        try!(m.append((interface, name)));
+        try!(m.append_variant(&value));
+        try!(m.call(0));
         Ok(())
     }
 
+    /// Call `org.freedesktop.DBus.Properties.GetAll` to get all properties of `interface` on the
+    /// object at `path` owned by `dest`.
+    pub fn get_all_properties(&mut self,
+                              dest: &BusName,
+                              path: &ObjectPath,
+                              interface: &InterfaceName)
+                              -> Result<HashMap<String, Variant>> {
+        let member = MemberName::from_bytes(b"GetAll\0").unwrap();
+        let mut m = try!(self.new_method_call(dest, path, names::interface::PROPERTIES, member));
+        try!(m.append(interface));
+        let mut reply = try!(m.call(0));
+        let mut i = try!(reply.iter());
+        Ok(try!(i.read_properties()).unwrap_or_else(HashMap::new))
+    }
+
     #[inline]
     pub fn add_object_manager(&self, path: &ObjectPath) -> super::Result<()> {
         sd_try!(ffi::bus::sd_bus_add_object_manager(self.as_ptr(),
@@ -933,31 +2344,144 @@ impl BusRef {
         Ok(())
     }
 
-    // pub fn add_object_vtable<T: Any + 'static>(&self,
-    //                                           path: ObjectPath,
-    //                                           interface: InterfaceName,
-    //                                           vtable: Vtable<T>,
-    //                                           userdata: T)
-    //                                           -> super::Result<()> {
-    //    let u = Box::into_raw(Box::new(userdata));
-    //    sd_try!(ffi::bus::sd_bus_add_object_vtable(self.raw,
-    //                                               ptr::null_mut(),
-    //                                               path.as_ptr() as *const _,
-    //                                               interface.as_ptr() as *const _,
-    //                                               vtable.as_ptr(),
-    //                                               Box::into_raw(Box::new(T))));
-    //    Ok(())
-    // }
+    /// Export the methods, properties and signals described by `vtable` as `interface` on the
+    /// object at `path`.
+    ///
+    /// `userdata` is the state of the object, passed to every callback in `vtable`. Both are owned
+    /// by the returned `Slot`: the interface is removed from the object when it is dropped.
+    pub fn add_object_vtable<T: 'static>(&self,
+                                         path: &ObjectPath,
+                                         interface: &InterfaceName,
+                                         vtable: Vtable<T>,
+                                         userdata: T)
+                                         -> super::Result<Slot> {
+        try!(vtable.validate());
+        let mut vtable = vtable;
+        let mut userdata = Box::new(userdata);
+        unsafe { vtable.set_userdata(&mut *userdata, interface) };
+        let mut slot = ptr::null_mut();
+        sd_try!(ffi::bus::sd_bus_add_object_vtable(self.as_ptr(),
+                                                   &mut slot,
+                                                   &*path as *const _ as *const _,
+                                                   &*interface as *const _ as *const _,
+                                                   vtable.as_ptr(),
+                                                   ptr::null_mut()));
+        Ok(unsafe { Slot::take_ptr(slot, Box::new((vtable, userdata))) })
+    }
+
+    /// Export `object` on the bus at `path`, as described by its `Interface` implementation.
+    ///
+    /// `object` is owned by the returned `Slot`, see `add_object_vtable()`.
+    #[inline]
+    pub fn add_interface<T: Interface>(&self, path: &ObjectPath, object: T)
+        -> super::Result<Slot>
+    {
+        self.add_object_vtable(path, T::interface(), T::vtable(), object)
+    }
+
+    /// Emit the signal `interface.member` from the object at `path`, with `args` as its
+    /// arguments.
+    ///
+    /// Pass a tuple to emit a signal with several arguments, or `()` for none.
+    pub fn emit_signal<A: types::ToSdBusMessage>(&mut self,
+                                                 path: &ObjectPath,
+                                                 interface: &InterfaceName,
+                                                 member: &MemberName,
+                                                 args: A)
+                                                 -> super::Result<()> {
+        let mut m = try!(self.new_signal(path, interface, member));
+        try!(m.append(args));
+        try!(m.send());
+        Ok(())
+    }
 
+    /// Emit `org.freedesktop.DBus.Properties.PropertiesChanged` for the property `name` of
+    /// `interface` on the object at `path`.
+    ///
+    /// The property must have been registered with `add_object_vtable()`. Its new value is
+    /// included in the signal by calling the property's getter.
+    pub fn emit_properties_changed(&self,
+                                   path: &ObjectPath,
+                                   interface: &InterfaceName,
+                                   name: &MemberName)
+                                   -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_emit_properties_changed(self.as_ptr(),
+                                                         &*path as *const _ as *const _,
+                                                         &*interface as *const _ as *const _,
+                                                         &*name as *const _ as *const c_char,
+                                                         ptr::null::<c_char>()));
+        Ok(())
+    }
+
+    /// Like `emit_properties_changed()`, but announces changes to all properties in `names` with
+    /// a single signal.
+    pub fn emit_properties_changed_strv(&self,
+                                        path: &ObjectPath,
+                                        interface: &InterfaceName,
+                                        names: &[&MemberName])
+                                        -> super::Result<()> {
+        let mut strv = strv(names.iter().map(|n| &**n as *const _ as *const c_char));
+        sd_try!(ffi::bus::sd_bus_emit_properties_changed_strv(self.as_ptr(),
+                                                              &*path as *const _ as *const _,
+                                                              &*interface as *const _ as *const _,
+                                                              strv.as_mut_ptr()));
+        Ok(())
+    }
+
+    /// Emit `org.freedesktop.DBus.ObjectManager.InterfacesAdded` for all interfaces of the
+    /// object at `path`, which must be below a path registered with `add_object_manager()`.
+    ///
+    /// Call this after registering all vtables of a new object.
+    #[inline]
+    pub fn emit_object_added(&self, path: &ObjectPath) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_emit_object_added(self.as_ptr(), &*path as *const _ as *const _));
+        Ok(())
+    }
+
+    /// Emit `org.freedesktop.DBus.ObjectManager.InterfacesRemoved` for all interfaces of the
+    /// object at `path`.
+    ///
+    /// Call this before removing the vtables of the object, as they are needed to find its
+    /// interfaces.
+    #[inline]
+    pub fn emit_object_removed(&self, path: &ObjectPath) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_emit_object_removed(self.as_ptr(),
+                                                     &*path as *const _ as *const _));
+        Ok(())
+    }
+
+    /// Emit `org.freedesktop.DBus.ObjectManager.InterfacesAdded` for `interfaces` of the existing
+    /// object at `path`, including the current values of their properties.
+    pub fn emit_interfaces_added(&self,
+                                 path: &ObjectPath,
+                                 interfaces: &[&InterfaceName])
+                                 -> super::Result<()> {
+        let mut strv = strv(interfaces.iter().map(|i| &**i as *const _ as *const c_char));
+        sd_try!(ffi::bus::sd_bus_emit_interfaces_added_strv(self.as_ptr(),
+                                                            &*path as *const _ as *const _,
+                                                            strv.as_mut_ptr()));
+        Ok(())
+    }
 
-    // emit_signal
-    // emit_properties_changed
-    // emit_object_added
-    // emit_object_removed
-    // emit_interfaces_added
-    // emit_interfaces_removed
+    /// Emit `org.freedesktop.DBus.ObjectManager.InterfacesRemoved` for `interfaces` of the object
+    /// at `path`.
+    pub fn emit_interfaces_removed(&self,
+                                   path: &ObjectPath,
+                                   interfaces: &[&InterfaceName])
+                                   -> super::Result<()> {
+        let mut strv = strv(interfaces.iter().map(|i| &**i as *const _ as *const c_char));
+        sd_try!(ffi::bus::sd_bus_emit_interfaces_removed_strv(self.as_ptr(),
+                                                              &*path as *const _ as *const _,
+                                                              strv.as_mut_ptr()));
+        Ok(())
+    }
+}
 
-    // track
+/// Build a NULL terminated array of strings, as used by the `_strv` variants of sd-bus functions.
+///
+/// The returned pointers borrow from whatever `names` points into.
+fn strv<I: Iterator<Item = *const c_char>>(names: I) -> Vec<*mut c_char> {
+    names.map(|n| n as *mut c_char).chain(Some(ptr::null_mut())).collect()
 }
 
 impl AsRawFd for BusRef {
@@ -967,27 +2491,136 @@ impl AsRawFd for BusRef {
     }
 }
 
-/*
-extern "C" fn raw_track_handler<F: FnMut(Track) -> c_int>(
-    track: *mut ffi::bus::sd_bus_track, userdata: *mut c_void) -> c_int
-{
-    let m : &mut F = unsafe { transmute(userdata) };
-    m(Track::from_ptr(track))
+/// A registration of a callback (or object) with a `Bus`.
+///
+/// The `Slot` owns any state (closures, userdata) passed when it was created. Dropping it
+/// unregisters the callback and then frees that state.
+pub struct Slot {
+    raw: *mut ffi::bus::sd_bus_slot,
+    _userdata: Box<Any>,
 }
 
+impl Slot {
+    /// Construct a Slot, taking over an already existing reference count on the provided pointer
+    ///
+    /// Unsafety:
+    ///
+    ///  - `userdata` must contain everything the callbacks registered via `raw` refer to.
+    #[inline]
+    unsafe fn take_ptr(raw: *mut ffi::bus::sd_bus_slot, userdata: Box<Any>) -> Slot {
+        Slot { raw: raw, _userdata: userdata }
+    }
+}
+
+impl Drop for Slot {
+    #[inline]
+    fn drop(&mut self) {
+        /* unref first so sd-bus can no longer call into `_userdata` once it is freed */
+        unsafe { ffi::bus::sd_bus_slot_unref(self.raw) };
+    }
+}
+
+impl fmt::Debug for Slot {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Slot").finish()
+    }
+}
+
+extern "C" fn raw_track_handler<F: FnMut()>(_track: *mut ffi::bus::sd_bus_track,
+                                            userdata: *mut c_void)
+                                            -> c_int {
+    if !userdata.is_null() {
+        let f: &mut F = unsafe { &mut *(userdata as *mut F) };
+        f();
+    }
+    0
+}
+
+/// Tracks a set of peers on the bus, calling a handler once all of them have disconnected.
+///
+/// Peers are added by name (unique or well-known) or as the sender of a message, typically of a
+/// method call that acquires some resource on behalf of the caller. Once every tracked name is
+/// gone (or removed again), the handler is called from `BusRef::process()`, which allows services
+/// to release resources or exit when their last client went away.
 pub struct Track {
-    raw: *mut ffi::bus::sd_bus_track
+    raw: *mut ffi::bus::sd_bus_track,
+    _handler: Box<Any>,
 }
 
 impl Track {
-    unsafe fn from_ptr(track: *mut ff::bus::sd_bus_track) {
-        Track { raw: unsafe { ffi::bus::sd_bus_tracK_ref(tracK) } }
+    /// Create an empty set of peers, calling `handler` whenever it becomes empty after peers
+    /// were added.
+    pub fn new<F: FnMut() + 'static>(bus: &BusRef, handler: F) -> super::Result<Track> {
+        let mut b = Box::new(handler);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::bus::sd_bus_track_new(bus.as_ptr(),
+                                           &mut raw,
+                                           Some(raw_track_handler::<F>),
+                                           u as *mut _));
+        Ok(Track { raw: raw, _handler: b })
+    }
+
+    /// Track `name`. Returns `false` if it already was tracked.
+    pub fn add_name(&mut self, name: &BusName) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_track_add_name(self.raw, name.as_ptr())) > 0)
+    }
+
+    /// Stop tracking `name`. Returns `false` if it was not tracked.
+    pub fn remove_name(&mut self, name: &BusName) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_track_remove_name(self.raw, name.as_ptr())) > 0)
     }
 
-    fn new<F: FnMut(Track)>(bus: &mut Bus, handler: F) -> super::Result<Track> {
+    /// Track the sender of `m`. Returns `false` if it already was tracked.
+    pub fn add_sender(&mut self, m: &mut MessageRef) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_track_add_sender(self.raw, m.as_mut_ptr())) > 0)
+    }
+
+    /// Stop tracking the sender of `m`. Returns `false` if it was not tracked.
+    pub fn remove_sender(&mut self, m: &mut MessageRef) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_track_remove_sender(self.raw, m.as_mut_ptr())) > 0)
+    }
+
+    /// The number of tracked names
+    #[inline]
+    pub fn count(&self) -> u32 {
+        unsafe { ffi::bus::sd_bus_track_count(self.raw) as u32 }
+    }
+
+    /// Whether `name` is tracked
+    pub fn contains(&self, name: &BusName) -> bool {
+        !unsafe { ffi::bus::sd_bus_track_contains(self.raw, name.as_ptr()) }.is_null()
+    }
+
+    /// All tracked names
+    pub fn names(&self) -> Vec<BusNameBuf> {
+        let mut v = Vec::new();
+        let mut n = unsafe { ffi::bus::sd_bus_track_first(self.raw) };
+        while !n.is_null() {
+            v.push(unsafe { BusName::from_ptr_unchecked(n) }.to_owned());
+            n = unsafe { ffi::bus::sd_bus_track_next(self.raw) };
+        }
+        v
+    }
+}
+
+impl Drop for Track {
+    fn drop(&mut self) {
+        /* sd-bus may hold on to the track a little longer (while dispatching), keep it from calling
+         * into the handler once that is freed */
+        unsafe {
+            ffi::bus::sd_bus_track_set_userdata(self.raw, ptr::null_mut());
+            ffi::bus::sd_bus_track_unref(self.raw);
+        }
+    }
+}
+
+impl fmt::Debug for Track {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = self.names().iter().map(|n| n.to_string_lossy().into_owned()).collect();
+        fmt.debug_struct("Track").field("names", &names).finish()
     }
 }
-*/
 
 /*
  * TODO: determine if the lifetime of a message is tied to the lifetime of the bus used to create
@@ -1001,6 +2634,139 @@ pub struct Message {
     raw: *mut ffi::bus::sd_bus_message,
 }
 
+/// Byte order of a message on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// The byte order of this machine, which sd-bus uses for the messages it builds
+    pub fn native() -> ByteOrder {
+        if cfg!(target_endian = "little") {
+            ByteOrder::Little
+        } else {
+            ByteOrder::Big
+        }
+    }
+}
+
+/// The fixed part of a message header which sd-bus has no getters for. See
+/// `MessageRef::wire_header()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WireHeader {
+    pub byte_order: ByteOrder,
+    /// The raw header flags: `NO_REPLY_EXPECTED` (1), `NO_AUTO_START` (2) and
+    /// `ALLOW_INTERACTIVE_AUTHORIZATION` (4)
+    pub flags: u8,
+    /// Major protocol version, 1 for all D-Bus messages
+    pub version: u8,
+}
+
+impl WireHeader {
+    /// Parse the first line of a message dumped with `sd_bus_message_dump()`, ie:
+    /// `Type=signal  Endian=l  Flags=1  Version=1 ...`
+    fn parse(dump: &str) -> Option<WireHeader> {
+        let line = dump.lines().next().unwrap_or("");
+        let field = |name: &str| {
+            line.split_whitespace().filter_map(|w| {
+                let mut kv = w.splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some(k), Some(v)) if k == name => Some(v),
+                    _ => None,
+                }
+            }).next()
+        };
+        Some(WireHeader {
+            byte_order: match field("Endian") {
+                Some("l") => ByteOrder::Little,
+                Some("B") => ByteOrder::Big,
+                _ => return None,
+            },
+            flags: match field("Flags").and_then(|f| f.parse().ok()) {
+                Some(f) => f,
+                None => return None,
+            },
+            version: match field("Version").and_then(|v| v.parse().ok()) {
+                Some(v) => v,
+                None => return None,
+            },
+        })
+    }
+}
+
+#[test]
+fn t_wire_header_parse() {
+    let h = WireHeader::parse("\u{2023} Type=signal  Endian=B  Flags=1  Version=1 Cookie=2\n  x\n");
+    assert_eq!(h, Some(WireHeader { byte_order: ByteOrder::Big, flags: 1, version: 1 }));
+    /* with colors, as printed to a terminal */
+    let h = WireHeader::parse("\x1b[0;32m\u{2023}\x1b[0m Type=\x1b[1mmethod_call\x1b[0m  \
+                               Endian=l  Flags=0  Version=1  Cookie=5");
+    assert_eq!(h, Some(WireHeader { byte_order: ByteOrder::Little, flags: 0, version: 1 }));
+    assert_eq!(WireHeader::parse("Type=signal  Endian=x  Flags=1  Version=1"), None);
+    assert_eq!(WireHeader::parse("Type=signal  Endian=l  Flags=1"), None);
+    assert_eq!(WireHeader::parse("Type=signal\n  Endian=l  Flags=1  Version=1"), None);
+    assert_eq!(WireHeader::parse(""), None);
+}
+
+/// Where reading a message has got to: the number of elements left in each container the read
+/// position is in, innermost first.
+///
+/// sd-bus has no way to save the read position, but it can be found again by counting elements.
+struct ReadPosition(Vec<usize>);
+
+#[test]
+fn t_wire_header() {
+    use std::os::unix::io::IntoRawFd;
+    use std::os::unix::net::UnixStream;
+
+    /* a connection still waiting for authentication is enough to build and seal messages */
+    let (a, _b) = UnixStream::pair().unwrap();
+    let fd = a.into_raw_fd();
+    let mut bus = unsafe {
+        let mut raw = ptr::null_mut();
+        assert!(ffi::bus::sd_bus_new(&mut raw) >= 0);
+        let bus = Bus { raw: raw };
+        assert!(ffi::bus::sd_bus_set_fd(raw, fd, fd) >= 0);
+        assert!(ffi::bus::sd_bus_start(raw) >= 0);
+        bus
+    };
+    let path = ObjectPath::from_bytes(b"/org/example/test\0").unwrap();
+    let interface = InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = MemberName::from_bytes(b"Changed\0").unwrap();
+    let mut m = bus.new_signal(path, interface, member).unwrap();
+    let p = m.as_mut_ptr();
+    m.append(1u32).unwrap();
+    unsafe {
+        ffi::bus::sd_bus_message_open_container(p, b'a' as c_char, b"u\0".as_ptr() as *const _);
+    }
+    m.append(2u32).unwrap();
+    m.append(3u32).unwrap();
+    unsafe { ffi::bus::sd_bus_message_close_container(p) };
+    m.append(4u32).unwrap();
+
+    /* signals don't expect replies */
+    let native = WireHeader { byte_order: ByteOrder::native(), flags: 1, version: 1 };
+    assert_eq!(m.wire_header().unwrap(), native);
+    m.set_auto_start(false).unwrap();
+    m.send().unwrap();
+    assert_eq!(m.wire_header().unwrap(), WireHeader { flags: 3, ..native });
+
+    /* the read position survives, even within a container */
+    let other = m.clone();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.read_next::<u32>().unwrap(), Some(1));
+    {
+        let mut c = i.enter_container(b'a', Some("u")).unwrap().unwrap();
+        assert_eq!(c.read_next::<u32>().unwrap(), Some(2));
+        assert_eq!(other.wire_header().unwrap().flags, 3);
+        assert_eq!(c.read_next::<u32>().unwrap(), Some(3));
+        c.exit().unwrap();
+    }
+    assert_eq!(i.read_next::<u32>().unwrap(), Some(4));
+}
+
 /// A reference to a `Message`
 pub struct MessageRef {
     _inner: ffi::bus::sd_bus_message
@@ -1012,7 +2778,9 @@ pub struct MessageRef {
 /// properly.
 pub struct MessageIter<'a> {
     raw: *mut ffi::bus::sd_bus_message,
-    life: PhantomData<&'a MessageRef>
+    life: PhantomData<&'a MessageRef>,
+    // set once reading a value as an `Iterator` failed, so the same error isn't yielded forever
+    failed: bool,
 }
 
 impl Message {
@@ -1082,6 +2850,71 @@ impl BorrowMut<MessageRef> for Message {
 // to have multiple mutable references to the same data). This issue is all over the place in
 // sd-bus.
 //
+impl fmt::Debug for Message {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// Shows the header and body of the message as `MessageRef::dump()` does, leaving the read position
+/// alone.
+///
+/// Messages which are not sealed yet can't be dumped, for those only the type, addressing fields
+/// and signature are shown.
+impl fmt::Debug for MessageRef {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Ok(dump) = self.dump() {
+            return fmt.write_str(dump.trim_right());
+        }
+        let mut t = 0;
+        unsafe { ffi::bus::sd_bus_message_get_type(self.as_ptr() as *mut _, &mut t) };
+        let t = match t {
+            1 => "method_call",
+            2 => "method_return",
+            3 => "error",
+            4 => "signal",
+            _ => "unknown",
+        };
+        fmt.debug_struct("MessageRef")
+            .field("type", &t)
+            .field("cookie", &self.cookie().ok())
+            .field("reply_cookie", &self.reply_cookie().ok())
+            .field("sender", &self.sender().map(|n| &**n))
+            .field("destination", &self.destination().map(|n| &**n))
+            .field("path", &self.path().map(|p| &**p))
+            .field("interface", &self.interface().map(|i| &**i))
+            .field("member", &self.member().map(|m| &**m))
+            .field("error", &self.error().map(|e| e.name().to_string()))
+            .field("signature", &self.signature())
+            .finish()
+    }
+}
+
+#[test]
+fn t_message_debug() {
+    let mut client = testing::serve_echo().unwrap();
+    let mut call = testing::new_call(&mut client, "Echo", ("a", 2u32)).unwrap();
+    let d = format!("{:?}", call);
+    assert!(d.contains("type: \"method_call\""), "{}", d);
+    assert!(d.contains("member: Some(\"Echo\")"), "{}", d);
+    let mut reply = call.call(5_000_000).unwrap();
+
+    /* formatting shows the header and body, and leaves the read position alone */
+    assert_eq!(reply.iter().unwrap().read_next::<String>().unwrap().unwrap(), "a");
+    let d = format!("{:?}", reply);
+    assert!(d.contains("Type=method_return"), "{}", d);
+    assert!(d.contains("MESSAGE \"su\""), "{}", d);
+    assert!(d.contains("STRING \"a\";") && d.contains("UINT32 2;"), "{}", d);
+    assert_eq!(reply.iter().unwrap().read_next::<u32>().unwrap(), Some(2));
+
+    /* so does dumping */
+    reply.iter().unwrap().rewind(true).unwrap();
+    assert_eq!(reply.iter().unwrap().read_next::<String>().unwrap().unwrap(), "a");
+    let body = |d: &str| d.trim_right().lines().skip(1).map(str::to_owned).collect::<Vec<_>>();
+    assert_eq!(body(&reply.dump().unwrap()), body(&d));
+    assert_eq!(reply.iter().unwrap().read_next::<u32>().unwrap(), Some(2));
+}
+
 impl ToOwned for MessageRef {
     type Owned = Message;
     #[inline]
@@ -1138,36 +2971,329 @@ impl MessageRef {
     ///
     /// ---
     ///
-    /// This controls the NO_AUTO_START dbus header flag.
+    /// This controls the NO_AUTO_START dbus header flag.
+    ///
+    /// The
+    /// [specification](https://dbus.freedesktop.org/doc/dbus-specification.html#message-bus-starting-services)
+    /// covers some details about the auto start mechanism, but not all of it is specified.
+    #[inline]
+    pub fn set_auto_start(&mut self, yes: bool) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_set_auto_start(self.as_mut_ptr(), yes as c_int));
+        Ok(())
+    }
+
+    /// Whether a reply to this method call is expected. `true` unless changed with
+    /// `set_expect_reply()`.
+    #[inline]
+    pub fn expect_reply(&self) -> bool {
+        unsafe { ffi::bus::sd_bus_message_get_expect_reply(self.as_ptr() as *mut _) > 0 }
+    }
+
+    /// Flag this method call with NO_REPLY_EXPECTED (by passing `false`), telling the receiver not
+    /// to send a reply, not even an error. This saves a round trip for fire-and-forget calls,
+    /// which should then be sent with `send_no_reply()`.
+    ///
+    /// Fails for messages other than method calls, and once the message is sealed.
+    #[inline]
+    pub fn set_expect_reply(&mut self, yes: bool) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_set_expect_reply(self.as_mut_ptr(), yes as c_int));
+        Ok(())
+    }
+
+    /// Whether the caller is prepared to wait for an interactive authorization prompt (for
+    /// example by polkit) before the method call is answered.
+    #[inline]
+    pub fn allow_interactive_authorization(&self) -> bool {
+        let m = self.as_ptr() as *mut _;
+        unsafe { ffi::bus::sd_bus_message_get_allow_interactive_authorization(m) > 0 }
+    }
+
+    /// Allow the service to prompt the user for authorization before answering this method
+    /// call, instead of failing with `InteractiveAuthorizationRequired`.
+    ///
+    /// Calls which may show a prompt can take a long time, so use a generous timeout.
+    #[inline]
+    pub fn set_allow_interactive_authorization(&mut self, yes: bool) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_set_allow_interactive_authorization(self.as_mut_ptr(),
+                                                                             yes as c_int));
+        Ok(())
+    }
+
+    /// The priority of this message. Defaults to 0; lower values are more important.
+    ///
+    /// Priorities were only ever acted upon by kdbus. Current versions of libsystemd still accept
+    /// them but no longer store them, so this always returns 0 there.
+    #[inline]
+    pub fn priority(&self) -> super::Result<i64> {
+        let mut p = 0;
+        sd_try!(ffi::bus::sd_bus_message_get_priority(self.as_ptr() as *mut _, &mut p));
+        Ok(p)
+    }
+
+    /// Set the priority of this message. Fails if the message is already sealed.
+    #[inline]
+    pub fn set_priority(&mut self, priority: i64) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_set_priority(self.as_mut_ptr(), priority));
+        Ok(())
+    }
+
+    /// Read one of the optional message metadata values using `get`, mapping `ENODATA` (not
+    /// attached to this message) to `None`
+    fn metadata_u64(&self,
+                    get: unsafe extern "C" fn(*mut ffi::bus::sd_bus_message, *mut u64) -> c_int)
+                    -> super::Result<Option<u64>> {
+        let mut v = 0;
+        match unsafe { get(self.as_ptr() as *mut _, &mut v) } {
+            r if r == -libc::ENODATA => Ok(None),
+            r => {
+                try!(::ffi_result(r));
+                Ok(Some(v))
+            }
+        }
+    }
+
+    /// `CLOCK_MONOTONIC` time (in usec) at which the message was received.
+    ///
+    /// Returns `None` unless timestamps were negotiated (see `BusBuilder::timestamps()`) and the
+    /// transport attached them to this message.
+    #[inline]
+    pub fn monotonic_usec(&self) -> super::Result<Option<u64>> {
+        self.metadata_u64(ffi::bus::sd_bus_message_get_monotonic_usec)
+    }
+
+    /// `CLOCK_REALTIME` time (in usec) at which the message was received. Like
+    /// `monotonic_usec()`, this requires negotiating timestamps.
+    #[inline]
+    pub fn realtime_usec(&self) -> super::Result<Option<u64>> {
+        self.metadata_u64(ffi::bus::sd_bus_message_get_realtime_usec)
+    }
+
+    /// The sequence number the transport assigned to the message, if any. Like
+    /// `monotonic_usec()`, this requires negotiating timestamps.
+    #[inline]
+    pub fn seqnum(&self) -> super::Result<Option<u64>> {
+        self.metadata_u64(ffi::bus::sd_bus_message_get_seqnum)
+    }
+
+    /// The cookie (serial number) of this message. Only available once the message has been
+    /// sealed, which happens when sending it.
+    #[inline]
+    pub fn cookie(&self) -> super::Result<u64> {
+        let mut c = 0;
+        sd_try!(ffi::bus::sd_bus_message_get_cookie(self.as_ptr() as *mut _, &mut c));
+        Ok(c)
+    }
+
+    /// For method replies and errors, the cookie of the method call this message replies to.
+    ///
+    /// Fails with `ENODATA` for other kinds of messages.
+    #[inline]
+    pub fn reply_cookie(&self) -> super::Result<u64> {
+        let mut c = 0;
+        sd_try!(ffi::bus::sd_bus_message_get_reply_cookie(self.as_ptr() as *mut _, &mut c));
+        Ok(c)
+    }
+
+    /// If this is an error reply, the error it carries.
+    ///
+    /// If the error maps to an errno, it is available as the error's `source()`.
+    pub fn error(&self) -> Option<Error> {
+        let e = unsafe { ffi::bus::sd_bus_message_get_error(self.as_ptr() as *mut _) };
+        if e.is_null() {
+            return None;
+        }
+        let mut raw = RawError::new();
+        unsafe { ffi::bus::sd_bus_error_copy(raw.as_mut_ptr(), e) };
+        let errno = unsafe { ffi::bus::sd_bus_message_get_errno(self.as_ptr() as *mut _) };
+        let errno = if errno > 0 { Some(errno) } else { None };
+        Some(unsafe { Error::from_raw(raw, errno, false) })
+    }
+
+    /// The object path of a method call or signal
+    #[inline]
+    pub fn path(&self) -> Option<&ObjectPath> {
+        let p = unsafe { ffi::bus::sd_bus_message_get_path(self.as_ptr() as *mut _) };
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { ObjectPath::from_ptr_unchecked(p) })
+        }
+    }
+
+    /// The interface of a method call or signal. Method calls may omit it.
+    #[inline]
+    pub fn interface(&self) -> Option<&InterfaceName> {
+        let p = unsafe { ffi::bus::sd_bus_message_get_interface(self.as_ptr() as *mut _) };
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { InterfaceName::from_ptr_unchecked(p) })
+        }
+    }
+
+    /// The member name of a method call or signal
+    #[inline]
+    pub fn member(&self) -> Option<&MemberName> {
+        let p = unsafe { ffi::bus::sd_bus_message_get_member(self.as_ptr() as *mut _) };
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { MemberName::from_ptr_unchecked(p) })
+        }
+    }
+
+    /// The unique name of the sender. Not set for messages we create, nor on direct connections.
+    #[inline]
+    pub fn sender(&self) -> Option<&BusName> {
+        let p = unsafe { ffi::bus::sd_bus_message_get_sender(self.as_ptr() as *mut _) };
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { BusName::from_ptr_unchecked(p) })
+        }
+    }
+
+    /// The bus name this message is addressed to, if any
+    #[inline]
+    pub fn destination(&self) -> Option<&BusName> {
+        let p = unsafe { ffi::bus::sd_bus_message_get_destination(self.as_ptr() as *mut _) };
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { BusName::from_ptr_unchecked(p) })
+        }
+    }
+
+    /// Whether this message was sent by the connection it was received on, ie: a signal this
+    /// connection emitted and also matches.
+    pub fn is_from_self(&self) -> bool {
+        match (self.sender(), self.bus().unique_name()) {
+            (Some(sender), Ok(me)) => sender == me,
+            _ => false,
+        }
+    }
+
+    /// The signature of the complete message body, ie: `sa{sv}as`
+    #[inline]
+    pub fn signature(&self) -> &str {
+        let p = unsafe { ffi::bus::sd_bus_message_get_signature(self.as_ptr() as *mut _, 1) };
+        if p.is_null() {
+            ""
+        } else {
+            unsafe { str::from_utf8_unchecked(CStr::from_ptr(p).to_bytes()) }
+        }
+    }
+
+    /// The byte order, flags and protocol version from the fixed part of the header.
+    ///
+    /// sd-bus has no getters for these. For sealed messages, they are taken from the header line
+    /// `sd_bus_message_dump()` prints (ie: `Type=signal  Endian=l  Flags=1  Version=1 ...`). That
+    /// format is not a documented interface: should it change, this fails with `InvalidData`.
+    /// Dumping reads the whole message, the read position is restored afterwards.
     ///
-    /// The
-    /// [specification](https://dbus.freedesktop.org/doc/dbus-specification.html#message-bus-starting-services)
-    /// covers some details about the auto start mechanism, but not all of it is specified.
-    #[inline]
-    pub fn set_auto_start(&mut self, yes: bool) -> super::Result<()> {
-        sd_try!(ffi::bus::sd_bus_message_set_auto_start(self.as_mut_ptr(), yes as c_int));
+    /// Messages which are not sealed yet were built by sd-bus here, so they use the native byte
+    /// order and version 1, and their flags are those set on the message so far.
+    pub fn wire_header(&self) -> super::Result<WireHeader> {
+        let m = self.as_ptr() as *mut _;
+        match self.dump_raw(ffi::bus::SD_BUS_MESSAGE_DUMP_WITH_HEADER) {
+            Ok(dump) => {
+                WireHeader::parse(&dump).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "unexpected message dump")
+                })
+            }
+            Err(ref e) if e.raw_os_error() == Some(libc::EPERM) => {
+                let expect_reply = sd_try!(ffi::bus::sd_bus_message_get_expect_reply(m)) > 0;
+                let auto_start = sd_try!(ffi::bus::sd_bus_message_get_auto_start(m)) > 0;
+                let interactive =
+                    sd_try!(ffi::bus::sd_bus_message_get_allow_interactive_authorization(m)) > 0;
+                Ok(WireHeader {
+                    byte_order: ByteOrder::native(),
+                    flags: !expect_reply as u8 | (!auto_start as u8) << 1 |
+                           (interactive as u8) << 2,
+                    version: 1,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Render the message into a string with `sd_bus_message_dump()`, leaving the read position
+    /// where it was. Fails with `EPERM` for messages which are not sealed yet.
+    fn dump_raw(&self, flags: u64) -> super::Result<String> {
+        let m = self.as_ptr() as *mut _;
+        let pos = try!(self.read_position());
+        let mut buf = ptr::null_mut();
+        let mut len = 0;
+        let f = unsafe { libc::open_memstream(&mut buf, &mut len) };
+        if f.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let r = unsafe { ffi::bus::sd_bus_message_dump(m, f, flags) };
+        /* closing flushes the stream and updates buf and len */
+        unsafe { libc::fclose(f) };
+        let s = unsafe { String::from_utf8_lossy(slice::from_raw_parts(buf as *const u8, len)) }
+            .into_owned();
+        unsafe { libc::free(buf as *mut c_void) };
+        try!(self.seek(&pos));
+        try!(::ffi_result(r));
+        Ok(s)
+    }
+
+    /// Skip to the end of the container the read position is in, returning the number of elements
+    /// skipped
+    fn skip_to_end(&self) -> super::Result<usize> {
+        let m = self.as_ptr() as *mut _;
+        let mut n = 0;
+        while sd_try!(ffi::bus::sd_bus_message_at_end(m, 0)) == 0 {
+            sd_try!(ffi::bus::sd_bus_message_skip(m, ptr::null()));
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// Find the read position, see `ReadPosition`. This leaves the message at its end.
+    fn read_position(&self) -> super::Result<ReadPosition> {
+        let m = self.as_ptr() as *mut _;
+        let mut left = Vec::new();
+        loop {
+            left.push(try!(self.skip_to_end()));
+            let r = unsafe { ffi::bus::sd_bus_message_exit_container(m) };
+            if r == -libc::ENXIO {
+                /* not in a container */
+                return Ok(ReadPosition(left));
+            }
+            try!(::ffi_result(r));
+        }
+    }
+
+    /// Return to `pos`, found with `read_position()`
+    fn seek(&self, pos: &ReadPosition) -> super::Result<()> {
+        let m = self.as_ptr() as *mut _;
+        sd_try!(ffi::bus::sd_bus_message_rewind(m, 1));
+        for (depth, &left) in pos.0.iter().rev().enumerate() {
+            /* count the elements of the container, then go back to its start */
+            let total = try!(self.skip_to_end());
+            sd_try!(ffi::bus::sd_bus_message_rewind(m, 0));
+            let inner = depth + 1 < pos.0.len();
+            /* the containers we were in are not counted as left */
+            let before = total - left - if inner { 1 } else { 0 };
+            for _ in 0..before {
+                sd_try!(ffi::bus::sd_bus_message_skip(m, ptr::null()));
+            }
+            if inner {
+                let mut t = 0;
+                let mut contents = ptr::null();
+                sd_try!(ffi::bus::sd_bus_message_peek_type(m, &mut t, &mut contents));
+                sd_try!(ffi::bus::sd_bus_message_enter_container(m, t, contents));
+            }
+        }
         Ok(())
     }
 
     // # properties
     // type
-    // cookie
-    // reply_cookie
-    // priority
-    // expect_reply
     // auto_start
-    // allow_interactive_authorization
-    // signature
-    // path
-    // interface
-    // member
     // destination
-    // sender
-    // error
-    // errno
-    // monotonic_usec
-    // realtime_usec
-    // seqnum
 
     // is_signal
     // is_method_call
@@ -1190,6 +3316,7 @@ impl MessageRef {
         // self.bus().send(self)
         let mut m = unsafe { uninitialized() };
         sd_try!(ffi::bus::sd_bus_send(ptr::null_mut(), self.as_mut_ptr(), &mut m));
+        self.report_sent();
         Ok(m)
     }
 
@@ -1199,6 +3326,7 @@ impl MessageRef {
     pub fn send_no_reply(&mut self) -> super::Result<()> {
         // self.bus().send_no_reply(self)
         sd_try!(ffi::bus::sd_bus_send(ptr::null_mut(), self.as_mut_ptr(), ptr::null_mut()));
+        self.report_sent();
         Ok(())
     }
 
@@ -1214,6 +3342,7 @@ impl MessageRef {
                                          self.as_mut_ptr(),
                                          &*dest as *const _ as *const _,
                                          &mut c));
+        self.report_sent();
         Ok(c)
     }
 
@@ -1226,9 +3355,24 @@ impl MessageRef {
                                          self.as_mut_ptr(),
                                          &*dest as *const _ as *const _,
                                          ptr::null_mut()));
+        self.report_sent();
         Ok(())
     }
 
+    /// Render the header and body of the message into a string, in the format `busctl monitor`
+    /// uses (see `sd_bus_message_dump()`).
+    ///
+    /// This reads the whole message, the read position is restored afterwards. Fails with `EPERM`
+    /// for messages which are not sealed yet.
+    pub fn dump(&self) -> super::Result<String> {
+        self.dump_raw(ffi::bus::SD_BUS_MESSAGE_DUMP_WITH_HEADER)
+    }
+
+    /// Report this message as sent to the hook instrumenting the bus, if any
+    fn report_sent(&self) {
+        stats::with_hook(self.bus(), |h| h.sent(self));
+    }
+
     /// Use this message to call a dbus method. Blocks until a reply is recieved or `usec`
     /// microseconds elapse (ie: this times out)
     ///
@@ -1238,39 +3382,74 @@ impl MessageRef {
     pub fn call(&mut self, usec: u64) -> Result<Message> {
         let mut r = unsafe { uninitialized() };
         let mut e = RawError::new();
-        unsafe {
+        let start = Instant::now();
+        let ret = unsafe {
             ffi::bus::sd_bus_call(ptr::null_mut(),
                     self.as_mut_ptr(),
                     usec,
                     e.as_mut_ptr(),
-                    &mut r);
-        }
-        e.into_result().map(|_| unsafe { Message::take_ptr(r)})
+                    &mut r)
+        };
+        let r = e.into_result(ret).map(|_| unsafe { Message::take_ptr(r)});
+        stats::with_hook(self.bus(), |h| {
+            h.sent(self);
+            if let Ok(ref reply) = r {
+                h.received(reply);
+            }
+            h.call_completed(self, start.elapsed(), r.as_ref().err());
+        });
+        r
     }
 
     /// Use this message to call a dbus method. Returns immediately and will call the callback when
     /// a reply is recieved.
     ///
+    /// The callback is owned by the returned `Slot`. Dropping the `Slot` before the reply arrives
+    /// cancels the call. The reply is not passed on to filters or matches.
+    ///
     /// XXX: document how timeout affects this
     /// Seals `self`.
-    // XXX: we may need to move this, unclear we have the right lifetime here (we're being too
-    // strict)
     #[inline]
-    pub fn call_async<F: FnMut(&mut MessageRef) -> Result<()>>(&mut self,
-                                                                      callback: &mut F,
-                                                                      usec: u64)
-                                                                      -> super::Result<()> {
+    pub fn call_async<F: FnMut(&mut MessageRef) -> Result<()> + 'static>(&mut self,
+                                                                        mut callback: F,
+                                                                        usec: u64)
+                                                                        -> super::Result<Slot> {
+        /* the call is only reported to hooks attached by the time it is sent */
+        if !stats::is_instrumented(self.bus()) {
+            return self.call_async_raw(callback, usec);
+        }
+        let call = self.to_owned();
+        let start = Instant::now();
+        let callback = move |reply: &mut MessageRef| {
+            stats::with_hook(reply.bus(), |h| {
+                h.received(reply);
+                h.call_completed(&call, start.elapsed(), reply.error().as_ref());
+            });
+            callback(reply)
+        };
+        let slot = try!(self.call_async_raw(callback, usec));
+        self.report_sent();
+        Ok(slot)
+    }
+
+    fn call_async_raw<F: FnMut(&mut MessageRef) -> Result<()> + 'static>(&mut self,
+                                                                        callback: F,
+                                                                        usec: u64)
+                                                                        -> super::Result<Slot> {
         let f: extern "C" fn(*mut ffi::bus::sd_bus_message,
                              *mut c_void,
                              *mut ffi::bus::sd_bus_error)
-                             -> c_int = raw_message_handler::<F>;
+                             -> c_int = raw_handled_message_handler::<F>;
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut slot = ptr::null_mut();
         sd_try!(ffi::bus::sd_bus_call_async(ptr::null_mut(),
-                                            ptr::null_mut(),
+                                            &mut slot,
                                             self.as_mut_ptr(),
                                             Some(f),
-                                            callback as *mut _ as *mut _,
+                                            u as *mut _,
                                             usec));
-        Ok(())
+        Ok(unsafe { Slot::take_ptr(slot, b) })
     }
 
     #[inline]
@@ -1280,6 +3459,16 @@ impl MessageRef {
         Ok(unsafe { Message::take_ptr(m) })
     }
 
+    /// Create an error reply to this method call which describes the errno `errno`, named as
+    /// `Error::from_errno()` would name it.
+    #[inline]
+    pub fn new_method_errno(&mut self, errno: c_int) -> super::Result<Message> {
+        let mut m = unsafe { uninitialized() };
+        sd_try!(ffi::bus::sd_bus_message_new_method_errno(self.as_mut_ptr(), &mut m, errno,
+                                                          ptr::null()));
+        Ok(unsafe { Message::take_ptr(m) })
+    }
+
     #[inline]
     pub fn new_method_return(&mut self) -> super::Result<Message> {
         let mut m = unsafe { uninitialized() };
@@ -1287,6 +3476,32 @@ impl MessageRef {
         Ok(unsafe { Message::take_ptr(m) })
     }
 
+    /// Reply to this method call with `args`, sending the reply right away.
+    ///
+    /// Use `()` for methods which return nothing.
+    pub fn reply<A: types::ToSdBusMessage>(&mut self, args: A) -> super::Result<()> {
+        let mut reply = try!(self.new_method_return());
+        try!(reply.append(args));
+        try!(reply.send());
+        Ok(())
+    }
+
+    /// Reply to this method call with the error `name`, sending the reply right away.
+    pub fn reply_error(&mut self, name: &Utf8CStr, message: Option<&Utf8CStr>)
+        -> super::Result<()>
+    {
+        let mut reply = try!(self.new_method_error(&Error::new(name, message)));
+        try!(reply.send());
+        Ok(())
+    }
+
+    /// Reply to this method call with an error describing the errno `errno` (ie: `libc::EBUSY`
+    /// is sent as `System.Error.EBUSY`), sending the reply right away.
+    pub fn reply_errno(&mut self, errno: c_int) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_reply_method_errno(self.as_mut_ptr(), errno, ptr::null()));
+        Ok(())
+    }
+
     /// Raw access to append data to this message
     /// Will fail if the message is sealed
     // XXX: unclear if this should operate directly on the message or be split out to the iterator
@@ -1297,10 +3512,182 @@ impl MessageRef {
         Ok(())
     }
 
-    /// Append a value to the message
+    /// Append `v` wrapped in a variant
+    fn append_variant<V: types::ToSdBusMessage>(&mut self, v: &V) -> ::Result<()> {
+        self.append_variant_with(&v.signature(), |m| v.to_message(m))
+    }
+
+    /// Start appending a container of type `t` (one of `a`, `r`, `v` or `e`) whose contents have
+    /// the signature `contents` (the element type for arrays).
+    ///
+    /// Following values are appended to the container until `close_container()` is called.
+    pub fn open_container(&mut self, t: u8, contents: &str) -> ::Result<()> {
+        let contents = try!(signature(Some(contents))).unwrap();
+        sd_try!(ffi::bus::sd_bus_message_open_container(self.as_mut_ptr(),
+                                                        t as c_char,
+                                                        contents.as_ptr()));
+        Ok(())
+    }
+
+    /// Finish the container opened last by `open_container()`
+    #[inline]
+    pub fn close_container(&mut self) -> ::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_close_container(self.as_mut_ptr()));
+        Ok(())
+    }
+
+    /// Append a container of type `t`, with `f` appending its contents
+    fn append_container_with<F>(&mut self, t: u8, contents: &str, f: F) -> ::Result<()>
+        where F: FnOnce(&mut MessageRef) -> ::Result<()>
+    {
+        try!(self.open_container(t, contents));
+        try!(f(self));
+        self.close_container()
+    }
+
+    /// Append an array of elements with the signature `element`, with `f` appending the elements.
+    #[inline]
+    pub fn append_array_with<F>(&mut self, element: &str, f: F) -> ::Result<()>
+        where F: FnOnce(&mut MessageRef) -> ::Result<()>
+    {
+        self.append_container_with(b'a', element, f)
+    }
+
+    /// Append a struct with the signature `(contents)`, with `f` appending its fields
+    #[inline]
+    pub fn append_struct_with<F>(&mut self, contents: &str, f: F) -> ::Result<()>
+        where F: FnOnce(&mut MessageRef) -> ::Result<()>
+    {
+        self.append_container_with(b'r', contents, f)
+    }
+
+    /// Append a variant holding a value with the signature `contents`, with `f` appending the
+    /// value
+    #[inline]
+    pub fn append_variant_with<F>(&mut self, contents: &str, f: F) -> ::Result<()>
+        where F: FnOnce(&mut MessageRef) -> ::Result<()>
+    {
+        self.append_container_with(b'v', contents, f)
+    }
+
+    /// Append a dict entry with the signature `{contents}` (as an element of an array), with `f`
+    /// appending the key and value
+    #[inline]
+    pub fn append_dict_entry_with<F>(&mut self, contents: &str, f: F) -> ::Result<()>
+        where F: FnOnce(&mut MessageRef) -> ::Result<()>
+    {
+        self.append_container_with(b'e', contents, f)
+    }
+
+    /// Append a value to the message. The elements of a tuple are appended as separate values.
     #[inline]
     pub fn append<V: types::ToSdBusMessage>(&mut self, v: V) -> ::Result<()> {
-        v.to_message(self)
+        v.to_args(self)
+    }
+
+    /// Read the whole body as `T`, the counterpart of `append()`: the elements of a tuple are
+    /// read from separate arguments. Fails if the body is empty, or has arguments left after `T`.
+    ///
+    /// Reading starts from the beginning of the body, whatever was read before. Requires that the
+    /// message is sealed.
+    pub fn read_body<'a, T: types::FromSdBusMessage<'a>>(&'a mut self) -> ::Result<T> {
+        let mut i = try!(self.iter());
+        try!(i.rewind(true));
+        let v = match try!(T::from_args(&mut i)) {
+            Some(v) => v,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "message body is empty")),
+        };
+        if try!(i.peek_raw()).is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "message body is too long"));
+        }
+        Ok(v)
+    }
+
+    /// Append an array of fixed size elements, copying `v` into the message at once.
+    ///
+    /// Unsafety:
+    ///
+    ///  - `dbus_type` must be a fixed size type with the same size and representation as `T`
+    unsafe fn append_array<T>(&mut self, dbus_type: u8, v: &[T]) -> ::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_append_array(self.as_mut_ptr(),
+                                                      dbus_type as c_char,
+                                                      v.as_ptr() as *const c_void,
+                                                      v.len() * size_of::<T>()));
+        Ok(())
+    }
+
+    /// Append an array of bytes (`ay`)
+    #[inline]
+    pub fn append_array_u8(&mut self, v: &[u8]) -> ::Result<()> {
+        unsafe { self.append_array(b'y', v) }
+    }
+
+    /// Append an array of `n`
+    #[inline]
+    pub fn append_array_i16(&mut self, v: &[i16]) -> ::Result<()> {
+        unsafe { self.append_array(b'n', v) }
+    }
+
+    /// Append an array of `q`
+    #[inline]
+    pub fn append_array_u16(&mut self, v: &[u16]) -> ::Result<()> {
+        unsafe { self.append_array(b'q', v) }
+    }
+
+    /// Append an array of `i`
+    #[inline]
+    pub fn append_array_i32(&mut self, v: &[i32]) -> ::Result<()> {
+        unsafe { self.append_array(b'i', v) }
+    }
+
+    /// Append an array of `u`
+    #[inline]
+    pub fn append_array_u32(&mut self, v: &[u32]) -> ::Result<()> {
+        unsafe { self.append_array(b'u', v) }
+    }
+
+    /// Append an array of `x`
+    #[inline]
+    pub fn append_array_i64(&mut self, v: &[i64]) -> ::Result<()> {
+        unsafe { self.append_array(b'x', v) }
+    }
+
+    /// Append an array of `t`
+    #[inline]
+    pub fn append_array_u64(&mut self, v: &[u64]) -> ::Result<()> {
+        unsafe { self.append_array(b't', v) }
+    }
+
+    /// Append an array of `d`
+    #[inline]
+    pub fn append_array_f64(&mut self, v: &[f64]) -> ::Result<()> {
+        unsafe { self.append_array(b'd', v) }
+    }
+
+    /// Append values read from `source` to this message, starting at the current read position
+    /// of `source` and advancing it.
+    ///
+    /// Copies a single complete type if `all` is false, otherwise everything up to the end of the
+    /// current container of `source`. Returns `false` if there was nothing left to copy.
+    #[inline]
+    pub fn copy(&mut self, source: &mut MessageRef, all: bool) -> ::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_message_copy(self.as_mut_ptr(),
+                                                 source.as_mut_ptr(),
+                                                 all as c_int)) > 0)
+    }
+
+    /// Append the complete body of `source` to this message.
+    ///
+    /// Useful for forwarding a received message, ie: by copying a method call into a new one
+    /// addressed elsewhere. `source` must be sealed (as received messages are), its read position
+    /// is reset.
+    pub fn copy_from(&mut self, source: &MessageRef) -> ::Result<()> {
+        let s = source.as_ptr() as *mut _;
+        sd_try!(ffi::bus::sd_bus_message_rewind(s, 1));
+        let r = unsafe { ffi::bus::sd_bus_message_copy(self.as_mut_ptr(), s, 1) };
+        unsafe { ffi::bus::sd_bus_message_rewind(s, 1) };
+        try!(::ffi_result(r));
+        Ok(())
     }
 
     /// Get an iterator over the message. This iterator really exists with in the `Message` itself,
@@ -1314,9 +3701,94 @@ impl MessageRef {
     pub fn iter<'a>(&'a mut self) -> ::Result<MessageIter<'a>> {
         /* probe the `Message` to check if we can iterate on it */
         sd_try!(ffi::bus::sd_bus_message_peek_type(self.as_mut_ptr(), ptr::null_mut(), ptr::null_mut()));
-        Ok(MessageIter { raw: self.as_mut_ptr(), life: PhantomData })
+        Ok(MessageIter { raw: self.as_mut_ptr(), life: PhantomData, failed: false })
+    }
+
+}
+
+/// Reinterpret the `size` bytes at `p` (as returned by `sd_bus_message_read_array()`) as a slice
+/// of `T`.
+///
+/// sd-bus aligns array contents within the message, but the message itself may not be suitably
+/// aligned in memory (for example when received from a `memfd`), so this is checked rather than
+/// assumed.
+unsafe fn array_from_raw<'a, T>(p: *const c_void, size: usize) -> ::Result<&'a [T]> {
+    if size == 0 {
+        return Ok(&[]);
+    }
+    if size % size_of::<T>() != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  "array size is not a multiple of its element size"));
+    }
+    if p as usize % align_of::<T>() != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "array is not aligned"));
+    }
+    Ok(slice::from_raw_parts(p as *const T, size / size_of::<T>()))
+}
+
+#[test]
+fn t_array_from_raw() {
+    let v = [1u32, 2, 3];
+    let p = v.as_ptr() as *const c_void;
+    assert_eq!(unsafe { array_from_raw::<u32>(p, 12) }.unwrap(), &v[..]);
+    assert_eq!(unsafe { array_from_raw::<u32>(ptr::null(), 0) }.unwrap(), &[]);
+    unsafe { array_from_raw::<u32>(p, 10) }.err().unwrap();
+    unsafe { array_from_raw::<u32>((p as *const u8).offset(1) as *const c_void, 8) }
+        .err()
+        .unwrap();
+}
+
+/// Convert an optional signature for passing to sd-bus
+fn signature(s: Option<&str>) -> ::Result<Option<CString>> {
+    match s {
+        Some(s) => {
+            CString::new(s)
+                .map(Some)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul in signature"))
+        }
+        None => Ok(None),
+    }
+}
+
+/// A container being read, created by `MessageIter::enter_container()`.
+///
+/// Derefs to the `MessageIter`, which yields the elements of the container until the container is
+/// left by dropping this or calling `exit()`.
+pub struct Container<'b, 'a: 'b> {
+    iter: &'b mut MessageIter<'a>,
+    exited: bool,
+}
+
+impl<'b, 'a> Container<'b, 'a> {
+    /// Leave the container, skipping any elements which were not read. Unlike dropping the
+    /// `Container`, this reports errors.
+    pub fn exit(mut self) -> ::Result<()> {
+        self.exited = true;
+        self.iter.skip_and_exit_container()
+    }
+}
+
+impl<'b, 'a> Deref for Container<'b, 'a> {
+    type Target = MessageIter<'a>;
+    #[inline]
+    fn deref(&self) -> &MessageIter<'a> {
+        self.iter
+    }
+}
+
+impl<'b, 'a> DerefMut for Container<'b, 'a> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut MessageIter<'a> {
+        self.iter
     }
+}
 
+impl<'b, 'a> Drop for Container<'b, 'a> {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = self.iter.skip_and_exit_container();
+        }
+    }
 }
 
 impl<'a> MessageIter<'a> {
@@ -1325,18 +3797,14 @@ impl<'a> MessageIter<'a> {
         self.raw
     }
 
-    /*
-     * XXX: 'T' may reference the parent `Message`, and should be tied to the lifetime of the
-     * `MessageIter` (to ensure they don't change out from underneath us) but shouldn't be tied to
-     * the lifetime of the &mut self of this call
-     */
     /// Read an element from the message and advance the internal cursor
-    /// References returned by this function are valid until the iterator itself is dropped (just
-    /// to garuntee they don't change).
     ///
-    /// XXX: really, they are valid until the message is un-sealed: reading from the message can
-    /// only occur while the message is sealed. Unclear if we can track lifetimes against message
-    /// sealing.
+    /// `T` may borrow from the message (ie: strings, which sd-bus returns as pointers into the
+    /// message body) for `'a`, the borrow of the message the iterator was created from. That is
+    /// sound as messages can only be read once sealed, a sealed message is never modified again,
+    /// and the borrow keeps our reference to it (and thus the message) alive. Other `Message`s
+    /// referring to the same message (clones) may move the read cursor, but can not invalidate
+    /// what was read.
     ///
     /// Unsafety:
     ///
@@ -1361,69 +3829,313 @@ impl<'a> MessageIter<'a> {
         }
     }
 
-    /// This needs to be `&mut` as the `&str` will be invalid after either of:
-    ///  - self is dropped
-    ///  - sd_bus_message_peek_type is called a second time
+    /// The type of the next element, and the signature of its contents if it is a container
+    /// (empty otherwise).
     ///
-    /// Using &mut allows us to prevent #2.
-    ///
-    /// FIXME/WARNING: Message might have been cloned, in which case we can't rely on the lifetime of
-    /// &str! As `Message` isn't `Send` or `Sync`, we can garuntee we're not racing with someone
-    /// else to free it though. Probably need to allocate space for it here rather than return a
-    /// ref.
-    // &str lasts until next call of sd_bus_message_peek_type
-    // XXX: confirm that lifetimes here match that!
-    #[inline]
-    pub fn peek_type(&mut self) -> ::Result<(c_char, &str)>
-    {
-        let mut t: c_char = unsafe { uninitialized() };
-        let mut cont: *const c_char = unsafe { uninitialized() };
-        try!(::ffi_result(unsafe { ffi::bus::sd_bus_message_peek_type(self.as_mut_ptr(), &mut t, &mut cont) }));
+    /// The contents are copied: for variants, sd-bus keeps them in a buffer which is replaced by
+    /// the next peek, which may also happen through a clone of the `Message`, so they can't be
+    /// borrowed safely.
+    pub fn peek_type(&mut self) -> ::Result<(c_char, String)> {
+        let mut t: c_char = 0;
+        let mut cont: *const c_char = ptr::null();
+        sd_try!(ffi::bus::sd_bus_message_peek_type(self.as_mut_ptr(), &mut t, &mut cont));
 
         let s = if cont.is_null() {
-            /* XXX: we may need to adjust here and return an option, but it isn't yet clear if
-             * there will be confusion between NULL and "" here */
-            ""
+            String::new()
         } else {
-            unsafe {str::from_utf8_unchecked(CStr::from_ptr(cont).to_bytes())}
+            unsafe { str::from_utf8_unchecked(CStr::from_ptr(cont).to_bytes()) }.to_owned()
         };
         Ok((t, s))
     }
 
-    // XXX: handle containers
+    /// Read an array of fixed size elements as a single slice, without copying.
+    ///
+    /// Unsafety:
+    ///
+    ///  - `dbus_type` must be a fixed size type with the same size and representation as `T`
+    unsafe fn read_array_raw<T>(&mut self, dbus_type: u8) -> ::Result<Option<&'a [T]>> {
+        let mut p: *const c_void = ptr::null();
+        let mut size = 0;
+        match try!(::ffi_result(ffi::bus::sd_bus_message_read_array(self.as_mut_ptr(),
+                                                                    dbus_type as c_char,
+                                                                    &mut p,
+                                                                    &mut size))) {
+            0 => Ok(None),
+            _ => array_from_raw(p, size).map(Some),
+        }
+    }
+
+    /// Read an array of fixed size elements (ie: `ay` as `&[u8]`) as a slice pointing into the
+    /// message, without copying.
+    ///
+    /// Returns `None` at the end of the current container. Fails if the next element is not an
+    /// array of `T`.
+    #[inline]
+    pub fn read_array<T: types::SdBusMessageDirect>(&mut self) -> ::Result<Option<&'a [T]>> {
+        unsafe { self.read_array_raw(T::dbus_type()) }
+    }
+
+    /// Read an array of bytes (`ay`)
+    #[inline]
+    pub fn read_array_u8(&mut self) -> ::Result<Option<&'a [u8]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `n`
+    #[inline]
+    pub fn read_array_i16(&mut self) -> ::Result<Option<&'a [i16]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `q`
+    #[inline]
+    pub fn read_array_u16(&mut self) -> ::Result<Option<&'a [u16]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `i`
+    #[inline]
+    pub fn read_array_i32(&mut self) -> ::Result<Option<&'a [i32]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `u`
+    #[inline]
+    pub fn read_array_u32(&mut self) -> ::Result<Option<&'a [u32]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `x`
+    #[inline]
+    pub fn read_array_i64(&mut self) -> ::Result<Option<&'a [i64]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `t`
+    #[inline]
+    pub fn read_array_u64(&mut self) -> ::Result<Option<&'a [u64]>> {
+        self.read_array()
+    }
+
+    /// Read an array of `d`
+    #[inline]
+    pub fn read_array_f64(&mut self) -> ::Result<Option<&'a [f64]>> {
+        self.read_array()
+    }
+
+    /// Look at the type of the next element, returning `None` at the end of the current
+    /// container. The contents pointer is valid until the iterator is advanced.
+    fn peek_raw(&mut self) -> ::Result<Option<(u8, *const c_char)>> {
+        let mut t: c_char = 0;
+        let mut contents: *const c_char = ptr::null();
+        match try!(::ffi_result(unsafe {
+            ffi::bus::sd_bus_message_peek_type(self.as_mut_ptr(), &mut t, &mut contents)
+        })) {
+            0 => Ok(None),
+            _ => Ok(Some((t as u8, contents))),
+        }
+    }
+
+    /// Enter the container of type `t` with the given `contents` signature. Returns `false` if
+    /// there are no more elements in the current container.
+    fn enter_container_raw(&mut self, t: u8, contents: *const c_char) -> ::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_message_enter_container(self.as_mut_ptr(),
+                                                            t as c_char,
+                                                            contents)) > 0)
+    }
 
-    pub fn next<V: types::FromSdBusMessage<'a>>(&'a mut self) -> ::Result<Option<V>>
+    fn exit_container_raw(&mut self) -> ::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_exit_container(self.as_mut_ptr()));
+        Ok(())
+    }
+
+    /// Skip the remaining elements of the current container, then leave it
+    fn skip_and_exit_container(&mut self) -> ::Result<()> {
+        while sd_try!(ffi::bus::sd_bus_message_at_end(self.as_mut_ptr(), 0)) == 0 {
+            sd_try!(ffi::bus::sd_bus_message_skip(self.as_mut_ptr(), ptr::null()));
+        }
+        self.exit_container_raw()
+    }
+
+    /// Enter the next element, a container of type `t` (one of `a`, `r`, `e` or `v`), checking
+    /// that its contents have the signature `contents` (ie: `"s"` for an `as`, `"sv"` for the
+    /// entries of an `a{sv}`). Pass `None` to accept any contents, which is mostly useful for
+    /// variants.
+    ///
+    /// Reading from the returned `Container` yields the elements of the container. Dropping it
+    /// leaves the container again, skipping any elements which were not read. Returns `None` at
+    /// the end of an array, and fails with `ENXIO` if the next element is of a different type.
+    pub fn enter_container<'b>(&'b mut self, t: u8, contents: Option<&str>)
+        -> ::Result<Option<Container<'b, 'a>>>
     {
-        V::from_message(self)
+        let contents = try!(signature(contents));
+        let p = contents.as_ref().map_or(ptr::null(), |c| c.as_ptr());
+        if !try!(self.enter_container_raw(t, p)) {
+            return Ok(None);
+        }
+        Ok(Some(Container { iter: self, exited: false }))
     }
-}
 
-/*
-struct Vtable;
-struct VtableBuilder<T> {
-    Vec<ffi::bus::sd_bus_vtable>,
-}
+    /// Skip elements matching `types` (ie: `"sv"` to skip a string and a variant), or the next
+    /// element (whatever its type) if `types` is `None`. Returns `false` at the end of an array,
+    /// and fails with `ENXIO` if the next elements don't match `types`.
+    pub fn skip(&mut self, types: Option<&str>) -> ::Result<bool> {
+        let types = try!(signature(types));
+        let p = types.as_ref().map_or(ptr::null(), |c| c.as_ptr());
+        Ok(sd_try!(ffi::bus::sd_bus_message_skip(self.as_mut_ptr(), p)) > 0)
+    }
+
+    /// Go back to the first element of the current container, or of the whole message if
+    /// `complete` is set (leaving all containers).
+    pub fn rewind(&mut self, complete: bool) -> ::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_rewind(self.as_mut_ptr(), complete as c_int));
+        Ok(())
+    }
 
-type PropertyGet<T> = fn(Bus, ObjectPath, InterfaceName, MessageRef, &mut T, &mut Error) -> c_int;
-type PropertySet<T> = fn(Bus, ObjectPath, InterfaceName, MessageRef, &mut T, &mut Error) -> c_int;
+    /// Whether the next element is of type `t` and, for containers, has `contents` (if given).
+    /// Returns `false` at the end of the current container.
+    ///
+    /// Useful to probe for optional or unknown fields before reading them.
+    pub fn verify_type(&mut self, t: u8, contents: Option<&str>) -> ::Result<bool> {
+        let contents = try!(signature(contents));
+        let p = contents.as_ref().map_or(ptr::null(), |c| c.as_ptr());
+        Ok(sd_try!(ffi::bus::sd_bus_message_verify_type(self.as_mut_ptr(),
+                                                        t as c_char,
+                                                        p)) > 0)
+    }
+
+    /// Read the elements of the container of type `t` (with element signature `contents`) by
+    /// calling `f` until it returns `None`.
+    fn read_container<T, F>(&mut self, t: u8, contents: &[u8], mut f: F) -> ::Result<Option<Vec<T>>>
+        where F: FnMut(&mut Self) -> ::Result<Option<T>>
+    {
+        if !try!(self.enter_container_raw(t, contents.as_ptr() as *const c_char)) {
+            return Ok(None);
+        }
+        let mut v = Vec::new();
+        while let Some(e) = try!(f(self)) {
+            v.push(e);
+        }
+        try!(self.exit_container_raw());
+        Ok(Some(v))
+    }
+
+    unsafe fn read_string(&mut self, t: u8) -> ::Result<Option<String>> {
+        self.read_basic_raw(t, |x: *const c_char| CStr::from_ptr(x).to_string_lossy().into_owned())
+    }
+
+    /// Read the next element, whatever its type, including all its contents
+    pub fn read_value(&mut self) -> ::Result<Option<types::Variant>> {
+        use self::types::Variant as V;
+        let (t, contents) = match try!(self.peek_raw()) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
 
+        unsafe {
+            match t {
+                b'y' => self.read_basic_raw(t, V::Byte),
+                b'b' => self.read_basic_raw(t, |x: c_int| V::Bool(x != 0)),
+                b'n' => self.read_basic_raw(t, V::Int16),
+                b'q' => self.read_basic_raw(t, V::UInt16),
+                b'i' => self.read_basic_raw(t, V::Int32),
+                b'u' => self.read_basic_raw(t, V::UInt32),
+                b'x' => self.read_basic_raw(t, V::Int64),
+                b't' => self.read_basic_raw(t, V::UInt64),
+                b'd' => self.read_basic_raw(t, V::Double),
+                b'h' => self.read_basic_raw(t, V::UnixFd),
+                b's' => Ok(try!(self.read_string(t)).map(V::String)),
+                b'g' => Ok(try!(self.read_string(t)).map(V::Signature)),
+                b'o' => {
+                    self.read_basic_raw(t, |x: *const c_char| {
+                        V::ObjectPath(ObjectPath::from_ptr_unchecked(x).to_owned())
+                    })
+                }
+                b'a' | b'r' | b'e' | b'v' => {
+                    if !try!(self.enter_container_raw(t, contents)) {
+                        return Ok(None);
+                    }
+                    let mut v = Vec::new();
+                    while let Some(e) = try!(self.read_value()) {
+                        v.push(e);
+                    }
+                    try!(self.exit_container_raw());
+                    Ok(Some(match t {
+                        b'a' => V::Array(v),
+                        b'r' => V::Struct(v),
+                        b'e' if v.len() == 2 => {
+                            let value = v.pop().unwrap();
+                            V::DictEntry(Box::new(v.pop().unwrap()), Box::new(value))
+                        }
+                        b'v' if v.len() == 1 => V::Variant(Box::new(v.pop().unwrap())),
+                        _ => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                       "malformed container")),
+                    }))
+                }
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown dbus type")),
+            }
+        }
+    }
 
-impl VtableBuilder {
-    fn method(mut self, member: &str, signature: &str, result: &str, handler: MessageHandler) {
-        /* verify */
-        /* track */
+    /// Read a variant (`v`), returning its contents
+    pub fn read_variant(&mut self) -> ::Result<Option<types::Variant>> {
+        match try!(self.read_value()) {
+            Some(types::Variant::Variant(v)) => Ok(Some(*v)),
+            Some(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "expected a variant")),
+            None => Ok(None),
+        }
     }
 
-    fn property(mut self, member: &str, signature: &str, get: PropertyGet) {
+    /// Read a dict entry with signature `contents`, using `key` and `value` to read its parts.
+    fn read_entry<K, V, FK, FV>(&mut self, contents: &[u8], key: FK, value: FV)
+        -> ::Result<Option<(K, V)>>
+        where FK: FnOnce(&mut Self) -> ::Result<Option<K>>,
+              FV: FnOnce(&mut Self) -> ::Result<Option<V>>
+    {
+        if !try!(self.enter_container_raw(b'e', contents.as_ptr() as *const c_char)) {
+            return Ok(None);
+        }
+        let k = try!(key(self));
+        let v = try!(value(self));
+        try!(self.exit_container_raw());
+        match (k, v) {
+            (Some(k), Some(v)) => Ok(Some((k, v))),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete dict entry")),
+        }
     }
 
-    fn property_writable(mut self, member: &str, signature: &str, get: PropertyGet, set: PropertySet) {
+    /// Read a dictionary of properties (`a{sv}`)
+    pub fn read_properties(&mut self) -> ::Result<Option<HashMap<String, types::Variant>>> {
+        let v = try!(self.read_container(b'a', b"{sv}\0", |i| {
+            i.read_entry(b"sv\0", |i| unsafe { i.read_string(b's') }, |i| i.read_variant())
+        }));
+        Ok(v.map(|v| v.into_iter().collect()))
     }
 
-    fn signal(mut self, member: &str, signature: &str) {
+    /// Read the next element as `V`, or `None` at the end of the current container.
+    ///
+    /// Named so it doesn't shadow `Iterator::next()`, which reads elements as `Variant`s.
+    pub fn read_next<V: types::FromSdBusMessage<'a>>(&mut self) -> ::Result<Option<V>>
+    {
+        V::from_message(self)
     }
+}
+
+/// Walks the remaining elements of the current container (the body, unless one was entered) as
+/// dynamic values, see `read_value()`. Iteration ends after the first error.
+impl<'a> Iterator for MessageIter<'a> {
+    type Item = ::Result<types::Variant>;
 
-    fn create(mut self) -> Vtable {
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.read_value() {
+            Ok(v) => v.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
     }
 }
-*/
@@ -0,0 +1,145 @@
+/*!
+ * Well-known names of the bus itself and of systemd's services
+ *
+ * These are validated by the tests in this module, so they can be used directly wherever a
+ * `BusName`, `ObjectPath` or `InterfaceName` is expected. Error names have the same form as
+ * interface names, and are `InterfaceName`s as well.
+ */
+
+use super::{BusName, InterfaceName, ObjectPath};
+
+macro_rules! names {
+    ($test:ident, $t:ident { $($(#[$attr:meta])* $name:ident = $s:expr;)* }) => {
+        $(
+            $(#[$attr])*
+            pub const $name: &'static $t = unsafe { $t::from_bytes_unchecked($s) };
+        )*
+
+        #[test]
+        fn $test() {
+            $(
+                assert!($t::from_bytes($s).is_ok(), "invalid: {}", stringify!($name));
+            )*
+        }
+    }
+}
+
+/// Well-known bus names
+pub mod bus {
+    use super::*;
+
+    names!(t_bus_names, BusName {
+        /// The message bus itself
+        DBUS = b"org.freedesktop.DBus\0";
+        SYSTEMD1 = b"org.freedesktop.systemd1\0";
+        LOGIN1 = b"org.freedesktop.login1\0";
+        HOSTNAME1 = b"org.freedesktop.hostname1\0";
+        LOCALE1 = b"org.freedesktop.locale1\0";
+        TIMEDATE1 = b"org.freedesktop.timedate1\0";
+        MACHINE1 = b"org.freedesktop.machine1\0";
+        RESOLVE1 = b"org.freedesktop.resolve1\0";
+        NETWORK1 = b"org.freedesktop.network1\0";
+        POLKIT1 = b"org.freedesktop.PolicyKit1\0";
+    });
+}
+
+/// Object paths of the main object of each service in `bus`
+pub mod path {
+    use super::*;
+
+    names!(t_paths, ObjectPath {
+        DBUS = b"/org/freedesktop/DBus\0";
+        SYSTEMD1 = b"/org/freedesktop/systemd1\0";
+        LOGIN1 = b"/org/freedesktop/login1\0";
+        HOSTNAME1 = b"/org/freedesktop/hostname1\0";
+        LOCALE1 = b"/org/freedesktop/locale1\0";
+        TIMEDATE1 = b"/org/freedesktop/timedate1\0";
+        MACHINE1 = b"/org/freedesktop/machine1\0";
+        RESOLVE1 = b"/org/freedesktop/resolve1\0";
+        NETWORK1 = b"/org/freedesktop/network1\0";
+        POLKIT1_AUTHORITY = b"/org/freedesktop/PolicyKit1/Authority\0";
+    });
+}
+
+/// Standard D-Bus interfaces and the interfaces of systemd's services
+pub mod interface {
+    use super::*;
+
+    names!(t_interfaces, InterfaceName {
+        /// Methods of the message bus itself (`RequestName`, `AddMatch`, ...)
+        DBUS = b"org.freedesktop.DBus\0";
+        PEER = b"org.freedesktop.DBus.Peer\0";
+        INTROSPECTABLE = b"org.freedesktop.DBus.Introspectable\0";
+        PROPERTIES = b"org.freedesktop.DBus.Properties\0";
+        OBJECT_MANAGER = b"org.freedesktop.DBus.ObjectManager\0";
+
+        SYSTEMD1_MANAGER = b"org.freedesktop.systemd1.Manager\0";
+        SYSTEMD1_UNIT = b"org.freedesktop.systemd1.Unit\0";
+        SYSTEMD1_SERVICE = b"org.freedesktop.systemd1.Service\0";
+        SYSTEMD1_SOCKET = b"org.freedesktop.systemd1.Socket\0";
+        SYSTEMD1_TIMER = b"org.freedesktop.systemd1.Timer\0";
+        SYSTEMD1_SCOPE = b"org.freedesktop.systemd1.Scope\0";
+        SYSTEMD1_SLICE = b"org.freedesktop.systemd1.Slice\0";
+        SYSTEMD1_MOUNT = b"org.freedesktop.systemd1.Mount\0";
+        SYSTEMD1_SWAP = b"org.freedesktop.systemd1.Swap\0";
+        SYSTEMD1_JOB = b"org.freedesktop.systemd1.Job\0";
+
+        LOGIN1_MANAGER = b"org.freedesktop.login1.Manager\0";
+        LOGIN1_SEAT = b"org.freedesktop.login1.Seat\0";
+        LOGIN1_SESSION = b"org.freedesktop.login1.Session\0";
+        LOGIN1_USER = b"org.freedesktop.login1.User\0";
+
+        HOSTNAME1 = b"org.freedesktop.hostname1\0";
+        LOCALE1 = b"org.freedesktop.locale1\0";
+        TIMEDATE1 = b"org.freedesktop.timedate1\0";
+        MACHINE1_MANAGER = b"org.freedesktop.machine1.Manager\0";
+        MACHINE1_MACHINE = b"org.freedesktop.machine1.Machine\0";
+        RESOLVE1_MANAGER = b"org.freedesktop.resolve1.Manager\0";
+        NETWORK1_MANAGER = b"org.freedesktop.network1.Manager\0";
+        POLKIT1_AUTHORITY = b"org.freedesktop.PolicyKit1.Authority\0";
+    });
+}
+
+/// Standard error names, as used by the bus and by sd-bus (see `sd-bus-errors(3)`)
+pub mod error {
+    use super::*;
+
+    names!(t_errors, InterfaceName {
+        FAILED = b"org.freedesktop.DBus.Error.Failed\0";
+        NO_MEMORY = b"org.freedesktop.DBus.Error.NoMemory\0";
+        /// The destination of a call doesn't exist and can't be activated
+        SERVICE_UNKNOWN = b"org.freedesktop.DBus.Error.ServiceUnknown\0";
+        NAME_HAS_NO_OWNER = b"org.freedesktop.DBus.Error.NameHasNoOwner\0";
+        /// No reply was received in time
+        NO_REPLY = b"org.freedesktop.DBus.Error.NoReply\0";
+        IO_ERROR = b"org.freedesktop.DBus.Error.IOError\0";
+        BAD_ADDRESS = b"org.freedesktop.DBus.Error.BadAddress\0";
+        NOT_SUPPORTED = b"org.freedesktop.DBus.Error.NotSupported\0";
+        LIMITS_EXCEEDED = b"org.freedesktop.DBus.Error.LimitsExceeded\0";
+        ACCESS_DENIED = b"org.freedesktop.DBus.Error.AccessDenied\0";
+        AUTH_FAILED = b"org.freedesktop.DBus.Error.AuthFailed\0";
+        NO_SERVER = b"org.freedesktop.DBus.Error.NoServer\0";
+        /// An operation timed out (sd-bus uses this for `ETIMEDOUT`)
+        TIMEOUT = b"org.freedesktop.DBus.Error.Timeout\0";
+        NO_NETWORK = b"org.freedesktop.DBus.Error.NoNetwork\0";
+        ADDRESS_IN_USE = b"org.freedesktop.DBus.Error.AddressInUse\0";
+        DISCONNECTED = b"org.freedesktop.DBus.Error.Disconnected\0";
+        INVALID_ARGS = b"org.freedesktop.DBus.Error.InvalidArgs\0";
+        FILE_NOT_FOUND = b"org.freedesktop.DBus.Error.FileNotFound\0";
+        FILE_EXISTS = b"org.freedesktop.DBus.Error.FileExists\0";
+        UNKNOWN_METHOD = b"org.freedesktop.DBus.Error.UnknownMethod\0";
+        UNKNOWN_OBJECT = b"org.freedesktop.DBus.Error.UnknownObject\0";
+        UNKNOWN_INTERFACE = b"org.freedesktop.DBus.Error.UnknownInterface\0";
+        UNKNOWN_PROPERTY = b"org.freedesktop.DBus.Error.UnknownProperty\0";
+        PROPERTY_READ_ONLY = b"org.freedesktop.DBus.Error.PropertyReadOnly\0";
+        UNIX_PROCESS_ID_UNKNOWN = b"org.freedesktop.DBus.Error.UnixProcessIdUnknown\0";
+        INVALID_SIGNATURE = b"org.freedesktop.DBus.Error.InvalidSignature\0";
+        INCONSISTENT_MESSAGE = b"org.freedesktop.DBus.Error.InconsistentMessage\0";
+        TIMED_OUT = b"org.freedesktop.DBus.Error.TimedOut\0";
+        MATCH_RULE_NOT_FOUND = b"org.freedesktop.DBus.Error.MatchRuleNotFound\0";
+        MATCH_RULE_INVALID = b"org.freedesktop.DBus.Error.MatchRuleInvalid\0";
+        /// The call needs authorization, which requires user interaction the caller didn't allow
+        INTERACTIVE_AUTHORIZATION_REQUIRED =
+            b"org.freedesktop.DBus.Error.InteractiveAuthorizationRequired\0";
+    });
+}
@@ -0,0 +1,120 @@
+/*!
+ * Authorization of bus calls using polkit (`org.freedesktop.PolicyKit1`)
+ *
+ * `Polkit` is an `Authorizer` which maps each call to a polkit action and asks polkit whether the
+ * caller may perform it, so privileged services can share their access control policy with the
+ * rest of the system.
+ */
+
+use std::io;
+use super::{Creds, CredsMask, InterfaceName, MemberName, MessageRef, ObjectPath, Result};
+use super::names::{bus, interface, path};
+use super::types::Variant;
+use super::vtable::Authorizer;
+
+/// `AllowUserInteraction` flag of `CheckAuthorization`
+const CHECK_ALLOW_USER_INTERACTION: u32 = 1;
+
+/// An `Authorizer` checking calls against polkit actions.
+///
+/// Each check is a blocking call to polkit on the connection the call was received on, other
+/// messages on that connection are not processed in the meantime. If the caller set
+/// `allow_interactive_authorization`, polkit may ask the user to authenticate.
+pub struct Polkit<F> {
+    action: F,
+}
+
+impl<F> Polkit<F>
+    where F: Fn(&ObjectPath, &InterfaceName, &MemberName) -> Option<String>
+{
+    /// Authorize calls using the polkit action id `action` returns for them (ie:
+    /// `org.example.service.manage`).
+    ///
+    /// Calls for which `action` returns `None` are allowed without asking polkit.
+    pub fn new(action: F) -> Polkit<F> {
+        Polkit { action: action }
+    }
+}
+
+/// Extract `is_authorized` from the `(bba{ss})` result of `CheckAuthorization`
+fn is_authorized(v: Option<Variant>) -> Result<bool> {
+    match v {
+        Some(Variant::Struct(ref v)) => {
+            match v.first() {
+                Some(&Variant::Bool(b)) => return Ok(b),
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    Err(From::from(io::Error::new(io::ErrorKind::InvalidData, "unexpected polkit reply")))
+}
+
+impl<F> Authorizer for Polkit<F>
+    where F: Fn(&ObjectPath, &InterfaceName, &MemberName) -> Option<String>
+{
+    fn authorize(&self,
+                 call: &MessageRef,
+                 path: &ObjectPath,
+                 interface: &InterfaceName,
+                 member: &MemberName,
+                 sender: &Creds)
+                 -> Result<bool> {
+        let action = match (self.action)(path, interface, member) {
+            Some(action) => action,
+            None => return Ok(true),
+        };
+        let name = match sender.unique_name() {
+            Ok(name) => name,
+            Err(e) => try!(call.sender().ok_or(e)),
+        };
+        let name = name.to_string_lossy().into_owned();
+        let flags = if call.allow_interactive_authorization() {
+            CHECK_ALLOW_USER_INTERACTION
+        } else {
+            0
+        };
+
+        let mut bus = call.bus().to_owned();
+        let mut m = try!(bus.new_method_call(bus::POLKIT1,
+                                             path::POLKIT1_AUTHORITY,
+                                             interface::POLKIT1_AUTHORITY,
+                                             MemberName::from_bytes(b"CheckAuthorization\0")
+                                                 .unwrap()));
+        /* subject: ("system-bus-name", {"name": <sender>}) */
+        try!(m.append_struct_with("sa{sv}", |m| {
+            try!(m.append("system-bus-name"));
+            m.append_array_with("{sv}", |m| {
+                m.append_dict_entry_with("sv", |m| {
+                    try!(m.append("name"));
+                    m.append_variant(&name)
+                })
+            })
+        }));
+        try!(m.append(action));
+        /* no details */
+        try!(m.append_array_with("{ss}", |_| Ok(())));
+        try!(m.append(flags));
+        /* no cancellation id */
+        try!(m.append(""));
+
+        let mut reply = try!(m.call(0));
+        let mut i = try!(reply.iter());
+        is_authorized(try!(i.read_value()))
+    }
+
+    fn creds_mask(&self) -> CredsMask {
+        CredsMask::UNIQUE_NAME
+    }
+}
+
+#[test]
+fn t_is_authorized() {
+    let reply = |b| {
+        Some(Variant::Struct(vec![Variant::Bool(b), Variant::Bool(false), Variant::Array(vec![])]))
+    };
+    assert_eq!(is_authorized(reply(true)).unwrap(), true);
+    assert_eq!(is_authorized(reply(false)).unwrap(), false);
+    is_authorized(Some(Variant::Struct(vec![]))).err().unwrap();
+    is_authorized(None).err().unwrap();
+}
@@ -0,0 +1,405 @@
+/*!
+ * Client for systemd-resolved (`org.freedesktop.resolve1`)
+ *
+ * resolved returns resource records in DNS wire format. They are decoded into `ResourceRecord`s,
+ * with the record data of common types (A, AAAA, MX, SRV, TXT) parsed into `RecordData`.
+ */
+
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::{BitOr, BitOrAssign};
+use super::{Bus, BusRef, MemberName, Result};
+use super::names::{bus, interface, path};
+use super::types::Variant;
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+macro_rules! dns_enum {
+    ($(#[$attr:meta])*
+     pub enum $name:ident { $($(#[$vattr:meta])* $variant:ident = $value:expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vattr])* $variant,)*
+            /// Any other value
+            Other(u16),
+        }
+
+        impl From<u16> for $name {
+            fn from(v: u16) -> $name {
+                match v {
+                    $($value => $name::$variant,)*
+                    v => $name::Other(v),
+                }
+            }
+        }
+
+        impl From<$name> for u16 {
+            fn from(v: $name) -> u16 {
+                match v {
+                    $($name::$variant => $value,)*
+                    $name::Other(v) => v,
+                }
+            }
+        }
+    }
+}
+
+dns_enum! {
+    /// The type of a resource record
+    pub enum RecordType {
+        A = 1,
+        NS = 2,
+        CNAME = 5,
+        SOA = 6,
+        PTR = 12,
+        MX = 15,
+        TXT = 16,
+        AAAA = 28,
+        SRV = 33,
+        DS = 43,
+        SSHFP = 44,
+        RRSIG = 46,
+        NSEC = 47,
+        DNSKEY = 48,
+        TLSA = 52,
+        CAA = 257,
+    }
+}
+
+dns_enum! {
+    /// The class of a resource record
+    pub enum RecordClass {
+        /// Internet, the only class in common use
+        IN = 1,
+        /// Chaos
+        CH = 3,
+        /// Hesiod
+        HS = 4,
+        ANY = 255,
+    }
+}
+
+/// Flags controlling a lookup (`SD_RESOLVED_*`), and describing how its result was obtained.
+///
+/// Combine flags with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResolveFlags(u64);
+
+impl ResolveFlags {
+    pub const NONE: ResolveFlags = ResolveFlags(0);
+    /// Look up using classic unicast DNS
+    pub const DNS: ResolveFlags = ResolveFlags(1 << 0);
+    pub const LLMNR_IPV4: ResolveFlags = ResolveFlags(1 << 1);
+    pub const LLMNR_IPV6: ResolveFlags = ResolveFlags(1 << 2);
+    pub const MDNS_IPV4: ResolveFlags = ResolveFlags(1 << 3);
+    pub const MDNS_IPV6: ResolveFlags = ResolveFlags(1 << 4);
+    /// Don't follow CNAME/DNAME redirections
+    pub const NO_CNAME: ResolveFlags = ResolveFlags(1 << 5);
+    /// Don't apply search domains to the name
+    pub const NO_SEARCH: ResolveFlags = ResolveFlags(1 << 8);
+    /// Result: the data was validated using DNSSEC (or is otherwise known to be trustworthy, such
+    /// as locally synthesized data)
+    pub const AUTHENTICATED: ResolveFlags = ResolveFlags(1 << 9);
+    /// Don't validate the result using DNSSEC
+    pub const NO_VALIDATE: ResolveFlags = ResolveFlags(1 << 10);
+    /// Don't answer from locally synthesized records (such as the local hostname)
+    pub const NO_SYNTHESIZE: ResolveFlags = ResolveFlags(1 << 11);
+    /// Don't answer from the cache
+    pub const NO_CACHE: ResolveFlags = ResolveFlags(1 << 12);
+    /// Don't answer from locally registered zones
+    pub const NO_ZONE: ResolveFlags = ResolveFlags(1 << 13);
+    /// Don't answer from the DNSSEC trust anchor
+    pub const NO_TRUST_ANCHOR: ResolveFlags = ResolveFlags(1 << 14);
+    /// Don't send queries to the network
+    pub const NO_NETWORK: ResolveFlags = ResolveFlags(1 << 15);
+    /// Result: the data was only transferred encrypted, or never left the machine
+    pub const CONFIDENTIAL: ResolveFlags = ResolveFlags(1 << 18);
+    /// Result: the data was synthesized locally
+    pub const SYNTHETIC: ResolveFlags = ResolveFlags(1 << 19);
+    /// Result: the data came from the cache
+    pub const FROM_CACHE: ResolveFlags = ResolveFlags(1 << 20);
+    /// Result: the data came from a locally registered zone
+    pub const FROM_ZONE: ResolveFlags = ResolveFlags(1 << 21);
+    /// Result: the data came from the DNSSEC trust anchor
+    pub const FROM_TRUST_ANCHOR: ResolveFlags = ResolveFlags(1 << 22);
+    /// Result: the data was received from the network
+    pub const FROM_NETWORK: ResolveFlags = ResolveFlags(1 << 23);
+
+    #[inline]
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    pub fn from_bits(bits: u64) -> ResolveFlags {
+        ResolveFlags(bits)
+    }
+
+    /// Whether all flags set in `other` are also set in `self`
+    #[inline]
+    pub fn contains(self, other: ResolveFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the result was validated by DNSSEC (or is otherwise trustworthy)
+    #[inline]
+    pub fn is_authenticated(self) -> bool {
+        self.contains(ResolveFlags::AUTHENTICATED)
+    }
+}
+
+impl BitOr for ResolveFlags {
+    type Output = ResolveFlags;
+    #[inline]
+    fn bitor(self, other: ResolveFlags) -> ResolveFlags {
+        ResolveFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for ResolveFlags {
+    #[inline]
+    fn bitor_assign(&mut self, other: ResolveFlags) {
+        self.0 |= other.0
+    }
+}
+
+/// The decoded data of a resource record
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Mx { preference: u16, exchange: String },
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    /// The character strings making up the record
+    Txt(Vec<Vec<u8>>),
+    /// Record data of types not decoded here, in wire format
+    Other(Vec<u8>),
+}
+
+/// A resource record, as returned by `Resolver::resolve_record()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceRecord {
+    /// The interface the record was received on, 0 if not specific to one
+    pub ifindex: i32,
+    pub name: String,
+    pub class: RecordClass,
+    pub rtype: RecordType,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+impl fmt::Display for ResourceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {:?} {:?} {:?}", self.name, self.ttl, self.class, self.rtype, self.data)
+    }
+}
+
+/// Reads big endian values and names out of wire format data
+struct Wire<'a> {
+    b: &'a [u8],
+}
+
+impl<'a> Wire<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.b.len() < n {
+            return Err(From::from(invalid("truncated resource record")));
+        }
+        let (v, rest) = self.b.split_at(n);
+        self.b = rest;
+        Ok(v)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = try!(self.bytes(2));
+        Ok((b[0] as u16) << 8 | b[1] as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = try!(self.bytes(4));
+        Ok(b.iter().fold(0, |v, &b| v << 8 | b as u32))
+    }
+
+    /// An uncompressed domain name, returned in dotted form without the trailing dot (`.` for the
+    /// root).
+    fn name(&mut self) -> Result<String> {
+        let mut labels = Vec::new();
+        loop {
+            let len = try!(self.bytes(1))[0] as usize;
+            if len == 0 {
+                break;
+            }
+            if len > 63 {
+                return Err(From::from(invalid("compressed or invalid name in resource record")));
+            }
+            labels.push(String::from_utf8_lossy(try!(self.bytes(len))).into_owned());
+        }
+        Ok(if labels.is_empty() { ".".to_owned() } else { labels.join(".") })
+    }
+}
+
+impl RecordData {
+    /// Decode the wire format data `b` of a record of type `rtype`
+    pub fn parse(rtype: RecordType, b: &[u8]) -> Result<RecordData> {
+        let mut w = Wire { b: b };
+        let d = match rtype {
+            RecordType::A if b.len() == 4 => RecordData::A(Ipv4Addr::new(b[0], b[1], b[2], b[3])),
+            RecordType::AAAA if b.len() == 16 => {
+                let mut a = [0u8; 16];
+                a.copy_from_slice(b);
+                RecordData::Aaaa(Ipv6Addr::from(a))
+            }
+            RecordType::A | RecordType::AAAA => {
+                return Err(From::from(invalid("address record of the wrong size")))
+            }
+            RecordType::MX => {
+                RecordData::Mx { preference: try!(w.u16()), exchange: try!(w.name()) }
+            }
+            RecordType::SRV => {
+                RecordData::Srv {
+                    priority: try!(w.u16()),
+                    weight: try!(w.u16()),
+                    port: try!(w.u16()),
+                    target: try!(w.name()),
+                }
+            }
+            RecordType::TXT => {
+                let mut strings = Vec::new();
+                while !w.b.is_empty() {
+                    let len = try!(w.bytes(1))[0] as usize;
+                    strings.push(try!(w.bytes(len)).to_vec());
+                }
+                RecordData::Txt(strings)
+            }
+            _ => RecordData::Other(b.to_vec()),
+        };
+        Ok(d)
+    }
+}
+
+impl ResourceRecord {
+    /// Decode a complete resource record in wire format (name, type, class, TTL and data)
+    pub fn parse(ifindex: i32, b: &[u8]) -> Result<ResourceRecord> {
+        let mut w = Wire { b: b };
+        let name = try!(w.name());
+        let rtype = RecordType::from(try!(w.u16()));
+        let class = RecordClass::from(try!(w.u16()));
+        let ttl = try!(w.u32());
+        let len = try!(w.u16()) as usize;
+        let data = try!(RecordData::parse(rtype, try!(w.bytes(len))));
+        Ok(ResourceRecord {
+            ifindex: ifindex,
+            name: name,
+            class: class,
+            rtype: rtype,
+            ttl: ttl,
+            data: data,
+        })
+    }
+
+    /// Decode one `(iqqay)` element of a `ResolveRecord` reply
+    fn from_variant(v: Variant) -> Result<ResourceRecord> {
+        let fields = match v {
+            Variant::Struct(f) => f,
+            _ => return Err(From::from(invalid("unexpected record in reply"))),
+        };
+        match (fields.get(0), fields.get(3)) {
+            (Some(&Variant::Int32(ifindex)), Some(&Variant::Array(ref data))) => {
+                let data: Vec<u8> = data.iter()
+                    .filter_map(|b| match *b {
+                        Variant::Byte(b) => Some(b),
+                        _ => None,
+                    })
+                    .collect();
+                ResourceRecord::parse(ifindex, &data)
+            }
+            _ => Err(From::from(invalid("unexpected record in reply"))),
+        }
+    }
+}
+
+/// A connection to systemd-resolved.
+pub struct Resolver {
+    bus: Bus,
+}
+
+impl Resolver {
+    /// Talk to resolved via `bus` (which should be a system bus connection).
+    pub fn new(bus: &BusRef) -> Resolver {
+        Resolver { bus: bus.to_owned() }
+    }
+
+    /// Talk to resolved using the default system bus connection of this thread.
+    pub fn system() -> Result<Resolver> {
+        Ok(Resolver { bus: try!(Bus::default_system()) })
+    }
+
+    /// Look up the records of type `rtype` and class `class` for `name` (`ResolveRecord`).
+    ///
+    /// `ifindex` limits the lookup to one network interface (0 for any). Returns the records
+    /// along with flags describing how they were obtained, such as `AUTHENTICATED`.
+    pub fn resolve_record(&mut self,
+                          ifindex: i32,
+                          name: &str,
+                          class: RecordClass,
+                          rtype: RecordType,
+                          flags: ResolveFlags)
+                          -> Result<(Vec<ResourceRecord>, ResolveFlags)> {
+        let mut m = try!(self.bus.new_method_call(bus::RESOLVE1,
+                                                  path::RESOLVE1,
+                                                  interface::RESOLVE1_MANAGER,
+                                                  MemberName::from_bytes(b"ResolveRecord\0")
+                                                      .unwrap()));
+        try!(m.append((ifindex, name, u16::from(class), u16::from(rtype), flags.bits())));
+        let mut reply = try!(m.call(0));
+        let mut i = try!(reply.iter());
+        let records = match try!(i.read_value()) {
+            Some(Variant::Array(records)) => records,
+            _ => return Err(From::from(invalid("unexpected ResolveRecord reply"))),
+        };
+        let records = try!(records.into_iter().map(ResourceRecord::from_variant).collect());
+        let flags: Option<u64> = try!(i.read_next());
+        Ok((records, ResolveFlags(flags.unwrap_or(0))))
+    }
+}
+
+#[test]
+fn t_record_types() {
+    assert_eq!(RecordType::from(28), RecordType::AAAA);
+    assert_eq!(RecordType::from(999), RecordType::Other(999));
+    assert_eq!(u16::from(RecordType::SRV), 33);
+    assert_eq!(u16::from(RecordClass::IN), 1);
+    assert!((ResolveFlags::AUTHENTICATED | ResolveFlags::FROM_NETWORK).is_authenticated());
+    assert!(!ResolveFlags::FROM_CACHE.is_authenticated());
+}
+
+#[test]
+fn t_parse_record() {
+    let rr = [7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0,
+              0, 33, 0, 1, 0, 0, 0x0e, 0x10, 0, 14,
+              0, 10, 0, 5, 0x13, 0xc4, 3, b'x', b'm', b'p', 2, b'i', b'o', 0];
+    let r = ResourceRecord::parse(2, &rr).unwrap();
+    assert_eq!(r.name, "example.com");
+    assert_eq!(r.rtype, RecordType::SRV);
+    assert_eq!(r.class, RecordClass::IN);
+    assert_eq!(r.ttl, 3600);
+    assert_eq!(r.data,
+               RecordData::Srv {
+                   priority: 10,
+                   weight: 5,
+                   port: 5060,
+                   target: "xmp.io".to_owned(),
+               });
+
+    assert_eq!(RecordData::parse(RecordType::A, &[10, 0, 0, 1]).unwrap(),
+               RecordData::A(Ipv4Addr::new(10, 0, 0, 1)));
+    assert_eq!(RecordData::parse(RecordType::TXT, b"\x03a=b\x00").unwrap(),
+               RecordData::Txt(vec![b"a=b".to_vec(), vec![]]));
+    assert_eq!(RecordData::parse(RecordType::MX, b"\x00\x05\x00").unwrap(),
+               RecordData::Mx { preference: 5, exchange: ".".to_owned() });
+    RecordData::parse(RecordType::AAAA, &[0; 4]).err().unwrap();
+    RecordData::parse(RecordType::SRV, &[0; 4]).err().unwrap();
+}
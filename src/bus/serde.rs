@@ -0,0 +1,988 @@
+/*!
+ * Appending and reading `serde` types (requires the `serde` feature)
+ *
+ * serde's data model doesn't carry D-Bus types, so `to_message()` is given the signature to
+ * append a value as. Reading needs no signature, as messages describe their own contents.
+ *
+ * Values map to D-Bus types as follows:
+ *
+ *  - booleans to `b`, floats to `d`, and integers to any integer type (including `h`) they fit in
+ *  - strings and `char`s to `s`, `o` or `g`; bytes (ie: through `serde_bytes`) to `ay`
+ *  - sequences to arrays, maps to arrays of dict entries (`a{..}`), tuples to structs
+ *  - structs to structs, or to arrays of dict entries with string keys (ie: `a{sv}`), using the
+ *    field names as keys. Fields which are `None` are left out of the latter.
+ *  - unit variants of enums to their name (`s`)
+ *  - newtype structs and `Some(v)` to the type of their contents
+ *
+ * Anything can be appended as a variant (`v`), its contents then have the signature inferred from
+ * the value (ie: `x` for an `i64`). Values read from a variant are those of its contents, and
+ * `h` is read as the `i32` of the file descriptor.
+ */
+
+use std::convert::TryFrom;
+use std::error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::io;
+use std::result;
+use std::str;
+use ffi::c_char;
+use serde::{de, ser};
+use serde::de::IntoDeserializer;
+use super::{MessageIter, MessageRef};
+use super::types::{self, FromSdBusMessage, ToSdBusMessage, UnixFd};
+
+/// Errors of the serializers, wrapping the `io::Error` returned by the public functions
+#[derive(Debug)]
+struct Error(io::Error);
+
+type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    fn new(kind: io::ErrorKind, msg: String) -> Error {
+        Error(io::Error::new(kind, msg))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+impl error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::new(io::ErrorKind::InvalidInput, msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::new(io::ErrorKind::InvalidData, msg.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error(e)
+    }
+}
+
+fn invalid_signature(sig: &str) -> Error {
+    Error::new(io::ErrorKind::InvalidInput, format!("invalid signature '{}'", sig))
+}
+
+/// The length of the complete type at the start of `sig`
+fn complete_type(sig: &[u8]) -> Option<usize> {
+    match sig.first() {
+        Some(&b'a') => complete_type(&sig[1..]).map(|l| l + 1),
+        Some(&c) if c == b'(' || c == b'{' => {
+            let end = if c == b'(' { b')' } else { b'}' };
+            let mut i = 1;
+            loop {
+                match sig.get(i) {
+                    Some(&b) if b == end => return Some(i + 1),
+                    Some(_) => i += match complete_type(&sig[i..]) {
+                        Some(l) => l,
+                        None => return None,
+                    },
+                    None => return None,
+                }
+            }
+        }
+        Some(&c) if b"ybnqiuxtdsoghv".contains(&c) => Some(1),
+        _ => None,
+    }
+}
+
+/// Split `sig` into its complete types
+fn split(sig: &str) -> Result<Vec<&str>> {
+    let mut types = Vec::new();
+    let mut rest = sig;
+    while !rest.is_empty() {
+        let len = try!(complete_type(rest.as_bytes()).ok_or_else(|| invalid_signature(sig)));
+        types.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Ok(types)
+}
+
+/// Append `v` as the complete type `sig`, inferring the contents of variants from `v`
+fn serialize_value<T: ser::Serialize + ?Sized>(m: &mut MessageRef, sig: &str, v: &T)
+    -> Result<()>
+{
+    if sig != "v" {
+        return v.serialize(Serializer { m: m, sig: sig, body: false });
+    }
+    let inner = try!(v.serialize(SignatureOf));
+    if inner.is_empty() {
+        return Err(ser::Error::custom("can't append None as a variant"));
+    }
+    try!(m.open_container(b'v', &inner));
+    try!(v.serialize(Serializer { m: &mut *m, sig: &inner, body: false }));
+    Ok(try!(m.close_container()))
+}
+
+/// Appends a value with the signature `sig` to `m`
+struct Serializer<'a, 's> {
+    m: &'a mut MessageRef,
+    sig: &'s str,
+    /// Whether `sig` may be several complete types, taken by the fields of a tuple or struct
+    body: bool,
+}
+
+impl<'a, 's> Serializer<'a, 's> {
+    fn mismatch(&self, what: &str) -> Error {
+        ser::Error::custom(format_args!("can't append {} as '{}'", what, self.sig))
+    }
+
+    fn append<V: ToSdBusMessage>(self, v: V) -> Result<()> {
+        Ok(try!(v.to_message(self.m)))
+    }
+
+    fn integer(self, v: i128) -> Result<()> {
+        fn fit<T: TryFrom<i128>>(v: i128, sig: &str) -> Result<T> {
+            T::try_from(v).map_err(|_| {
+                ser::Error::custom(format_args!("{} is out of range for '{}'", v, sig))
+            })
+        }
+        let sig = self.sig;
+        match sig {
+            "y" => self.append(try!(fit::<u8>(v, sig))),
+            "n" => self.append(try!(fit::<i16>(v, sig))),
+            "q" => self.append(try!(fit::<u16>(v, sig))),
+            "i" => self.append(try!(fit::<i32>(v, sig))),
+            "u" => self.append(try!(fit::<u32>(v, sig))),
+            "x" => self.append(try!(fit::<i64>(v, sig))),
+            "t" => self.append(try!(fit::<u64>(v, sig))),
+            "h" => self.append(UnixFd(try!(fit::<i32>(v, sig)))),
+            _ => Err(self.mismatch("an integer")),
+        }
+    }
+
+    /// Start a struct, or the body of the message if `self.body` is set
+    fn fields(self) -> Result<Compound<'a, 's>> {
+        if self.body {
+            return Ok(Compound::new(self.m, try!(split(self.sig)), Kind::Struct, false));
+        }
+        let sig = self.sig;
+        if !sig.starts_with('(') {
+            return Err(self.mismatch("a tuple or struct"));
+        }
+        let contents = &sig[1..sig.len() - 1];
+        try!(self.m.open_container(b'r', contents));
+        Ok(Compound::new(self.m, try!(split(contents)), Kind::Struct, true))
+    }
+
+    /// Start an array of `a{..}` dict entries
+    fn entries(self, what: &str) -> Result<(&'a mut MessageRef, &'s str)> {
+        let sig = self.sig;
+        if !sig.starts_with("a{") || self.body {
+            return Err(self.mismatch(what));
+        }
+        try!(self.m.open_container(b'a', &sig[1..]));
+        Ok((self.m, &sig[2..sig.len() - 1]))
+    }
+}
+
+impl<'a, 's> ser::Serializer for Serializer<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, 's>;
+    type SerializeTuple = Compound<'a, 's>;
+    type SerializeTupleStruct = Compound<'a, 's>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = Compound<'a, 's>;
+    type SerializeStruct = Compound<'a, 's>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        match self.sig {
+            "b" => self.append(v),
+            _ => Err(self.mismatch("a boolean")),
+        }
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        match self.sig {
+            "d" => self.append(v),
+            _ => Err(self.mismatch("a float")),
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        let mut buf = [0; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        match self.sig {
+            "s" => self.append(v),
+            "g" => self.append(types::Signature(v.to_owned())),
+            "o" => {
+                /* sd-bus checks that `v` is a valid object path */
+                let v = try!(CString::new(v).map_err(|_| self.mismatch("a string with a nul")));
+                Ok(try!(unsafe { self.m.append_basic_raw(b'o', v.as_ptr() as *const _) }))
+            }
+            _ => Err(self.mismatch("a string")),
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        match self.sig {
+            "ay" => Ok(try!(self.m.append_array_u8(v))),
+            _ => Err(self.mismatch("bytes")),
+        }
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(self.mismatch("None"))
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, v: &T) -> Result<()> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(self.mismatch("()"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
+        Err(self.mismatch(name))
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str)
+        -> Result<()>
+    {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(self, _: &'static str, v: &T)
+        -> Result<()>
+    {
+        v.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(self,
+                                                             name: &'static str,
+                                                             _: u32,
+                                                             _: &'static str,
+                                                             _: &T)
+                                                             -> Result<()> {
+        Err(self.mismatch(name))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Compound<'a, 's>> {
+        let sig = self.sig;
+        if !sig.starts_with('a') || self.body {
+            return Err(self.mismatch("a sequence"));
+        }
+        try!(self.m.open_container(b'a', &sig[1..]));
+        Ok(Compound::new(self.m, vec![&sig[1..]], Kind::Array, true))
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Compound<'a, 's>> {
+        self.fields()
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Compound<'a, 's>> {
+        self.fields()
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _: u32, _: &'static str, _: usize)
+        -> Result<Self::SerializeTupleVariant>
+    {
+        Err(self.mismatch(name))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Compound<'a, 's>> {
+        let (m, entry) = try!(self.entries("a map"));
+        let types = try!(split(entry));
+        Ok(Compound::new(m, types, Kind::Map(entry), true))
+    }
+
+    fn serialize_struct(self, name: &'static str, _: usize) -> Result<Compound<'a, 's>> {
+        if self.body || self.sig.starts_with('(') {
+            return self.fields();
+        }
+        if !self.sig.starts_with("a{s") {
+            return Err(self.mismatch(name));
+        }
+        let (m, entry) = try!(self.entries(name));
+        Ok(Compound::new(m, vec![&entry[1..]], Kind::Dict, true))
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _: u32, _: &'static str, _: usize)
+        -> Result<Self::SerializeStructVariant>
+    {
+        Err(self.mismatch(name))
+    }
+}
+
+enum Kind<'s> {
+    /// Elements of the single type in `types`
+    Array,
+    /// One value for each of `types`
+    Struct,
+    /// Dict entries with the contents `{..}`, whose key and value are `types`
+    Map(&'s str),
+    /// Dict entries with string keys, and values of the single type in `types`
+    Dict,
+}
+
+/// Appends the contents of a container (or of the body of a message)
+struct Compound<'a, 's> {
+    m: &'a mut MessageRef,
+    types: Vec<&'s str>,
+    kind: Kind<'s>,
+    /// The number of values appended so far
+    next: usize,
+    /// Whether a container needs to be closed at the end
+    container: bool,
+}
+
+impl<'a, 's> Compound<'a, 's> {
+    fn new(m: &'a mut MessageRef, types: Vec<&'s str>, kind: Kind<'s>, container: bool)
+        -> Compound<'a, 's>
+    {
+        Compound { m: m, types: types, kind: kind, next: 0, container: container }
+    }
+
+    fn element<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        let sig = match self.kind {
+            Kind::Array => self.types[0],
+            _ => {
+                match self.types.get(self.next) {
+                    Some(sig) => *sig,
+                    None => return Err(ser::Error::custom("more values than the signature has")),
+                }
+            }
+        };
+        self.next += 1;
+        serialize_value(self.m, sig, v)
+    }
+
+    fn entry<T: ser::Serialize + ?Sized>(&mut self, key: &str, v: &T) -> Result<()> {
+        let sig = self.types[0];
+        /* None can't be appended, but leaving out a field makes sense */
+        if let Ok(ref s) = v.serialize(SignatureOf) {
+            if s.is_empty() {
+                return Ok(());
+            }
+        }
+        try!(self.m.open_container(b'e', &format!("s{}", sig)));
+        try!(self.m.append(key));
+        try!(serialize_value(self.m, sig, v));
+        Ok(try!(self.m.close_container()))
+    }
+
+    fn end(self) -> Result<()> {
+        if let Kind::Struct = self.kind {
+            if self.next != self.types.len() {
+                return Err(ser::Error::custom("fewer values than the signature has"));
+            }
+        }
+        if self.container {
+            try!(self.m.close_container());
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 's> ser::SerializeSeq for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 's> ser::SerializeTuple for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 's> ser::SerializeTupleStruct for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.element(v)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 's> ser::SerializeMap for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, k: &T) -> Result<()> {
+        if let Kind::Map(entry) = self.kind {
+            try!(self.m.open_container(b'e', entry));
+        }
+        self.next = 0;
+        self.element(k)
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        try!(self.element(v));
+        Ok(try!(self.m.close_container()))
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(try!(self.m.close_container()))
+    }
+}
+
+impl<'a, 's> ser::SerializeStruct for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, key: &'static str, v: &T)
+        -> Result<()>
+    {
+        match self.kind {
+            Kind::Dict => self.entry(key, v),
+            _ => self.element(v),
+        }
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+/// Infers the signature of a value, the empty string for `None`
+struct SignatureOf;
+
+fn no_signature(what: &str) -> Error {
+    ser::Error::custom(format_args!("can't infer a D-Bus type for {}", what))
+}
+
+/// Collects the signatures of the contents of a container
+struct Signatures {
+    /// The signatures of the first element or entry of an array, of each field of a struct
+    parts: Vec<String>,
+    /// Whether this is an array (in which case only the first element matters)
+    array: bool,
+    /// Whether this is an array of dict entries
+    map: bool,
+}
+
+impl Signatures {
+    fn push<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        if self.array && self.parts.len() == (if self.map { 2 } else { 1 }) {
+            return Ok(());
+        }
+        let sig = try!(v.serialize(SignatureOf));
+        if sig.is_empty() {
+            return Err(no_signature("None"));
+        }
+        self.parts.push(sig);
+        Ok(())
+    }
+
+    fn end(self) -> Result<String> {
+        let contents = self.parts.concat();
+        match (self.array, self.map) {
+            _ if contents.is_empty() => Err(no_signature("an empty container")),
+            (true, true) => Ok(format!("a{{{}}}", contents)),
+            (true, false) => Ok(format!("a{}", contents)),
+            _ => Ok(format!("({})", contents)),
+        }
+    }
+}
+
+impl ser::Serializer for SignatureOf {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Signatures;
+    type SerializeTuple = Signatures;
+    type SerializeTupleStruct = Signatures;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = Signatures;
+    type SerializeStruct = Signatures;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, _: bool) -> Result<String> {
+        Ok("b".into())
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<String> {
+        Ok("n".into())
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<String> {
+        Ok("n".into())
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<String> {
+        Ok("i".into())
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<String> {
+        Ok("x".into())
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<String> {
+        Ok("y".into())
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<String> {
+        Ok("q".into())
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<String> {
+        Ok("u".into())
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<String> {
+        Ok("t".into())
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<String> {
+        Ok("d".into())
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<String> {
+        Ok("d".into())
+    }
+
+    fn serialize_char(self, _: char) -> Result<String> {
+        Ok("s".into())
+    }
+
+    fn serialize_str(self, _: &str) -> Result<String> {
+        Ok("s".into())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<String> {
+        Ok("ay".into())
+    }
+
+    fn serialize_none(self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, v: &T) -> Result<String> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Err(no_signature("()"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<String> {
+        Err(no_signature(name))
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str)
+        -> Result<String>
+    {
+        Ok("s".into())
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(self, _: &'static str, v: &T)
+        -> Result<String>
+    {
+        v.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(self,
+                                                             name: &'static str,
+                                                             _: u32,
+                                                             _: &'static str,
+                                                             _: &T)
+                                                             -> Result<String> {
+        Err(no_signature(name))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Signatures> {
+        Ok(Signatures { parts: Vec::new(), array: true, map: false })
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Signatures> {
+        Ok(Signatures { parts: Vec::new(), array: false, map: false })
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Signatures> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _: u32, _: &'static str, _: usize)
+        -> Result<Self::SerializeTupleVariant>
+    {
+        Err(no_signature(name))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Signatures> {
+        Ok(Signatures { parts: Vec::new(), array: true, map: true })
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Signatures> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _: u32, _: &'static str, _: usize)
+        -> Result<Self::SerializeStructVariant>
+    {
+        Err(no_signature(name))
+    }
+}
+
+impl ser::SerializeSeq for Signatures {
+    type Ok = String;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> Result<String> {
+        Signatures::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Signatures {
+    type Ok = String;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> Result<String> {
+        Signatures::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Signatures {
+    type Ok = String;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> Result<String> {
+        Signatures::end(self)
+    }
+}
+
+impl ser::SerializeMap for Signatures {
+    type Ok = String;
+    type Error = Error;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, k: &T) -> Result<()> {
+        self.push(k)
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, v: &T) -> Result<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> Result<String> {
+        Signatures::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Signatures {
+    type Ok = String;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, _: &'static str, v: &T)
+        -> Result<()>
+    {
+        self.push(v)
+    }
+
+    fn end(self) -> Result<String> {
+        Signatures::end(self)
+    }
+}
+
+fn no_more_values() -> Error {
+    de::Error::custom("no more values in the message")
+}
+
+/// Reads the next value of a message
+struct Deserializer<'i, 'de: 'i> {
+    i: &'i mut MessageIter<'de>,
+}
+
+impl<'i, 'de> Deserializer<'i, 'de> {
+    fn peek(&mut self) -> Result<(u8, *const c_char)> {
+        try!(self.i.peek_raw()).ok_or_else(no_more_values)
+    }
+
+    fn read<T: FromSdBusMessage<'de>>(&mut self) -> Result<T> {
+        try!(T::from_message(self.i)).ok_or_else(no_more_values)
+    }
+
+    fn read_str(&mut self, t: u8) -> Result<&'de str> {
+        let s = try!(unsafe {
+            self.i.read_basic_raw(t, |x: *const c_char| {
+                str::from_utf8_unchecked(CStr::from_ptr(x).to_bytes())
+            })
+        });
+        s.ok_or_else(no_more_values)
+    }
+
+    /// Read the container of type `t` with `f`, skipping whatever it leaves unread
+    fn container<T, F>(&mut self, t: u8, contents: *const c_char, f: F) -> Result<T>
+        where F: FnOnce(&mut MessageIter<'de>) -> Result<T>
+    {
+        if !try!(self.i.enter_container_raw(t, contents)) {
+            return Err(no_more_values());
+        }
+        let v = try!(f(self.i));
+        try!(self.i.skip_and_exit_container());
+        Ok(v)
+    }
+}
+
+impl<'i, 'de> de::Deserializer<'de> for Deserializer<'i, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        let (t, contents) = try!(self.peek());
+        match t {
+            b'y' => visitor.visit_u8(try!(self.read())),
+            b'b' => visitor.visit_bool(try!(self.read())),
+            b'n' => visitor.visit_i16(try!(self.read())),
+            b'q' => visitor.visit_u16(try!(self.read())),
+            b'i' => visitor.visit_i32(try!(self.read())),
+            b'u' => visitor.visit_u32(try!(self.read())),
+            b'x' => visitor.visit_i64(try!(self.read())),
+            b't' => visitor.visit_u64(try!(self.read())),
+            b'd' => visitor.visit_f64(try!(self.read())),
+            b'h' => visitor.visit_i32(try!(self.read::<UnixFd>()).0),
+            b's' | b'o' | b'g' => visitor.visit_borrowed_str(try!(self.read_str(t))),
+            b'a' if unsafe { *contents } == b'{' as c_char => {
+                self.container(t, contents, |i| visitor.visit_map(Access { i: i }))
+            }
+            b'a' | b'r' => self.container(t, contents, |i| visitor.visit_seq(Access { i: i })),
+            b'v' => self.container(t, contents, |i| Deserializer { i: i }.deserialize_any(visitor)),
+            _ => Err(de::Error::custom(format_args!("unknown type '{}'", t as char))),
+        }
+    }
+
+    fn deserialize_bytes<V: de::Visitor<'de>>(mut self, visitor: V) -> Result<V::Value> {
+        let (t, contents) = try!(self.peek());
+        if t == b'v' {
+            return self.container(t, contents, |i| {
+                Deserializer { i: i }.deserialize_bytes(visitor)
+            });
+        }
+        if t == b'a' && unsafe { *contents } == b'y' as c_char {
+            let v = try!(self.i.read_array_u8());
+            return visitor.visit_borrowed_bytes(try!(v.ok_or_else(no_more_values)));
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _: &'static str, visitor: V)
+        -> Result<V::Value>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(mut self,
+                                             name: &'static str,
+                                             variants: &'static [&'static str],
+                                             visitor: V)
+                                             -> Result<V::Value> {
+        match try!(self.peek()) {
+            (b'v', contents) => {
+                self.container(b'v', contents, |i| {
+                    Deserializer { i: i }.deserialize_enum(name, variants, visitor)
+                })
+            }
+            (b's', _) => {
+                let variant = try!(self.read_str(b's'));
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            (t, _) => {
+                Err(de::Error::custom(format_args!("can't read '{}' as an enum", t as char)))
+            }
+        }
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        try!(self.i.skip(None));
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string unit unit_struct
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// Reads the elements of an array or struct, or the entries of an `a{..}`
+struct Access<'i, 'de: 'i> {
+    i: &'i mut MessageIter<'de>,
+}
+
+impl<'i, 'de> de::SeqAccess<'de> for Access<'i, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T)
+        -> Result<Option<T::Value>>
+    {
+        if try!(self.i.peek_raw()).is_none() {
+            return Ok(None);
+        }
+        seed.deserialize(Deserializer { i: &mut *self.i }).map(Some)
+    }
+}
+
+impl<'i, 'de> de::MapAccess<'de> for Access<'i, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K)
+        -> Result<Option<K::Value>>
+    {
+        let (t, contents) = match try!(self.i.peek_raw()) {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        try!(self.i.enter_container_raw(t, contents));
+        seed.deserialize(Deserializer { i: &mut *self.i }).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let v = try!(seed.deserialize(Deserializer { i: &mut *self.i }));
+        try!(self.i.exit_container_raw());
+        Ok(v)
+    }
+}
+
+/// Reads the remaining values of a message as a sequence
+struct Args<'i, 'de: 'i> {
+    i: &'i mut MessageIter<'de>,
+}
+
+impl<'i, 'de> de::Deserializer<'de> for Args<'i, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { i: self.i })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+/// Append `value` to `m` as the type `signature`.
+///
+/// If `signature` consists of several complete types (ie: `"sa{sv}"`, the arguments of a method),
+/// `value` must be a tuple or struct with a field for each, which are appended as separate values.
+pub fn to_message<T>(m: &mut MessageRef, signature: &str, value: &T) -> ::Result<()>
+    where T: ser::Serialize + ?Sized
+{
+    let r = match split(signature) {
+        Ok(ref types) if types.len() > 1 => {
+            value.serialize(Serializer { m: m, sig: signature, body: true })
+        }
+        Ok(_) => serialize_value(m, signature, value),
+        Err(e) => Err(e),
+    };
+    r.map_err(|e| e.0)
+}
+
+/// Read the next value from `i` as a `T`. Returns `None` at the end of the current container.
+///
+/// Strings and byte arrays may be borrowed from the message.
+pub fn from_message<'de, T: de::Deserialize<'de>>(i: &mut MessageIter<'de>)
+    -> ::Result<Option<T>>
+{
+    if try!(i.peek_raw()).is_none() {
+        return Ok(None);
+    }
+    T::deserialize(Deserializer { i: i }).map(Some).map_err(|e| e.0)
+}
+
+/// Read all remaining values of the current container (ie: the arguments of a method call) into
+/// a tuple or struct with a field for each.
+pub fn from_args<'de, T: de::Deserialize<'de>>(i: &mut MessageIter<'de>) -> ::Result<T> {
+    T::deserialize(Args { i: i }).map_err(|e| e.0)
+}
+
+#[test]
+fn t_signatures() {
+    assert_eq!(split("sa{sv}(ia(yy))").unwrap(), vec!["s", "a{sv}", "(ia(yy))"]);
+    split("a{sv").err().unwrap();
+    split("(").err().unwrap();
+    split("z").err().unwrap();
+
+    use serde::Serialize;
+    assert_eq!((1u8, "x", vec![(1i32, 2.0)]).serialize(SignatureOf).unwrap(), "(ysa(id))");
+    assert_eq!(None::<u32>.serialize(SignatureOf).unwrap(), "");
+    Vec::<u32>::new().serialize(SignatureOf).err().unwrap();
+}
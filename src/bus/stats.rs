@@ -0,0 +1,235 @@
+/*!
+ * Instrumentation of bus connections
+ *
+ * `BusRef::instrument()` attaches a `MessageHook` to a connection. The hook sees every message
+ * received on the connection and every message sent through this crate, along with the latency of
+ * each method call, so services can export bus health metrics without wrapping every call site.
+ * `Stats` is a ready made hook which keeps counters and a latency histogram.
+ *
+ * Messages sd-bus sends on its own (such as replies to `Properties.Get` or `Introspect`) are not
+ * seen. sd-bus does not expose the encoded size of messages, so there are no byte counters.
+ *
+ * Connections can't be shared between threads, so hooks are tracked per thread.
+ */
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+use super::{BusRef, Error, MessageRef, Slot};
+
+/// Callbacks invoked for the traffic on an instrumented connection.
+///
+/// Hooks must not send messages on the connection they instrument themselves, such messages are
+/// not reported.
+pub trait MessageHook {
+    /// `m` was received, before it is dispatched to any handler
+    fn received(&mut self, _m: &MessageRef) {}
+
+    /// `m` was sent
+    fn sent(&mut self, _m: &MessageRef) {}
+
+    /// The method call `call` completed after `latency`, failing with `error` if set. Both the call
+    /// and its reply are also reported to `sent()` and `received()`.
+    fn call_completed(&mut self, _call: &MessageRef, _latency: Duration, _error: Option<&Error>) {}
+}
+
+thread_local! {
+    static HOOKS: RefCell<Vec<(usize, Rc<RefCell<MessageHook>>)>> = RefCell::new(Vec::new());
+}
+
+fn key(bus: &BusRef) -> usize {
+    bus as *const BusRef as usize
+}
+
+/// Whether a hook is attached to `bus`
+pub(super) fn is_instrumented(bus: &BusRef) -> bool {
+    let key = key(bus);
+    HOOKS.with(|h| h.borrow().iter().any(|&(k, _)| k == key))
+}
+
+/// Call `f` with the hook attached to `bus`, if any.
+pub(super) fn with_hook<F: FnOnce(&mut MessageHook)>(bus: &BusRef, f: F) {
+    let hook = HOOKS.with(|h| {
+        let h = h.borrow();
+        if h.is_empty() {
+            return None;
+        }
+        let key = key(bus);
+        h.iter().find(|&&(k, _)| k == key).map(|&(_, ref hook)| hook.clone())
+    });
+    /* A hook which (indirectly) triggers itself is skipped rather than panicking */
+    if let Some(hook) = hook {
+        if let Ok(mut hook) = hook.try_borrow_mut() {
+            f(&mut *hook);
+        }
+    }
+}
+
+/// Keeps a `MessageHook` attached to a connection. Dropping it detaches the hook.
+pub struct Instrumentation {
+    key: usize,
+    _filter: Slot,
+}
+
+impl Drop for Instrumentation {
+    fn drop(&mut self) {
+        let key = self.key;
+        HOOKS.with(|h| h.borrow_mut().retain(|&(k, _)| k != key));
+    }
+}
+
+impl fmt::Debug for Instrumentation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Instrumentation").finish()
+    }
+}
+
+impl BusRef {
+    /// Attach `hook` to this connection, replacing any hook attached before.
+    ///
+    /// The hook is kept in an `Rc<RefCell<_>>` so it can be inspected while attached, for example
+    /// to export the counters of `Stats`.
+    pub fn instrument<H: MessageHook + 'static>(&self, hook: Rc<RefCell<H>>)
+        -> super::super::Result<Instrumentation>
+    {
+        let filter = try!(self.add_filter(|m| {
+            with_hook(m.bus(), |h| h.received(m));
+            Ok(())
+        }));
+        let key = key(self);
+        let hook: Rc<RefCell<MessageHook>> = hook;
+        HOOKS.with(|h| {
+            let mut h = h.borrow_mut();
+            h.retain(|&(k, _)| k != key);
+            h.push((key, hook));
+        });
+        Ok(Instrumentation { key: key, _filter: filter })
+    }
+}
+
+/// Upper bounds of the `LatencyHistogram` buckets, in microseconds
+const LATENCY_BOUNDS_USEC: [u64; 14] = [100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000,
+                                        50_000, 100_000, 250_000, 500_000, 1_000_000, 5_000_000];
+
+/// A histogram of durations with fixed buckets ranging from 100µs to 5s.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    // one more than LATENCY_BOUNDS_USEC, for everything above the largest bound
+    counts: [u64; 15],
+    sum: Duration,
+}
+
+impl LatencyHistogram {
+    /// Record a single duration
+    pub fn observe(&mut self, d: Duration) {
+        let usec = ::duration_usec(d);
+        let i = LATENCY_BOUNDS_USEC.iter()
+            .position(|&b| usec <= b)
+            .unwrap_or(LATENCY_BOUNDS_USEC.len());
+        self.counts[i] += 1;
+        self.sum += d;
+    }
+
+    /// The number of durations recorded
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The sum of all durations recorded
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// The buckets as (upper bound, count) pairs. The last bucket has no upper bound.
+    ///
+    /// Counts are per bucket, not cumulative.
+    pub fn buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        LATENCY_BOUNDS_USEC.iter()
+            .map(|&b| Some(Duration::from_micros(b)))
+            .chain(Some(None))
+            .zip(self.counts.iter().cloned())
+            .collect()
+    }
+}
+
+/// A `MessageHook` counting messages and recording method call latencies.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// Method calls which completed, successfully or not
+    pub calls: u64,
+    /// Method calls which failed, including timeouts
+    pub call_errors: u64,
+    pub call_latency: LatencyHistogram,
+}
+
+impl MessageHook for Stats {
+    fn received(&mut self, _m: &MessageRef) {
+        self.messages_received += 1;
+    }
+
+    fn sent(&mut self, _m: &MessageRef) {
+        self.messages_sent += 1;
+    }
+
+    fn call_completed(&mut self, _call: &MessageRef, latency: Duration, error: Option<&Error>) {
+        self.calls += 1;
+        if error.is_some() {
+            self.call_errors += 1;
+        }
+        self.call_latency.observe(latency);
+    }
+}
+
+#[test]
+fn t_latency_histogram() {
+    let mut h = LatencyHistogram::default();
+    h.observe(Duration::from_micros(50));
+    h.observe(Duration::from_micros(100));
+    h.observe(Duration::from_millis(3));
+    h.observe(Duration::from_secs(60));
+    assert_eq!(h.count(), 4);
+    assert_eq!(h.sum(), Duration::from_micros(60_003_150));
+
+    let b = h.buckets();
+    assert_eq!(b.len(), 15);
+    assert_eq!(b[0], (Some(Duration::from_micros(100)), 2));
+    assert_eq!(b[5], (Some(Duration::from_millis(5)), 1));
+    assert_eq!(b[14], (None, 1));
+}
+
+#[test]
+fn t_instrument() {
+    use super::testing;
+    let mut client = testing::serve(|bus| {
+        bus.add_object(testing::path(), |m| {
+            match m.member().unwrap().to_str().unwrap() {
+                "Hello" => m.reply("ok"),
+                _ => m.reply_errno(::libc::ENOENT),
+            }.map_err(From::from)
+        })
+    }).unwrap();
+
+    /* without a hook, calls work all the same */
+    testing::call(&mut client, "Hello").unwrap();
+    assert!(!is_instrumented(&client));
+
+    let stats = Rc::new(RefCell::new(Stats::default()));
+    let i = client.instrument(stats.clone()).unwrap();
+    assert!(is_instrumented(&client));
+    testing::call(&mut client, "Hello").unwrap();
+    testing::call(&mut client, "Nope").unwrap();
+    {
+        let s = stats.borrow();
+        assert_eq!((s.messages_sent, s.messages_received), (2, 2));
+        assert_eq!((s.calls, s.call_errors), (2, 1));
+        assert_eq!(s.call_latency.count(), 2);
+    }
+
+    drop(i);
+    assert!(!is_instrumented(&client));
+    testing::call(&mut client, "Hello").unwrap();
+    assert_eq!(stats.borrow().calls, 2);
+}
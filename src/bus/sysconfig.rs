@@ -0,0 +1,158 @@
+/*!
+ * Clients for the system settings services: hostnamed (`org.freedesktop.hostname1`), localed
+ * (`org.freedesktop.locale1`) and timedated (`org.freedesktop.timedate1`)
+ *
+ * Besides reading the current settings, each client can subscribe to changes made by anyone else
+ * using `on_changed()`, so settings panels can update live instead of polling.
+ */
+
+use std::io;
+use super::{Bus, BusRef, BusName, InterfaceName, MemberName, ObjectPath, PropertiesChanged,
+            Result, Slot};
+use super::names::{bus, interface, path};
+use super::types::{FromSdBusMessage, Variant};
+
+/// Define a client type `$name` for the settings service `$dest` exporting `$interface` at `$path`
+macro_rules! settings_client {
+    ($(#[$attr:meta])* $name:ident, $dest:expr, $path:expr, $interface:expr) => {
+        $(#[$attr])*
+        pub struct $name {
+            bus: Bus,
+        }
+
+        impl $name {
+            /// Talk to the service via `bus` (which should be a system bus connection).
+            pub fn new(bus: &BusRef) -> $name {
+                $name { bus: bus.to_owned() }
+            }
+
+            /// Talk to the service using the default system bus connection of this thread.
+            pub fn system() -> Result<$name> {
+                Ok($name { bus: try!(Bus::default_system()) })
+            }
+
+            fn property<T: for<'b> FromSdBusMessage<'b>>(&mut self, name: &[u8]) -> Result<T> {
+                self.bus.get_property($dest,
+                                      $path,
+                                      $interface,
+                                      MemberName::from_bytes(name).unwrap())
+            }
+
+            /// Call `f` with the changed properties whenever the settings are modified.
+            ///
+            /// Changes are only delivered while the bus is being processed. Unsubscribes when the
+            /// returned `Slot` is dropped.
+            pub fn on_changed<F>(&self, f: F) -> Result<Slot>
+                where F: FnMut(PropertiesChanged) -> Result<()> + 'static
+            {
+                let dest: &BusName = $dest;
+                let path: &ObjectPath = $path;
+                let interface: &InterfaceName = $interface;
+                Ok(try!(self.bus.on_properties_changed(dest, path, interface, f)))
+            }
+        }
+    }
+}
+
+/// Convert an array of strings read as a `Variant` (`as`)
+fn strings(v: Variant) -> Result<Vec<String>> {
+    let invalid = || From::from(io::Error::new(io::ErrorKind::InvalidData, "expected strings"));
+    match v {
+        Variant::Array(v) => {
+            v.into_iter()
+                .map(|s| match s {
+                    Variant::String(s) => Ok(s),
+                    _ => Err(invalid()),
+                })
+                .collect()
+        }
+        _ => Err(invalid()),
+    }
+}
+
+settings_client!(
+    /// A client for hostnamed, which manages the hostname and related machine metadata.
+    Hostname, bus::HOSTNAME1, path::HOSTNAME1, interface::HOSTNAME1);
+
+impl Hostname {
+    /// The hostname currently in use
+    pub fn hostname(&mut self) -> Result<String> {
+        self.property(b"Hostname\0")
+    }
+
+    /// The hostname configured in `/etc/hostname`
+    pub fn static_hostname(&mut self) -> Result<String> {
+        self.property(b"StaticHostname\0")
+    }
+
+    /// A free-form, human readable name for the machine
+    pub fn pretty_hostname(&mut self) -> Result<String> {
+        self.property(b"PrettyHostname\0")
+    }
+
+    /// An icon name following the XDG icon naming spec, ie: `computer-laptop`
+    pub fn icon_name(&mut self) -> Result<String> {
+        self.property(b"IconName\0")
+    }
+
+    /// `desktop`, `laptop`, `server`, `vm`, ...
+    pub fn chassis(&mut self) -> Result<String> {
+        self.property(b"Chassis\0")
+    }
+}
+
+settings_client!(
+    /// A client for localed, which manages the system locale and keyboard layouts.
+    Locale, bus::LOCALE1, path::LOCALE1, interface::LOCALE1);
+
+impl Locale {
+    /// The system locale, as `VARIABLE=value` assignments (ie: `LANG=de_DE.UTF-8`)
+    pub fn locale(&mut self) -> Result<Vec<String>> {
+        strings(try!(self.property(b"Locale\0")))
+    }
+
+    /// The keymap of the virtual console
+    pub fn vc_keymap(&mut self) -> Result<String> {
+        self.property(b"VConsoleKeymap\0")
+    }
+
+    /// The X11 keyboard layout
+    pub fn x11_layout(&mut self) -> Result<String> {
+        self.property(b"X11Layout\0")
+    }
+}
+
+settings_client!(
+    /// A client for timedated, which manages the system clock, timezone and NTP.
+    Timedate, bus::TIMEDATE1, path::TIMEDATE1, interface::TIMEDATE1);
+
+impl Timedate {
+    /// The timezone, ie: `Europe/Berlin`
+    pub fn timezone(&mut self) -> Result<String> {
+        self.property(b"Timezone\0")
+    }
+
+    /// Whether the RTC is kept in local time rather than UTC
+    pub fn local_rtc(&mut self) -> Result<bool> {
+        self.property(b"LocalRTC\0")
+    }
+
+    /// Whether NTP synchronization is enabled
+    pub fn ntp(&mut self) -> Result<bool> {
+        self.property(b"NTP\0")
+    }
+
+    /// Whether the system clock is currently synchronized
+    pub fn ntp_synchronized(&mut self) -> Result<bool> {
+        self.property(b"NTPSynchronized\0")
+    }
+}
+
+#[test]
+fn t_strings() {
+    let v = Variant::Array(vec![Variant::String("LANG=C".to_owned()),
+                                Variant::String("LC_TIME=de_DE".to_owned())]);
+    assert_eq!(strings(v).unwrap(), vec!["LANG=C", "LC_TIME=de_DE"]);
+    strings(Variant::Array(vec![Variant::UInt32(1)])).err().unwrap();
+    strings(Variant::String("LANG=C".to_owned())).err().unwrap();
+}
@@ -0,0 +1,247 @@
+/*!
+ * Assertions for tests of bus services
+ *
+ * Meant for integration tests which talk to a service over a private connection and check the
+ * replies it sends. `serve()` sets such a connection up, running the service on its own thread.
+ * Like `assert_eq!()`, failures panic with a description of what differed.
+ *
+ * Message bodies are compared as `Variant`s, so expected values are written as such:
+ *
+ * `assert_body_eq(&mut reply, &[Variant::String("ok".to_owned()), Variant::UInt32(1)])`
+ *
+ * `serve_echo()` is a ready-made service for tests of the client side, `call()` and friends send
+ * method calls to it (or any other service exporting the object at `path()`).
+ */
+
+use std::cell::RefCell;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use id128::Id128;
+use super::{Bus, BusBuilder, BusName, InterfaceName, MemberName, Message, MessageRef, ObjectPath};
+use super::types::{ToSdBusMessage, Variant};
+
+/// Run a service on a new thread, returning a client connected to it.
+///
+/// The service uses the server end of a `UnixStream::pair()`. `setup` is called on the new thread
+/// to export objects on it; what it returns (ie: the `Slot`s of the objects) is kept until the
+/// client end is closed, at which point the thread exits. Errors returned by `setup` are returned
+/// from `serve()`.
+///
+/// Calls to the service can be sent to any destination, peer-to-peer connections ignore it.
+pub fn serve<F, R>(setup: F) -> ::Result<Bus>
+    where F: FnOnce(&mut Bus) -> ::Result<R> + Send + 'static
+{
+    let (client, server) = try!(UnixStream::pair());
+    let id = try!(Id128::from_random());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let r = BusBuilder::fd(server).server(id).build().and_then(|mut bus| {
+            let state = try!(setup(&mut bus));
+            Ok((bus, state))
+        });
+        let (bus, _state) = match r {
+            Ok(r) => {
+                let _ = tx.send(Ok(()));
+                r
+            }
+            Err(e) => {
+                let _ = tx.send(Err(e));
+                return;
+            }
+        };
+        /* processing fails once the client is gone */
+        loop {
+            match bus.process() {
+                Ok(true) => {}
+                Ok(false) => {
+                    if bus.wait(None).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+    try!(rx.recv().unwrap());
+    BusBuilder::fd(client).build()
+}
+
+/// The object path `serve_echo()` exports its object at, `/org/example/test`
+pub fn path() -> &'static ObjectPath {
+    ObjectPath::from_bytes(b"/org/example/test\0").unwrap()
+}
+
+/// The interface `new_call()` calls methods of, `org.example.Test`
+pub fn interface() -> &'static InterfaceName {
+    InterfaceName::from_bytes(b"org.example.Test\0").unwrap()
+}
+
+/// Run a service which answers every call to the object at `path()` with a copy of its body.
+/// Before replying, it emits the signal `Echoed` from that object with the same body.
+pub fn serve_echo() -> ::Result<Bus> {
+    serve(|bus| {
+        let mut b = bus.clone();
+        bus.add_object(path(), move |m| {
+            let echoed = MemberName::from_bytes(b"Echoed\0").unwrap();
+            let mut signal = try!(b.new_signal(path(), interface(), echoed));
+            try!(signal.copy_from(m));
+            try!(signal.send());
+            let mut reply = try!(m.new_method_return());
+            try!(reply.copy_from(m));
+            try!(reply.send());
+            Ok(())
+        })
+    })
+}
+
+/// Create a call of the method `member` of the object at `path()`, with `args` as its arguments
+pub fn new_call<A: ToSdBusMessage>(bus: &mut Bus, member: &str, args: A) -> ::Result<Message> {
+    let dest = BusName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = try!(MemberName::new(member)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+    let mut m = try!(bus.new_method_call(dest, path(), interface(), &member));
+    try!(m.append(args));
+    Ok(m)
+}
+
+/// Call the method `member` of the object at `path()` without arguments, and wait for the reply.
+/// Error replies are returned like any other reply, check them with `assert_error()`.
+pub fn call(client: &mut Bus, member: &str) -> ::Result<Message> {
+    call_with(client, member, ())
+}
+
+/// Like `call()`, with `args` as the arguments of the call
+pub fn call_with<A: ToSdBusMessage>(client: &mut Bus, member: &str, args: A) -> ::Result<Message> {
+    let mut m = try!(new_call(client, member, args));
+    let reply = Rc::new(RefCell::new(None));
+    let r = reply.clone();
+    let set = move |m: &mut MessageRef| {
+        *r.borrow_mut() = Some(m.to_owned());
+        Ok(())
+    };
+    let _slot = try!(m.call_async(set, 5_000_000));
+    while reply.borrow().is_none() {
+        if !try!(client.process()) {
+            try!(client.wait(None));
+        }
+    }
+    let mut reply = reply.borrow_mut();
+    Ok(reply.take().unwrap())
+}
+
+/// Read the complete body of the sealed message `m`. The read position is reset afterwards.
+pub fn body(m: &mut MessageRef) -> ::Result<Vec<Variant>> {
+    sd_try!(::ffi::bus::sd_bus_message_rewind(m.as_mut_ptr(), 1));
+    let mut values = Vec::new();
+    {
+        let mut i = try!(m.iter());
+        while let Some(v) = try!(i.read_value()) {
+            values.push(v);
+        }
+    }
+    sd_try!(::ffi::bus::sd_bus_message_rewind(m.as_mut_ptr(), 1));
+    Ok(values)
+}
+
+/// Describe `m` for assertion failures
+fn describe(m: &MessageRef) -> String {
+    match (m.error(), m.member()) {
+        (Some(e), _) => format!("error reply ({})", e),
+        (None, Some(member)) => {
+            format!("{} (signature {:?})", member.to_string_lossy(), m.signature())
+        }
+        (None, None) => format!("reply (signature {:?})", m.signature()),
+    }
+}
+
+/// Assert that the body of `m` has the signature `signature` (ie: `a{sv}`).
+pub fn assert_signature(m: &MessageRef, signature: &str) {
+    if m.signature() != signature {
+        panic!("signature mismatch: expected {:?}, got {}", signature, describe(m));
+    }
+}
+
+/// Assert that the body of the sealed message `m` consists of the values `expected`.
+pub fn assert_body_eq(m: &mut MessageRef, expected: &[Variant]) {
+    let actual = match body(m) {
+        Ok(actual) => actual,
+        Err(e) => panic!("could not read the body of {}: {}", describe(m), e),
+    };
+    if actual != expected {
+        panic!("body mismatch in {}:\n expected: {:?}\n   actual: {:?}",
+               describe(m),
+               expected,
+               actual);
+    }
+}
+
+/// Assert that `m` is an error reply with the error name `name`.
+pub fn assert_error(m: &MessageRef, name: &str) {
+    match m.error() {
+        Some(ref e) if *e == name => {}
+        _ => panic!("expected error reply {:?}, got {}", name, describe(m)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+
+    #[test]
+    fn t_serve() {
+        let mut client = serve(|bus| {
+            bus.add_object(path(), |m| {
+                match m.member().unwrap().to_str().unwrap() {
+                    "Hello" => m.reply(("ok", 1u32)),
+                    _ => m.reply_errno(::libc::ENOENT),
+                }.map_err(From::from)
+            })
+        }).unwrap();
+        /* sd-bus replies UnknownMethod to calls the object didn't tell it it handled */
+        let unknown = Rc::new(RefCell::new(0));
+        let _filter = {
+            let unknown = unknown.clone();
+            client.add_filter(move |m| {
+                if m.error().map_or(false, |e| e.name().contains("UnknownMethod")) {
+                    *unknown.borrow_mut() += 1;
+                }
+                Ok(())
+            }).unwrap()
+        };
+
+        let mut reply = call(&mut client, "Hello").unwrap();
+        assert_signature(&reply, "su");
+        assert_body_eq(&mut reply, &[Variant::String("ok".to_owned()), Variant::UInt32(1)]);
+        /* the body can be checked again */
+        assert_body_eq(&mut reply, &[Variant::String("ok".to_owned()), Variant::UInt32(1)]);
+
+        let reply = call(&mut client, "Nope").unwrap();
+        assert_error(&reply, "org.freedesktop.DBus.Error.FileNotFound");
+
+        /* the server handles calls in order, anything sent for the first two came before this */
+        call(&mut client, "Hello").unwrap();
+        while client.process().unwrap() {}
+        assert_eq!(*unknown.borrow(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "body mismatch")]
+    fn t_assert_body_eq_mismatch() {
+        let mut client = serve_echo().unwrap();
+        let mut reply = call_with(&mut client, "Echo", "ok").unwrap();
+        assert_body_eq(&mut reply, &[Variant::String("not ok".to_owned())]);
+    }
+
+    #[test]
+    fn t_serve_setup_error() {
+        let r = serve(|_| -> ::Result<()> {
+            Err(::std::io::Error::new(::std::io::ErrorKind::Other, "nope"))
+        });
+        assert_eq!(r.err().unwrap().to_string(), "nope");
+    }
+}
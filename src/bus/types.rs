@@ -19,14 +19,23 @@
  */
 
 use super::utf8_cstr::Utf8CStr;
-use std::ffi::CStr;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use super::{MessageRef, MessageIter};
 use ffi::{c_int, c_char};
+use libc;
+use std::ptr;
+#[cfg(feature = "derive")]
+pub use systemd_derive::{FromSdBusMessage, ToSdBusMessage};
 
 /**
  * When impled for a Type, promises that a reference to the type cast to a pointer can be encoded
  * using the given `dbus_type()` using `sd_bus_message_append_basic` and decoded similarly using
- * `sd_bus_message_read_basic`
+ * `sd_bus_message_read_basic`, and that `dbus_type()` is a fixed size type with the same size and
+ * representation as the Type (so arrays of it can be read in place).
  *
  * It is unlikely that this is something you want to impliment, all the basic types are already
  * implimented internally.
@@ -42,10 +51,20 @@ pub unsafe trait SdBusMessageDirect {
  * Allows types to provide a convertion to a dbus message
  */
 pub trait ToSdBusMessage {
-    // type signature?
-    // function to do append?
     // Do we need a ToOwned bit? Check ToSql
     fn to_message(&self, m: &mut MessageRef) -> ::Result<()>;
+
+    /// The dbus type signature of the value(s) appended by `to_message()`. Needed to wrap the
+    /// value in a variant.
+    fn signature(&self) -> String;
+
+    /// Append the value as the arguments of a message (used by `MessageRef::append()`).
+    ///
+    /// This is the same as `to_message()`, except for tuples: they are appended as structs by
+    /// `to_message()`, but their elements are appended as separate arguments here.
+    fn to_args(&self, m: &mut MessageRef) -> ::Result<()> {
+        self.to_message(m)
+    }
 }
 
 /**
@@ -55,18 +74,46 @@ pub trait ToSdBusMessage {
  * may need to add a `from_message_to()` that takes a reference, much like `Clone`.
  */
 pub trait FromSdBusMessage<'a> {
-    fn from_message(m: &'a mut MessageIter<'a>) -> ::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
         where Self: Sized;
+
+    /// Read the value from the arguments of a message (used by `MessageRef::read_body()`).
+    ///
+    /// The counterpart of `ToSdBusMessage::to_args()`: this is the same as `from_message()`,
+    /// except that tuples are read from separate arguments rather than from a struct.
+    fn from_args(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        Self::from_message(m)
+    }
+}
+
+/**
+ * Types whose dbus type signature is known without having a value, which is needed to append
+ * containers of them (an empty array still has an element type)
+ */
+pub trait SdBusType {
+    fn type_signature() -> String;
+}
+
+impl<T: SdBusMessageDirect> SdBusType for T {
+    fn type_signature() -> String {
+        (Self::dbus_type() as char).to_string()
+    }
 }
 
 impl<T: SdBusMessageDirect> ToSdBusMessage for T {
     fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
         unsafe { m.append_basic_raw(Self::dbus_type(), self as *const _ as *const _) }
     }
+
+    fn signature(&self) -> String {
+        (Self::dbus_type() as char).to_string()
+    }
 }
 
 impl<'a, T: SdBusMessageDirect + 'a> FromSdBusMessage<'a> for T {
-    fn from_message(m: &'a mut MessageIter<'a>) -> ::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
         where Self: Sized
     {
         let t = Self::dbus_type();
@@ -131,6 +178,16 @@ impl ToSdBusMessage for bool {
         try!(unsafe { m.append_basic_raw(b'b', &i as *const _ as *const _) });
         Ok(())
     }
+
+    fn signature(&self) -> String {
+        "b".to_owned()
+    }
+}
+
+impl SdBusType for bool {
+    fn type_signature() -> String {
+        "b".to_owned()
+    }
 }
 
 impl<'a> FromSdBusMessage<'a> for bool {
@@ -144,6 +201,9 @@ impl<'a> FromSdBusMessage<'a> for bool {
 /**
  * A basic wrapper that simply ensures we send a Fd via the dbus file descriptor mechanisms rather
  * than as a integer
+ *
+ * When read, the fd still belongs to the message and is closed along with it. Use `OwnedFd` to
+ * keep it.
  */
 pub struct UnixFd(pub c_int);
 
@@ -153,30 +213,127 @@ impl ToSdBusMessage for UnixFd {
         try!(unsafe { m.append_basic_raw(b'h', &i as *const _ as *const _)});
         Ok(())
     }
+
+    fn signature(&self) -> String {
+        "h".to_owned()
+    }
+}
+
+impl SdBusType for UnixFd {
+    fn type_signature() -> String {
+        "h".to_owned()
+    }
 }
 
 impl<'a> FromSdBusMessage<'a> for UnixFd {
-    fn from_message(m: &'a mut MessageIter<'a>) -> ::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
         where Self: Sized
     {
         unsafe { m.read_basic_raw(b'h', |x: c_int| UnixFd(x)) }
     }
 }
 
+/**
+ * A file descriptor (`h`) owned by this value, and closed when it is dropped
+ *
+ * Reading one from a message duplicates the fd the message holds. Appending one leaves it open,
+ * as sd-bus duplicates it for the message.
+ *
+ * Passing fds requires a connection which negotiated it (see `BusRef::can_send_fds()`).
+ */
+#[derive(Debug)]
+pub struct OwnedFd(RawFd);
+
+impl OwnedFd {
+    /// Duplicate `fd` (with close-on-exec set)
+    pub fn dup(fd: RawFd) -> io::Result<OwnedFd> {
+        let fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 3) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(OwnedFd(fd))
+    }
+}
+
+impl AsRawFd for OwnedFd {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl IntoRawFd for OwnedFd {
+    #[inline]
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.0;
+        ::std::mem::forget(self);
+        fd
+    }
+}
+
+impl FromRawFd for OwnedFd {
+    #[inline]
+    unsafe fn from_raw_fd(fd: RawFd) -> OwnedFd {
+        OwnedFd(fd)
+    }
+}
+
+impl Drop for OwnedFd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+impl ToSdBusMessage for OwnedFd {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        UnixFd(self.0).to_message(m)
+    }
+
+    fn signature(&self) -> String {
+        "h".to_owned()
+    }
+}
+
+impl SdBusType for OwnedFd {
+    fn type_signature() -> String {
+        "h".to_owned()
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for OwnedFd {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        match try!(UnixFd::from_message(m)) {
+            Some(fd) => OwnedFd::dup(fd.0).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
 impl<'a> ToSdBusMessage for &'a super::ObjectPath {
     fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
         try!(unsafe { m.append_basic_raw(b'o', self.as_ptr() as *const _)});
         Ok(())
     }
+
+    fn signature(&self) -> String {
+        "o".to_owned()
+    }
+}
+
+impl<'a> SdBusType for &'a super::ObjectPath {
+    fn type_signature() -> String {
+        "o".to_owned()
+    }
 }
 
 // For string likes, (object path, string, signature) sd_bus_message_read_basic returns a *const
-// c_char reference to the string owned by the underlying message. Unclear if we can represent this
-// without copying.
-//
-// If we could use &MessageRef instead this could be useful.
+// c_char reference to the string owned by the underlying message. These are returned without
+// copying, borrowing from the message for as long as the `MessageIter` does (see
+// `MessageIter::read_basic_raw()` for why that is sound).
 impl<'a> FromSdBusMessage<'a> for &'a super::ObjectPath {
-    fn from_message(m: &'a mut MessageIter<'a>) -> ::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
         where Self: Sized
     {
         unsafe {m.read_basic_raw(b'o', |x: *const c_char| super::ObjectPath::from_ptr_unchecked(x))}
@@ -187,20 +344,699 @@ impl<'a> ToSdBusMessage for &'a Utf8CStr {
     fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
         unsafe { m.append_basic_raw(b's', self.as_ptr() as *const _) }
     }
+
+    fn signature(&self) -> String {
+        "s".to_owned()
+    }
+}
+
+impl<'a> SdBusType for &'a Utf8CStr {
+    fn type_signature() -> String {
+        "s".to_owned()
+    }
 }
 
 impl<'a> FromSdBusMessage<'a> for &'a Utf8CStr {
-    fn from_message(m: &'a mut MessageIter<'a>) -> ::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
         where Self: Sized
     {
         unsafe {m.read_basic_raw(b's', |x: *const c_char| Utf8CStr::from_cstr_unchecked(CStr::from_ptr(x)))}
     }
 }
 
-// TODO:
-//  string-likes (string, object path, signature)
-//  array
-//  variant
-//  struct
-//  dict
-//
+impl<'a> ToSdBusMessage for &'a str {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        let s = try!(CString::new(*self)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul in string")));
+        unsafe { m.append_basic_raw(b's', s.as_ptr() as *const _) }
+    }
+
+    fn signature(&self) -> String {
+        "s".to_owned()
+    }
+}
+
+impl<'a> SdBusType for &'a str {
+    fn type_signature() -> String {
+        "s".to_owned()
+    }
+}
+
+/* sd-bus refuses to read strings which are not valid UTF-8 */
+impl<'a> FromSdBusMessage<'a> for &'a str {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        unsafe {
+            m.read_basic_raw(b's', |x: *const c_char| {
+                ::std::str::from_utf8_unchecked(CStr::from_ptr(x).to_bytes())
+            })
+        }
+    }
+}
+
+impl ToSdBusMessage for String {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        (&self[..]).to_message(m)
+    }
+
+    fn signature(&self) -> String {
+        "s".to_owned()
+    }
+}
+
+impl SdBusType for String {
+    fn type_signature() -> String {
+        "s".to_owned()
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for String {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        unsafe { m.read_string(b's') }
+    }
+}
+
+impl ToSdBusMessage for super::ObjectPathBuf {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        (&**self).to_message(m)
+    }
+
+    fn signature(&self) -> String {
+        "o".to_owned()
+    }
+}
+
+impl SdBusType for super::ObjectPathBuf {
+    fn type_signature() -> String {
+        "o".to_owned()
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for super::ObjectPathBuf {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        unsafe {
+            m.read_basic_raw(b'o', |x: *const c_char| {
+                super::ObjectPath::from_ptr_unchecked(x).to_owned()
+            })
+        }
+    }
+}
+
+/*
+ * Bus, interface and member names are passed as strings (ie: the arguments of
+ * `org.freedesktop.DBus.Properties.Get`)
+ */
+macro_rules! msg_name {
+    ($($name:ident),*) => {$(
+        impl<'a> ToSdBusMessage for &'a super::$name {
+            fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+                unsafe { m.append_basic_raw(b's', self.as_ptr() as *const _) }
+            }
+
+            fn signature(&self) -> String {
+                "s".to_owned()
+            }
+        }
+
+        impl<'a> SdBusType for &'a super::$name {
+            fn type_signature() -> String {
+                "s".to_owned()
+            }
+        }
+    )*}
+}
+
+msg_name!{BusName, InterfaceName, MemberName}
+
+macro_rules! msg_name_buf {
+    ($($name:ident),*) => {$(
+        impl ToSdBusMessage for super::$name {
+            fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+                (&**self).to_message(m)
+            }
+
+            fn signature(&self) -> String {
+                "s".to_owned()
+            }
+        }
+
+        impl SdBusType for super::$name {
+            fn type_signature() -> String {
+                "s".to_owned()
+            }
+        }
+
+        impl<'a> FromSdBusMessage<'a> for super::$name {
+            fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+                where Self: Sized
+            {
+                match try!(String::from_message(m)) {
+                    Some(s) => {
+                        super::$name::new(s)
+                            .map(Some)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                    }
+                    None => Ok(None),
+                }
+            }
+        }
+    )*}
+}
+
+msg_name_buf!{BusNameBuf, InterfaceNameBuf, MemberNameBuf}
+
+/// A type signature (`g`), ie: `a{sv}`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Signature(pub String);
+
+impl ToSdBusMessage for Signature {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        let s = try!(CString::new(&self.0[..])
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul in signature")));
+        unsafe { m.append_basic_raw(b'g', s.as_ptr() as *const _) }
+    }
+
+    fn signature(&self) -> String {
+        "g".to_owned()
+    }
+}
+
+impl SdBusType for Signature {
+    fn type_signature() -> String {
+        "g".to_owned()
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for Signature {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        unsafe { m.read_string(b'g') }.map(|s| s.map(Signature))
+    }
+}
+
+/*
+ * Tuples are dbus structs. When appended as the arguments of a message (ie: with
+ * `MessageRef::append()`), each element is a separate argument instead, so a method with several
+ * arguments can be called with a tuple of them. To pass a struct as the single argument, wrap it in
+ * a tuple of one element: `m.append(((a, b),))`.
+ *
+ * `()` is an empty list of arguments, and can not be nested in containers.
+ */
+impl ToSdBusMessage for () {
+    fn to_message(&self, _: &mut MessageRef) -> ::Result<()> {
+        Ok(())
+    }
+
+    fn signature(&self) -> String {
+        String::new()
+    }
+}
+
+macro_rules! msg_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: ToSdBusMessage + SdBusType),*> ToSdBusMessage for ($($name,)*) {
+            fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+                let contents = Self::type_signature();
+                m.append_struct_with(&contents[1..contents.len() - 1], |m| self.to_args(m))
+            }
+
+            fn signature(&self) -> String {
+                Self::type_signature()
+            }
+
+            #[allow(non_snake_case)]
+            fn to_args(&self, m: &mut MessageRef) -> ::Result<()> {
+                let ($(ref $name,)*) = *self;
+                $(try!($name.to_message(m));)*
+                Ok(())
+            }
+        }
+
+        impl<$($name: SdBusType),*> SdBusType for ($($name,)*) {
+            fn type_signature() -> String {
+                let s = String::from("(");
+                $(let s = s + &$name::type_signature();)*
+                s + ")"
+            }
+        }
+
+        impl<'a, $($name: FromSdBusMessage<'a>),*> FromSdBusMessage<'a> for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+                where Self: Sized
+            {
+                if try!(m.peek_raw()).is_none() || !try!(m.enter_container_raw(b'r', ptr::null()))
+                {
+                    return Ok(None);
+                }
+                $(
+                    let $name = match try!($name::from_message(m)) {
+                        Some(v) => v,
+                        None => {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                      "struct has too few fields"))
+                        }
+                    };
+                )*
+                try!(m.exit_container_raw());
+                Ok(Some(($($name,)*)))
+            }
+
+            #[allow(non_snake_case, unused_assignments)]
+            fn from_args(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+                where Self: Sized
+            {
+                let mut first = true;
+                $(
+                    let $name = match try!($name::from_message(m)) {
+                        Some(v) => v,
+                        None if first => return Ok(None),
+                        None => {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                      "too few arguments"))
+                        }
+                    };
+                    first = false;
+                )*
+                Ok(Some(($($name,)*)))
+            }
+        }
+    }
+}
+
+msg_tuple!{A}
+msg_tuple!{A, B}
+msg_tuple!{A, B, C}
+msg_tuple!{A, B, C, D}
+msg_tuple!{A, B, C, D, E}
+msg_tuple!{A, B, C, D, E, F}
+msg_tuple!{A, B, C, D, E, F, G}
+msg_tuple!{A, B, C, D, E, F, G, H}
+
+/*
+ * Slices and `Vec`s are passed as arrays. For arrays of fixed size types, `append_array_u8()` and
+ * friends are faster, as they copy all elements at once.
+ */
+impl<'b, T: ToSdBusMessage + SdBusType> ToSdBusMessage for &'b [T] {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        m.append_array_with(&T::type_signature(), |m| {
+            for e in self.iter() {
+                try!(e.to_message(m));
+            }
+            Ok(())
+        })
+    }
+
+    fn signature(&self) -> String {
+        format!("a{}", T::type_signature())
+    }
+}
+
+impl<'b, T: SdBusType> SdBusType for &'b [T] {
+    fn type_signature() -> String {
+        format!("a{}", T::type_signature())
+    }
+}
+
+impl<T: ToSdBusMessage + SdBusType> ToSdBusMessage for Vec<T> {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        (&self[..]).to_message(m)
+    }
+
+    fn signature(&self) -> String {
+        format!("a{}", T::type_signature())
+    }
+}
+
+impl<T: SdBusType> SdBusType for Vec<T> {
+    fn type_signature() -> String {
+        format!("a{}", T::type_signature())
+    }
+}
+
+impl<'a, T: FromSdBusMessage<'a>> FromSdBusMessage<'a> for Vec<T> {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        /* without a contents signature, sd-bus fails to enter anything at the end of a container */
+        if try!(m.peek_raw()).is_none() || !try!(m.enter_container_raw(b'a', ptr::null())) {
+            return Ok(None);
+        }
+        let mut v = Vec::new();
+        while let Some(e) = try!(T::from_message(m)) {
+            v.push(e);
+        }
+        try!(m.exit_container_raw());
+        Ok(Some(v))
+    }
+}
+
+/*
+ * D-Bus has no optional values (no `maybe` type). By convention, `Option<T>` is passed as an array
+ * of `T` which is empty for `None` and holds a single element for `Some`. Reading fails if the
+ * array holds more than one element.
+ */
+impl<T: ToSdBusMessage + SdBusType> ToSdBusMessage for Option<T> {
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        m.append_array_with(&T::type_signature(), |m| {
+            match *self {
+                Some(ref v) => v.to_message(m),
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn signature(&self) -> String {
+        format!("a{}", T::type_signature())
+    }
+}
+
+impl<T: SdBusType> SdBusType for Option<T> {
+    fn type_signature() -> String {
+        format!("a{}", T::type_signature())
+    }
+}
+
+impl<'a, T: FromSdBusMessage<'a>> FromSdBusMessage<'a> for Option<T> {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        let mut v: Vec<T> = match try!(FromSdBusMessage::from_message(m)) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if v.len() > 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "more than one element in an optional value"));
+        }
+        Ok(Some(v.pop()))
+    }
+}
+
+/// The signature of a dict with keys `K` and values `V`
+fn dict_signature<K: SdBusType, V: SdBusType>() -> String {
+    format!("a{{{}{}}}", K::type_signature(), V::type_signature())
+}
+
+/// Append the entries yielded by `entries` as a dict (`a{kv}`)
+fn append_dict<'b, K, V, I>(m: &mut MessageRef, entries: I) -> ::Result<()>
+    where K: ToSdBusMessage + SdBusType + 'b,
+          V: ToSdBusMessage + SdBusType + 'b,
+          I: Iterator<Item = (&'b K, &'b V)>
+{
+    let entry = format!("{}{}", K::type_signature(), V::type_signature());
+    m.append_array_with(&format!("{{{}}}", entry), |m| {
+        for (k, v) in entries {
+            try!(m.append_dict_entry_with(&entry, |m| {
+                try!(k.to_message(m));
+                v.to_message(m)
+            }));
+        }
+        Ok(())
+    })
+}
+
+/// Read a dict (`a{kv}`), collecting its entries into a `C`
+fn read_dict<'a, K, V, C>(m: &mut MessageIter<'a>) -> ::Result<Option<C>>
+    where K: FromSdBusMessage<'a>,
+          V: FromSdBusMessage<'a>,
+          C: Default + Extend<(K, V)>
+{
+    /* without a contents signature, sd-bus fails to enter anything at the end of a container */
+    if try!(m.peek_raw()).is_none() || !try!(m.enter_container_raw(b'a', ptr::null())) {
+        return Ok(None);
+    }
+    let mut c = C::default();
+    while try!(m.peek_raw()).is_some() {
+        try!(m.enter_container_raw(b'e', ptr::null()));
+        let k = try!(K::from_message(m));
+        let v = try!(V::from_message(m));
+        try!(m.exit_container_raw());
+        match (k, v) {
+            (Some(k), Some(v)) => c.extend(Some((k, v))),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete dict entry")),
+        }
+    }
+    try!(m.exit_container_raw());
+    Ok(Some(c))
+}
+
+/*
+ * Maps are passed as dicts (`a{kv}`). Reading a dict with variant values (`a{sv}`) into a map of
+ * `Variant`s yields `Variant::Variant`s.
+ */
+impl<K, V, S> ToSdBusMessage for HashMap<K, V, S>
+    where K: ToSdBusMessage + SdBusType + Eq + Hash,
+          V: ToSdBusMessage + SdBusType,
+          S: BuildHasher
+{
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        append_dict(m, self.iter())
+    }
+
+    fn signature(&self) -> String {
+        dict_signature::<K, V>()
+    }
+}
+
+impl<K: SdBusType, V: SdBusType, S> SdBusType for HashMap<K, V, S> {
+    fn type_signature() -> String {
+        dict_signature::<K, V>()
+    }
+}
+
+impl<'a, K, V, S> FromSdBusMessage<'a> for HashMap<K, V, S>
+    where K: FromSdBusMessage<'a> + Eq + Hash,
+          V: FromSdBusMessage<'a>,
+          S: BuildHasher + Default
+{
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        read_dict(m)
+    }
+}
+
+impl<K, V> ToSdBusMessage for BTreeMap<K, V>
+    where K: ToSdBusMessage + SdBusType + Ord,
+          V: ToSdBusMessage + SdBusType
+{
+    fn to_message(&self, m: &mut MessageRef) -> ::Result<()> {
+        append_dict(m, self.iter())
+    }
+
+    fn signature(&self) -> String {
+        dict_signature::<K, V>()
+    }
+}
+
+impl<K: SdBusType, V: SdBusType> SdBusType for BTreeMap<K, V> {
+    fn type_signature() -> String {
+        dict_signature::<K, V>()
+    }
+}
+
+impl<'a, K, V> FromSdBusMessage<'a> for BTreeMap<K, V>
+    where K: FromSdBusMessage<'a> + Ord,
+          V: FromSdBusMessage<'a>
+{
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        read_dict(m)
+    }
+}
+
+/**
+ * Any single dbus value, for when the type is only known at runtime (most commonly, the contents
+ * of a dbus variant)
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variant {
+    Byte(u8),
+    Bool(bool),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Double(f64),
+    String(String),
+    ObjectPath(super::ObjectPathBuf),
+    Signature(String),
+    /// Only valid as long as the message it was read from is alive
+    UnixFd(c_int),
+    /// Arrays, including dictionaries (in which case all elements are `DictEntry`s)
+    Array(Vec<Variant>),
+    Struct(Vec<Variant>),
+    DictEntry(Box<Variant>, Box<Variant>),
+    Variant(Box<Variant>),
+}
+
+impl<'a> FromSdBusMessage<'a> for Variant {
+    fn from_message(m: &mut MessageIter<'a>) -> ::Result<Option<Self>>
+        where Self: Sized
+    {
+        m.read_value()
+    }
+}
+
+#[test]
+fn t_signature() {
+    assert_eq!(5u32.signature(), "u");
+    assert_eq!(1.5f64.signature(), "d");
+    assert_eq!(true.signature(), "b");
+    assert_eq!(UnixFd(0).signature(), "h");
+    assert_eq!(OwnedFd::dup(0).unwrap().signature(), "h");
+    assert_eq!(().signature(), "");
+    assert_eq!(("a", 1u32, true).signature(), "(sub)");
+    assert_eq!(HashMap::<String, u32>::new().signature(), "a{su}");
+    assert_eq!(Vec::<Vec<String>>::new().signature(), "aas");
+    assert_eq!((&[true][..]).signature(), "ab");
+    assert_eq!(("a", (1u32, true)).signature(), "(s(ub))");
+    assert_eq!(Vec::<(String, u8)>::type_signature(), "a(sy)");
+    assert_eq!(BTreeMap::<u8, HashMap<String, bool>>::type_signature(), "a{ya{sb}}");
+    assert_eq!(Signature("as".to_owned()).signature(), "g");
+    assert_eq!((-1i16, 1u16, -1i64, 1u64, 0u8).signature(), "(nqxty)");
+    assert_eq!(<&super::MemberName>::type_signature(), "s");
+    assert_eq!(Some(1u32).signature(), "au");
+    assert_eq!(None::<(String, bool)>.signature(), "a(sb)");
+}
+
+#[test]
+fn t_borrowed_strings() {
+    use std::os::unix::net::UnixStream;
+    use super::{BusBuilder, InterfaceName, MemberName, ObjectPath};
+
+    /* a connection still waiting for authentication is enough to build and seal messages */
+    let (a, _b) = UnixStream::pair().unwrap();
+    let mut bus = BusBuilder::fd(a).build().unwrap();
+    let path = ObjectPath::from_bytes(b"/org/example/test\0").unwrap();
+    let interface = InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = MemberName::from_bytes(b"Changed\0").unwrap();
+    let mut m = bus.new_signal(path, interface, member).unwrap();
+    m.append(("first", path, "second")).unwrap();
+    m.send().unwrap();
+
+    let mut clone = m.clone();
+    let mut i = m.iter().unwrap();
+    let first = i.read_next::<&str>().unwrap().unwrap();
+    let p = i.read_next::<&ObjectPath>().unwrap().unwrap();
+    let second = i.read_next::<&Utf8CStr>().unwrap().unwrap();
+
+    /* the clone shares the read position, but can't change what the strings point to */
+    clone.iter().unwrap().rewind(true).unwrap();
+    assert_eq!(i.read_next::<&str>().unwrap(), Some("first"));
+    assert_eq!((first, p.to_bytes(), second.as_ref()),
+               ("first", &b"/org/example/test"[..], "second"));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{Bus, Message};
+    use super::super::testing;
+
+    /// Send `args` through the echo service
+    fn echoed<V: ToSdBusMessage>(client: &mut Bus, args: V) -> Message {
+        testing::call_with(client, "Echo", args).unwrap()
+    }
+
+    /// Send `args` through the echo service and read the reply back as `T`
+    fn round_trip<V, T>(client: &mut Bus, args: V) -> T
+        where V: ToSdBusMessage,
+              T: for<'b> FromSdBusMessage<'b>
+    {
+        echoed(client, args).read_body::<T>().unwrap()
+    }
+
+    #[test]
+    fn t_round_trip() {
+        let mut c = testing::serve_echo().unwrap();
+
+        /* arrays */
+        let v: Vec<u32> = round_trip(&mut c, &[1u32, 2, 3][..]);
+        assert_eq!(v, [1, 2, 3]);
+        let v: Vec<String> = round_trip(&mut c, vec!["a", "", "c"]);
+        assert_eq!(v, ["a", "", "c"]);
+        let v: Vec<Vec<u8>> = round_trip(&mut c, vec![vec![1u8], vec![], vec![2, 3]]);
+        assert_eq!(v, [vec![1], vec![], vec![2, 3]]);
+        let v: Vec<bool> = round_trip(&mut c, Vec::<bool>::new());
+        assert!(v.is_empty());
+
+        /* options are arrays of 0 or 1 elements */
+        let v: Option<u32> = round_trip(&mut c, Some(5u32));
+        assert_eq!(v, Some(5));
+        let v: Option<String> = round_trip(&mut c, None::<&str>);
+        assert_eq!(v, None);
+        let v: Option<(String, bool)> = round_trip(&mut c, Some(("x", true)));
+        assert_eq!(v, Some(("x".to_owned(), true)));
+        assert!(echoed(&mut c, vec![1u32, 2]).read_body::<Option<u32>>().is_err());
+
+        /* dicts */
+        let mut h = HashMap::new();
+        h.insert("one".to_owned(), 1u32);
+        h.insert("two".to_owned(), 2);
+        let v: HashMap<String, u32> = round_trip(&mut c, h.clone());
+        assert_eq!(v, h);
+        let mut b = BTreeMap::new();
+        b.insert(1u8, vec!["a".to_owned()]);
+        b.insert(2, vec![]);
+        let v: BTreeMap<u8, Vec<String>> = round_trip(&mut c, b.clone());
+        assert_eq!(v, b);
+        let e = BTreeMap::<&str, BTreeMap<i32, bool>>::new();
+        let v: BTreeMap<String, BTreeMap<i32, bool>> = round_trip(&mut c, e);
+        assert!(v.is_empty());
+
+        /* tuples are separate arguments at the top level, and structs inside of them */
+        let v: (String, u32, bool) = round_trip(&mut c, ("a", 1u32, true));
+        assert_eq!(v, ("a".to_owned(), 1, true));
+        let v: ((String, (u32, bool)),) = round_trip(&mut c, (("a", (1u32, true)),));
+        assert_eq!(v, (("a".to_owned(), (1, true)),));
+        let v: Vec<(String, u8)> = round_trip(&mut c, vec![("a", 1u8), ("b", 2)]);
+        assert_eq!(v, [("a".to_owned(), 1), ("b".to_owned(), 2)]);
+        let v: (i16, u16, i64, u64, f64, Signature) =
+            round_trip(&mut c, (-1i16, 1u16, -1i64, 1u64, 0.5f64, Signature("a{sv}".to_owned())));
+        assert_eq!(v, (-1, 1, -1, 1, 0.5, Signature("a{sv}".to_owned())));
+        /* reading fewer or more arguments than were sent fails */
+        assert!(echoed(&mut c, ("a", 1u32)).read_body::<String>().is_err());
+        assert!(echoed(&mut c, ("a", 1u32)).read_body::<(String, u32, u32)>().is_err());
+
+        /* anything can be read as a Variant */
+        let v: Variant = round_trip(&mut c, vec![("a", 1u8)]);
+        assert_eq!(v,
+                   Variant::Array(vec![Variant::Struct(vec![Variant::String("a".to_owned()),
+                                                            Variant::Byte(1)])]));
+        let mut b = BTreeMap::new();
+        b.insert("k", -2i32);
+        let v: Variant = round_trip(&mut c, b);
+        assert_eq!(v,
+                   Variant::Array(vec![Variant::DictEntry(Box::new(Variant::String("k".to_owned())),
+                                                          Box::new(Variant::Int32(-2)))]));
+        let v: (Variant, Variant) = round_trip(&mut c, (Signature("s".to_owned()), 7u64));
+        assert_eq!(v, (Variant::Signature("s".to_owned()), Variant::UInt64(7)));
+    }
+
+    #[test]
+    fn t_round_trip_variant() {
+        let mut c = testing::serve_echo().unwrap();
+        let mut m = testing::new_call(&mut c, "Echo", ()).unwrap();
+        m.append_variant_with("s", |m| m.append("x")).unwrap();
+        m.append_variant_with("v", |m| m.append_variant_with("au", |m| m.append(vec![1u32])))
+            .unwrap();
+        let mut reply = m.call(5_000_000).unwrap();
+        testing::assert_signature(&reply, "vv");
+        let v = reply.read_body::<(Variant, Variant)>().unwrap();
+        assert_eq!(v,
+                   (Variant::Variant(Box::new(Variant::String("x".to_owned()))),
+                    Variant::Variant(Box::new(Variant::Variant(Box::new(
+                        Variant::Array(vec![Variant::UInt32(1)])))))));
+    }
+}
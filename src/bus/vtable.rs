@@ -0,0 +1,632 @@
+/*!
+ * Export objects onto the bus by describing their methods, properties and signals
+ *
+ * sd-bus dispatches vtable callbacks with `userdata + offset` as their userdata. We register the
+ * object with `NULL` userdata and use each entry's `offset` to carry a pointer to its own boxed
+ * handler, which in turn points at the object's state (`T`). This lets every entry have its own
+ * closure while all of them share a single `T`.
+ *
+ * A vtable may carry an `Authorizer`. Once the vtable is registered, every method and property
+ * entry holds a `Guard` which consults it (together with the interface name, which is only known
+ * at that point) before running the handler.
+ *
+ * Methods added with `VtableBuilder::method_async()` return a future instead of replying right
+ * away. It is handed to the vtable's `Spawn` (ie: the executor of an async runtime), and the reply
+ * is sent once it resolves, so that long-running calls don't hold up the dispatching of others.
+ */
+
+use ffi;
+use ffi::{c_int, c_char, c_void};
+use ffi::bus::vtable::SdBusVtableFlag;
+use std::ffi::CString;
+use std::future::Future;
+use std::io;
+use std::mem::size_of;
+use std::ops::{BitOr, BitOrAssign};
+use std::pin::Pin;
+use std::ptr;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use super::{Creds, CredsMask, Error, InterfaceName, InterfaceNameBuf, Message, MessageRef,
+            MemberName, ObjectPath, Result, handler_return};
+use super::types::ToSdBusMessage;
+use super::utf8_cstr::Utf8CStr;
+
+type MethodHandler<T> = Box<FnMut(&mut T, &mut MessageRef) -> Result<()>>;
+type AsyncMethodHandler<T> = Box<FnMut(&mut T, &mut MessageRef) -> Result<Task>>;
+type PropertyGet<T> = Box<FnMut(&mut T, &mut MessageRef) -> Result<()>>;
+type PropertySet<T> = Box<FnMut(&mut T, &mut MessageRef) -> Result<()>>;
+
+/// Decides whether peers may call methods (and set properties) of exported objects.
+///
+/// Attach one to a vtable using `VtableBuilder::authorizer()` or `Vtable::set_authorizer()`.
+/// Property reads are not checked.
+pub trait Authorizer {
+    /// Whether the peer with credentials `sender` may call `interface.member` on the object at
+    /// `path`. For property writes, `member` is the property name and `call` is the `Set` call.
+    ///
+    /// If this returns `false`, the call fails with `org.freedesktop.DBus.Error.AccessDenied`. If
+    /// it returns an error, the call fails with that error.
+    fn authorize(&self,
+                 call: &MessageRef,
+                 path: &ObjectPath,
+                 interface: &InterfaceName,
+                 member: &MemberName,
+                 sender: &Creds)
+                 -> Result<bool>;
+
+    /// The credentials to collect for `authorize()`
+    fn creds_mask(&self) -> CredsMask {
+        CredsMask::PID | CredsMask::UID | CredsMask::EUID | CredsMask::GID |
+        CredsMask::EFFECTIVE_CAPS | CredsMask::UNIQUE_NAME
+    }
+}
+
+/// A future run by a `Spawn`
+pub type Task = Pin<Box<Future<Output = ()>>>;
+
+/// Runs the futures of asynchronous methods (see `VtableBuilder::method_async()`), typically by
+/// spawning them onto the executor of an async runtime.
+///
+/// Implemented for closures, ie: `Rc::new(|task| { tokio::task::spawn_local(task); })`.
+pub trait Spawn {
+    fn spawn(&self, task: Task);
+}
+
+impl<F: Fn(Task)> Spawn for F {
+    fn spawn(&self, task: Task) {
+        self(task)
+    }
+}
+
+/// Sends the reply to `call` once `future` resolves
+struct Reply<F> {
+    call: Message,
+    future: Pin<Box<F>>,
+}
+
+impl<F, R> Future for Reply<F>
+    where F: Future<Output = Result<R>>,
+          R: ToSdBusMessage
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let r = match self.future.as_mut().poll(cx) {
+            Poll::Ready(r) => r,
+            Poll::Pending => return Poll::Pending,
+        };
+        /* there is no one left to report a failure to send the reply to */
+        let _ = match r {
+            Ok(v) => self.call.reply(v),
+            Err(e) => self.call.new_method_error(&e).and_then(|mut m| m.send()).map(|_| ()),
+        };
+        Poll::Ready(())
+    }
+}
+
+/// An `Authorizer` bound to the interface a vtable was registered as
+struct Guard {
+    authorizer: Rc<Authorizer>,
+    interface: InterfaceNameBuf,
+}
+
+impl Guard {
+    fn check(&self, call: &MessageRef, path: &ObjectPath, member: &MemberName) -> Result<()> {
+        let creds = try!(call.query_sender_creds(self.authorizer.creds_mask()));
+        if try!(self.authorizer.authorize(call, path, &self.interface, member, &creds)) {
+            Ok(())
+        } else {
+            let name = Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.AccessDenied\0").unwrap();
+            let message = Utf8CStr::from_bytes(b"Access denied\0").unwrap();
+            Err(Error::new(name, Some(message)))
+        }
+    }
+}
+
+/// Run `guard`, if any, for the call `m`
+fn check(guard: &Option<Rc<Guard>>,
+         m: &MessageRef,
+         path: Option<&ObjectPath>,
+         member: Option<&MemberName>)
+         -> Result<()> {
+    match (guard, path, member) {
+        (&None, _, _) => Ok(()),
+        (&Some(ref g), Some(path), Some(member)) => g.check(m, path, member),
+        /* sd-bus only dispatches calls with both set, refuse rather than skip the check */
+        (&Some(_), _, _) => Err(From::from(io::Error::from_raw_os_error(::libc::EINVAL))),
+    }
+}
+
+enum Handler<T> {
+    Sync(MethodHandler<T>),
+    Async(AsyncMethodHandler<T>),
+}
+
+struct Method<T> {
+    handler: Handler<T>,
+    userdata: *mut T,
+    guard: Option<Rc<Guard>>,
+    spawner: Option<Rc<Spawn>>,
+}
+
+struct Property<T> {
+    get: PropertyGet<T>,
+    set: Option<PropertySet<T>>,
+    userdata: *mut T,
+    guard: Option<Rc<Guard>>,
+}
+
+extern "C" fn raw_method_handler<T>(msg: *mut ffi::bus::sd_bus_message,
+                                    userdata: *mut c_void,
+                                    ret_error: *mut ffi::bus::sd_bus_error)
+                                    -> c_int {
+    let m = unsafe { &mut *(userdata as *mut Method<T>) };
+    let msg = unsafe { MessageRef::from_mut_ptr(msg) };
+    let userdata = unsafe { &mut *m.userdata };
+    let r = check(&m.guard, msg, msg.path(), msg.member()).and_then(|_| {
+        match m.handler {
+            Handler::Sync(ref mut h) => h(userdata, msg),
+            Handler::Async(ref mut h) => {
+                let spawner = match m.spawner {
+                    Some(ref s) => s.clone(),
+                    None => return Err(Error::from_errno(::libc::EOPNOTSUPP)),
+                };
+                spawner.spawn(try!(h(userdata, msg)));
+                Ok(())
+            }
+        }
+    });
+    match r {
+        /*
+         * The reply was sent (or will be, once the future resolves). Returning 0 would make sd-bus
+         * look for another handler, and reply with UnknownMethod when there is none.
+         */
+        Ok(()) => 1,
+        r => unsafe { handler_return(r, ret_error) },
+    }
+}
+
+extern "C" fn raw_property_get<T>(_bus: *mut ffi::bus::sd_bus,
+                                  _path: *const c_char,
+                                  _interface: *const c_char,
+                                  _property: *const c_char,
+                                  reply: *mut ffi::bus::sd_bus_message,
+                                  userdata: *mut c_void,
+                                  ret_error: *mut ffi::bus::sd_bus_error)
+                                  -> c_int {
+    let p = unsafe { &mut *(userdata as *mut Property<T>) };
+    let r = (p.get)(unsafe { &mut *p.userdata }, unsafe { MessageRef::from_mut_ptr(reply) });
+    unsafe { handler_return(r, ret_error) }
+}
+
+extern "C" fn raw_property_set<T>(_bus: *mut ffi::bus::sd_bus,
+                                  path: *const c_char,
+                                  _interface: *const c_char,
+                                  property: *const c_char,
+                                  value: *mut ffi::bus::sd_bus_message,
+                                  userdata: *mut c_void,
+                                  ret_error: *mut ffi::bus::sd_bus_error)
+                                  -> c_int {
+    let p = unsafe { &mut *(userdata as *mut Property<T>) };
+    let value = unsafe { MessageRef::from_mut_ptr(value) };
+    let path = unsafe { ObjectPath::from_ptr_unchecked(path) };
+    let property = unsafe { MemberName::from_ptr_unchecked(property) };
+    let r = check(&p.guard, value, Some(path), Some(property)).and_then(|_| {
+        match p.set {
+            Some(ref mut set) => set(unsafe { &mut *p.userdata }, value),
+            /* sd-bus only calls this for writable properties, but don't unwind into it if not */
+            None => {
+                let name = Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.PropertyReadOnly\0")
+                    .unwrap();
+                let message = Utf8CStr::from_bytes(b"Property is read-only\0").unwrap();
+                Err(Error::new(name, Some(message)))
+            }
+        }
+    });
+    unsafe { handler_return(r, ret_error) }
+}
+
+/// Flags modifying how sd-bus treats (and introspects) a vtable entry.
+///
+/// Combine flags with `|`. Not every flag is meaningful for every kind of entry, sd-bus rejects
+/// invalid combinations when the vtable is registered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VtableFlags(u64);
+
+impl VtableFlags {
+    pub const NONE: VtableFlags = VtableFlags(0);
+    /// Marked as deprecated in introspection data
+    pub const DEPRECATED: VtableFlags = VtableFlags(SdBusVtableFlag::Deprecated as u64);
+    /// Omitted from introspection data
+    pub const HIDDEN: VtableFlags = VtableFlags(SdBusVtableFlag::Hidden as u64);
+    /// May be called (or set) by unprivileged clients. Without this, sd-bus only lets clients
+    /// with the same uid as the service (or root) through.
+    pub const UNPRIVILEGED: VtableFlags = VtableFlags(SdBusVtableFlag::Unprivileged as u64);
+    /// Methods: the method does not send a reply
+    pub const METHOD_NO_REPLY: VtableFlags = VtableFlags(SdBusVtableFlag::MethodNoReply as u64);
+    /// Properties: the value never changes for the lifetime of the object
+    pub const PROPERTY_CONST: VtableFlags = VtableFlags(SdBusVtableFlag::PropertyConst as u64);
+    /// Properties: changes are announced with `PropertiesChanged`, including the new value
+    pub const PROPERTY_EMITS_CHANGE: VtableFlags =
+        VtableFlags(SdBusVtableFlag::PropertyEmitsChange as u64);
+    /// Properties: changes are announced with `PropertiesChanged`, without the new value
+    pub const PROPERTY_EMITS_INVALIDATION: VtableFlags =
+        VtableFlags(SdBusVtableFlag::PropertyEmitsInvalidation as u64);
+
+    /// The raw `SD_BUS_VTABLE_*` flags
+    #[inline]
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Whether all flags set in `other` are also set in `self`
+    #[inline]
+    pub fn contains(self, other: VtableFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for VtableFlags {
+    type Output = VtableFlags;
+    #[inline]
+    fn bitor(self, other: VtableFlags) -> VtableFlags {
+        VtableFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for VtableFlags {
+    #[inline]
+    fn bitor_assign(&mut self, other: VtableFlags) {
+        self.0 |= other.0
+    }
+}
+
+#[test]
+fn t_vtable_flags() {
+    let f = VtableFlags::UNPRIVILEGED | VtableFlags::METHOD_NO_REPLY;
+    assert_eq!(f.bits(), (1 << 2) | (1 << 3));
+    assert!(f.contains(VtableFlags::UNPRIVILEGED));
+    assert!(!f.contains(VtableFlags::HIDDEN | VtableFlags::UNPRIVILEGED));
+    assert_eq!(VtableFlags::default(), VtableFlags::NONE);
+}
+
+/// A complete vtable, ready to be registered with `BusRef::add_object_vtable()`.
+///
+/// Created using `VtableBuilder`.
+pub struct Vtable<T> {
+    entries: Vec<ffi::bus::sd_bus_vtable>,
+    // backing storage for the strings `entries` point into
+    strings: Vec<CString>,
+    methods: Vec<Box<Method<T>>>,
+    properties: Vec<Box<Property<T>>>,
+    authorizer: Option<Rc<Authorizer>>,
+    spawner: Option<Rc<Spawn>>,
+    // a signature contained a nul byte, registering fails
+    invalid: bool,
+}
+
+impl<T> Vtable<T> {
+    /// Consult `authorizer` before every method call and property write. Replaces any
+    /// `Authorizer` set before.
+    ///
+    /// Only takes effect when the vtable is registered.
+    pub fn set_authorizer<A: Authorizer + 'static>(&mut self, authorizer: Rc<A>) {
+        self.authorizer = Some(authorizer);
+    }
+
+    /// Run the futures of asynchronous methods using `spawner`. Replaces any `Spawn` set before.
+    ///
+    /// Without one, calls to asynchronous methods fail with `NotSupported`.
+    pub fn set_spawner<S: Spawn + 'static>(&mut self, spawner: Rc<S>) {
+        self.spawner = Some(spawner);
+    }
+
+    /// Point all handlers at the object state, registered as `interface`.
+    ///
+    /// Unsafety:
+    ///
+    ///  - `userdata` must outlive any registration of this vtable
+    pub(super) unsafe fn set_userdata(&mut self, userdata: *mut T, interface: &InterfaceName) {
+        let guard = self.authorizer.as_ref().map(|a| {
+            Rc::new(Guard { authorizer: a.clone(), interface: interface.to_owned() })
+        });
+        for m in self.methods.iter_mut() {
+            m.userdata = userdata;
+            m.guard = guard.clone();
+            m.spawner = self.spawner.clone();
+        }
+        for p in self.properties.iter_mut() {
+            p.userdata = userdata;
+            p.guard = guard.clone();
+        }
+    }
+
+    /// Check for errors sd-bus can't detect itself
+    pub(super) fn validate(&self) -> io::Result<()> {
+        if self.invalid {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "vtable signature contains a nul byte"));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(super) fn as_ptr(&self) -> *const ffi::bus::sd_bus_vtable {
+        self.entries.as_ptr()
+    }
+}
+
+/// An interface that objects of this type implement, registered with `BusRef::add_interface()`.
+///
+/// Usually implemented using `#[dbus_interface]` (with the `derive` feature).
+pub trait Interface: Sized + 'static {
+    /// The name of the interface
+    fn interface() -> &'static InterfaceName;
+
+    /// The members of the interface
+    fn vtable() -> Vtable<Self>;
+}
+
+/// Describes the members of a single interface of an object with state `T`.
+///
+/// Signatures are checked when the vtable is registered: registering fails if one contains a nul
+/// byte or is rejected by sd-bus.
+pub struct VtableBuilder<T> {
+    vtable: Vtable<T>,
+}
+
+impl<T> VtableBuilder<T> {
+    pub fn new() -> Self {
+        VtableBuilder {
+            vtable: Vtable {
+                entries: vec![ffi::bus::sd_bus_vtable::start(size_of::<ffi::bus::sd_bus_vtable>(),
+                                                             0)],
+                strings: Vec::new(),
+                methods: Vec::new(),
+                properties: Vec::new(),
+                authorizer: None,
+                spawner: None,
+                invalid: false,
+            },
+        }
+    }
+
+    /// Keep a copy of `s` alive alongside the vtable, returning a pointer to it
+    ///
+    /// If `s` contains a nul byte, the vtable is marked as invalid (and fails to register).
+    fn string(&mut self, s: &[u8]) -> *const c_char {
+        let c = match CString::new(s) {
+            Ok(c) => c,
+            Err(_) => {
+                self.vtable.invalid = true;
+                CString::default()
+            }
+        };
+        let p = c.as_ptr();
+        self.vtable.strings.push(c);
+        p
+    }
+
+    fn member(&mut self, member: &MemberName) -> *const c_char {
+        self.string(member.to_bytes())
+    }
+
+    /// Add a method named `member` taking arguments of `signature` and returning `result`.
+    ///
+    /// `handler` is responsible for sending a reply. If it returns an `Error`, that error is sent
+    /// as the reply instead.
+    pub fn method<F>(mut self,
+                     member: &MemberName,
+                     signature: &str,
+                     result: &str,
+                     flags: VtableFlags,
+                     handler: F)
+                     -> Self
+        where F: FnMut(&mut T, &mut MessageRef) -> Result<()> + 'static
+    {
+        self.add_method(member, signature, result, flags, Handler::Sync(Box::new(handler)));
+        self
+    }
+
+    /// Add a method like `method()`, whose reply is sent once the future returned by `handler`
+    /// resolves (see `Spawn`). The future resolves to the return value, or to an `Error` which is
+    /// sent instead.
+    ///
+    /// `handler` is called with the call message, so that it can read the arguments. The future
+    /// can't borrow the object state: share whatever it needs, ie: using an `Rc`. If `handler`
+    /// returns an `Error`, that error is sent as the reply right away.
+    pub fn method_async<F, Fut, R>(mut self,
+                                   member: &MemberName,
+                                   signature: &str,
+                                   result: &str,
+                                   flags: VtableFlags,
+                                   mut handler: F)
+                                   -> Self
+        where F: FnMut(&mut T, &mut MessageRef) -> Result<Fut> + 'static,
+              Fut: Future<Output = Result<R>> + 'static,
+              R: ToSdBusMessage + 'static
+    {
+        let handler = move |this: &mut T, m: &mut MessageRef| -> Result<Task> {
+            let future = try!(handler(this, m));
+            Ok(Box::pin(Reply { call: m.to_owned(), future: Box::pin(future) }))
+        };
+        self.add_method(member, signature, result, flags, Handler::Async(Box::new(handler)));
+        self
+    }
+
+    fn add_method(&mut self,
+                  member: &MemberName,
+                  signature: &str,
+                  result: &str,
+                  flags: VtableFlags,
+                  handler: Handler<T>) {
+        let mut m = Box::new(Method {
+            handler: handler,
+            userdata: ptr::null_mut(),
+            guard: None,
+            spawner: None,
+        });
+        let offset = &mut *m as *mut Method<T> as usize;
+        let member = self.member(member);
+        let signature = self.string(signature.as_bytes());
+        let result = self.string(result.as_bytes());
+        self.vtable.methods.push(m);
+        self.vtable.entries.push(ffi::bus::sd_bus_vtable::method(member,
+                                                                 signature,
+                                                                 result,
+                                                                 Some(raw_method_handler::<T>),
+                                                                 offset,
+                                                                 flags.bits()));
+    }
+
+    fn add_property(&mut self,
+                    member: &MemberName,
+                    signature: &str,
+                    flags: VtableFlags,
+                    get: PropertyGet<T>,
+                    set: Option<PropertySet<T>>) {
+        let writable = set.is_some();
+        let mut p = Box::new(Property {
+            get: get,
+            set: set,
+            userdata: ptr::null_mut(),
+            guard: None,
+        });
+        let offset = &mut *p as *mut Property<T> as usize;
+        let member = self.member(member);
+        let signature = self.string(signature.as_bytes());
+        self.vtable.properties.push(p);
+        self.vtable.entries.push(ffi::bus::sd_bus_vtable::property(member,
+                                                                   signature,
+                                                                   Some(raw_property_get::<T>),
+                                                                   if writable {
+                                                                       Some(raw_property_set::<T>)
+                                                                   } else {
+                                                                       None
+                                                                   },
+                                                                   offset,
+                                                                   flags.bits()));
+    }
+
+    /// Add a read-only property named `member` of type `signature`.
+    ///
+    /// `get` is called with the reply message and must append exactly one value of type
+    /// `signature` to it.
+    pub fn property<G>(mut self, member: &MemberName, signature: &str, flags: VtableFlags, get: G)
+        -> Self
+        where G: FnMut(&mut T, &mut MessageRef) -> Result<()> + 'static
+    {
+        self.add_property(member, signature, flags, Box::new(get), None);
+        self
+    }
+
+    /// Add a property named `member` of type `signature` which peers may also set.
+    ///
+    /// `get` behaves as for `property()`. `set` is called with the `Set` call message, positioned
+    /// at the new value (of type `signature`), which it should read and apply. sd-bus sends the
+    /// reply. Note that sd-bus does not emit `PropertiesChanged` on its own, use
+    /// `BusRef::emit_properties_changed()` for that.
+    pub fn property_writable<G, S>(mut self,
+                                   member: &MemberName,
+                                   signature: &str,
+                                   flags: VtableFlags,
+                                   get: G,
+                                   set: S)
+                                   -> Self
+        where G: FnMut(&mut T, &mut MessageRef) -> Result<()> + 'static,
+              S: FnMut(&mut T, &mut MessageRef) -> Result<()> + 'static
+    {
+        self.add_property(member, signature, flags, Box::new(get), Some(Box::new(set)));
+        self
+    }
+
+    /// Declare a signal named `member` with arguments of type `signature`.
+    ///
+    /// This only affects introspection, signals are emitted separately.
+    pub fn signal(mut self, member: &MemberName, signature: &str, flags: VtableFlags) -> Self {
+        let member = self.member(member);
+        let signature = self.string(signature.as_bytes());
+        self.vtable.entries.push(ffi::bus::sd_bus_vtable::signal(member, signature, flags.bits()));
+        self
+    }
+
+    /// Consult `authorizer` before every method call and property write.
+    pub fn authorizer<A: Authorizer + 'static>(mut self, authorizer: Rc<A>) -> Self {
+        self.vtable.set_authorizer(authorizer);
+        self
+    }
+
+    /// Run the futures of asynchronous methods using `spawner`.
+    pub fn spawner<S: Spawn + 'static>(mut self, spawner: Rc<S>) -> Self {
+        self.vtable.set_spawner(spawner);
+        self
+    }
+
+    pub fn create(mut self) -> Vtable<T> {
+        self.vtable.entries.push(ffi::bus::sd_bus_vtable::end());
+        self.vtable
+    }
+}
+
+#[test]
+fn t_vtable_call() {
+    use std::cell::Cell;
+    use super::{BusName, testing};
+
+    let (path, interface) = (testing::path(), testing::interface());
+    let mut client = testing::serve(move |bus| {
+        let vtable = VtableBuilder::<u32>::new()
+            .method(MemberName::from_bytes(b"Add\0").unwrap(), "u", "u", VtableFlags::NONE,
+                    |n, m| {
+                let v: u32 = try!(m.read_body());
+                *n += v;
+                m.reply(*n).map_err(From::from)
+            })
+            .property(MemberName::from_bytes(b"Count\0").unwrap(), "u", VtableFlags::NONE,
+                      |n, m| m.append(*n).map_err(From::from))
+            .create();
+        bus.add_object_vtable(path, interface, vtable, 1)
+    }).unwrap();
+
+    /* count everything the client receives besides replies to its calls */
+    let stray = Rc::new(Cell::new(0));
+    let _filter = {
+        let stray = stray.clone();
+        client.add_filter(move |_| {
+            stray.set(stray.get() + 1);
+            Ok(())
+        }).unwrap()
+    };
+
+    for &(v, sum) in &[(2u32, 3), (3, 6)] {
+        let mut reply = testing::call_with(&mut client, "Add", v).unwrap();
+        assert_eq!(reply.read_body::<u32>().unwrap(), sum);
+    }
+    let dest = BusName::from_bytes(b"org.example.Test\0").unwrap();
+    let count: u32 = client.get_property(dest, path, interface,
+                                         MemberName::from_bytes(b"Count\0").unwrap()).unwrap();
+    assert_eq!(count, 6);
+
+    /* anything sent after the replies arrives before the reply to this */
+    let peer = InterfaceName::from_bytes(b"org.freedesktop.DBus.Peer\0").unwrap();
+    let ping = MemberName::from_bytes(b"Ping\0").unwrap();
+    client.new_method_call(dest, path, peer, ping).unwrap().call(5_000_000).unwrap();
+    while client.process().unwrap() {}
+    assert_eq!(stray.get(), 0);
+}
+
+#[test]
+fn t_vtable_invalid_signature() {
+    use super::testing;
+    let e = testing::serve(|bus| {
+        let path = ObjectPath::from_bytes(b"/org/example/test\0").unwrap();
+        let interface = InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+        let vtable = VtableBuilder::<()>::new()
+            .method(MemberName::from_bytes(b"Add\0").unwrap(), "u\0", "u", VtableFlags::NONE,
+                    |_, m| m.reply(()).map_err(From::from))
+            .create();
+        bus.add_object_vtable(path, interface, vtable, ())
+    }).err().unwrap();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
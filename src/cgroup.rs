@@ -0,0 +1,239 @@
+/*!
+ * Delegated cgroup subtrees
+ *
+ * Services started with `Delegate=yes` may manage the cgroup subtree below their own cgroup. The
+ * workflow systemd documents for this (see "Control Group APIs and Delegation" in the systemd
+ * documentation) is:
+ *
+ *  1. find the delegated cgroup (`Cgroup::delegated()`)
+ *  2. create child cgroups in it, and move the service's own processes out of the delegated
+ *     cgroup into one of them. A cgroup with controllers enabled for its children may not
+ *     contain processes itself.
+ *  3. enable the controllers needed by the children (`enable_controllers()`)
+ *  4. create further children and move processes into them as needed
+ *
+ * Only the unified (v2) hierarchy is supported. On hybrid systems that is the one mounted at
+ * `/sys/fs/cgroup/unified`.
+ *
+ * Processes can also be started directly in a cgroup with `Cgroup::spawn()`, ie: in the cgroup
+ * of another unit found with `bus::manager::Unit::cgroup()`.
+ */
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use libc;
+use super::ffi::pid_t;
+use super::Result;
+
+/// Where the unified cgroup hierarchy is mounted
+pub const CGROUP_ROOT: &'static str = "/sys/fs/cgroup";
+
+/// A cgroup in the unified hierarchy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// The cgroup of the calling process, which is the delegated cgroup when called early by a
+    /// service with `Delegate=yes`.
+    pub fn delegated() -> Result<Cgroup> {
+        let mut s = String::new();
+        try!(try!(fs::File::open("/proc/self/cgroup")).read_to_string(&mut s));
+        let cg = try!(unified_path(&s).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "not in a unified cgroup hierarchy")
+        }));
+        Ok(Cgroup::from_path(unified_root().join(cg.trim_start_matches('/'))))
+    }
+
+    /// The cgroup whose directory (in the mounted hierarchy) is `path`.
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> Cgroup {
+        Cgroup { path: path.into() }
+    }
+
+    /// The directory of this cgroup in the mounted hierarchy
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_attr(&self, attr: &str) -> Result<String> {
+        let mut s = String::new();
+        try!(try!(fs::File::open(self.path.join(attr))).read_to_string(&mut s));
+        Ok(s)
+    }
+
+    /// Attributes must be written with a single `write()` call, which `write_all()` does for
+    /// short writes.
+    fn write_attr(&self, attr: &str, value: &str) -> Result<()> {
+        let mut f = try!(OpenOptions::new().write(true).open(self.path.join(attr)));
+        f.write_all(value.as_bytes())
+    }
+
+    /// Create the child cgroup `name`, or use it if it already exists.
+    pub fn create_child(&self, name: &str) -> Result<Cgroup> {
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid cgroup name"));
+        }
+
+        let child = Cgroup::from_path(self.path.join(name));
+        match fs::create_dir(&child.path) {
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            r => try!(r),
+        }
+        Ok(child)
+    }
+
+    /// Remove this cgroup. It must not contain any processes or children.
+    pub fn remove(self) -> Result<()> {
+        fs::remove_dir(&self.path)
+    }
+
+    /// Move the process `pid` (with all its threads) into this cgroup. Use 0 for the calling
+    /// process.
+    pub fn move_pid(&self, pid: pid_t) -> Result<()> {
+        self.write_attr("cgroup.procs", &pid.to_string())
+    }
+
+    /// Spawn `cmd` with the child process already in this cgroup.
+    ///
+    /// The child moves itself into the cgroup before it executes the program, so it never runs any
+    /// code of the program elsewhere (which a `move_pid()` after spawning could not guarantee).
+    /// The caller needs write access to `cgroup.procs` of this cgroup and of the common ancestor
+    /// of this cgroup and its own.
+    pub fn spawn(&self, cmd: &mut Command) -> Result<Child> {
+        /* opened here, as the child may only make async-signal-safe calls before exec */
+        let procs = try!(OpenOptions::new().write(true).open(self.path.join("cgroup.procs")));
+        let fd = procs.as_raw_fd();
+        unsafe {
+            cmd.pre_exec(move || {
+                /* "0" is the writing process itself */
+                if libc::write(fd, b"0".as_ptr() as *const _, 1) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = cmd.spawn();
+        drop(procs);
+        child
+    }
+
+    /// The processes in this cgroup (not including those in child cgroups)
+    pub fn pids(&self) -> Result<Vec<pid_t>> {
+        let s = try!(self.read_attr("cgroup.procs"));
+        s.lines()
+            .map(|l| {
+                l.trim().parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad pid"))
+            })
+            .collect()
+    }
+
+    /// The controllers available to this cgroup, which may be enabled for its children
+    pub fn controllers(&self) -> Result<Vec<String>> {
+        let s = try!(self.read_attr("cgroup.controllers"));
+        Ok(s.split_whitespace().map(String::from).collect())
+    }
+
+    /// The controllers currently enabled for the children of this cgroup
+    pub fn subtree_controllers(&self) -> Result<Vec<String>> {
+        let s = try!(self.read_attr("cgroup.subtree_control"));
+        Ok(s.split_whitespace().map(String::from).collect())
+    }
+
+    /// Enable `controllers` (ie: `"cpu"`, `"memory"`) for the children of this cgroup.
+    pub fn enable_controllers(&self, controllers: &[&str]) -> Result<()> {
+        self.change_controllers('+', controllers)
+    }
+
+    /// Disable `controllers` for the children of this cgroup.
+    pub fn disable_controllers(&self, controllers: &[&str]) -> Result<()> {
+        self.change_controllers('-', controllers)
+    }
+
+    fn change_controllers(&self, op: char, controllers: &[&str]) -> Result<()> {
+        let v: Vec<String> = controllers.iter().map(|c| format!("{}{}", op, c)).collect();
+        self.write_attr("cgroup.subtree_control", &v.join(" "))
+    }
+}
+
+/// Where the unified hierarchy is mounted: `CGROUP_ROOT`, or its `unified` subdirectory on hybrid
+/// systems.
+fn unified_root() -> PathBuf {
+    let root = Path::new(CGROUP_ROOT);
+    if !root.join("cgroup.controllers").exists() && root.join("unified").is_dir() {
+        return root.join("unified");
+    }
+    root.to_owned()
+}
+
+/// The path of the unified hierarchy entry (`0::<path>`) in the contents of `/proc/<pid>/cgroup`.
+/// Legacy (v1) hierarchies have a non-zero id and are skipped.
+fn unified_path(proc_cgroup: &str) -> Option<&str> {
+    proc_cgroup.lines().filter_map(|l| {
+        let mut f = l.splitn(3, ':');
+        match (f.next(), f.next(), f.next()) {
+            (Some("0"), Some(""), Some(path)) => Some(path),
+            _ => None,
+        }
+    }).next()
+}
+
+#[test]
+fn t_unified_path() {
+    /* unified */
+    assert_eq!(unified_path("0::/system.slice/foo.service\n"), Some("/system.slice/foo.service"));
+    /* hybrid: v1 controllers, the named systemd hierarchy, and the unified one */
+    let hybrid = "12:pids:/system.slice/foo.service\n\
+                  4:cpu,cpuacct:/system.slice/foo.service\n\
+                  1:name=systemd:/system.slice/foo.service\n\
+                  0::/system.slice/foo.service/main\n";
+    assert_eq!(unified_path(hybrid), Some("/system.slice/foo.service/main"));
+    /* legacy only */
+    let legacy = "11:memory:/user.slice\n\
+                  3:net_cls,net_prio:/\n\
+                  1:name=systemd:/user.slice/user-1000.slice/session-2.scope\n";
+    assert_eq!(unified_path(legacy), None);
+    /* paths may contain colons */
+    assert_eq!(unified_path("0::/a:b\n"), Some("/a:b"));
+    assert_eq!(unified_path(""), None);
+
+    /* whatever this system uses, its own file parses */
+    let mut s = String::new();
+    fs::File::open("/proc/self/cgroup").unwrap().read_to_string(&mut s).unwrap();
+    if let Some(p) = unified_path(&s) {
+        assert!(p.starts_with('/'));
+    }
+}
+
+#[test]
+fn t_cgroup() {
+    let dir = ::std::env::temp_dir()
+        .join(format!("rust-systemd-t_cgroup-{}", ::std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+
+    let root = Cgroup::from_path(&dir);
+    let child = root.create_child("worker").unwrap();
+    assert_eq!(root.create_child("worker").unwrap(), child);
+    root.create_child("a/b").err().unwrap();
+
+    fs::File::create(child.path().join("cgroup.procs")).unwrap();
+    child.move_pid(42).unwrap();
+    assert_eq!(child.pids().unwrap(), vec![42]);
+
+    /* a plain file keeps what was written, rather than applying it as cgroupfs would */
+    let subtree_control = root.path().join("cgroup.subtree_control");
+    fs::File::create(&subtree_control).unwrap();
+    root.enable_controllers(&["cpu", "memory"]).unwrap();
+    assert_eq!(fs::read(&subtree_control).unwrap(), b"+cpu +memory");
+    fs::File::create(&subtree_control).unwrap();
+    root.disable_controllers(&["cpu"]).unwrap();
+    assert_eq!(fs::read(&subtree_control).unwrap(), b"-cpu");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,13 +1,19 @@
 use std::{ptr, collections};
 use std::os::unix::io::RawFd as Fd;
-use libc::{c_char, c_uint};
+use libc::{self, c_char, c_uint};
 use super::ffi::{c_int, size_t, pid_t};
 use libc::{SOCK_STREAM, SOCK_DGRAM, SOCK_RAW};
-use std::net::TcpListener;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::UnixStream;
 use ffi::daemon as ffi;
 use super::{Result, Error};
 use std::io::ErrorKind;
 use std::os::unix::io::FromRawFd;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use event::{Event, Source};
 
 // XXX: this is stolen from std::old_io::net::addrinfo until we have a replacement in the standard
 // lib.
@@ -136,6 +142,117 @@ pub fn tcp_listener(fd: Fd) -> Result<TcpListener> {
     }
 }
 
+/// A connected stream socket handed to us by the service manager, see `inetd_connection()`.
+#[derive(Debug)]
+pub enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    /// Take ownership of the connected stream socket `fd`.
+    ///
+    /// Fails (without taking ownership) if `fd` is not a connected stream socket of
+    /// `AF_INET`/`AF_INET6` or `AF_UNIX`.
+    pub fn from_fd(fd: Fd) -> Result<Connection> {
+        if try!(is_socket_inet(fd, None, Some(SocketType::Stream), Listening::IsNotListening,
+                               None)) {
+            Ok(Connection::Tcp(unsafe { TcpStream::from_raw_fd(fd) }))
+        } else if try!(is_socket_unix(fd, Some(SocketType::Stream), Listening::IsNotListening,
+                                      None)) {
+            Ok(Connection::Unix(unsafe { UnixStream::from_raw_fd(fd) }))
+        } else {
+            Err(Error::new(ErrorKind::InvalidInput, "Socket type was not as expected"))
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match *self {
+            Connection::Tcp(ref mut s) => s.read(buf),
+            Connection::Unix(ref mut s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match *self {
+            Connection::Tcp(ref mut s) => s.write(buf),
+            Connection::Unix(ref mut s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match *self {
+            Connection::Tcp(ref mut s) => s.flush(),
+            Connection::Unix(ref mut s) => s.flush(),
+        }
+    }
+}
+
+/// Returns true if standard input is a connected stream socket, as it is for services activated
+/// by an `Accept=yes` socket with `StandardInput=socket` (inetd style activation).
+pub fn is_inetd() -> Result<bool> {
+    is_socket(libc::STDIN_FILENO, None, Some(SocketType::Stream), Listening::IsNotListening)
+}
+
+/// Take over the connection an inetd style activated service was started for (see `is_inetd()`).
+///
+/// The socket is moved to a new file descriptor and standard input and output are pointed at
+/// `/dev/null`, so that stray reads from stdin or prints to stdout can't interfere with the
+/// protocol spoken on the connection. Standard error is left alone.
+///
+/// Fails if standard input is not a connected stream socket.
+pub fn inetd_connection() -> Result<Connection> {
+    if !try!(is_inetd()) {
+        return Err(Error::new(ErrorKind::InvalidInput, "stdin is not a connected socket"));
+    }
+
+    let fd = unsafe { libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, LISTEN_FDS_START) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    let conn = match Connection::from_fd(fd) {
+        Ok(c) => c,
+        Err(e) => {
+            unsafe { libc::close(fd) };
+            return Err(e);
+        }
+    };
+
+    let null = unsafe { libc::open(b"/dev/null\0".as_ptr() as *const c_char, libc::O_RDWR) };
+    if null < 0 {
+        return Err(Error::last_os_error());
+    }
+    for &std_fd in &[libc::STDIN_FILENO, libc::STDOUT_FILENO] {
+        if unsafe { libc::dup2(null, std_fd) } < 0 {
+            let e = Error::last_os_error();
+            unsafe { libc::close(null) };
+            return Err(e);
+        }
+    }
+    unsafe { libc::close(null) };
+    Ok(conn)
+}
+
+#[test]
+fn t_connection_from_fd() {
+    use std::os::unix::io::IntoRawFd;
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut c = Connection::from_fd(a.into_raw_fd()).unwrap();
+    let mut b = b;
+    c.write_all(b"hi").unwrap();
+    let mut buf = [0u8; 2];
+    b.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hi");
+
+    let l = TcpListener::bind("127.0.0.1:0").unwrap();
+    let fd = ::std::os::unix::io::AsRawFd::as_raw_fd(&l);
+    Connection::from_fd(fd).err().unwrap();
+}
+
 /// Identifies whether the passed file descriptor is an AF_UNIX socket. If type
 /// are supplied, it must match as well. For normal sockets, leave the path set
 /// to None; otherwise, pass in the full socket path.  See `Listening` for
@@ -216,3 +333,330 @@ pub fn watchdog_enabled(unset_environment: bool) -> Result<u64> {
     sd_try!(ffi::sd_watchdog_enabled(unset_environment as c_int, &mut timeout));
     Ok(timeout)
 }
+
+/// Keeps the service manager from timing out while a long blocking operation (say, a database
+/// migration during startup) runs.
+///
+/// While the guard exists, a background thread repeatedly sends `EXTEND_TIMEOUT_USEC=`, which
+/// pushes out the start, stop or runtime timeout currently in effect. If the watchdog is enabled,
+/// it is kept happy with `WATCHDOG=1` as well. Dropping the guard stops the thread, after which
+/// the regular timeouts apply again.
+pub struct WatchdogGuard {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WatchdogGuard {
+    /// Extend the timeout by `duration` now, and keep extending it until the guard is dropped.
+    ///
+    /// Extensions are sent every half `duration` (or half the watchdog interval, if shorter), so
+    /// each one is renewed well before it runs out. They are not sent more often than every
+    /// millisecond, and `duration` must not be zero.
+    pub fn extend(duration: Duration) -> Result<WatchdogGuard> {
+        let watchdog_usec = try!(watchdog_enabled(false));
+        WatchdogGuard::start(duration, watchdog_usec, |state| notify(false, state))
+    }
+
+    /// `extend()`, with the watchdog interval (0 if disabled) and the function sending the
+    /// notifications passed in rather than taken from the environment
+    fn start<F>(duration: Duration, watchdog_usec: u64, notify: F) -> Result<WatchdogGuard>
+        where F: Fn(collections::HashMap<&str, &str>) -> Result<bool> + Send + 'static
+    {
+        if duration == Duration::from_secs(0) {
+            return Err(Error::new(ErrorKind::InvalidInput, "timeout extension must not be zero"));
+        }
+        let extend = format!("{}", ::duration_usec(duration));
+        let mut interval = duration / 2;
+        if watchdog_usec != 0 {
+            interval = ::std::cmp::min(interval, Duration::from_micros(watchdog_usec / 2));
+        }
+        interval = ::std::cmp::max(interval, Duration::from_millis(1));
+
+        let send = move || {
+            let mut state = collections::HashMap::new();
+            state.insert("EXTEND_TIMEOUT_USEC", &extend[..]);
+            if watchdog_usec != 0 {
+                state.insert(STATE_WATCHDOG, "1");
+            }
+            notify(state)
+        };
+        try!(send());
+
+        let (stop, stopped) = mpsc::channel();
+        let thread = try!(thread::Builder::new()
+            .name("watchdog-guard".to_owned())
+            .spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    if let Err(e) = send() {
+                        warn!("failed to extend service timeout: {}", e);
+                    }
+                }
+            }));
+
+        Ok(WatchdogGuard { stop: Some(stop), thread: Some(thread) })
+    }
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        /* dropping the sender wakes the thread */
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[test]
+fn t_watchdog_guard() {
+    use std::os::unix::net::UnixDatagram;
+
+    WatchdogGuard::start(Duration::from_secs(0), 0, |_| Ok(true)).err().unwrap();
+
+    let (tx, rx) = UnixDatagram::pair().unwrap();
+    rx.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    let g = WatchdogGuard::start(Duration::from_millis(40), 10000, move |state| {
+        tx.send(state_to_c_string(state).as_bytes()).map(|_| true)
+    }).unwrap();
+    let mut b = [0u8; 256];
+    /* the first one is sent right away, the others every 5ms (half the watchdog interval) */
+    for _ in 0..3 {
+        let n = rx.recv(&mut b).unwrap();
+        let mut lines: Vec<_> = b[..n].split(|&c| c == b'\n').collect();
+        lines.sort();
+        assert_eq!(lines, [&b"EXTEND_TIMEOUT_USEC=40000"[..], b"WATCHDOG=1"]);
+    }
+    drop(g);
+
+    /* the thread is gone once the guard is dropped, nothing is sent after that */
+    rx.set_nonblocking(true).unwrap();
+    while rx.recv(&mut b).is_ok() {}
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(rx.recv(&mut b).unwrap_err().kind(), ErrorKind::WouldBlock);
+}
+
+/// Carries out the steps a daemon should take when it is asked to stop, in the right order.
+///
+/// `SIGTERM` and `SIGINT` are received by signal sources on an sd-event loop (see
+/// `Event::add_signal()`), which exit the loop with the signal number. Creating them blocks both
+/// signals in the calling thread. Signal masks are per-thread and inherited, so create the
+/// `Shutdown` before spawning any threads, otherwise those threads may receive the signal instead
+/// (and be killed by it).
+///
+/// Once a signal arrived, `run()`:
+///
+///  1. notifies the service manager with `STOPPING=1` (and `STATUS=`, if set)
+///  2. disarms the watchdog, if one is enabled, so a slow shutdown does not get us killed
+///  3. flushes and closes every bus connection handed over with `bus()`
+///  4. flushes the global `log` logger, for loggers which buffer records. Records sent to the
+///     journal (ie: by `JournalLog`) are written right away, there is nothing to flush for them.
+pub struct Shutdown {
+    event: Event,
+    _signals: Vec<Source>,
+    status: Option<String>,
+    #[cfg(feature = "bus")]
+    buses: Vec<::bus::Bus>,
+}
+
+impl Shutdown {
+    /// Watch for `SIGTERM` and `SIGINT` on a new event loop, which `wait()` runs.
+    pub fn new() -> Result<Shutdown> {
+        Shutdown::with_event(&try!(Event::new()))
+    }
+
+    /// Watch for `SIGTERM` and `SIGINT` on `event`, an event loop the daemon also uses for other
+    /// sources. Running the loop (with `Event::run_loop()` or `wait()`) then returns the signal
+    /// number once either arrives, after which call `run()`.
+    pub fn with_event(event: &Event) -> Result<Shutdown> {
+        let mut signals = Vec::new();
+        for &sig in &[libc::SIGTERM, libc::SIGINT] {
+            signals.push(try!(event.add_signal(sig, |s, si| {
+                s.event().exit(si.ssi_signo as c_int)
+            })));
+        }
+
+        Ok(Shutdown {
+            event: event.clone(),
+            _signals: signals,
+            status: None,
+            #[cfg(feature = "bus")]
+            buses: Vec::new(),
+        })
+    }
+
+    /// Status text sent along with `STOPPING=1`
+    pub fn status<S: Into<String>>(mut self, status: S) -> Self {
+        self.status = Some(status.into());
+        self
+    }
+
+    /// Flush and close `bus` during `run()`. Buses are closed in the order they were added.
+    #[cfg(feature = "bus")]
+    pub fn bus(mut self, bus: ::bus::Bus) -> Self {
+        self.buses.push(bus);
+        self
+    }
+
+    /// Run the event loop until `SIGTERM` or `SIGINT` is received, returning the signal number
+    /// (or the code another source exited the loop with).
+    #[inline]
+    pub fn wait(&self) -> Result<c_int> {
+        self.event.run_loop()
+    }
+
+    /// Run the shutdown sequence.
+    ///
+    /// Every step is attempted even if an earlier one fails, the first error is returned.
+    pub fn run(self) -> Result<()> {
+        let mut result = Ok(());
+        let mut state = collections::HashMap::new();
+        state.insert("STOPPING", "1");
+        if let Some(ref status) = self.status {
+            state.insert(STATE_STATUS, status);
+        }
+        result = result.and(notify(false, state).map(|_| ()));
+
+        match watchdog_enabled(false) {
+            Ok(0) => {}
+            Ok(_) => {
+                let mut state = collections::HashMap::new();
+                state.insert("WATCHDOG_USEC", "0");
+                result = result.and(notify(false, state).map(|_| ()));
+            }
+            Err(e) => result = result.and(Err(e)),
+        }
+
+        #[cfg(feature = "bus")]
+        for bus in self.buses {
+            result = result.and(bus.flush());
+            bus.close();
+        }
+
+        ::log::logger().flush();
+        result
+    }
+}
+
+#[test]
+fn t_shutdown_wait() {
+    let s = Shutdown::new().unwrap();
+    assert_eq!(unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGTERM) }, 0);
+    assert_eq!(s.wait().unwrap(), libc::SIGTERM);
+    drop(s);
+
+    /* on a loop shared with other sources */
+    let e = Event::new().unwrap();
+    let _s = Shutdown::with_event(&e).unwrap();
+    let _t = e.add_timeout(Duration::from_millis(1), |_, _| {
+        assert_eq!(unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGINT) }, 0);
+        Ok(())
+    }).unwrap();
+    assert_eq!(e.run_loop().unwrap(), libc::SIGINT);
+}
+
+/// Reports startup completion to whoever started us, for both `Type=notify` and `Type=forking`
+/// services (as well as plain invocation from a shell).
+///
+/// When started with `$NOTIFY_SOCKET` set, nothing special happens at startup and `ready()` sends
+/// `READY=1`. Otherwise `Readiness::daemonize()` forks: the parent stays around until the child
+/// calls `ready()` (exiting with status 0) or `failed()`/drops the `Readiness` (exiting with
+/// status 1), so that whoever started it only continues once the daemon is actually up.
+pub struct Readiness {
+    /// Write end of the pipe to the waiting parent, if we forked
+    pipe: Option<Fd>,
+}
+
+const READY_OK: u8 = b'R';
+const READY_FAILED: u8 = b'F';
+
+impl Readiness {
+    /// Daemonize unless running under `Type=notify`.
+    ///
+    /// This forks, so it must be called before any threads are spawned. The child becomes a
+    /// session leader; its working directory, umask and standard streams are left alone.
+    pub fn daemonize() -> Result<Readiness> {
+        if ::std::env::var_os("NOTIFY_SOCKET").is_some() {
+            return Ok(Readiness { pipe: None });
+        }
+
+        let mut fds = [0 as c_int; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        match unsafe { libc::fork() } {
+            -1 => {
+                let e = Error::last_os_error();
+                unsafe {
+                    libc::close(fds[0]);
+                    libc::close(fds[1]);
+                }
+                Err(e)
+            }
+            0 => {
+                unsafe {
+                    libc::close(fds[0]);
+                    libc::setsid();
+                }
+                Ok(Readiness { pipe: Some(fds[1]) })
+            }
+            _ => {
+                unsafe { libc::close(fds[1]) };
+                let mut b = READY_FAILED;
+                let r = loop {
+                    let r = unsafe { libc::read(fds[0], &mut b as *mut u8 as *mut _, 1) };
+                    if r >= 0 || Error::last_os_error().kind() != ErrorKind::Interrupted {
+                        break r;
+                    }
+                };
+                let status = if r == 1 && b == READY_OK { 0 } else { 1 };
+                unsafe { libc::_exit(status) }
+            }
+        }
+    }
+
+    /// Whether `daemonize()` forked (ie: we are not running under `Type=notify`)
+    #[inline]
+    pub fn forked(&self) -> bool {
+        self.pipe.is_some()
+    }
+
+    fn report(&mut self, b: u8) -> Result<()> {
+        if let Some(fd) = self.pipe.take() {
+            let r = unsafe { libc::write(fd, &b as *const u8 as *const _, 1) };
+            let e = Error::last_os_error();
+            unsafe { libc::close(fd) };
+            if r != 1 {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Startup completed: let the parent exit successfully, or send `READY=1`.
+    pub fn ready(mut self) -> Result<()> {
+        if !self.forked() {
+            let mut state = collections::HashMap::new();
+            state.insert(STATE_READY, "1");
+            try!(notify(false, state));
+        }
+        self.report(READY_OK)
+    }
+
+    /// Startup failed with `errno`: let the parent exit with a failure status, or send `ERRNO=`.
+    pub fn failed(mut self, errno: c_int) -> Result<()> {
+        if !self.forked() {
+            let errno = errno.to_string();
+            let mut state = collections::HashMap::new();
+            state.insert(STATE_ERRNO, &errno[..]);
+            try!(notify(false, state));
+        }
+        self.report(READY_FAILED)
+    }
+}
+
+impl Drop for Readiness {
+    fn drop(&mut self) {
+        let _ = self.report(READY_FAILED);
+    }
+}
@@ -0,0 +1,994 @@
+/*!
+ * An event loop, using sd-event
+ *
+ * `Event` owns a reference to an `sd_event` loop, `EventRef` is what is borrowed from it (and what
+ * callbacks are handed), like `Bus` and `BusRef`. An event loop belongs to the thread which
+ * created it: neither is `Send`.
+ *
+ * A single-threaded daemon typically gets the loop of its thread with `Event::default()`, adds
+ * its sources and then calls `run_loop()` until one of them calls `exit()`.
+ *
+ * Adding an event source (ie: with `EventRef::add_io()`) returns a `Source`, which owns the
+ * callback. Dropping it removes the source from the loop. Callbacks are passed the `SourceRef`
+ * they were registered with, so that they can adjust it. A callback returning an error disables
+ * its source.
+ */
+
+use ffi;
+use ffi::{c_int, c_void};
+use libc;
+use std::any::Any;
+use std::borrow::{Borrow, BorrowMut};
+use std::ffi::{CStr, CString, OsStr};
+use std::fmt;
+use std::mem::{self, transmute};
+use std::ops::{BitOr, BitOrAssign, Deref, DerefMut};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::{Error, Result};
+
+/// Priority of sources which should be dispatched before others
+pub const PRIORITY_IMPORTANT: i64 = ffi::event::SD_EVENT_PRIORITY_IMPORTANT as i64;
+/// The priority sources are created with
+pub const PRIORITY_NORMAL: i64 = ffi::event::SD_EVENT_PRIORITY_NORMAL as i64;
+/// Priority of sources which should only be dispatched when nothing else is pending
+pub const PRIORITY_IDLE: i64 = ffi::event::SD_EVENT_PRIORITY_IDLE as i64;
+
+/// The clocks sd-event can measure time on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Clock {
+    /// Wall-clock time (`CLOCK_REALTIME`)
+    Realtime,
+    /// Time since boot, not counting suspend (`CLOCK_MONOTONIC`)
+    Monotonic,
+    /// Time since boot, counting suspend (`CLOCK_BOOTTIME`)
+    Boottime,
+    /// Like `Realtime`, but timers on it wake the system from suspend (`CLOCK_REALTIME_ALARM`)
+    RealtimeAlarm,
+    /// Like `Boottime`, but timers on it wake the system from suspend (`CLOCK_BOOTTIME_ALARM`)
+    BoottimeAlarm,
+}
+
+impl Clock {
+    fn raw(self) -> libc::clockid_t {
+        match self {
+            Clock::Realtime => libc::CLOCK_REALTIME,
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::Boottime => libc::CLOCK_BOOTTIME,
+            Clock::RealtimeAlarm => libc::CLOCK_REALTIME_ALARM,
+            Clock::BoottimeAlarm => libc::CLOCK_BOOTTIME_ALARM,
+        }
+    }
+
+    fn from_raw(clock: libc::clockid_t) -> Result<Clock> {
+        Ok(match clock {
+            libc::CLOCK_REALTIME => Clock::Realtime,
+            libc::CLOCK_MONOTONIC => Clock::Monotonic,
+            libc::CLOCK_BOOTTIME => Clock::Boottime,
+            libc::CLOCK_REALTIME_ALARM => Clock::RealtimeAlarm,
+            libc::CLOCK_BOOTTIME_ALARM => Clock::BoottimeAlarm,
+            _ => return Err(Error::from_raw_os_error(libc::EINVAL)),
+        })
+    }
+}
+
+/// The `epoll` events an IO source waits for, or which occurred
+///
+/// Combine flags with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IoEvents(u32);
+
+impl IoEvents {
+    pub const NONE: IoEvents = IoEvents(0);
+    /// The fd is readable (`EPOLLIN`)
+    pub const IN: IoEvents = IoEvents(libc::EPOLLIN as u32);
+    /// The fd is writable (`EPOLLOUT`)
+    pub const OUT: IoEvents = IoEvents(libc::EPOLLOUT as u32);
+    /// Urgent data can be read (`EPOLLPRI`)
+    pub const PRI: IoEvents = IoEvents(libc::EPOLLPRI as u32);
+    /// The peer closed its end, or shut down writing (`EPOLLRDHUP`)
+    pub const RDHUP: IoEvents = IoEvents(libc::EPOLLRDHUP as u32);
+    /// An error occurred (`EPOLLERR`). Always reported, there is no need to wait for it.
+    pub const ERR: IoEvents = IoEvents(libc::EPOLLERR as u32);
+    /// The fd was hung up (`EPOLLHUP`). Always reported, there is no need to wait for it.
+    pub const HUP: IoEvents = IoEvents(libc::EPOLLHUP as u32);
+    /// Only report changes of readiness, instead of as long as the fd is ready (`EPOLLET`)
+    pub const EDGE_TRIGGERED: IoEvents = IoEvents(libc::EPOLLET as u32);
+
+    /// The raw `EPOLL*` flags
+    #[inline]
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether all flags set in `other` are also set in `self`
+    #[inline]
+    pub fn contains(self, other: IoEvents) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for IoEvents {
+    type Output = IoEvents;
+    #[inline]
+    fn bitor(self, other: IoEvents) -> IoEvents {
+        IoEvents(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for IoEvents {
+    #[inline]
+    fn bitor_assign(&mut self, other: IoEvents) {
+        self.0 |= other.0
+    }
+}
+
+/// Whether an event source is dispatched
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Enabled {
+    /// Not dispatched
+    Off,
+    /// Dispatched whenever it is ready
+    On,
+    /// Dispatched once, then turned `Off`
+    Oneshot,
+}
+
+/// Return value of a callback: sd-event disables the source if it is negative
+fn handler_return(r: Result<()>) -> c_int {
+    match r {
+        Ok(()) => 0,
+        Err(e) => -e.raw_os_error().unwrap_or(libc::EIO),
+    }
+}
+
+unsafe extern "C" fn raw_io_handler<F>(s: *mut ffi::event::sd_event_source,
+                                       fd: c_int,
+                                       revents: u32,
+                                       userdata: *mut c_void)
+                                       -> c_int
+    where F: FnMut(&SourceRef, RawFd, IoEvents) -> Result<()>
+{
+    let f = &mut *(userdata as *mut F);
+    handler_return(f(SourceRef::from_ptr(s), fd, IoEvents(revents)))
+}
+
+unsafe extern "C" fn raw_time_handler<F>(s: *mut ffi::event::sd_event_source,
+                                         usec: u64,
+                                         userdata: *mut c_void)
+                                         -> c_int
+    where F: FnMut(&SourceRef, Duration) -> Result<()>
+{
+    let f = &mut *(userdata as *mut F);
+    handler_return(f(SourceRef::from_ptr(s), Duration::from_micros(usec)))
+}
+
+unsafe extern "C" fn raw_signal_handler<F>(s: *mut ffi::event::sd_event_source,
+                                           si: *const libc::signalfd_siginfo,
+                                           userdata: *mut c_void)
+                                           -> c_int
+    where F: FnMut(&SourceRef, &libc::signalfd_siginfo) -> Result<()>
+{
+    let f = &mut *(userdata as *mut F);
+    handler_return(f(SourceRef::from_ptr(s), &*si))
+}
+
+unsafe extern "C" fn raw_child_handler<F>(s: *mut ffi::event::sd_event_source,
+                                          si: *const libc::siginfo_t,
+                                          userdata: *mut c_void)
+                                          -> c_int
+    where F: FnMut(&SourceRef, &libc::siginfo_t) -> Result<()>
+{
+    let f = &mut *(userdata as *mut F);
+    handler_return(f(SourceRef::from_ptr(s), &*si))
+}
+
+unsafe extern "C" fn raw_handler<F>(s: *mut ffi::event::sd_event_source,
+                                    userdata: *mut c_void)
+                                    -> c_int
+    where F: FnMut(&SourceRef) -> Result<()>
+{
+    let f = &mut *(userdata as *mut F);
+    handler_return(f(SourceRef::from_ptr(s)))
+}
+
+unsafe extern "C" fn raw_inotify_handler<F>(s: *mut ffi::event::sd_event_source,
+                                            event: *const libc::inotify_event,
+                                            userdata: *mut c_void)
+                                            -> c_int
+    where F: FnMut(&SourceRef, u32, Option<&Path>) -> Result<()>
+{
+    let f = &mut *(userdata as *mut F);
+    /* the name follows the event, padded with NULs to `len` */
+    let name = if (*event).len == 0 {
+        None
+    } else {
+        let p = (event as *const u8).offset(mem::size_of::<libc::inotify_event>() as isize);
+        Some(Path::new(OsStr::from_bytes(CStr::from_ptr(p as *const _).to_bytes())))
+    };
+    handler_return(f(SourceRef::from_ptr(s), (*event).mask, name))
+}
+
+/// Block `sig` in the calling thread, so that sd-event can receive it through a `signalfd`
+fn block_signal(sig: c_int) -> Result<()> {
+    let mut signals: libc::sigset_t = unsafe { mem::zeroed() };
+    unsafe {
+        libc::sigemptyset(&mut signals);
+        if libc::sigaddset(&mut signals, sig) < 0 {
+            return Err(Error::from_raw_os_error(libc::EINVAL));
+        }
+    }
+    let r = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &signals, ptr::null_mut()) };
+    if r != 0 {
+        return Err(Error::from_raw_os_error(r));
+    }
+    Ok(())
+}
+
+pub struct Event {
+    raw: *mut ffi::event::sd_event,
+}
+
+impl Event {
+    /// Create a new event loop
+    #[inline]
+    pub fn new() -> Result<Event> {
+        let mut e = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_new(&mut e));
+        Ok(Event { raw: e })
+    }
+
+    /// The default event loop of the calling thread, created on first use
+    #[inline]
+    pub fn default() -> Result<Event> {
+        let mut e = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_default(&mut e));
+        Ok(Event { raw: e })
+    }
+
+    #[inline]
+    pub(crate) unsafe fn from_ptr(r: *mut ffi::event::sd_event) -> Event {
+        Event { raw: ffi::event::sd_event_ref(r) }
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const ffi::event::sd_event {
+        self.raw
+    }
+
+    #[inline]
+    fn as_mut_ptr(&mut self) -> *mut ffi::event::sd_event {
+        self.raw
+    }
+}
+
+impl Borrow<EventRef> for Event {
+    #[inline]
+    fn borrow(&self) -> &EventRef {
+        unsafe { EventRef::from_ptr(self.as_ptr()) }
+    }
+}
+
+impl BorrowMut<EventRef> for Event {
+    #[inline]
+    fn borrow_mut(&mut self) -> &mut EventRef {
+        unsafe { EventRef::from_mut_ptr(self.as_mut_ptr()) }
+    }
+}
+
+impl Deref for Event {
+    type Target = EventRef;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.borrow()
+    }
+}
+
+impl DerefMut for Event {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.borrow_mut()
+    }
+}
+
+impl Drop for Event {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { ffi::event::sd_event_unref(self.raw) };
+    }
+}
+
+impl Clone for Event {
+    #[inline]
+    fn clone(&self) -> Event {
+        Event { raw: unsafe { ffi::event::sd_event_ref(self.raw) } }
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Event").finish()
+    }
+}
+
+pub struct EventRef {
+    _inner: ffi::event::sd_event,
+}
+
+impl fmt::Debug for EventRef {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("EventRef").finish()
+    }
+}
+
+impl ToOwned for EventRef {
+    type Owned = Event;
+    #[inline]
+    fn to_owned(&self) -> Self::Owned {
+        unsafe { Event::from_ptr(self.as_ptr()) }
+    }
+}
+
+impl EventRef {
+    #[inline]
+    unsafe fn from_ptr<'a>(r: *const ffi::event::sd_event) -> &'a EventRef {
+        transmute(r)
+    }
+
+    #[inline]
+    unsafe fn from_mut_ptr<'a>(r: *mut ffi::event::sd_event) -> &'a mut EventRef {
+        transmute(r)
+    }
+
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *mut ffi::event::sd_event {
+        unsafe { transmute(self) }
+    }
+
+    /// Run a single iteration of the loop: wait until an event source is ready or `timeout`
+    /// elapsed (`None` waits indefinitely), then dispatch the highest priority one.
+    ///
+    /// Returns `false` if nothing was dispatched.
+    #[inline]
+    pub fn run(&self, timeout: Option<Duration>) -> Result<bool> {
+        let usec = timeout.map_or(!0, ::duration_usec);
+        Ok(sd_try!(ffi::event::sd_event_run(self.as_ptr(), usec)) > 0)
+    }
+
+    /// Run the loop until `exit()` is called, returning the exit code passed to it
+    /// (`sd_event_loop()`).
+    #[inline]
+    pub fn run_loop(&self) -> Result<c_int> {
+        Ok(sd_try!(ffi::event::sd_event_loop(self.as_ptr())))
+    }
+
+    /// Ask the loop to exit with `code`, once the current iteration is done
+    #[inline]
+    pub fn exit(&self, code: c_int) -> Result<()> {
+        sd_try!(ffi::event::sd_event_exit(self.as_ptr(), code));
+        Ok(())
+    }
+
+    /// The code passed to `exit()`, if it was called
+    pub fn exit_code(&self) -> Result<Option<c_int>> {
+        let mut code = 0;
+        match unsafe { ffi::event::sd_event_get_exit_code(self.as_ptr(), &mut code) } {
+            r if r == -libc::ENODATA => Ok(None),
+            r => {
+                try!(::ffi_result(r));
+                Ok(Some(code))
+            }
+        }
+    }
+
+    /// The time on `clock` at which the current iteration of the loop started (or the current
+    /// time, if the loop hasn't run yet), as the time since the epoch of `clock`.
+    ///
+    /// Using this rather than the current time keeps timers of the same iteration consistent.
+    #[inline]
+    pub fn now(&self, clock: Clock) -> Result<Duration> {
+        let mut usec = 0;
+        sd_try!(ffi::event::sd_event_now(self.as_ptr(), clock.raw(), &mut usec));
+        Ok(Duration::from_micros(usec))
+    }
+
+    /// Call `callback` whenever `fd` is ready for any of `events` (`ERR` and `HUP` are always
+    /// included), with the fd and the events that occurred.
+    ///
+    /// `fd` remains owned by the caller, and must stay open as long as the source exists.
+    pub fn add_io<F>(&self, fd: RawFd, events: IoEvents, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef, RawFd, IoEvents) -> Result<()> + 'static
+    {
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_add_io(self.as_ptr(),
+                                            &mut raw,
+                                            fd,
+                                            events.bits(),
+                                            Some(raw_io_handler::<F>),
+                                            u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+
+    /// Call `callback` once the time on `clock` reached `at` (the time since the epoch of
+    /// `clock`, see `now()`), with the time the source was set for.
+    ///
+    /// sd-event may delay the callback by up to `accuracy` to wake up less often, by default
+    /// (when `accuracy` is zero) up to 250ms.
+    ///
+    /// Timer sources are `Oneshot`: to fire again, ie: periodically, the callback can move the
+    /// time with `SourceRef::set_time()` and turn the source on again.
+    pub fn add_time<F>(&self,
+                       clock: Clock,
+                       at: Duration,
+                       accuracy: Duration,
+                       callback: F)
+                       -> Result<Source>
+        where F: FnMut(&SourceRef, Duration) -> Result<()> + 'static
+    {
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_add_time(self.as_ptr(),
+                                              &mut raw,
+                                              clock.raw(),
+                                              ::duration_usec(at),
+                                              ::duration_usec(accuracy),
+                                              Some(raw_time_handler::<F>),
+                                              u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+
+    /// Call `callback` once `after` elapsed, measured on the `Monotonic` clock from the start of
+    /// the current iteration. See `add_time()`.
+    pub fn add_timeout<F>(&self, after: Duration, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef, Duration) -> Result<()> + 'static
+    {
+        let now = try!(self.now(Clock::Monotonic));
+        /* too far in the future to represent: never */
+        let at = now.checked_add(after).unwrap_or(Duration::from_secs(!0));
+        self.add_time(Clock::Monotonic, at, Duration::from_secs(0), callback)
+    }
+
+    /// Call `callback` once the wall-clock time reached `at`, following changes of the system
+    /// clock. See `add_time()`, the callback is passed the time since the Unix epoch.
+    pub fn add_deadline<F>(&self, at: SystemTime, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef, Duration) -> Result<()> + 'static
+    {
+        /* a deadline before the epoch has passed all the same */
+        let at = at.duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+        self.add_time(Clock::Realtime, at, Duration::from_secs(0), callback)
+    }
+
+    /// Call `callback` whenever the signal `sig` (ie: `libc::SIGTERM`) is received, with the
+    /// details of its delivery. Only one source may exist per signal and loop.
+    ///
+    /// sd-event receives signals through a `signalfd`, which requires them to be blocked: this
+    /// blocks `sig` in the calling thread, and leaves it blocked once the source is gone. Signal
+    /// masks are per-thread and inherited, so add signal sources before spawning any threads,
+    /// otherwise those threads may receive the signal instead (and be killed by it).
+    pub fn add_signal<F>(&self, sig: c_int, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef, &libc::signalfd_siginfo) -> Result<()> + 'static
+    {
+        try!(block_signal(sig));
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_add_signal(self.as_ptr(),
+                                                &mut raw,
+                                                sig,
+                                                Some(raw_signal_handler::<F>),
+                                                u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+
+    /// Call `callback` whenever the child process `pid` changes state, with the details of the
+    /// change as returned by `waitid()`.
+    ///
+    /// `options` selects the changes to watch for, any of `libc::WEXITED`, `libc::WSTOPPED` and
+    /// `libc::WCONTINUED`. Unless `libc::WNOWAIT` is included too, sd-event reaps the process
+    /// once it exited, so don't wait for it elsewhere (ie: with `std::process::Child::wait()`).
+    ///
+    /// Like `add_signal()`, this blocks `SIGCHLD` in the calling thread.
+    pub fn add_child<F>(&self, pid: libc::pid_t, options: c_int, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef, &libc::siginfo_t) -> Result<()> + 'static
+    {
+        try!(block_signal(libc::SIGCHLD));
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_add_child(self.as_ptr(),
+                                               &mut raw,
+                                               pid,
+                                               options,
+                                               Some(raw_child_handler::<F>),
+                                               u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+
+    /// Like `add_child()`, with the child referred to by a pidfd (as returned by `pidfd_open()`
+    /// or `clone3()`), which avoids races with the reuse of its pid.
+    ///
+    /// `pidfd` remains owned by the caller, and must stay open as long as the source exists.
+    pub fn add_child_pidfd<F>(&self, pidfd: RawFd, options: c_int, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef, &libc::siginfo_t) -> Result<()> + 'static
+    {
+        try!(block_signal(libc::SIGCHLD));
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_add_child_pidfd(self.as_ptr(),
+                                                     &mut raw,
+                                                     pidfd,
+                                                     options,
+                                                     Some(raw_child_handler::<F>),
+                                                     u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+
+    /// Call `callback` whenever one of the inotify events in `mask` (ie: `libc::IN_CLOSE_WRITE |
+    /// libc::IN_MOVED_TO`) happens to `path`, with the event and, for events on files in a
+    /// watched directory, the name of that file.
+    ///
+    /// Watching the directory of a configuration file, rather than the file itself, also catches
+    /// it being replaced (as editors and package managers do).
+    pub fn add_inotify<P, F>(&self, path: P, mask: u32, callback: F) -> Result<Source>
+        where P: AsRef<Path>,
+              F: FnMut(&SourceRef, u32, Option<&Path>) -> Result<()> + 'static
+    {
+        let path = try!(CString::new(path.as_ref().as_os_str().as_bytes()));
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(ffi::event::sd_event_add_inotify(self.as_ptr(),
+                                                 &mut raw,
+                                                 path.as_ptr(),
+                                                 mask,
+                                                 Some(raw_inotify_handler::<F>),
+                                                 u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+
+    /// Call `callback` in the next iteration of the loop, without waiting for anything. Useful
+    /// to postpone work until the current callback returned.
+    ///
+    /// Defer sources are `Oneshot`, turn the source on again to be called again.
+    pub fn add_defer<F>(&self, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef) -> Result<()> + 'static
+    {
+        self.add_simple(ffi::event::sd_event_add_defer, callback)
+    }
+
+    /// Call `callback` after any other (non-post) source was dispatched, before the loop waits
+    /// again. Useful for work batching up the effects of other callbacks, ie: flushing buffers.
+    ///
+    /// Post sources are `On`: the callback is called in every iteration something else happened.
+    pub fn add_post<F>(&self, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef) -> Result<()> + 'static
+    {
+        self.add_simple(ffi::event::sd_event_add_post, callback)
+    }
+
+    /// Call `callback` once the loop is exiting, after `exit()` was called. Exit sources are
+    /// dispatched in order of their priority, before `run_loop()` returns. Useful for cleanup.
+    pub fn add_exit<F>(&self, callback: F) -> Result<Source>
+        where F: FnMut(&SourceRef) -> Result<()> + 'static
+    {
+        self.add_simple(ffi::event::sd_event_add_exit, callback)
+    }
+
+    fn add_simple<F>(&self,
+                     add: unsafe extern "C" fn(*mut ffi::event::sd_event,
+                                               *mut *mut ffi::event::sd_event_source,
+                                               ffi::event::sd_event_handler_t,
+                                               *mut c_void)
+                                               -> c_int,
+                     callback: F)
+                     -> Result<Source>
+        where F: FnMut(&SourceRef) -> Result<()> + 'static
+    {
+        let mut b = Box::new(callback);
+        let u: *mut F = &mut *b;
+        let mut raw = ptr::null_mut();
+        sd_try!(add(self.as_ptr(), &mut raw, Some(raw_handler::<F>), u as *mut c_void));
+        Ok(unsafe { Source::take_ptr(raw, b) })
+    }
+}
+
+/// An event source added to an event loop.
+///
+/// The `Source` owns the callback passed when it was added. Dropping it removes the source from
+/// the loop and then frees the callback.
+pub struct Source {
+    raw: *mut ffi::event::sd_event_source,
+    _userdata: Box<Any>,
+}
+
+impl Source {
+    /// Construct a Source, taking over an already existing reference count on the provided
+    /// pointer
+    ///
+    /// Unsafety:
+    ///
+    ///  - `userdata` must contain everything the callbacks registered via `raw` refer to.
+    #[inline]
+    unsafe fn take_ptr(raw: *mut ffi::event::sd_event_source, userdata: Box<Any>) -> Source {
+        Source { raw: raw, _userdata: userdata }
+    }
+}
+
+impl Deref for Source {
+    type Target = SourceRef;
+    #[inline]
+    fn deref(&self) -> &SourceRef {
+        unsafe { SourceRef::from_ptr(self.raw) }
+    }
+}
+
+impl Drop for Source {
+    #[inline]
+    fn drop(&mut self) {
+        /* unref first so sd-event can no longer call into `_userdata` once it is freed */
+        unsafe { ffi::event::sd_event_source_unref(self.raw) };
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Source").finish()
+    }
+}
+
+/// An event source, as borrowed from a `Source` or passed to its callback
+pub struct SourceRef {
+    _inner: ffi::event::sd_event_source,
+}
+
+impl fmt::Debug for SourceRef {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SourceRef").finish()
+    }
+}
+
+impl SourceRef {
+    #[inline]
+    unsafe fn from_ptr<'a>(r: *const ffi::event::sd_event_source) -> &'a SourceRef {
+        transmute(r)
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut ffi::event::sd_event_source {
+        unsafe { transmute(self) }
+    }
+
+    /// The event loop this source belongs to
+    #[inline]
+    pub fn event(&self) -> &EventRef {
+        unsafe { EventRef::from_ptr(ffi::event::sd_event_source_get_event(self.as_ptr())) }
+    }
+
+    /// Whether the source is dispatched. Sources are `On` when created.
+    pub fn enabled(&self) -> Result<Enabled> {
+        let mut e = 0;
+        sd_try!(ffi::event::sd_event_source_get_enabled(self.as_ptr(), &mut e));
+        Ok(match e {
+            ffi::event::SD_EVENT_OFF => Enabled::Off,
+            ffi::event::SD_EVENT_ONESHOT => Enabled::Oneshot,
+            _ => Enabled::On,
+        })
+    }
+
+    /// Turn dispatching of the source on or off
+    #[inline]
+    pub fn set_enabled(&self, enabled: Enabled) -> Result<()> {
+        let e = match enabled {
+            Enabled::Off => ffi::event::SD_EVENT_OFF,
+            Enabled::On => ffi::event::SD_EVENT_ON,
+            Enabled::Oneshot => ffi::event::SD_EVENT_ONESHOT,
+        };
+        sd_try!(ffi::event::sd_event_source_set_enabled(self.as_ptr(), e));
+        Ok(())
+    }
+
+    /// The priority of the source, lower values are dispatched first
+    #[inline]
+    pub fn priority(&self) -> Result<i64> {
+        let mut p = 0;
+        sd_try!(ffi::event::sd_event_source_get_priority(self.as_ptr(), &mut p));
+        Ok(p)
+    }
+
+    /// Set the priority of the source, ie: to `PRIORITY_IMPORTANT`
+    #[inline]
+    pub fn set_priority(&self, priority: i64) -> Result<()> {
+        sd_try!(ffi::event::sd_event_source_set_priority(self.as_ptr(), priority));
+        Ok(())
+    }
+
+    /// Whether the source is ready, and waiting to be dispatched
+    #[inline]
+    pub fn is_pending(&self) -> Result<bool> {
+        Ok(sd_try!(ffi::event::sd_event_source_get_pending(self.as_ptr())) > 0)
+    }
+
+    /// IO sources: the fd being watched
+    #[inline]
+    pub fn io_fd(&self) -> Result<RawFd> {
+        Ok(sd_try!(ffi::event::sd_event_source_get_io_fd(self.as_ptr())))
+    }
+
+    /// IO sources: watch `fd` instead. The fd watched before remains open.
+    #[inline]
+    pub fn set_io_fd(&self, fd: RawFd) -> Result<()> {
+        sd_try!(ffi::event::sd_event_source_set_io_fd(self.as_ptr(), fd));
+        Ok(())
+    }
+
+    /// IO sources: the events waited for
+    #[inline]
+    pub fn io_events(&self) -> Result<IoEvents> {
+        let mut e = 0;
+        sd_try!(ffi::event::sd_event_source_get_io_events(self.as_ptr(), &mut e));
+        Ok(IoEvents(e))
+    }
+
+    /// IO sources: wait for `events` instead, ie: add `OUT` while there is data to be written
+    #[inline]
+    pub fn set_io_events(&self, events: IoEvents) -> Result<()> {
+        sd_try!(ffi::event::sd_event_source_set_io_events(self.as_ptr(), events.bits()));
+        Ok(())
+    }
+
+    /// Timer sources: the time the source fires at, since the epoch of its clock
+    #[inline]
+    pub fn time(&self) -> Result<Duration> {
+        let mut usec = 0;
+        sd_try!(ffi::event::sd_event_source_get_time(self.as_ptr(), &mut usec));
+        Ok(Duration::from_micros(usec))
+    }
+
+    /// Timer sources: fire at `at` instead. This doesn't turn the source on.
+    #[inline]
+    pub fn set_time(&self, at: Duration) -> Result<()> {
+        sd_try!(ffi::event::sd_event_source_set_time(self.as_ptr(), ::duration_usec(at)));
+        Ok(())
+    }
+
+    /// Timer sources: how much the callback may be delayed
+    #[inline]
+    pub fn time_accuracy(&self) -> Result<Duration> {
+        let mut usec = 0;
+        sd_try!(ffi::event::sd_event_source_get_time_accuracy(self.as_ptr(), &mut usec));
+        Ok(Duration::from_micros(usec))
+    }
+
+    /// Timer sources: allow delaying the callback by up to `accuracy` (zero restores the default)
+    #[inline]
+    pub fn set_time_accuracy(&self, accuracy: Duration) -> Result<()> {
+        sd_try!(ffi::event::sd_event_source_set_time_accuracy(self.as_ptr(),
+                                                              ::duration_usec(accuracy)));
+        Ok(())
+    }
+
+    /// Timer sources: the clock the time is measured on
+    pub fn time_clock(&self) -> Result<Clock> {
+        let mut clock = 0;
+        sd_try!(ffi::event::sd_event_source_get_time_clock(self.as_ptr(), &mut clock));
+        Clock::from_raw(clock)
+    }
+
+    /// Signal sources: the signal received
+    #[inline]
+    pub fn signal(&self) -> Result<c_int> {
+        Ok(sd_try!(ffi::event::sd_event_source_get_signal(self.as_ptr())))
+    }
+
+    /// Child sources: the pid of the process watched
+    #[inline]
+    pub fn child_pid(&self) -> Result<libc::pid_t> {
+        let mut pid = 0;
+        sd_try!(ffi::event::sd_event_source_get_child_pid(self.as_ptr(), &mut pid));
+        Ok(pid)
+    }
+
+    /// Child sources: the pidfd of the process watched
+    #[inline]
+    pub fn child_pidfd(&self) -> Result<RawFd> {
+        Ok(sd_try!(ffi::event::sd_event_source_get_child_pidfd(self.as_ptr())))
+    }
+
+    /// Child sources: send `sig` to the process watched. Unlike `kill()` with the pid, this can't
+    /// hit another process once the child exited and its pid is reused.
+    #[inline]
+    pub fn send_child_signal(&self, sig: c_int) -> Result<()> {
+        sd_try!(ffi::event::sd_event_source_send_child_signal(self.as_ptr(),
+                                                              sig,
+                                                              ptr::null(),
+                                                              0));
+        Ok(())
+    }
+}
+
+#[test]
+fn t_event() {
+    let e = Event::new().unwrap();
+    assert_eq!(e.exit_code().unwrap(), None);
+    assert!(!e.run(Some(Duration::from_millis(1))).unwrap());
+    assert!(e.now(Clock::Monotonic).unwrap() > Duration::from_secs(0));
+
+    let e2 = e.clone();
+    e2.exit(3).unwrap();
+    assert_eq!(e.exit_code().unwrap(), Some(3));
+    assert_eq!(e.run_loop().unwrap(), 3);
+
+    let d = Event::default().unwrap();
+    assert_eq!(d.as_ptr(), Event::default().unwrap().as_ptr());
+    assert!(d.as_ptr() != e.as_ptr());
+}
+
+#[test]
+fn t_io() {
+    use std::cell::Cell;
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::rc::Rc;
+
+    let e = Event::new().unwrap();
+    let (mut a, b) = UnixStream::pair().unwrap();
+    let seen = Rc::new(Cell::new(IoEvents::NONE));
+    let s = {
+        let seen = seen.clone();
+        e.add_io(b.as_raw_fd(), IoEvents::IN, move |s, _, events| {
+            seen.set(events);
+            s.set_enabled(Enabled::Off)
+        }).unwrap()
+    };
+    assert_eq!(s.io_fd().unwrap(), b.as_raw_fd());
+    assert_eq!(s.io_events().unwrap(), IoEvents::IN);
+    assert_eq!(s.enabled().unwrap(), Enabled::On);
+    s.set_priority(PRIORITY_IMPORTANT).unwrap();
+    assert_eq!(s.priority().unwrap(), PRIORITY_IMPORTANT);
+    assert!(!e.run(Some(Duration::from_millis(1))).unwrap());
+
+    a.write_all(b"x").unwrap();
+    assert!(e.run(Some(Duration::from_secs(1))).unwrap());
+    assert!(seen.get().contains(IoEvents::IN));
+    assert_eq!(s.enabled().unwrap(), Enabled::Off);
+    assert_eq!((IoEvents::IN | IoEvents::OUT).bits(), 5);
+}
+
+#[test]
+fn t_time() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let e = Event::new().unwrap();
+    let fired = Rc::new(Cell::new(0));
+    let s = {
+        let fired = fired.clone();
+        e.add_timeout(Duration::from_millis(1), move |s, at| {
+            fired.set(fired.get() + 1);
+            if fired.get() < 2 {
+                try!(s.set_time(at + Duration::from_millis(1)));
+                try!(s.set_enabled(Enabled::Oneshot));
+            }
+            Ok(())
+        }).unwrap()
+    };
+    assert_eq!(s.time_clock().unwrap(), Clock::Monotonic);
+    assert_eq!(s.enabled().unwrap(), Enabled::Oneshot);
+    s.set_time_accuracy(Duration::from_millis(1)).unwrap();
+    assert_eq!(s.time_accuracy().unwrap(), Duration::from_millis(1));
+
+    while fired.get() < 2 {
+        assert!(e.run(Some(Duration::from_secs(1))).unwrap());
+    }
+    assert_eq!(s.enabled().unwrap(), Enabled::Off);
+
+    /* too far off to represent, never fires */
+    let _never = e.add_timeout(Duration::from_secs(!0), |s, _| s.event().exit(1)).unwrap();
+    let d = e.add_deadline(SystemTime::now(), |s, _| s.event().exit(7)).unwrap();
+    assert_eq!(d.time_clock().unwrap(), Clock::Realtime);
+    assert_eq!(e.run_loop().unwrap(), 7);
+}
+
+#[test]
+fn t_signal() {
+    let e = Event::new().unwrap();
+    let s = e.add_signal(libc::SIGUSR2, |s, si| s.event().exit(si.ssi_signo as c_int)).unwrap();
+    assert_eq!(s.signal().unwrap(), libc::SIGUSR2);
+    e.add_signal(libc::SIGUSR2, |_, _| Ok(())).err().unwrap();
+    e.add_signal(-1, |_, _| Ok(())).err().unwrap();
+
+    /* blocked in this thread by add_signal(), so this doesn't kill the test */
+    assert_eq!(unsafe { libc::raise(libc::SIGUSR2) }, 0);
+    assert_eq!(e.run_loop().unwrap(), libc::SIGUSR2);
+}
+
+#[test]
+fn t_child() {
+    use std::process::Command;
+
+    let e = Event::new().unwrap();
+    let child = Command::new("true").spawn().unwrap();
+    let s = e.add_child(child.id() as libc::pid_t, libc::WEXITED, |s, si| {
+        s.event().exit(unsafe { si.si_status() })
+    }).unwrap();
+    assert_eq!(s.child_pid().unwrap(), child.id() as libc::pid_t);
+    assert_eq!(e.run_loop().unwrap(), 0);
+
+    let e = Event::new().unwrap();
+    let child = Command::new("sleep").arg("10").spawn().unwrap();
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, child.id(), 0) } as RawFd;
+    assert!(pidfd >= 0);
+    let s = e.add_child_pidfd(pidfd, libc::WEXITED, |s, si| {
+        s.event().exit(unsafe { si.si_status() })
+    }).unwrap();
+    assert_eq!(s.child_pidfd().unwrap(), pidfd);
+    s.send_child_signal(libc::SIGKILL).unwrap();
+    assert_eq!(e.run_loop().unwrap(), libc::SIGKILL);
+    drop(s);
+    unsafe { libc::close(pidfd) };
+}
+
+#[test]
+fn t_defer_post_exit() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let e = Event::new().unwrap();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let add = |name: &'static str| {
+        let log = log.clone();
+        move |_: &SourceRef| {
+            RefCell::borrow_mut(&log).push(name);
+            Ok(())
+        }
+    };
+    let d = e.add_defer(add("defer")).unwrap();
+    let _p = e.add_post(add("post")).unwrap();
+    let _x = e.add_exit(add("exit")).unwrap();
+    assert_eq!(d.enabled().unwrap(), Enabled::Oneshot);
+
+    assert!(e.run(Some(Duration::from_secs(1))).unwrap());
+    assert!(e.run(Some(Duration::from_secs(1))).unwrap());
+    assert!(!e.run(Some(Duration::from_millis(1))).unwrap());
+    e.exit(0).unwrap();
+    assert_eq!(e.run_loop().unwrap(), 0);
+    assert_eq!(*RefCell::borrow(&log), ["defer", "post", "exit"]);
+}
+
+#[test]
+fn t_inotify() {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    let dir = ::std::env::temp_dir()
+        .join(format!("rust-systemd-t_inotify-{}", ::std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+
+    let e = Event::new().unwrap();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let _s = {
+        let seen = seen.clone();
+        e.add_inotify(&dir, libc::IN_CLOSE_WRITE, move |_, mask, name| {
+            RefCell::borrow_mut(&seen).push((mask, name.map(PathBuf::from)));
+            Ok(())
+        }).unwrap()
+    };
+    e.add_inotify("a\0b", libc::IN_CLOSE_WRITE, |_, _, _| Ok(())).err().unwrap();
+
+    fs::write(dir.join("app.conf"), "x").unwrap();
+    assert!(e.run(Some(Duration::from_secs(1))).unwrap());
+    assert_eq!(*RefCell::borrow(&seen),
+               [(libc::IN_CLOSE_WRITE, Some(PathBuf::from("app.conf")))]);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,9 +1,11 @@
 use std::mem::uninitialized;
 use std::fmt;
+use std::str::FromStr;
 use ffi;
 use std::ffi::CStr;
 use super::Result;
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Id128 {
     inner: ffi::id128::sd_id128_t,
 }
@@ -17,7 +19,45 @@ impl fmt::Display for Id128 {
     }
 }
 
+impl fmt::Debug for Id128 {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Id128({})", self)
+    }
+}
+
+/// Parse an id formatted as 32 hex digits, or as a UUID (with dashes), without going through
+/// libsystemd.
+impl FromStr for Id128 {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> ::std::result::Result<Id128, &'static str> {
+        let hex: String = if s.len() == 36 {
+            for &i in &[8, 13, 18, 23] {
+                if s.as_bytes()[i] != b'-' {
+                    return Err("invalid UUID");
+                }
+            }
+            s.chars().filter(|&c| c != '-').collect()
+        } else {
+            s.to_owned()
+        };
+        if hex.len() != 32 || !hex.bytes().all(|b| (b as char).is_ascii_hexdigit()) {
+            return Err("expected 32 hex digits");
+        }
+        let mut bytes = [0u8; 16];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        Ok(Id128::from_bytes(bytes))
+    }
+}
+
 impl Id128 {
+    pub fn from_bytes(bytes: [u8; 16]) -> Id128 {
+        Id128 { inner: ffi::id128::sd_id128_t { bytes: bytes } }
+    }
+
+
     pub fn from_cstr(s: &CStr) -> Result<Id128> {
         let mut r: Id128 = unsafe { uninitialized() };
         sd_try!(ffi::id128::sd_id128_from_string(s.as_ptr(), &mut r.inner));
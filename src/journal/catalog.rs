@@ -0,0 +1,178 @@
+/*!
+ * Message catalogs (the `.catalog` files in `/usr/lib/systemd/catalog/`)
+ *
+ * Catalogs explain journal messages carrying a `MESSAGE_ID`; `journalctl -x` shows the matching
+ * entry along with each message. A catalog file is a list of entries, each starting with a line
+ * `-- <id>` (or `-- <id> <language>` for translations) which must follow an empty line (or the
+ * start of the file). It is followed by header fields (`Subject:`, `Defined-By:`, `Support:`,
+ * `Documentation:`), an empty line and the body. Both may refer to fields of the message as
+ * `@FIELD@`. Lines starting with `#` are comments.
+ *
+ * journald compiles the installed catalogs with `journalctl --update-catalog`.
+ */
+
+use std::fmt;
+use std::io;
+use id128::Id128;
+use super::super::Result;
+
+/// A single entry of a catalog.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogEntry {
+    /// The `MESSAGE_ID` the entry describes
+    pub id: Id128,
+    /// The language of the entry (ie: `de` or `pt_BR`), `None` for the default (English) one
+    pub language: Option<String>,
+    /// Header fields as (name, value) pairs, in order
+    pub headers: Vec<(String, String)>,
+    /// The text following the headers, without trailing newlines
+    pub body: String,
+}
+
+impl CatalogEntry {
+    /// An entry for `id` without any headers or text
+    pub fn new(id: Id128) -> CatalogEntry {
+        CatalogEntry { id: id, language: None, headers: Vec::new(), body: String::new() }
+    }
+
+    /// The value of the header `name`. Header names are case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|h| h.0.eq_ignore_ascii_case(name)).map(|h| &h.1[..])
+    }
+
+    /// The `Subject` header, a one line summary of the message
+    pub fn subject(&self) -> Option<&str> {
+        self.header("Subject")
+    }
+}
+
+/// Formats the entry as it appears in a catalog file. Body lines starting with `#` would be read
+/// back as comments.
+impl fmt::Display for CatalogEntry {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, "-- {}", self.id));
+        if let Some(ref l) = self.language {
+            try!(write!(fmt, " {}", l));
+        }
+        try!(writeln!(fmt));
+        for &(ref name, ref value) in &self.headers {
+            try!(writeln!(fmt, "{}: {}", name, value));
+        }
+        if !self.body.is_empty() {
+            try!(write!(fmt, "\n{}\n", self.body));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(line: usize, what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("catalog line {}: {}", line, what))
+}
+
+/// Whether `line` has the shape of the line starting an entry. Other lines starting with `--` are
+/// part of the body.
+fn is_start(line: &str) -> bool {
+    line.starts_with("-- ") && line.len() >= 35 &&
+    line.as_bytes().get(35).map_or(true, |&b| b == b' ')
+}
+
+/// Parse the `-- <id> [<language>]` line starting an entry
+fn parse_start(line: &str) -> ::std::result::Result<CatalogEntry, &'static str> {
+    let mut words = line[3..].split(' ');
+    let id = try!(words.next().unwrap().parse());
+    let language = match words.next() {
+        Some(l) if l.is_empty() || l.contains(char::is_whitespace) => {
+            return Err("invalid language")
+        }
+        l => l.map(String::from),
+    };
+    if words.next().is_some() {
+        return Err("unexpected text after the language");
+    }
+    Ok(CatalogEntry { language: language, ..CatalogEntry::new(id) })
+}
+
+fn finish(mut e: CatalogEntry, entries: &mut Vec<CatalogEntry>) {
+    let len = e.body.trim_end_matches('\n').len();
+    e.body.truncate(len);
+    entries.push(e);
+}
+
+/// Parse the contents of a catalog file
+pub fn parse(s: &str) -> Result<Vec<CatalogEntry>> {
+    let mut entries = Vec::new();
+    let mut entry: Option<CatalogEntry> = None;
+    let mut in_body = false;
+    let mut after_empty = true;
+
+    for (n, line) in s.lines().enumerate() {
+        let n = n + 1;
+        if line.starts_with('#') {
+            continue;
+        }
+        if after_empty && is_start(line) {
+            if let Some(e) = entry.take() {
+                finish(e, &mut entries);
+            }
+            entry = Some(try!(parse_start(line).map_err(|e| invalid(n, e))));
+            in_body = false;
+            after_empty = false;
+            continue;
+        }
+        after_empty = line.is_empty();
+
+        let e = match entry {
+            Some(ref mut e) => e,
+            None if line.is_empty() => continue,
+            None => return Err(invalid(n, "text before the first entry")),
+        };
+        if in_body {
+            e.body.push_str(line);
+            e.body.push('\n');
+        } else if line.is_empty() {
+            in_body = true;
+        } else {
+            let i = try!(line.find(':').ok_or_else(|| invalid(n, "expected a header field")));
+            e.headers.push((line[..i].to_owned(), line[i + 1..].trim().to_owned()));
+        }
+    }
+    if let Some(e) = entry {
+        finish(e, &mut entries);
+    }
+    Ok(entries)
+}
+
+/// Format `entries` as the contents of a catalog file, as read by `parse()`
+pub fn format(entries: &[CatalogEntry]) -> String {
+    entries.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+#[test]
+fn t_catalog() {
+    let s = "# comment\n\
+             \n\
+             -- fc2e22bc6ee647b6b90729ab34a250b1\n\
+             Subject: Process @COREDUMP_PID@ dumped core\n\
+             Defined-By: systemd\n\
+             \n\
+             Process @COREDUMP_PID@ crashed.\n\
+             \n\
+             -- not an entry\n\
+             \n\
+             -- fc2e22bc6ee647b6b90729ab34a250b1 de\n\
+             Subject: Prozess @COREDUMP_PID@ abgestürzt\n";
+    let c = parse(s).unwrap();
+    assert_eq!(c.len(), 2);
+    assert_eq!(c[0].id.to_string(), "fc2e22bc6ee647b6b90729ab34a250b1");
+    assert_eq!(c[0].subject(), Some("Process @COREDUMP_PID@ dumped core"));
+    assert_eq!(c[0].header("defined-by"), Some("systemd"));
+    assert_eq!(c[0].body, "Process @COREDUMP_PID@ crashed.\n\n-- not an entry");
+    assert_eq!(c[1].id, c[0].id);
+    assert_eq!(c[1].language, Some("de".to_owned()));
+    assert_eq!(c[1].body, "");
+    assert_eq!(parse(&format(&c)).unwrap(), c);
+
+    parse("Subject: x\n").err().unwrap();
+    parse("-- fc2e22bc6ee647b6b90729ab34a250bx\n").err().unwrap();
+    parse("-- fc2e22bc6ee647b6b90729ab34a250b1\nno header\n").err().unwrap();
+}
@@ -0,0 +1,495 @@
+/*!
+ * Offline reader for journal files
+ *
+ * This parses the on-disk format described in systemd's `journal-def.h` directly, without going
+ * through libsystemd. That allows inspecting files copied off of other machines and surfaces
+ * details that sd-journal keeps to itself (file state, sealing, payload compression).
+ *
+ * All integers in journal files are little endian. Objects are 8 byte aligned and begin with a
+ * common 16 byte header (type, flags, reserved, size).
+ */
+
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::io::ErrorKind::InvalidData;
+use std::path::Path;
+use super::super::Result;
+
+const SIGNATURE: &'static [u8; 8] = b"LPKSHHRH";
+
+/* Size of the header as of systemd 187, everything we read lives within it */
+const HEADER_SIZE_MIN: u64 = 208;
+const OBJECT_HEADER_SIZE: u64 = 16;
+
+/// `compatible_flags`: the file contains forward-secure sealing tags
+pub const HEADER_COMPATIBLE_SEALED: u32 = 1 << 0;
+
+/// `incompatible_flags`: objects use the compact layout (systemd 252+)
+pub const HEADER_INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_TAG: u8 = 7;
+
+const OBJECT_COMPRESSED_XZ: u8 = 1 << 0;
+const OBJECT_COMPRESSED_LZ4: u8 = 1 << 1;
+const OBJECT_COMPRESSED_ZSTD: u8 = 1 << 2;
+
+/* header, hash, next_hash_offset, next_field_offset, entry_offset, entry_array_offset, n_entries */
+const DATA_PAYLOAD_OFFSET: u64 = 64;
+/* compact files add tail_entry_array_offset and tail_entry_array_n_entries */
+const DATA_PAYLOAD_OFFSET_COMPACT: u64 = 72;
+
+fn le32(b: &[u8]) -> u32 {
+    (0..4).fold(0, |v, i| v | (b[i] as u32) << (8 * i))
+}
+
+fn le64(b: &[u8]) -> u64 {
+    (0..8).fold(0, |v, i| v | (b[i] as u64) << (8 * i))
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(InvalidData, msg)
+}
+
+/// The state a journal file was left in by its last writer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileState {
+    /// Cleanly closed
+    Offline,
+    /// Currently open for writing (or the writer crashed)
+    Online,
+    /// Rotated, will not be written to again
+    Archived,
+    /// A state this parser does not know about
+    Unknown(u8),
+}
+
+/// Fields from the journal file header.
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub compatible_flags: u32,
+    pub incompatible_flags: u32,
+    pub state: FileState,
+    pub header_size: u64,
+    pub arena_size: u64,
+    pub tail_object_offset: u64,
+    pub n_objects: u64,
+    pub n_entries: u64,
+    pub head_entry_realtime: u64,
+    pub tail_entry_realtime: u64,
+}
+
+impl Header {
+    fn parse(b: &[u8]) -> Result<Header> {
+        if b.len() < HEADER_SIZE_MIN as usize || &b[0..8] != SIGNATURE {
+            return Err(invalid("not a journal file (bad signature)"));
+        }
+
+        let h = Header {
+            compatible_flags: le32(&b[8..]),
+            incompatible_flags: le32(&b[12..]),
+            state: match b[16] {
+                0 => FileState::Offline,
+                1 => FileState::Online,
+                2 => FileState::Archived,
+                s => FileState::Unknown(s),
+            },
+            header_size: le64(&b[88..]),
+            arena_size: le64(&b[96..]),
+            tail_object_offset: le64(&b[136..]),
+            n_objects: le64(&b[144..]),
+            n_entries: le64(&b[152..]),
+            head_entry_realtime: le64(&b[184..]),
+            tail_entry_realtime: le64(&b[192..]),
+        };
+
+        if h.header_size < HEADER_SIZE_MIN {
+            return Err(invalid("journal header too small"));
+        }
+
+        Ok(h)
+    }
+
+    /// Whether the writer of this file sealed it using forward-secure sealing (`Seal=yes`).
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        self.compatible_flags & HEADER_COMPATIBLE_SEALED != 0
+    }
+}
+
+/// How the payload of a data object is compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Xz,
+    /// LZ4 block format, preceded by the uncompressed size as a little endian u64
+    Lz4,
+    Zstd,
+}
+
+/// A data object, holding a single `FIELD=value` pair that entries refer to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataObject {
+    /// Offset of the object within the file
+    pub offset: u64,
+    /// How `payload` is compressed, if at all
+    pub compression: Option<Compression>,
+    /// Number of entries referring to this object
+    pub n_entries: u64,
+    /// The payload exactly as stored in the file, still compressed if `compression` is set.
+    ///
+    /// Copying this into another journal file (along with `compression`) avoids a
+    /// decompress/recompress cycle. This parser does not decompress payloads itself.
+    pub payload: Vec<u8>,
+}
+
+impl DataObject {
+    /// The `FIELD=value` pair, if the payload is not compressed
+    pub fn data(&self) -> Option<&[u8]> {
+        match self.compression {
+            None => Some(&self.payload),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Tamper-evidence information gathered from the TAG objects of a sealed file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sealing {
+    /// Number of TAG objects in the file
+    pub n_tags: u64,
+    /// FSS epoch of the most recent tag
+    pub last_epoch: Option<u64>,
+    /// Realtime timestamp (usec) of the newest entry covered by a tag. Entries written after this
+    /// point are not (yet) protected by a seal.
+    pub last_sealed_realtime: Option<u64>,
+}
+
+/// A journal file opened for offline inspection.
+pub struct JournalFile<R> {
+    inner: R,
+    header: Header,
+    /* end of the arena, no object may extend past this */
+    end: u64,
+}
+
+impl JournalFile<File> {
+    /// Open the journal file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<JournalFile<File>> {
+        JournalFile::from_reader(try!(File::open(path)))
+    }
+}
+
+impl<R: Read + Seek> JournalFile<R> {
+    /// Parse the header of the journal file available via `inner`.
+    ///
+    /// Objects are only read from the arena described by the header, and only as far as `inner`
+    /// actually extends: anything pointing outside of that is reported as `InvalidData`.
+    pub fn from_reader(mut inner: R) -> Result<JournalFile<R>> {
+        let mut b = [0u8; HEADER_SIZE_MIN as usize];
+        try!(inner.seek(SeekFrom::Start(0)));
+        try!(inner.read_exact(&mut b));
+        let header = try!(Header::parse(&b));
+
+        let len = try!(inner.seek(SeekFrom::End(0)));
+        if header.header_size > len {
+            return Err(invalid("journal header larger than the file"));
+        }
+        let end = match header.header_size.checked_add(header.arena_size) {
+            Some(end) => cmp::min(end, len),
+            None => return Err(invalid("journal arena size out of range")),
+        };
+        Ok(JournalFile { inner: inner, header: header, end: end })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Read the object header at `offset`, returning its type and size.
+    ///
+    /// The object is checked to lie within the arena, so reads within `size` bytes of `offset`
+    /// need no further checks.
+    fn object_at(&mut self, offset: u64) -> Result<(u8, u64)> {
+        if offset < self.header.header_size || offset % 8 != 0 ||
+           offset > self.end.saturating_sub(OBJECT_HEADER_SIZE) {
+            return Err(invalid("journal object offset out of range"));
+        }
+        let mut b = [0u8; OBJECT_HEADER_SIZE as usize];
+        try!(self.inner.seek(SeekFrom::Start(offset)));
+        try!(self.inner.read_exact(&mut b));
+        let size = le64(&b[8..]);
+        if size < OBJECT_HEADER_SIZE {
+            return Err(invalid("journal object smaller than its header"));
+        }
+        if size > self.end - offset {
+            return Err(invalid("journal object extends past the end of the file"));
+        }
+        Ok((b[0], size))
+    }
+
+    /// Read the little endian u64 `field` bytes into the object of `size` bytes at `offset`.
+    fn object_u64(&mut self, offset: u64, size: u64, field: u64) -> Result<u64> {
+        if size < field + 8 {
+            return Err(invalid("journal object too small"));
+        }
+        let mut b = [0u8; 8];
+        try!(self.inner.seek(SeekFrom::Start(offset + field)));
+        try!(self.inner.read_exact(&mut b));
+        Ok(le64(&b))
+    }
+
+    /// Read the data object at `offset`, leaving its payload as is.
+    pub fn data_object(&mut self, offset: u64) -> Result<DataObject> {
+        let (typ, size) = try!(self.object_at(offset));
+        if typ != OBJECT_DATA {
+            return Err(invalid("not a data object"));
+        }
+        self.read_data_object(offset, size)
+    }
+
+    fn read_data_object(&mut self, offset: u64, size: u64) -> Result<DataObject> {
+        let start = if self.header.incompatible_flags & HEADER_INCOMPATIBLE_COMPACT != 0 {
+            DATA_PAYLOAD_OFFSET_COMPACT
+        } else {
+            DATA_PAYLOAD_OFFSET
+        };
+        if size < start {
+            return Err(invalid("data object too small"));
+        }
+
+        let mut b = [0u8; DATA_PAYLOAD_OFFSET as usize];
+        try!(self.inner.seek(SeekFrom::Start(offset)));
+        try!(self.inner.read_exact(&mut b));
+
+        let compression = match b[1] & (OBJECT_COMPRESSED_XZ | OBJECT_COMPRESSED_LZ4 |
+                                         OBJECT_COMPRESSED_ZSTD) {
+            0 => None,
+            OBJECT_COMPRESSED_XZ => Some(Compression::Xz),
+            OBJECT_COMPRESSED_LZ4 => Some(Compression::Lz4),
+            OBJECT_COMPRESSED_ZSTD => Some(Compression::Zstd),
+            _ => return Err(invalid("data object with conflicting compression flags")),
+        };
+
+        let mut payload = vec![0u8; (size - start) as usize];
+        try!(self.inner.seek(SeekFrom::Start(offset + start)));
+        try!(self.inner.read_exact(&mut payload));
+
+        Ok(DataObject {
+            offset: offset,
+            compression: compression,
+            n_entries: le64(&b[56..]),
+            payload: payload,
+        })
+    }
+
+    /// Read every data object in the file, in file order.
+    pub fn data_objects(&mut self) -> Result<Vec<DataObject>> {
+        let mut v = Vec::new();
+        try!(self.walk_objects(|f, offset, typ, size| {
+            if typ == OBJECT_DATA {
+                v.push(try!(f.read_data_object(offset, size)));
+            }
+            Ok(())
+        }));
+        Ok(v)
+    }
+
+    /// Walk every object in the file in order, calling `f` with each one's offset, type and size.
+    fn walk_objects<F: FnMut(&mut Self, u64, u8, u64) -> Result<()>>(&mut self, mut f: F)
+        -> Result<()>
+    {
+        let mut offset = self.header.header_size;
+        let tail = self.header.tail_object_offset;
+        while tail != 0 && offset <= tail {
+            let (typ, size) = try!(self.object_at(offset));
+            try!(f(self, offset, typ, size));
+            /* object_at() checked that the object ends within the arena */
+            offset += size;
+            offset = match offset.checked_add(7) {
+                Some(o) => o & !7,
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    /// Collect sealing information from the file. Returns `None` if the file is not sealed.
+    ///
+    /// This reads every object header in the file.
+    pub fn sealing(&mut self) -> Result<Option<Sealing>> {
+        if !self.header.is_sealed() {
+            return Ok(None);
+        }
+
+        let mut s = Sealing::default();
+        let mut last_entry_realtime = None;
+        try!(self.walk_objects(|f, offset, typ, size| {
+            match typ {
+                OBJECT_ENTRY => {
+                    /* header, seqnum, realtime */
+                    last_entry_realtime = Some(try!(f.object_u64(offset, size, 24)));
+                }
+                OBJECT_TAG => {
+                    /* header, seqnum, epoch */
+                    s.n_tags += 1;
+                    s.last_epoch = Some(try!(f.object_u64(offset, size, 24)));
+                    s.last_sealed_realtime = last_entry_realtime;
+                }
+                _ => {}
+            }
+            Ok(())
+        }));
+        Ok(Some(s))
+    }
+}
+
+#[cfg(test)]
+fn t_object(typ: u8, body: &[u64]) -> Vec<u8> {
+    let mut o = vec![typ, 0, 0, 0, 0, 0, 0, 0];
+    let size = 16 + 8 * body.len() as u64;
+    for v in [size].iter().chain(body.iter()) {
+        o.extend((0..8).map(|i| (v >> (8 * i)) as u8));
+    }
+    o
+}
+
+#[cfg(test)]
+fn t_data(flags: u8, n_entries: u64, payload: &[u8]) -> Vec<u8> {
+    let mut o = t_object(OBJECT_DATA, &[0, 0, 0, 0, 0, n_entries]);
+    o[1] = flags;
+    o.extend(payload);
+    let size = o.len() as u64;
+    for i in 0..8 {
+        o[8 + i] = (size >> (8 * i)) as u8;
+    }
+    while o.len() % 8 != 0 {
+        o.push(0);
+    }
+    o
+}
+
+#[cfg(test)]
+fn t_file(compatible_flags: u32, objects: &[Vec<u8>]) -> Vec<u8> {
+    let mut f = vec![0u8; HEADER_SIZE_MIN as usize];
+    f[0..8].copy_from_slice(SIGNATURE);
+    f[8] = compatible_flags as u8;
+    f[16] = 2;
+    f[88] = HEADER_SIZE_MIN as u8;
+    let mut tail = 0;
+    for o in objects {
+        tail = f.len() as u64;
+        f.extend(o);
+    }
+    let arena_size = f.len() as u64 - HEADER_SIZE_MIN;
+    for i in 0..8 {
+        f[96 + i] = (arena_size >> (8 * i)) as u8;
+        f[136 + i] = (tail >> (8 * i)) as u8;
+    }
+    f
+}
+
+#[test]
+fn t_sealing() {
+    use std::io::Cursor;
+    let objects = [t_object(OBJECT_ENTRY, &[1, 100]),
+                   t_object(OBJECT_TAG, &[1, 7, 0, 0, 0, 0]),
+                   t_object(OBJECT_ENTRY, &[2, 200])];
+
+    let mut j = JournalFile::from_reader(Cursor::new(t_file(HEADER_COMPATIBLE_SEALED, &objects)))
+        .unwrap();
+    assert_eq!(j.header().state, FileState::Archived);
+    assert_eq!(j.sealing().unwrap(),
+               Some(Sealing { n_tags: 1, last_epoch: Some(7), last_sealed_realtime: Some(100) }));
+
+    let mut j = JournalFile::from_reader(Cursor::new(t_file(0, &objects))).unwrap();
+    assert_eq!(j.sealing().unwrap(), None);
+
+    JournalFile::from_reader(Cursor::new(vec![0u8; 300])).err().unwrap();
+}
+
+#[test]
+fn t_data_objects() {
+    use std::io::Cursor;
+    let objects = [t_data(0, 2, b"MESSAGE=hi"),
+                   t_object(OBJECT_ENTRY, &[1, 100]),
+                   t_data(OBJECT_COMPRESSED_ZSTD, 1, b"\x28\xb5\x2f\xfd")];
+
+    let mut j = JournalFile::from_reader(Cursor::new(t_file(0, &objects))).unwrap();
+    let d = j.data_objects().unwrap();
+    assert_eq!(d.len(), 2);
+    assert_eq!(d[0].offset, HEADER_SIZE_MIN);
+    assert_eq!(d[0].compression, None);
+    assert_eq!(d[0].n_entries, 2);
+    assert_eq!(d[0].data(), Some(&b"MESSAGE=hi"[..]));
+    assert_eq!(d[1].compression, Some(Compression::Zstd));
+    assert_eq!(d[1].payload, b"\x28\xb5\x2f\xfd");
+    assert_eq!(d[1].data(), None);
+
+    assert_eq!(j.data_object(d[1].offset).unwrap(), d[1]);
+    j.data_object(d[1].offset - 32).err().unwrap();
+}
+
+#[test]
+fn t_corrupt() {
+    use std::io::Cursor;
+    fn set(f: &mut Vec<u8>, at: usize, v: u64) {
+        for i in 0..8 {
+            f[at + i] = (v >> (8 * i)) as u8;
+        }
+    }
+    fn check<T: ::std::fmt::Debug>(r: Result<T>) {
+        assert_eq!(r.unwrap_err().kind(), InvalidData);
+    }
+    let objects = [t_data(0, 1, b"MESSAGE=hi"), t_object(OBJECT_ENTRY, &[1, 100])];
+    let good = t_file(HEADER_COMPATIBLE_SEALED, &objects);
+    let entry = HEADER_SIZE_MIN as usize + objects[0].len();
+
+    /* truncated, the last object now extends past the end */
+    let mut f = good.clone();
+    f.truncate(good.len() - 8);
+    let mut j = JournalFile::from_reader(Cursor::new(f)).unwrap();
+    check(j.sealing());
+    check(j.data_objects());
+    assert_eq!(j.data_object(HEADER_SIZE_MIN).unwrap().data(), Some(&b"MESSAGE=hi"[..]));
+
+    /* arena smaller than the objects, larger than the file, or too large to add to the header */
+    let mut f = good.clone();
+    set(&mut f, 96, entry as u64 - HEADER_SIZE_MIN);
+    check(JournalFile::from_reader(Cursor::new(f)).unwrap().sealing());
+    let mut f = good.clone();
+    set(&mut f, 96, 1 << 40);
+    JournalFile::from_reader(Cursor::new(f)).unwrap().sealing().unwrap();
+    let mut f = good.clone();
+    set(&mut f, 96, !0);
+    check(JournalFile::from_reader(Cursor::new(f)).map(|_| ()));
+    let mut f = good.clone();
+    set(&mut f, 88, 1 << 40);
+    check(JournalFile::from_reader(Cursor::new(f)).map(|_| ()));
+
+    /* object sizes which would overflow, or run past the arena */
+    for &size in &[!0u64, !0 - 6, 1 << 40, 17] {
+        let mut f = good.clone();
+        set(&mut f, entry + 8, size);
+        let mut j = JournalFile::from_reader(Cursor::new(f)).unwrap();
+        check(j.sealing());
+    }
+    let mut f = good.clone();
+    set(&mut f, HEADER_SIZE_MIN as usize + 8, 24);
+    let mut j = JournalFile::from_reader(Cursor::new(f)).unwrap();
+    check(j.data_objects());
+    check(j.data_object(HEADER_SIZE_MIN));
+
+    /* offsets outside of the arena, or not aligned */
+    let mut j = JournalFile::from_reader(Cursor::new(good.clone())).unwrap();
+    check(j.data_object(0));
+    check(j.data_object(HEADER_SIZE_MIN + 4));
+    check(j.data_object(!0 - 7));
+    check(j.data_object(good.len() as u64));
+    let mut f = good.clone();
+    set(&mut f, 136, !0 - 7);
+    let mut j = JournalFile::from_reader(Cursor::new(f)).unwrap();
+    check(j.data_objects());
+}
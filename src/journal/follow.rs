@@ -0,0 +1,161 @@
+/*!
+ * Following a `Journal` from an event loop
+ *
+ * A journal reader learns about new entries through an fd (usually inotify), which has to be
+ * polled for the events `Journal::events()` asks for. Once it is ready (or `Journal::timeout()`
+ * passed, which is used for journal files that can't be watched), `Journal::process()` has to be
+ * called, after which new entries can be read.
+ *
+ * `Follower` takes care of this, and calls a callback for each new entry. It can be driven by a
+ * hand-written `poll()` loop (using `fd()`, `events()` and `timeout()`, then calling
+ * `process()`), or added to an sd-event loop with `attach()`.
+ */
+
+use libc::{self, c_short};
+use std::cell::RefCell;
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+use std::time::Duration;
+use event::{Clock, Enabled, EventRef, IoEvents, Source, SourceRef};
+use super::Journal;
+use super::super::Result;
+
+/// Calls a callback for each new entry of a `Journal`, see the module documentation.
+pub struct Follower<F> {
+    journal: Journal,
+    callback: F,
+}
+
+impl<F: FnMut(&mut Journal) -> Result<()>> Follower<F> {
+    /// Follow `journal`, calling `callback` with the journal positioned on each entry after its
+    /// current position. Seek to the tail first to only see entries written from now on.
+    pub fn new(journal: Journal, callback: F) -> Follower<F> {
+        Follower {
+            journal: journal,
+            callback: callback,
+        }
+    }
+
+    /// The fd to poll
+    #[inline]
+    pub fn fd(&self) -> Result<RawFd> {
+        self.journal.fd()
+    }
+
+    /// The `poll()` events to wait for on `fd()`
+    #[inline]
+    pub fn events(&self) -> Result<c_short> {
+        self.journal.events()
+    }
+
+    /// How long to wait for `fd()` at most before calling `process()` anyway (`None` waits
+    /// indefinitely)
+    #[inline]
+    pub fn timeout(&self) -> Result<Option<Duration>> {
+        self.journal.timeout()
+    }
+
+    /// Process the changes to the journal files, then call the callback for each new entry.
+    ///
+    /// Stops at the first error returned by the callback, the remaining entries are passed on
+    /// the next call.
+    pub fn process(&mut self) -> Result<()> {
+        try!(self.journal.process());
+        while try!(self.journal.next_record()).is_some() {
+            try!((self.callback)(&mut self.journal));
+        }
+        Ok(())
+    }
+
+    /// The journal being followed
+    #[inline]
+    pub fn journal(&mut self) -> &mut Journal {
+        &mut self.journal
+    }
+}
+
+impl<F: FnMut(&mut Journal) -> Result<()> + 'static> Follower<F> {
+    /// Follow the journal from `event`, calling the callback for new entries as they are
+    /// written, at the default priority.
+    ///
+    /// Entries already after the current position are passed in the first iteration of the loop.
+    /// The `FollowSource` returned owns the `Follower`, dropping it stops following. If the
+    /// callback returns an error, following stops as well.
+    pub fn attach(self, event: &EventRef) -> Result<FollowSource> {
+        let fd = try!(self.fd());
+        let mut events = IoEvents::NONE;
+        let e = try!(self.events());
+        if e & libc::POLLIN != 0 {
+            events |= IoEvents::IN;
+        }
+        if e & libc::POLLOUT != 0 {
+            events |= IoEvents::OUT;
+        }
+
+        let follower = Rc::new(RefCell::new(self));
+        let timer = {
+            let follower = follower.clone();
+            try!(event.add_time(Clock::Monotonic,
+                                Duration::from_secs(0),
+                                Duration::from_secs(0),
+                                move |s, _| {
+                                    let mut f = follower.borrow_mut();
+                                    try!(f.process());
+                                    f.rearm(s)
+                                }))
+        };
+        let timer = Rc::new(timer);
+        let io = {
+            let timer = Rc::downgrade(&timer);
+            try!(event.add_io(fd, events, move |_, _, _| {
+                let mut f = follower.borrow_mut();
+                try!(f.process());
+                match timer.upgrade() {
+                    Some(t) => f.rearm(&t),
+                    None => Ok(()),
+                }
+            }))
+        };
+        Ok(FollowSource {
+            _io: io,
+            _timer: timer,
+        })
+    }
+
+    /// Point the timer source `s` at the current deadline
+    fn rearm(&self, s: &SourceRef) -> Result<()> {
+        match try!(self.journal.deadline()) {
+            Some(d) => {
+                try!(s.set_time(d));
+                s.set_enabled(Enabled::Oneshot)
+            }
+            None => s.set_enabled(Enabled::Off),
+        }
+    }
+}
+
+/// The event sources of a `Follower` added to an event loop with `Follower::attach()`
+pub struct FollowSource {
+    /* dropped first, it refers to the timer */
+    _io: Source,
+    _timer: Rc<Source>,
+}
+
+#[test]
+fn t_follower() {
+    use event::Event;
+    use super::{JournalFiles, JournalSeek};
+
+    let mut j = Journal::open(JournalFiles::CurrentUser, true, true).unwrap();
+    let _ = j.seek(JournalSeek::Tail);
+    let f = Follower::new(j, |_| Ok(()));
+    assert!(f.fd().unwrap() >= 0);
+    assert!(f.events().unwrap() & libc::POLLIN != 0);
+    f.timeout().unwrap();
+
+    let e = Event::new().unwrap();
+    let s = f.attach(&e).unwrap();
+    /* the timer fires right away, to pick up entries already there */
+    assert!(e.run(Some(Duration::from_secs(1))).unwrap());
+    drop(s);
+}
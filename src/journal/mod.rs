@@ -0,0 +1,505 @@
+use libc::{self, c_char, c_int, c_short, c_void, size_t};
+use std::{io, ptr};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::unix::io::RawFd;
+use std::io::ErrorKind::InvalidData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ffi::id128::sd_id128_t;
+use ffi::journal as ffi;
+use id128::Id128;
+use super::Result;
+use mbox::MString;
+
+/// Parsing and formatting of message catalogs.
+pub mod catalog;
+/// Offline access to journal files, without going through libsystemd.
+pub mod file;
+/// Calling a callback for new entries, from sd-event or a `poll()` loop.
+pub mod follow;
+/// Parsing of the conventions journald applies to captured stdout/stderr.
+pub mod stream;
+/// Writing entries to journald, including the instances of log namespaces.
+pub mod writer;
+
+pub struct Journal {
+    j: *mut ffi::sd_journal,
+    flags: c_int,
+    // matches added so far, so `snapshot()` can replay them
+    matches: Vec<Match>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Match {
+    Data(Vec<u8>),
+    Or,
+    And,
+}
+
+/// Represents the set of journal files to read.
+pub enum JournalFiles {
+    /// The system-wide journal.
+    System,
+    /// The current user's journal.
+    CurrentUser,
+    /// Both the system-wide journal and the current user's journal.
+    All,
+}
+
+/// Seeking position in journal.
+pub enum JournalSeek {
+    Head,
+    Current,
+    Tail,
+    ClockMonotonic {
+        boot_id: Id128,
+        usec: u64,
+    },
+    ClockRealtime {
+        usec: u64,
+    },
+    Cursor {
+        cursor: String,
+    },
+}
+
+/// What changed while waiting for the journal, see `Journal::wait()` and `Journal::process()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalWaitResult {
+    /// Nothing changed (the wait timed out)
+    Nop,
+    /// New entries were appended
+    Append,
+    /// Journal files were added or removed (ie: rotated). New entries may have been added too.
+    Invalidate,
+}
+
+impl JournalWaitResult {
+    fn from_raw(r: c_int) -> JournalWaitResult {
+        match r {
+            ffi::SD_JOURNAL_NOP => JournalWaitResult::Nop,
+            ffi::SD_JOURNAL_APPEND => JournalWaitResult::Append,
+            _ => JournalWaitResult::Invalidate,
+        }
+    }
+}
+
+/// An entry read from the journal with `Journal::next_entry()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// All fields of the entry, by name. Values which aren't valid UTF-8 are converted lossily,
+    /// for fields present more than once the last value is kept.
+    pub fields: BTreeMap<String, String>,
+    realtime_usec: u64,
+    monotonic_usec: u64,
+}
+
+impl JournalEntry {
+    /// The value of the field `name`
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|v| &v[..])
+    }
+
+    /// The `MESSAGE` field, the human readable text of the entry
+    pub fn message(&self) -> Option<&str> {
+        self.field("MESSAGE")
+    }
+
+    /// The `PRIORITY` field, the syslog level from 0 (emerg) to 7 (debug)
+    pub fn priority(&self) -> Option<u8> {
+        self.field("PRIORITY").and_then(|p| p.parse().ok())
+    }
+
+    /// The `_PID` field, the process which wrote the entry
+    pub fn pid(&self) -> Option<libc::pid_t> {
+        self.field("_PID").and_then(|p| p.parse().ok())
+    }
+
+    /// The `_UID` field, the user of the process which wrote the entry
+    pub fn uid(&self) -> Option<libc::uid_t> {
+        self.field("_UID").and_then(|u| u.parse().ok())
+    }
+
+    /// The `_SYSTEMD_UNIT` field, the unit of the process which wrote the entry
+    pub fn systemd_unit(&self) -> Option<&str> {
+        self.field("_SYSTEMD_UNIT")
+    }
+
+    /// The `_BOOT_ID` field, the boot the entry was written in
+    pub fn boot_id(&self) -> Option<Id128> {
+        self.field("_BOOT_ID").and_then(|b| b.parse().ok())
+    }
+
+    /// The `MESSAGE_ID` field, identifying the kind of message (see `catalog`)
+    pub fn message_id(&self) -> Option<Id128> {
+        self.field("MESSAGE_ID").and_then(|m| m.parse().ok())
+    }
+
+    /// The wallclock time the entry was received by journald
+    pub fn realtime(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_micros(self.realtime_usec)
+    }
+
+    /// The time since the start of the boot `boot_id()` at which the entry was received by
+    /// journald
+    pub fn monotonic(&self) -> Duration {
+        Duration::from_micros(self.monotonic_usec)
+    }
+}
+
+impl Journal {
+    /// Open the systemd journal for reading.
+    ///
+    /// Params:
+    ///
+    /// * files: the set of journal files to read. If the calling process
+    ///   doesn't have permission to read the system journal, a call to
+    ///   `Journal::open` with `System` or `All` will succeed, but system
+    ///   journal entries won't be included. This behavior is due to systemd.
+    /// * runtime_only: if true, include only journal entries from the current
+    ///   boot. If false, include all entries.
+    /// * local_only: if true, include only journal entries originating from
+    ///   localhost. If false, include all entries.
+    pub fn open(files: JournalFiles, runtime_only: bool, local_only: bool) -> Result<Journal> {
+        let mut flags: c_int = 0;
+        if runtime_only {
+            flags |= ffi::SD_JOURNAL_RUNTIME_ONLY;
+        }
+        if local_only {
+            flags |= ffi::SD_JOURNAL_LOCAL_ONLY;
+        }
+        flags |= match files {
+            JournalFiles::System => ffi::SD_JOURNAL_SYSTEM,
+            JournalFiles::CurrentUser => ffi::SD_JOURNAL_CURRENT_USER,
+            JournalFiles::All => 0,
+        };
+
+        Journal::open_flags(flags)
+    }
+
+    fn open_flags(flags: c_int) -> Result<Journal> {
+        let mut journal = Journal { j: ptr::null_mut(), flags: flags, matches: Vec::new() };
+        sd_try!(ffi::sd_journal_open(&mut journal.j, flags));
+        Ok(journal)
+    }
+
+    /// Open a second handle on the same journal files, with the same matches and positioned on
+    /// the same entry as this one.
+    ///
+    /// This allows making several passes over the same window of entries (say, counting first and
+    /// then looking at details) without re-specifying filters. Entries written after the
+    /// snapshot is taken are visible to both handles.
+    ///
+    /// The snapshot is not atomic: the new handle opens the journal files anew and then seeks to
+    /// this handle's cursor. Files rotated or vacuumed in between are seen differently by the two
+    /// handles, and if the current entry itself was vacuumed this fails with `NotFound`. Without
+    /// a current entry (right after opening or seeking, before `next()`), the new handle starts
+    /// at the head whatever this one was seeked to.
+    pub fn snapshot(&self) -> Result<Journal> {
+        let mut j = try!(Journal::open_flags(self.flags));
+        for m in &self.matches {
+            try!(j.apply_match(m.clone()));
+        }
+        /* Without a current entry (e.g. right after opening) both handles are at the head */
+        if let Ok(cursor) = self.cursor() {
+            let c = try!(CString::new(cursor));
+            sd_try!(ffi::sd_journal_seek_cursor(j.j, c.as_ptr()));
+            /* Seeking places us next to the entry, step onto it */
+            sd_try!(ffi::sd_journal_next(j.j));
+            if sd_try!(ffi::sd_journal_test_cursor(j.j, c.as_ptr())) == 0 {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "entry vanished from journal"));
+            }
+        }
+        Ok(j)
+    }
+
+    fn apply_match(&mut self, m: Match) -> Result<&mut Journal> {
+        match m {
+            Match::Data(ref data) => {
+                sd_try!(ffi::sd_journal_add_match(self.j,
+                                                  data.as_ptr() as *const c_void,
+                                                  data.len() as size_t))
+            }
+            Match::Or => sd_try!(ffi::sd_journal_add_disjunction(self.j)),
+            Match::And => sd_try!(ffi::sd_journal_add_conjunction(self.j)),
+        };
+        self.matches.push(m);
+        Ok(self)
+    }
+
+    /// Only show entries where the field `key` is `val`.
+    ///
+    /// Matches for different fields are ANDed, matches for the same field are ORed.
+    pub fn match_add<T: Into<Vec<u8>>>(&mut self, key: &str, val: T) -> Result<&mut Journal> {
+        let mut data = Vec::from(key.as_bytes());
+        data.push(b'=');
+        data.extend(val.into());
+        self.apply_match(Match::Data(data))
+    }
+
+    /// Insert a logical OR between the matches added before and after this call.
+    pub fn match_or(&mut self) -> Result<&mut Journal> {
+        self.apply_match(Match::Or)
+    }
+
+    /// Insert a logical AND between the matches added before and after this call.
+    pub fn match_and(&mut self) -> Result<&mut Journal> {
+        self.apply_match(Match::And)
+    }
+
+    /// Remove all matches.
+    pub fn match_flush(&mut self) -> Result<&mut Journal> {
+        unsafe { ffi::sd_journal_flush_matches(self.j) };
+        self.matches.clear();
+        Ok(self)
+    }
+
+    /// Get and parse the currently journal record from the journal
+    pub fn get_next_field(&mut self) -> Result<Option<(&str, &str)>> {
+
+        let mut sz: size_t = 0;
+        let mut data: *const u8 = ptr::null();
+        let p = &mut data as *mut *const u8 as *mut *const c_void;
+        if sd_try!(ffi::sd_journal_enumerate_data(self.j, p, &mut sz)) > 0 {
+            unsafe {
+                let b = ::std::slice::from_raw_parts(data, sz as usize);
+                let field = ::std::str::from_utf8_unchecked(b);
+                let mut name_value = field.splitn(2, '=');
+                let name = name_value.next().unwrap();
+                let value = name_value.next().unwrap();
+                Ok(Some((name, value)))
+            }
+            
+        }else{
+            Ok(None)
+        }
+
+        
+    }
+
+    pub fn next_record(&mut self) -> Result<Option<i32>> {
+        let r = sd_try!(ffi::sd_journal_next(self.j));
+        unsafe { ffi::sd_journal_restart_data(self.j) }
+        if r == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(r))
+        }
+    }
+
+    /// Advance to the next entry, and read all its fields. Returns `None` at the end of the
+    /// journal.
+    pub fn next_entry(&mut self) -> Result<Option<JournalEntry>> {
+        if try!(self.next_record()).is_none() {
+            return Ok(None);
+        }
+
+        let mut fields = BTreeMap::new();
+        let mut sz: size_t = 0;
+        let mut data: *const u8 = ptr::null();
+        let p = &mut data as *mut *const u8 as *mut *const c_void;
+        while sd_try!(ffi::sd_journal_enumerate_data(self.j, p, &mut sz)) > 0 {
+            let b = unsafe { ::std::slice::from_raw_parts(data, sz as usize) };
+            let field = String::from_utf8_lossy(b);
+            let mut name_value = field.splitn(2, '=');
+            let name = name_value.next().unwrap();
+            let value = name_value.next().unwrap_or("");
+            fields.insert(name.to_owned(), value.to_owned());
+        }
+        unsafe { ffi::sd_journal_restart_data(self.j) }
+
+        let mut realtime_usec = 0;
+        sd_try!(ffi::sd_journal_get_realtime_usec(self.j, &mut realtime_usec));
+        let mut monotonic_usec = 0;
+        sd_try!(ffi::sd_journal_get_monotonic_usec(self.j, &mut monotonic_usec, ptr::null()));
+        Ok(Some(JournalEntry {
+            fields: fields,
+            realtime_usec: realtime_usec,
+            monotonic_usec: monotonic_usec,
+        }))
+    }
+
+    pub fn previous_record(&mut self) ->Result<Option<i32>> {
+        let r = sd_try!(ffi::sd_journal_previous(self.j));
+        unsafe { ffi::sd_journal_restart_data(self.j) }
+        if r == 0{
+            Ok(None)
+        }else{
+            Ok(Some(r))
+        }
+    }
+
+    /// Seek to a specific position in journal. On success, returns a cursor
+    /// to the current entry.
+    pub fn seek(&mut self, seek: JournalSeek) -> Result<String> {
+        match seek {
+            JournalSeek::Head => sd_try!(ffi::sd_journal_seek_head(self.j)),
+            JournalSeek::Current => 0,
+            JournalSeek::Tail => sd_try!(ffi::sd_journal_seek_tail(self.j)),
+            JournalSeek::ClockMonotonic { boot_id, usec } => {
+                sd_try!(ffi::sd_journal_seek_monotonic_usec(self.j,
+                                                            sd_id128_t {
+                                                                bytes: *boot_id.as_bytes(),
+                                                            },
+                                                            usec))
+            }
+            JournalSeek::ClockRealtime { usec } => {
+                sd_try!(ffi::sd_journal_seek_realtime_usec(self.j, usec))
+            }
+            JournalSeek::Cursor { cursor } => {
+                let c = try!(CString::new(cursor));
+                sd_try!(ffi::sd_journal_seek_cursor(self.j, c.as_ptr()))
+            }
+        };
+        let c: *mut c_char = ptr::null_mut();
+        if unsafe { ffi::sd_journal_get_cursor(self.j, &c) != 0 } {
+            // Cursor may need to be re-aligned on a real entry first.
+            sd_try!(ffi::sd_journal_next(self.j));
+            sd_try!(ffi::sd_journal_get_cursor(self.j, &c));
+        }
+        let cs = unsafe { MString::from_raw(c) };
+        let cs = try!(cs.or(Err(io::Error::new(InvalidData, "invalid cursor"))));
+        Ok(cs.to_string())
+    }
+
+    /// Block until the journal changes, or `timeout` elapsed (`None` waits indefinitely).
+    ///
+    /// New entries can then be read with `next_record()`.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<JournalWaitResult> {
+        let usec = timeout.map_or(!0, ::duration_usec);
+        Ok(JournalWaitResult::from_raw(sd_try!(ffi::sd_journal_wait(self.j, usec))))
+    }
+
+    /// The fd to poll for changes to the journal, for integrating the journal into an external
+    /// event loop instead of using `wait()`. Once it is ready, call `process()`.
+    ///
+    /// This has to be called before the first call to `wait()` or `process()`, so that changes
+    /// in between are not missed.
+    #[inline]
+    pub fn fd(&self) -> Result<RawFd> {
+        Ok(sd_try!(ffi::sd_journal_get_fd(self.j)))
+    }
+
+    /// The `poll()` events to wait for on `fd()`
+    #[inline]
+    pub fn events(&self) -> Result<c_short> {
+        Ok(sd_try!(ffi::sd_journal_get_events(self.j)) as c_short)
+    }
+
+    /// The time since boot (`CLOCK_MONOTONIC`) at which `process()` has to be called even if
+    /// `fd()` didn't become ready, if any
+    fn deadline(&self) -> Result<Option<Duration>> {
+        let mut usec = 0;
+        sd_try!(ffi::sd_journal_get_timeout(self.j, &mut usec));
+        Ok(if usec == !0 { None } else { Some(Duration::from_micros(usec)) })
+    }
+
+    /// How long to wait for `fd()` at most before calling `process()` anyway (`None` waits
+    /// indefinitely). Journal files which can't be watched (ie: on network file systems) are
+    /// polled for changes this way.
+    pub fn timeout(&self) -> Result<Option<Duration>> {
+        let deadline = match try!(self.deadline()) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let mut now: libc::timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+        let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+        Ok(Some(if deadline > now { deadline - now } else { Duration::from_secs(0) }))
+    }
+
+    /// Process the changes signalled on `fd()` (or due after `timeout()`), without blocking.
+    ///
+    /// New entries can then be read with `next_record()`.
+    pub fn process(&mut self) -> Result<JournalWaitResult> {
+        Ok(JournalWaitResult::from_raw(sd_try!(ffi::sd_journal_process(self.j))))
+    }
+
+    /// Call `f` for each entry after the current position, then wait for new entries and call it
+    /// for those as they are written, like `journalctl -f` (seek to the tail first to only see new
+    /// entries).
+    ///
+    /// `f` is called with the journal positioned on the entry. Returns once `f` returns
+    /// `Ok(false)`, or an error.
+    pub fn watch<F>(&mut self, mut f: F) -> Result<()>
+        where F: FnMut(&mut Journal) -> Result<bool>
+    {
+        loop {
+            while try!(self.next_record()).is_some() {
+                if !try!(f(self)) {
+                    return Ok(());
+                }
+            }
+            try!(self.wait(None));
+        }
+    }
+
+    /// Returns the cursor of current journal entry
+    pub fn cursor(&self) -> Result<String> {
+        let mut c_cursor: *mut c_char = ptr::null_mut();
+
+        sd_try!(ffi::sd_journal_get_cursor(self.j, &mut c_cursor));
+
+        let cursor = unsafe { MString::from_raw(c_cursor) };
+        let cursor = try!(cursor.or(Err(io::Error::new(InvalidData, "invalid cursor"))));
+        Ok(cursor.to_string())
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        if !self.j.is_null() {
+            unsafe { ffi::sd_journal_close(self.j) };
+        }
+    }
+}
+
+#[test]
+fn t_snapshot_matches() {
+    let mut j = Journal::open(JournalFiles::CurrentUser, true, true).unwrap();
+    j.match_add("_SYSTEMD_UNIT", "a.service").unwrap()
+        .match_or().unwrap()
+        .match_add("PRIORITY", vec![b'3']).unwrap();
+    let s = j.snapshot().unwrap();
+    assert_eq!(s.flags, j.flags);
+    assert_eq!(s.matches,
+               vec![Match::Data(b"_SYSTEMD_UNIT=a.service".to_vec()),
+                    Match::Or,
+                    Match::Data(b"PRIORITY=3".to_vec())]);
+
+    j.match_flush().unwrap();
+    assert!(j.matches.is_empty());
+}
+
+#[test]
+fn t_wait() {
+    let mut j = Journal::open(JournalFiles::CurrentUser, true, true).unwrap();
+    j.seek(JournalSeek::Tail).ok();
+    j.wait(Some(Duration::from_millis(1))).unwrap();
+
+    assert!(j.fd().unwrap() >= 0);
+    assert!(j.events().unwrap() & libc::POLLIN != 0);
+    j.timeout().unwrap();
+    j.process().unwrap();
+}
+
+#[test]
+fn t_journal_entry() {
+    let mut fields = BTreeMap::new();
+    fields.insert("MESSAGE".to_owned(), "hello".to_owned());
+    fields.insert("PRIORITY".to_owned(), "3".to_owned());
+    fields.insert("_PID".to_owned(), "42".to_owned());
+    fields.insert("_BOOT_ID".to_owned(), "0123456789abcdef0123456789abcdef".to_owned());
+    let e = JournalEntry { fields: fields, realtime_usec: 1_500_000, monotonic_usec: 2 };
+    assert_eq!(e.message(), Some("hello"));
+    assert_eq!(e.priority(), Some(3));
+    assert_eq!(e.pid(), Some(42));
+    assert_eq!(e.uid(), None);
+    assert_eq!(e.boot_id().unwrap().to_string(), "0123456789abcdef0123456789abcdef");
+    assert_eq!(e.realtime(), UNIX_EPOCH + Duration::from_millis(1500));
+    assert_eq!(e.monotonic(), Duration::from_micros(2));
+
+    let mut j = Journal::open(JournalFiles::CurrentUser, true, true).unwrap();
+    j.seek(JournalSeek::Tail).ok();
+    assert!(j.next_entry().unwrap().is_none());
+}
@@ -0,0 +1,76 @@
+/*!
+ * Helpers for handling output captured from services, the way journald's stdout streams do
+ *
+ * With `SyslogLevelPrefix=yes` (the default), journald interprets a `<N>` prefix on each line
+ * written to a service's stdout/stderr as the priority of that line and strips it from the
+ * message. Code forwarding captured output elsewhere can use these to do the same.
+ */
+
+/// Priority numbers use the low 3 bits for the level, the rest for the facility
+const LOG_PRIMASK: u32 = 7;
+const LOG_FACMASK: u32 = 0x3f8;
+
+/// Parse a `<N>` prefix of 1 to 3 decimal digits at the start of `line`, returning `N` and the
+/// rest of the line.
+fn parse_prefix(line: &str) -> Option<(u32, &str)> {
+    if !line.starts_with('<') {
+        return None;
+    }
+    let end = match line.find('>') {
+        Some(e) if e >= 2 && e <= 4 => e,
+        _ => return None,
+    };
+    let digits = &line[1..end];
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().map(|n| (n, &line[end + 1..]))
+}
+
+/// Split a `<N>` level prefix (`N` from 0 to 7) off `line`, as journald does for lines read from
+/// stdout streams.
+///
+/// Returns the level and the remaining message, or `None` if `line` has no valid prefix (in which
+/// case journald uses the stream's default priority and keeps the line as is).
+pub fn parse_level_prefix(line: &str) -> Option<(u8, &str)> {
+    match parse_prefix(line) {
+        Some((n, rest)) if n <= LOG_PRIMASK => Some((n as u8, rest)),
+        _ => None,
+    }
+}
+
+/// Split a syslog style `<N>` priority prefix, which may include a facility, off `line`.
+///
+/// Returns the full priority (`facility << 3 | level`) and the remaining message.
+pub fn parse_priority_prefix(line: &str) -> Option<(u16, &str)> {
+    match parse_prefix(line) {
+        Some((n, rest)) if n <= LOG_FACMASK | LOG_PRIMASK => Some((n as u16, rest)),
+        _ => None,
+    }
+}
+
+/// Replace the level of `priority` with `level`, keeping its facility.
+#[inline]
+pub fn with_level(priority: u16, level: u8) -> u16 {
+    (priority & LOG_FACMASK as u16) | (level as u16 & LOG_PRIMASK as u16)
+}
+
+#[test]
+fn t_level_prefix() {
+    assert_eq!(parse_level_prefix("<3>failed"), Some((3, "failed")));
+    assert_eq!(parse_level_prefix("<007> spaced"), Some((7, " spaced")));
+    assert_eq!(parse_level_prefix("<8>too high"), None);
+    assert_eq!(parse_level_prefix("<>empty"), None);
+    assert_eq!(parse_level_prefix("<1234>long"), None);
+    assert_eq!(parse_level_prefix("<a>nope"), None);
+    assert_eq!(parse_level_prefix("<3 unterminated"), None);
+    assert_eq!(parse_level_prefix("plain <3>"), None);
+}
+
+#[test]
+fn t_priority_prefix() {
+    assert_eq!(parse_priority_prefix("<30>daemon info"), Some((30, "daemon info")));
+    assert_eq!(parse_priority_prefix("<1023>"), None);
+    assert_eq!(parse_priority_prefix("<191>x"), Some((191, "x")));
+    assert_eq!(with_level(30, 3), 27);
+}
@@ -0,0 +1,195 @@
+/*!
+ * Sending entries to journald over its native protocol
+ *
+ * journald receives entries as datagrams on `/run/systemd/journal/socket`. Each log namespace
+ * (services started with `LogNamespace=<ns>`) has its own journald instance listening on
+ * `/run/systemd/journal.<ns>/socket`. `sd_journal_send()` only ever talks to the default one, so
+ * entries meant for a namespace have to be written to its socket directly.
+ *
+ * An entry is a list of `FIELD=value` lines. Values containing newlines are instead written as
+ * the field name, a newline, the length of the value as a 64 bit little endian integer, the value
+ * and a final newline.
+ */
+
+use std::io;
+use std::io::ErrorKind::InvalidInput;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use log::{self, Level, Log, Metadata, Record};
+use super::super::Result;
+
+/// The socket journald (or the instance for `namespace`) receives entries on
+pub fn socket_path(namespace: Option<&str>) -> PathBuf {
+    match namespace {
+        Some(ns) => PathBuf::from(format!("/run/systemd/journal.{}/socket", ns)),
+        None => PathBuf::from("/run/systemd/journal/socket"),
+    }
+}
+
+/// Whether `ns` is acceptable as a log namespace (a short name usable as a file name)
+fn is_valid_namespace(ns: &str) -> bool {
+    !ns.is_empty() && ns.len() <= 255 && !ns.starts_with('.') &&
+    ns.bytes().all(|b| match b {
+        b'0'...b'9' | b'A'...b'Z' | b'a'...b'z' | b'_' | b'-' | b'.' => true,
+        _ => false,
+    })
+}
+
+/// Whether journald accepts `name` as the name of a field supplied by a client. Names starting
+/// with `_` are reserved for fields journald adds itself.
+fn is_valid_field(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && !name.starts_with('_') &&
+    !name.starts_with(|c: char| c.is_ascii_digit()) &&
+    name.bytes().all(|b| match b {
+        b'0'...b'9' | b'A'...b'Z' | b'_' => true,
+        _ => false,
+    })
+}
+
+/// Append the native protocol encoding of `fields` to `out`
+fn serialize(fields: &[(&str, &[u8])], out: &mut Vec<u8>) -> Result<()> {
+    for &(name, value) in fields {
+        if !is_valid_field(name) {
+            return Err(io::Error::new(InvalidInput, "invalid journal field name"));
+        }
+        out.extend_from_slice(name.as_bytes());
+        if value.contains(&b'\n') {
+            out.push(b'\n');
+            let len = value.len() as u64;
+            for i in 0..8 {
+                out.push((len >> (i * 8)) as u8);
+            }
+        } else {
+            out.push(b'=');
+        }
+        out.extend_from_slice(value);
+        out.push(b'\n');
+    }
+    Ok(())
+}
+
+/// A connection to journald, or to the journald instance of a log namespace.
+///
+/// Entries larger than the maximum datagram size of the socket fail with `EMSGSIZE`.
+#[derive(Debug)]
+pub struct JournalWriter {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl JournalWriter {
+    /// Write to the default journal
+    pub fn new() -> Result<JournalWriter> {
+        JournalWriter::open(socket_path(None))
+    }
+
+    /// Write to the journal of the log namespace `namespace`, as services started with
+    /// `LogNamespace=namespace` do.
+    pub fn with_namespace(namespace: &str) -> Result<JournalWriter> {
+        if !is_valid_namespace(namespace) {
+            return Err(io::Error::new(InvalidInput, "invalid log namespace"));
+        }
+        JournalWriter::open(socket_path(Some(namespace)))
+    }
+
+    fn open(path: PathBuf) -> Result<JournalWriter> {
+        Ok(JournalWriter { socket: try!(UnixDatagram::unbound()), path: path })
+    }
+
+    /// Send an entry made up of `fields`, given as (name, value) pairs. Names must consist of
+    /// uppercase letters, digits and `_`, and must not start with `_` or a digit.
+    ///
+    /// journald requires a `MESSAGE` field for the entry to be displayed by most tools.
+    pub fn send(&self, fields: &[(&str, &[u8])]) -> Result<()> {
+        let mut buf = Vec::new();
+        try!(serialize(fields, &mut buf));
+        try!(self.socket.send_to(&buf, &self.path));
+        Ok(())
+    }
+
+    /// Send `msg` with the syslog level `priority` (0 to 7)
+    pub fn print(&self, priority: u8, msg: &str) -> Result<()> {
+        self.send(&[("PRIORITY", priority.to_string().as_bytes()), ("MESSAGE", msg.as_bytes())])
+    }
+}
+
+/// The syslog level journald should record a `log` record of `level` with
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// A `log` logger writing records to the journal.
+///
+/// Besides `MESSAGE` and `PRIORITY`, entries include the target of the record and, where known,
+/// its source location (`CODE_FILE`, `CODE_LINE` and `CODE_MODULE`).
+#[derive(Debug)]
+pub struct JournalLog {
+    writer: JournalWriter,
+}
+
+impl JournalLog {
+    /// Install a logger writing to the default journal as the global logger.
+    pub fn init() -> Result<()> {
+        JournalLog::install(try!(JournalWriter::new()))
+    }
+
+    /// Install a logger writing to the journal of the log namespace `namespace` as the global
+    /// logger.
+    pub fn init_with_namespace(namespace: &str) -> Result<()> {
+        JournalLog::install(try!(JournalWriter::with_namespace(namespace)))
+    }
+
+    fn install(writer: JournalWriter) -> Result<()> {
+        let logger = Box::new(JournalLog { writer: writer });
+        try!(log::set_logger(Box::leak(logger))
+            .map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "a logger is already set")));
+        log::set_max_level(log::LevelFilter::Trace);
+        Ok(())
+    }
+}
+
+impl Log for JournalLog {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let priority = priority(record.level()).to_string();
+        let message = record.args().to_string();
+        let line = record.line().map(|l| l.to_string());
+        let mut fields = vec![("PRIORITY", priority.as_bytes()),
+                              ("MESSAGE", message.as_bytes()),
+                              ("TARGET", record.target().as_bytes())];
+        if let Some(file) = record.file() {
+            fields.push(("CODE_FILE", file.as_bytes()));
+        }
+        if let Some(ref line) = line {
+            fields.push(("CODE_LINE", line.as_bytes()));
+        }
+        if let Some(module) = record.module_path() {
+            fields.push(("CODE_MODULE", module.as_bytes()));
+        }
+        /* there is nowhere to report failures to log */
+        let _ = self.writer.send(&fields);
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn t_serialize() {
+    let mut buf = Vec::new();
+    serialize(&[("MESSAGE", b"hi"), ("DETAILS", b"a\nb")], &mut buf).unwrap();
+    assert_eq!(buf, &b"MESSAGE=hi\nDETAILS\n\x03\0\0\0\0\0\0\0a\nb\n"[..]);
+    serialize(&[("_PID", b"1")], &mut buf).err().unwrap();
+    serialize(&[("message", b"x")], &mut buf).err().unwrap();
+
+    assert_eq!(socket_path(Some("app")), PathBuf::from("/run/systemd/journal.app/socket"));
+    JournalWriter::with_namespace("../x").err().unwrap();
+    JournalWriter::with_namespace("").err().unwrap();
+}
@@ -3,7 +3,19 @@ extern crate libc;
 extern crate log;
 extern crate libsystemd_sys as ffi;
 extern crate mbox;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "derive")]
+extern crate systemd_derive;
+#[cfg(feature = "async-io")]
+extern crate async_io;
+#[cfg(feature = "futures-core")]
+extern crate futures_core;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 pub use std::io::{Result, Error};
+use std::time::Duration;
 
 /// Convert a systemd ffi return value into a Result
 pub fn ffi_result(ret: ffi::c_int) -> Result<ffi::c_int>
@@ -15,6 +27,23 @@ pub fn ffi_result(ret: ffi::c_int) -> Result<ffi::c_int>
     }
 }
 
+/// Convert `d` to microseconds, the unit systemd uses for times. Durations too long to represent
+/// saturate to `u64::MAX`, which systemd treats as infinity.
+fn duration_usec(d: Duration) -> u64 {
+    d.as_secs()
+        .checked_mul(1_000_000)
+        .and_then(|usec| usec.checked_add(d.subsec_micros() as u64))
+        .unwrap_or(!0)
+}
+
+#[test]
+fn t_duration_usec() {
+    assert_eq!(duration_usec(Duration::new(2, 3_999)), 2_000_003);
+    assert_eq!(duration_usec(Duration::from_secs(!0 / 1_000_000)), !0 / 1_000_000 * 1_000_000);
+    assert_eq!(duration_usec(Duration::new(!0 / 1_000_000, 999_999_999)), !0);
+    assert_eq!(duration_usec(Duration::from_secs(!0)), !0);
+}
+
 /// An analogue of `try!()` for systemd FFI calls.
 ///
 /// The parameter should be a call to a systemd FFI fn with an c_int return
@@ -76,15 +105,21 @@ pub mod id128;
 /// Interface to introspect on seats, sessions and users.
 pub mod login;
 
+/// Helpers for managing a cgroup subtree delegated to a service (`Delegate=yes`).
+pub mod cgroup;
+
+/// The sd-event event loop, for single-threaded daemons built around it like systemd's own
+/// services.
+pub mod event;
+
 /// An interface to work with the dbus message bus.
 ///
-/// WARNING: this is not complete. Right now we're missing:
-///
-///  - message encoding/decoding
-///  - server support
-///  - async client support
+/// This covers:
 ///
-/// In short, the only functional thing is issuing blocking dbus calls with pre-populated messages
-/// and writing custom ffi decoders of the message replies.
+///  - blocking and callback based calls (`MessageRef::call()`, `MessageRef::call_async()`)
+///  - encoding and decoding message bodies (`bus::types`, `MessageIter`)
+///  - serving objects (`BusRef::add_object()`, `BusRef::add_object_vtable()`), and testing them
+///    against a private bus (`bus::testing`)
+///  - driving a connection from an async runtime (`bus::aio`)
 #[cfg(feature = "bus")]
 pub mod bus;
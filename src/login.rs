@@ -28,6 +28,14 @@ pub fn get_unit(unit_type: UnitType, pid: Option<pid_t>) -> Result<String> {
     Ok(unit_name.unwrap().to_string())
 }
 
+/// Determines the system unit the process `pid` belongs to.
+///
+/// This reads the cgroup of the process directly, without talking to the service manager. For a
+/// proxy object that can be used to query or control the unit, see `bus::manager::unit_by_pid()`.
+pub fn unit_of_pid(pid: pid_t) -> Result<String> {
+    get_unit(UnitType::SystemUnit, Some(pid))
+}
+
 /// Determines the slice (either in system or user session) of a process.
 ///
 /// Specific processes can be optionally targeted via their PID. When no PID is
@@ -0,0 +1,1096 @@
+/*!
+ * Procedural macros for the `systemd` crate
+ *
+ * These are re-exported by `systemd` when its `derive` feature is enabled, use them from there.
+ */
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro2::{Span, TokenStream};
+use syn::{AttributeArgs, Data, DeriveInput, Field, Fields, FnArg, GenericArgument, ImplItem,
+          ImplItemMethod, ItemImpl, Lit, Meta, NestedMeta, PathArguments, ReturnType, Type,
+          TypeParamBound};
+
+/// Export the methods of an `impl` block as a D-Bus interface.
+///
+/// ```ignore
+/// #[dbus_interface(name = "org.example.Counter")]
+/// impl Counter {
+///     fn add(&mut self, n: u32) -> u32 { self.count += n; self.count }
+///
+///     #[dbus(property)]
+///     fn count(&self) -> u32 { self.count }
+///
+///     #[dbus(property)]
+///     fn set_count(&mut self, count: u32) { self.count = count }
+/// }
+///
+/// let _slot = bus.add_interface(path, Counter::default())?;
+/// ```
+///
+/// This implements `systemd::bus::vtable::Interface` for the type, building a vtable that reads
+/// the arguments of incoming calls, invokes the matching method and sends its return value as the
+/// reply. sd-bus generates introspection data from the vtable.
+///
+/// Every method taking `self` becomes a D-Bus method, with its name converted to CamelCase. Each
+/// method may be annotated with `#[dbus(...)]`:
+///
+///  - `name = "Member"`: use `Member` as the D-Bus name instead
+///  - `property`: expose a getter (`fn foo(&self) -> T`) as the property `Foo`. A setter
+///    (`fn set_foo(&mut self, v: T)`) marked as `property` makes `Foo` writable.
+///  - `skip`: don't export this method
+///
+/// Arguments and return values may be of the basic types `u8`, `bool`, `i16`, `u16`, `i32`,
+/// `u32`, `i64`, `u64`, `f64`, `String`, `ObjectPathBuf` and `UnixFd`. Methods may also return
+/// `()` or a `Result` of one of these (whose error converts into `systemd::bus::Error`), an error
+/// is sent as the reply.
+///
+/// A method returning `impl Future<Output = T>` (where `T` is one of the above) is added with
+/// `VtableBuilder::method_async()`: the reply is sent once the future resolves, without holding up
+/// the dispatching of other calls. The future must not borrow `self`, so `async fn` can't be used
+/// directly. Such futures are run by the vtable's `Spawn`, which has to be set before registering
+/// it:
+///
+/// ```ignore
+/// #[dbus_interface(name = "org.example.Fetcher")]
+/// impl Fetcher {
+///     fn fetch(&mut self, url: String) -> impl Future<Output = Result<String, Error>> + 'static {
+///         let client = self.client.clone();
+///         async move { client.get(&url).await }
+///     }
+/// }
+///
+/// let mut vtable = Fetcher::vtable();
+/// vtable.set_spawner(Rc::new(|task| { tokio::task::spawn_local(task); }));
+/// let _slot = bus.add_object_vtable(path, Fetcher::interface(), vtable, fetcher)?;
+/// ```
+#[proc_macro_attribute]
+pub fn dbus_interface(args: proc_macro::TokenStream,
+                      item: proc_macro::TokenStream)
+                      -> proc_macro::TokenStream {
+    let args = syn::parse_macro_input!(args as AttributeArgs);
+    let item = syn::parse_macro_input!(item as ItemImpl);
+    match expand(args, item) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Pass a struct or enum as a D-Bus message value.
+///
+/// ```ignore
+/// #[derive(ToSdBusMessage, FromSdBusMessage)]
+/// struct Entry { name: String, size: u64 }
+///
+/// #[derive(ToSdBusMessage, FromSdBusMessage)]
+/// #[dbus(dict)]
+/// struct Options {
+///     #[dbus(name = "Timeout")]
+///     timeout: u64,
+///     label: Option<String>,
+/// }
+/// ```
+///
+/// By default the fields are passed as a struct (`(st)` for `Entry`), in order. This also
+/// implements `SdBusType`, so the type can be used in arrays and dicts.
+///
+/// With `#[dbus(dict)]`, the fields are passed as an `a{sv}` instead, with the field names as keys
+/// (or the name given with `#[dbus(name = "...")]` on a field). Fields of type `Option<T>` are left
+/// out when `None`, and are `None` when missing from a message being read. Other missing fields
+/// are an error, unknown keys are ignored.
+///
+/// Field types must implement the same trait (and `SdBusType`, for structs). Generic types are
+/// not supported.
+///
+/// Enums are passed as strings, the way systemd passes states:
+///
+/// ```ignore
+/// #[derive(ToSdBusMessage, FromSdBusMessage)]
+/// enum ActiveState {
+///     Active,
+///     Inactive,
+///     #[dbus(name = "reloading")]
+///     Reload,
+///     #[dbus(other)]
+///     Other(String),
+/// }
+/// ```
+///
+/// Each unit variant is passed as its name in lowercase, with dashes between words (`NotFound` is
+/// `"not-found"`), or as the name given with `#[dbus(name = "...")]`. Reading an unknown string
+/// fails, unless a variant holding a `String` is marked `#[dbus(other)]`. With `#[dbus(u32)]` on
+/// the enum, variants are passed as their discriminant (`u`) instead.
+#[proc_macro_derive(ToSdBusMessage, attributes(dbus))]
+pub fn derive_to_message(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(item as DeriveInput);
+    match to_message(item) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Read a struct or enum from a D-Bus message, see `ToSdBusMessage`.
+#[proc_macro_derive(FromSdBusMessage, attributes(dbus))]
+pub fn derive_from_message(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(item as DeriveInput);
+    match from_message(item) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn error<T, S: quote::ToTokens>(s: S, msg: &str) -> syn::Result<T> {
+    Err(syn::Error::new_spanned(s, msg))
+}
+
+/// Check that `s` is a valid member name, as `MemberName::from_bytes()` would
+fn valid_member(s: &str) -> bool {
+    let b = s.as_bytes();
+    !b.is_empty() && b.len() <= 255 && !b[0].is_ascii_digit() &&
+    b.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'_')
+}
+
+/// Check that `s` is a valid interface name, as `InterfaceName::from_bytes()` would
+fn valid_interface(s: &str) -> bool {
+    s.len() <= 255 && s.split('.').count() >= 2 && s.split('.').all(valid_member)
+}
+
+/// `get_unit_by_pid` -> `GetUnitByPid`
+fn camel_case(s: &str) -> String {
+    s.split('_')
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut c = w.chars();
+            c.next().map_or(String::new(), |f| f.to_uppercase().chain(c).collect())
+        })
+        .collect()
+}
+
+/// The D-Bus signature of `ty`, if it is one of the supported types. `()` has an empty signature.
+fn signature(ty: &Type) -> Option<&'static str> {
+    match *ty {
+        Type::Tuple(ref t) if t.elems.is_empty() => Some(""),
+        Type::Paren(ref p) => signature(&p.elem),
+        Type::Path(ref p) if p.qself.is_none() => {
+            let last = p.path.segments.last().unwrap();
+            if !last.arguments.is_empty() {
+                return None;
+            }
+            Some(match &*last.ident.to_string() {
+                "u8" => "y",
+                "bool" => "b",
+                "i16" => "n",
+                "u16" => "q",
+                "i32" => "i",
+                "u32" => "u",
+                "i64" => "x",
+                "u64" => "t",
+                "f64" => "d",
+                "String" => "s",
+                "ObjectPathBuf" => "o",
+                "UnixFd" => "h",
+                _ => return None,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// If `ty` is `Result<T, ..>`, return `T`
+fn result_inner(ty: &Type) -> Option<&Type> {
+    let p = match *ty {
+        Type::Path(ref p) if p.qself.is_none() => p,
+        _ => return None,
+    };
+    let last = p.path.segments.last().unwrap();
+    if last.ident != "Result" {
+        return None;
+    }
+    match last.arguments {
+        PathArguments::AngleBracketed(ref a) => {
+            a.args.iter().filter_map(|a| match *a {
+                GenericArgument::Type(ref t) => Some(t),
+                _ => None,
+            }).next()
+        }
+        _ => None,
+    }
+}
+
+/// If `ty` is `impl Future<Output = T>`, return `T`
+fn future_output(ty: &Type) -> Option<&Type> {
+    let bounds = match *ty {
+        Type::ImplTrait(ref i) => &i.bounds,
+        _ => return None,
+    };
+    bounds.iter().filter_map(|b| match *b {
+        TypeParamBound::Trait(ref t) => t.path.segments.last(),
+        _ => None,
+    }).filter(|s| s.ident == "Future").filter_map(|s| match s.arguments {
+        PathArguments::AngleBracketed(ref a) => {
+            a.args.iter().filter_map(|a| match *a {
+                GenericArgument::Binding(ref b) if b.ident == "Output" => Some(&b.ty),
+                _ => None,
+            }).next()
+        }
+        _ => None,
+    }).next()
+}
+
+/// A method's `#[dbus(...)]` options
+#[derive(Default)]
+struct Options {
+    name: Option<String>,
+    property: bool,
+    skip: bool,
+}
+
+/// Parse and remove the `#[dbus(...)]` attributes of `m`
+fn options(m: &mut ImplItemMethod) -> syn::Result<Options> {
+    let mut o = Options::default();
+    let mut attrs = Vec::new();
+    for a in m.attrs.drain(..) {
+        if !a.path.is_ident("dbus") {
+            attrs.push(a);
+            continue;
+        }
+        let list = match a.parse_meta()? {
+            Meta::List(l) => l,
+            other => return error(other, "expected #[dbus(...)]"),
+        };
+        for n in list.nested {
+            match n {
+                NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("property") => o.property = true,
+                NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("skip") => o.skip = true,
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") => {
+                    match nv.lit {
+                        Lit::Str(ref s) if valid_member(&s.value()) => o.name = Some(s.value()),
+                        ref l => return error(l, "expected a valid member name"),
+                    }
+                }
+                other => return error(other, "unknown dbus option"),
+            }
+        }
+    }
+    m.attrs = attrs;
+    Ok(o)
+}
+
+/// The arguments of `m` after `self`, with their types. Returns `None` if `m` has no receiver.
+fn inputs(m: &ImplItemMethod) -> Option<Vec<(syn::Ident, &Type)>> {
+    let mut i = m.sig.inputs.iter();
+    match i.next() {
+        Some(&FnArg::Receiver(_)) => {}
+        _ => return None,
+    }
+    Some(i.enumerate()
+        .filter_map(|(n, a)| match *a {
+            FnArg::Typed(ref t) => Some((syn::Ident::new(&format!("a{}", n), Span::call_site()),
+                                         &*t.ty)),
+            FnArg::Receiver(_) => None,
+        })
+        .collect())
+}
+
+/// The signature of the values read from a message into `args`, and the statement reading them
+fn read_args(args: &[(syn::Ident, &Type)]) -> syn::Result<(String, TokenStream)> {
+    let mut sig = String::new();
+    for &(_, ty) in args {
+        match signature(ty) {
+            Some(s) if !s.is_empty() => sig.push_str(s),
+            _ => return error(ty, "unsupported D-Bus argument type"),
+        }
+    }
+    let names = args.iter().map(|a| &a.0);
+    let types = args.iter().map(|a| a.1);
+    let read = quote! {
+        let (#(#names,)*) = {
+            let mut i = m.iter()?;
+            (#(::systemd::bus::codegen::expect(i.read_next::<#types>()?)?,)*)
+        };
+    };
+    Ok((sig, read))
+}
+
+/// The signature of the value returned by `m`, and an expression unwrapping `call` if `m` returns
+/// a `Result`
+fn output(m: &ImplItemMethod, call: TokenStream) -> syn::Result<(&'static str, TokenStream)> {
+    let ty = match m.sig.output {
+        ReturnType::Default => return Ok(("", call)),
+        ReturnType::Type(_, ref t) => &**t,
+    };
+    let (ty, call) = match result_inner(ty) {
+        Some(t) => (t, quote!(#call?)),
+        None => (ty, call),
+    };
+    match signature(ty) {
+        Some(s) => Ok((s, call)),
+        None => error(ty, "unsupported D-Bus return type"),
+    }
+}
+
+fn member_lit(name: &str) -> TokenStream {
+    let b = syn::LitByteStr::new(format!("{}\0", name).as_bytes(), Span::call_site());
+    quote!(::systemd::bus::MemberName::from_bytes(#b).unwrap())
+}
+
+/// A method returning `impl Future<Output = T>`, added with `method_async()`
+fn async_method(self_ty: &Type,
+                m: &ImplItemMethod,
+                name: &str,
+                output: &Type)
+                -> syn::Result<TokenStream> {
+    let args = inputs(m).unwrap();
+    let (sig, read) = read_args(&args)?;
+    let ident = &m.sig.ident;
+    let names = args.iter().map(|a| &a.0);
+    let (ty, map) = match result_inner(output) {
+        Some(t) => (t, quote!(|r| r.map_err(::std::convert::From::from))),
+        None => (output, quote!(|v| Ok(v))),
+    };
+    let result = match signature(ty) {
+        Some(s) => s,
+        None => return error(ty, "unsupported D-Bus return type"),
+    };
+    let member = member_lit(name);
+    Ok(quote! {
+        .method_async(#member, #sig, #result, ::systemd::bus::VtableFlags::NONE,
+                      |this: &mut #self_ty, m: &mut ::systemd::bus::MessageRef| {
+            #read
+            Ok(::systemd::bus::codegen::map_reply(this.#ident(#(#names),*), #map))
+        })
+    })
+}
+
+fn method(self_ty: &Type, m: &ImplItemMethod, name: &str) -> syn::Result<TokenStream> {
+    let args = match inputs(m) {
+        Some(a) => a,
+        None => return Ok(TokenStream::new()),
+    };
+    if m.sig.asyncness.is_some() {
+        return error(&m.sig,
+                     "an async fn borrows `self`, return `impl Future<Output = T> + 'static` \
+                      instead");
+    }
+    if let ReturnType::Type(_, ref t) = m.sig.output {
+        if let Some(output) = future_output(t) {
+            return async_method(self_ty, m, name, output);
+        }
+    }
+    let (sig, read) = read_args(&args)?;
+    let ident = &m.sig.ident;
+    let names = args.iter().map(|a| &a.0);
+    let (result, call) = output(m, quote!(this.#ident(#(#names),*)))?;
+    let append = if result.is_empty() {
+        quote!(let _: () = #call; m.reply(())?;)
+    } else {
+        quote!(m.reply(#call)?;)
+    };
+    let member = member_lit(name);
+    Ok(quote! {
+        .method(#member, #sig, #result, ::systemd::bus::VtableFlags::NONE,
+                |this: &mut #self_ty, m: &mut ::systemd::bus::MessageRef| {
+            #read
+            #append
+            Ok(())
+        })
+    })
+}
+
+fn property(self_ty: &Type,
+            get: &ImplItemMethod,
+            set: Option<&ImplItemMethod>,
+            name: &str)
+            -> syn::Result<TokenStream> {
+    match inputs(get) {
+        Some(ref a) if a.is_empty() => {}
+        _ => return error(&get.sig, "property getters must only take `&self`"),
+    }
+    let ident = &get.sig.ident;
+    let (sig, call) = output(get, quote!(this.#ident()))?;
+    if sig.is_empty() {
+        return error(&get.sig, "property getters must return a value");
+    }
+    let member = member_lit(name);
+    let get = quote! {
+        |this: &mut #self_ty, m: &mut ::systemd::bus::MessageRef| {
+            m.append(#call)?;
+            Ok(())
+        }
+    };
+
+    let flags = quote!(::systemd::bus::VtableFlags::NONE);
+    let set = match set {
+        None => return Ok(quote!(.property(#member, #sig, #flags, #get))),
+        Some(s) => s,
+    };
+    let args = match inputs(set) {
+        Some(ref a) if a.len() == 1 => a.clone(),
+        _ => return error(&set.sig, "property setters must take `&mut self` and a value"),
+    };
+    let (set_sig, read) = read_args(&args)?;
+    if set_sig != sig {
+        return error(&set.sig, "property setter and getter types differ");
+    }
+    let ident = &set.sig.ident;
+    let (_, call) = output(set, quote!(this.#ident(a0)))?;
+    Ok(quote! {
+        .property_writable(#member, #sig, #flags, #get,
+                           |this: &mut #self_ty, m: &mut ::systemd::bus::MessageRef| {
+            #read
+            let _: () = #call;
+            Ok(())
+        })
+    })
+}
+
+fn expand(args: AttributeArgs, mut item: ItemImpl) -> syn::Result<TokenStream> {
+    let mut interface = None;
+    for a in &args {
+        match *a {
+            NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") => {
+                match nv.lit {
+                    Lit::Str(ref s) if valid_interface(&s.value()) => interface = Some(s.value()),
+                    ref l => return error(l, "expected a valid interface name"),
+                }
+            }
+            ref other => return error(other, "unknown dbus_interface option"),
+        }
+    }
+    let interface = match interface {
+        Some(i) => i,
+        None => {
+            return Err(syn::Error::new(Span::call_site(),
+                                       "expected #[dbus_interface(name = \"...\")]"))
+        }
+    };
+    if item.trait_.is_some() || !item.generics.params.is_empty() {
+        return error(&item.self_ty, "dbus_interface requires an inherent impl of a concrete type");
+    }
+
+    let mut methods = Vec::new();
+    let mut getters = Vec::new();
+    let mut setters = Vec::new();
+    for i in item.items.iter_mut() {
+        if let ImplItem::Method(ref mut m) = *i {
+            let o = options(m)?;
+            if o.skip {
+                continue;
+            }
+            let rust_name = m.sig.ident.to_string();
+            if o.property {
+                if rust_name.starts_with("set_") {
+                    let name = o.name.unwrap_or_else(|| camel_case(&rust_name[4..]));
+                    setters.push((name, m.clone()));
+                } else {
+                    getters.push((o.name.unwrap_or_else(|| camel_case(&rust_name)), m.clone()));
+                }
+            } else {
+                methods.push((o.name.unwrap_or_else(|| camel_case(&rust_name)), m.clone()));
+            }
+        }
+    }
+
+    let self_ty = &*item.self_ty;
+    let mut entries = Vec::new();
+    for &(ref name, ref m) in &methods {
+        entries.push(method(self_ty, m, name)?);
+    }
+    for &(ref name, ref get) in &getters {
+        let set = setters.iter().find(|s| s.0 == *name).map(|s| &s.1);
+        entries.push(property(self_ty, get, set, name)?);
+    }
+    for &(ref name, ref set) in &setters {
+        if !getters.iter().any(|g| g.0 == *name) {
+            return error(&set.sig, "property setter without a getter");
+        }
+    }
+
+    let interface = syn::LitByteStr::new(format!("{}\0", interface).as_bytes(),
+                                         Span::call_site());
+    Ok(quote! {
+        #item
+
+        impl ::systemd::bus::vtable::Interface for #self_ty {
+            fn interface() -> &'static ::systemd::bus::InterfaceName {
+                ::systemd::bus::InterfaceName::from_bytes(#interface).unwrap()
+            }
+
+            fn vtable() -> ::systemd::bus::Vtable<Self> {
+                ::systemd::bus::VtableBuilder::new()
+                    #(#entries)*
+                    .create()
+            }
+        }
+    })
+}
+
+/// Parse the `#[dbus(...)]` attributes in `attrs` with `f`
+fn dbus_attrs<F>(attrs: &[syn::Attribute], mut f: F) -> syn::Result<()>
+    where F: FnMut(NestedMeta) -> syn::Result<()>
+{
+    for a in attrs.iter().filter(|a| a.path.is_ident("dbus")) {
+        match a.parse_meta()? {
+            Meta::List(l) => {
+                for n in l.nested {
+                    f(n)?;
+                }
+            }
+            other => return error(other, "expected #[dbus(...)]"),
+        }
+    }
+    Ok(())
+}
+
+/// A field of a struct deriving the message traits
+struct MessageField<'a> {
+    /// `self.<member>`, an identifier or an index
+    member: syn::Member,
+    ty: &'a Type,
+    /// Key in an `a{sv}`
+    name: String,
+    /// If `ty` is an `Option`, the type it holds
+    optional: Option<&'a Type>,
+}
+
+/// If `ty` is `Option<T>`, return `T`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let p = match *ty {
+        Type::Path(ref p) if p.qself.is_none() => p,
+        _ => return None,
+    };
+    let last = p.path.segments.last().unwrap();
+    if last.ident != "Option" {
+        return None;
+    }
+    match last.arguments {
+        PathArguments::AngleBracketed(ref a) if a.args.len() == 1 => {
+            match a.args[0] {
+                GenericArgument::Type(ref t) => Some(t),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether `item` is passed as an `a{sv}`, and its fields
+fn message_fields(item: &DeriveInput) -> syn::Result<(bool, Vec<MessageField>)> {
+    let mut dict = false;
+    dbus_attrs(&item.attrs, |n| match n {
+        NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("dict") => {
+            dict = true;
+            Ok(())
+        }
+        other => error(other, "unknown dbus option"),
+    })?;
+    if !item.generics.params.is_empty() {
+        return error(&item.generics, "generic types are not supported");
+    }
+    let fields = match item.data {
+        Data::Struct(ref s) => &s.fields,
+        _ => return error(&item.ident, "only structs and enums can be passed in D-Bus messages"),
+    };
+    if let Fields::Unnamed(_) = *fields {
+        if dict {
+            return error(fields, "#[dbus(dict)] requires named fields");
+        }
+    }
+
+    let mut v = Vec::new();
+    for (i, f) in fields.iter().enumerate() {
+        v.push(message_field(i, f, dict)?);
+    }
+    if v.is_empty() && !dict {
+        return error(&item.ident, "D-Bus structs must have at least one field");
+    }
+    Ok((dict, v))
+}
+
+fn message_field(i: usize, f: &Field, dict: bool) -> syn::Result<MessageField> {
+    let (member, mut name) = match f.ident {
+        Some(ref id) => (syn::Member::Named(id.clone()), id.to_string()),
+        None => (syn::Member::Unnamed(syn::Index::from(i)), i.to_string()),
+    };
+    dbus_attrs(&f.attrs, |n| match n {
+        NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") && dict => {
+            match nv.lit {
+                Lit::Str(ref s) => {
+                    name = s.value();
+                    Ok(())
+                }
+                ref l => error(l, "expected a string"),
+            }
+        }
+        other => error(other, "unknown dbus option"),
+    })?;
+    Ok(MessageField {
+        member: member,
+        ty: &f.ty,
+        name: name,
+        optional: if dict { option_inner(&f.ty) } else { None },
+    })
+}
+
+/// A unit variant of an enum deriving the message traits
+struct EnumVariant<'a> {
+    ident: &'a syn::Ident,
+    /// The string the variant is passed as
+    name: String,
+}
+
+/// An enum deriving the message traits
+struct MessageEnum<'a> {
+    /// Whether the enum is passed as the `u32` discriminant of its variants, rather than a string
+    integer: bool,
+    variants: Vec<EnumVariant<'a>>,
+    /// The `#[dbus(other)]` variant holding strings not matching any other variant
+    other: Option<&'a syn::Ident>,
+}
+
+/// `NotFound` -> `not-found`
+fn kebab_case(s: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('-');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn message_enum<'a>(item: &'a DeriveInput, e: &'a syn::DataEnum) -> syn::Result<MessageEnum<'a>> {
+    let mut integer = false;
+    dbus_attrs(&item.attrs, |n| match n {
+        NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("u32") => {
+            integer = true;
+            Ok(())
+        }
+        other => error(other, "unknown dbus option"),
+    })?;
+    if !item.generics.params.is_empty() {
+        return error(&item.generics, "generic types are not supported");
+    }
+
+    let mut en = MessageEnum { integer: integer, variants: Vec::new(), other: None };
+    for v in &e.variants {
+        let mut name = kebab_case(&v.ident.to_string());
+        let mut is_other = false;
+        dbus_attrs(&v.attrs, |n| match n {
+            NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") && !integer => {
+                match nv.lit {
+                    Lit::Str(ref s) => {
+                        name = s.value();
+                        Ok(())
+                    }
+                    ref l => error(l, "expected a string"),
+                }
+            }
+            NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("other") && !integer => {
+                is_other = true;
+                Ok(())
+            }
+            other => error(other, "unknown dbus option"),
+        })?;
+        match v.fields {
+            Fields::Unit if !is_other => {
+                en.variants.push(EnumVariant { ident: &v.ident, name: name });
+            }
+            Fields::Unnamed(ref f) if is_other && f.unnamed.len() == 1 && en.other.is_none() => {
+                en.other = Some(&v.ident);
+            }
+            _ => {
+                return error(v, "expected a unit variant, or a single `#[dbus(other)]` variant \
+                                 holding a `String`")
+            }
+        }
+    }
+    if en.variants.is_empty() {
+        return error(&item.ident, "enums must have at least one unit variant");
+    }
+    Ok(en)
+}
+
+fn enum_to_message(item: &DeriveInput, e: &syn::DataEnum) -> syn::Result<TokenStream> {
+    let en = message_enum(item, e)?;
+    let ident = &item.ident;
+    let types = quote!(::systemd::bus::types);
+    let variants = en.variants.iter().map(|v| v.ident);
+
+    let (sig, value) = if en.integer {
+        let variants1 = en.variants.iter().map(|v| v.ident);
+        let value = quote! {
+            let v: u32 = match *self { #(#ident::#variants => #ident::#variants1 as u32,)* };
+        };
+        ("u", value)
+    } else {
+        let names = en.variants.iter().map(|v| &v.name);
+        let other = en.other.map(|o| quote!(#ident::#o(ref s) => s,));
+        ("s", quote!(let v: &str = match *self { #(#ident::#variants => #names,)* #other };))
+    };
+    Ok(quote! {
+        impl #types::ToSdBusMessage for #ident {
+            fn to_message(&self, m: &mut ::systemd::bus::MessageRef) -> ::std::io::Result<()> {
+                #value
+                #types::ToSdBusMessage::to_message(&v, m)
+            }
+
+            fn signature(&self) -> String {
+                #sig.to_owned()
+            }
+        }
+
+        impl #types::SdBusType for #ident {
+            fn type_signature() -> String {
+                #sig.to_owned()
+            }
+        }
+    })
+}
+
+fn enum_from_message(item: &DeriveInput, e: &syn::DataEnum) -> syn::Result<TokenStream> {
+    let en = message_enum(item, e)?;
+    let ident = &item.ident;
+    let types = quote!(::systemd::bus::types);
+    let variants = en.variants.iter().map(|v| v.ident);
+    let unknown = format!("unknown {} value", ident);
+
+    let read = if en.integer {
+        let variants1 = en.variants.iter().map(|v| v.ident);
+        quote! {
+            let v: u32 = match #types::FromSdBusMessage::from_message(m)? {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+            match v {
+                #(v if v == #ident::#variants as u32 => #ident::#variants1,)*
+                v => {
+                    return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData,
+                                                     format!("{} {}", #unknown, v)))
+                }
+            }
+        }
+    } else {
+        let names = en.variants.iter().map(|v| &v.name);
+        let other = match en.other {
+            Some(o) => quote!(s => #ident::#o(s.to_owned()),),
+            None => {
+                quote! {
+                    s => {
+                        return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData,
+                                                         format!("{} \"{}\"", #unknown, s)))
+                    }
+                }
+            }
+        };
+        quote! {
+            let s: &str = match #types::FromSdBusMessage::from_message(m)? {
+                Some(s) => s,
+                None => return Ok(None),
+            };
+            match s {
+                #(#names => #ident::#variants,)*
+                #other
+            }
+        }
+    };
+
+    Ok(quote! {
+        impl<'a> #types::FromSdBusMessage<'a> for #ident {
+            fn from_message(m: &mut ::systemd::bus::MessageIter<'a>)
+                -> ::std::io::Result<Option<Self>>
+            {
+                Ok(Some({ #read }))
+            }
+        }
+    })
+}
+
+fn to_message(item: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(ref e) = item.data {
+        return enum_to_message(&item, e);
+    }
+    let (dict, fields) = message_fields(&item)?;
+    let ident = &item.ident;
+    let types = quote!(::systemd::bus::types);
+
+    if dict {
+        let entries = fields.iter().map(|f| {
+            let member = &f.member;
+            let name = &f.name;
+            let append = quote! {
+                m.append_dict_entry_with("sv", |m| {
+                    m.append(#name)?;
+                    m.append_variant_with(&#types::ToSdBusMessage::signature(v),
+                                          |m| #types::ToSdBusMessage::to_message(v, m))
+                })?;
+            };
+            if f.optional.is_some() {
+                quote!(if let Some(ref v) = self.#member { #append })
+            } else {
+                quote!({ let v = &self.#member; #append })
+            }
+        });
+        return Ok(quote! {
+            impl #types::ToSdBusMessage for #ident {
+                fn to_message(&self, m: &mut ::systemd::bus::MessageRef)
+                    -> ::std::io::Result<()>
+                {
+                    m.append_array_with("{sv}", |m| {
+                        #(#entries)*
+                        Ok(())
+                    })
+                }
+
+                fn signature(&self) -> String {
+                    "a{sv}".to_owned()
+                }
+            }
+
+            impl #types::SdBusType for #ident {
+                fn type_signature() -> String {
+                    "a{sv}".to_owned()
+                }
+            }
+        });
+    }
+
+    let members = fields.iter().map(|f| &f.member);
+    let types1 = fields.iter().map(|f| f.ty);
+    Ok(quote! {
+        impl #types::ToSdBusMessage for #ident {
+            fn to_message(&self, m: &mut ::systemd::bus::MessageRef) -> ::std::io::Result<()> {
+                let s = <Self as #types::SdBusType>::type_signature();
+                m.append_struct_with(&s[1..s.len() - 1], |m| {
+                    #(#types::ToSdBusMessage::to_message(&self.#members, m)?;)*
+                    Ok(())
+                })
+            }
+
+            fn signature(&self) -> String {
+                <Self as #types::SdBusType>::type_signature()
+            }
+        }
+
+        impl #types::SdBusType for #ident {
+            fn type_signature() -> String {
+                let mut s = String::from("(");
+                #(s.push_str(&<#types1 as #types::SdBusType>::type_signature());)*
+                s.push(')');
+                s
+            }
+        }
+    })
+}
+
+fn from_message(item: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(ref e) = item.data {
+        return enum_from_message(&item, e);
+    }
+    let (dict, fields) = message_fields(&item)?;
+    let ident = &item.ident;
+    let types = quote!(::systemd::bus::types);
+    let missing = |what: &str| {
+        quote!(::std::io::Error::new(::std::io::ErrorKind::InvalidData, #what))
+    };
+
+    let read = if dict {
+        let vars: Vec<_> = (0..fields.len())
+            .map(|i| syn::Ident::new(&format!("f{}", i), Span::call_site()))
+            .collect();
+        let vars1 = &vars;
+        let names = fields.iter().map(|f| &f.name);
+        let inner = fields.iter().map(|f| f.optional.unwrap_or(f.ty));
+        let finish = fields.iter().zip(vars.iter()).map(|(f, v)| {
+            let member = &f.member;
+            if f.optional.is_some() {
+                quote!(#member: #v)
+            } else {
+                let e = missing(&format!("dict lacks the entry \"{}\"", f.name));
+                quote!(#member: match #v { Some(v) => v, None => return Err(#e) })
+            }
+        });
+        let bad_key = missing("invalid dict key");
+        quote! {
+            let mut c = match m.enter_container(b'a', Some("{sv}"))? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            #(let mut #vars1: Option<#inner> = None;)*
+            while let Some(mut e) = c.enter_container(b'e', Some("sv"))? {
+                let key: String = match #types::FromSdBusMessage::from_message(&mut *e)? {
+                    Some(k) => k,
+                    None => return Err(#bad_key),
+                };
+                match &*key {
+                    #(#names => {
+                        if let Some(mut v) = e.enter_container(b'v', None)? {
+                            #vars1 = #types::FromSdBusMessage::from_message(&mut *v)?;
+                            v.exit()?;
+                        }
+                    })*
+                    _ => {}
+                }
+                e.exit()?;
+            }
+            c.exit()?;
+            #ident { #(#finish),* }
+        }
+    } else {
+        let members = fields.iter().map(|f| &f.member);
+        let short = missing("struct has too few fields");
+        quote! {
+            /* at the end of a container, sd-bus can't enter a container of any type */
+            if m.peek_type()?.0 == 0 {
+                return Ok(None);
+            }
+            let mut c = match m.enter_container(b'r', None)? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            let v = #ident {
+                #(#members: match #types::FromSdBusMessage::from_message(&mut *c)? {
+                    Some(v) => v,
+                    None => return Err(#short),
+                }),*
+            };
+            c.exit()?;
+            v
+        }
+    };
+
+    Ok(quote! {
+        impl<'a> #types::FromSdBusMessage<'a> for #ident {
+            fn from_message(m: &mut ::systemd::bus::MessageIter<'a>)
+                -> ::std::io::Result<Option<Self>>
+            {
+                Ok(Some({ #read }))
+            }
+        }
+    })
+}
+
+#[test]
+fn t_camel_case() {
+    assert_eq!(camel_case("get_unit_by_pid"), "GetUnitByPid");
+    assert_eq!(camel_case("ping"), "Ping");
+    assert_eq!(camel_case("_x__y"), "XY");
+}
+
+#[test]
+fn t_signature() {
+    let t = |s: &str| signature(&syn::parse_str(s).unwrap());
+    assert_eq!(t("u32"), Some("u"));
+    assert_eq!(t("::std::string::String"), Some("s"));
+    assert_eq!(t("systemd::bus::ObjectPathBuf"), Some("o"));
+    assert_eq!(t("()"), Some(""));
+    assert_eq!(t("Vec<u8>"), None);
+    assert_eq!(t("&str"), None);
+    let r: Type = syn::parse_str("Result<u64, Error>").unwrap();
+    assert_eq!(result_inner(&r).and_then(signature), Some("t"));
+}
+
+#[test]
+fn t_expand() {
+    let item = syn::parse_str(r#"
+        impl Counter {
+            fn add(&mut self, a: u32, b: u32) -> u32 { a + b }
+            #[dbus(name = "Reset")]
+            fn clear(&mut self) -> Result<(), Error> { Ok(()) }
+            #[dbus(property)]
+            fn count(&self) -> u32 { 0 }
+            #[dbus(property)]
+            fn set_count(&mut self, c: u32) {}
+            #[dbus(skip)]
+            fn helper(&self) {}
+            fn new() -> Self { Counter }
+        }"#)
+        .unwrap();
+    let args = vec![syn::parse_str("name = \"org.example.Counter\"").unwrap()];
+    let out = expand(args, item).unwrap().to_string();
+    assert!(out.contains("b\"Add\\0\""));
+    assert!(out.contains("\"uu\""));
+    assert!(out.contains("b\"Reset\\0\""));
+    assert!(out.contains("property_writable (:: systemd :: bus :: MemberName :: \
+                          from_bytes (b\"Count"));
+    assert!(!out.contains("Helper"));
+    assert!(!out.contains("New"));
+    assert!(!out.contains("# [dbus"));
+
+    let item = syn::parse_str("impl Counter { fn add(&mut self, v: Vec<u8>) {} }").unwrap();
+    let args = vec![syn::parse_str("name = \"org.example.Counter\"").unwrap()];
+    assert!(expand(args, item).is_err());
+
+    let item = syn::parse_str(r#"
+        impl Counter {
+            fn wait(&mut self, n: u32) -> impl Future<Output = Result<u64, Error>> + 'static {}
+            async fn sleep(&self) {}
+        }"#)
+        .unwrap();
+    let args = vec![syn::parse_str("name = \"org.example.Counter\"").unwrap()];
+    assert!(expand(args, item).is_err());
+    let item = syn::parse_str(r#"
+        impl Counter {
+            fn wait(&mut self, n: u32) -> impl Future<Output = Result<u64, Error>> + 'static {}
+        }"#)
+        .unwrap();
+    let args = vec![syn::parse_str("name = \"org.example.Counter\"").unwrap()];
+    let out = expand(args, item).unwrap().to_string();
+    assert!(out.contains(". method_async (:: systemd :: bus :: MemberName :: from_bytes \
+                          (b\"Wait\\0\") . unwrap () , \"u\" , \"t\""));
+}
+
+#[test]
+fn t_derive_message() {
+    let item = syn::parse_str("struct Entry { name: String, size: u64 }").unwrap();
+    let out = to_message(item).unwrap().to_string();
+    assert!(out.contains("append_struct_with"));
+    let item = syn::parse_str("struct Entry(String, u64);").unwrap();
+    assert!(from_message(item).unwrap().to_string().contains("enter_container (b'r'"));
+
+    let item = syn::parse_str(r#"
+        #[dbus(dict)]
+        struct Options {
+            #[dbus(name = "Timeout")]
+            timeout: u64,
+            label: Option<String>,
+        }"#)
+        .unwrap();
+    let out = from_message(item).unwrap().to_string();
+    assert!(out.contains("\"Timeout\" =>"));
+    assert!(out.contains("\"label\" =>"));
+    assert!(out.contains("Option < String > = None"));
+
+    let item = syn::parse_str("#[dbus(dict)] struct Entry(String);").unwrap();
+    assert!(to_message(item).is_err());
+    let item = syn::parse_str("struct Entry<T> { v: T }").unwrap();
+    assert!(to_message(item).is_err());
+
+    assert_eq!(kebab_case("NotFound"), "not-found");
+    let item = syn::parse_str(r#"
+        enum State {
+            NotFound,
+            #[dbus(name = "na")]
+            NotApplicable,
+            #[dbus(other)]
+            Other(String),
+        }"#)
+        .unwrap();
+    let out = from_message(item).unwrap().to_string();
+    assert!(out.contains("\"not-found\" => State :: NotFound"));
+    assert!(out.contains("\"na\" => State :: NotApplicable"));
+    assert!(out.contains("State :: Other (s . to_owned ())"));
+    let item = syn::parse_str("#[dbus(u32)] enum Level { Low = 1, High = 2 }").unwrap();
+    assert!(to_message(item).unwrap().to_string().contains("Level :: Low as u32"));
+    let item = syn::parse_str("enum State { Running(u32) }").unwrap();
+    assert!(to_message(item).is_err());
+}